@@ -1,13 +1,26 @@
+use crate::core::annotations::{AnnotationEdit, AnnotationStore};
+use crate::core::app_proto::AppProtoDetector;
 use crate::core::data_processing;
+use crate::core::db_queries::{DbQueryTable, create_db_queries_df};
+use crate::core::flows::{FlowTable, create_flows_df};
+use crate::core::messaging::{MessagingTables, create_broker_messages_df, create_mail_messages_df};
 use crate::core::models;
 use crate::core::queries;
+use crate::core::redaction::{RedactionReport, Redactor};
+use crate::core::ws_messages::{WsMessageTable, create_ws_messages_df};
 use anyhow::{Result, anyhow};
 use polars::prelude::*;
 use polars::sql::SQLContext;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot, watch};
 
 type Responder<T> = oneshot::Sender<T>;
 
+/// Index of the first row; readers ask for rows past the last index they
+/// saw, starting from 0.
+pub const FIRST_INDEX: u64 = 1;
+
 #[derive(Debug)]
 pub enum ActorMessage {
     UpdateBatch(Vec<models::PacketData>),
@@ -16,6 +29,43 @@ pub enum ActorMessage {
         sql: String,
         resp: Responder<PolarsResult<DataFrame>>,
     },
+
+    /// Drops the rows of a capture session, answering with their number.
+    /// Indexes start over once no row is left.
+    RemoveSession {
+        session_id: u64,
+        resp: Responder<PolarsResult<u64>>,
+    },
+
+    /// Drops every row but those matching `keep`, a condition on `packets`,
+    /// answering with the number dropped. Indexes start over once no row is
+    /// left.
+    Clear {
+        keep: Option<String>,
+        resp: Responder<PolarsResult<u64>>,
+    },
+
+    /// Changes the annotation of the packet at `index`, answering with it.
+    Annotate {
+        index: u64,
+        edit: AnnotationEdit,
+        resp: Responder<PolarsResult<models::PacketAnnotation>>,
+    },
+
+    /// Loads the annotations saved to `path` and writes them there on every
+    /// change from now on. Answers the highest session id annotated.
+    PersistAnnotations {
+        path: PathBuf,
+        resp: Responder<u64>,
+    },
+
+    /// Redacts batches with `redactor` before they are stored, or stops to.
+    SetRedactor(Option<Arc<Redactor>>),
+
+    /// Answers with what was redacted since the redactor was set.
+    GetRedactionReport {
+        resp: Responder<RedactionReport>,
+    },
 }
 
 enum ActorEvent {
@@ -66,11 +116,142 @@ impl DataFrameActorHandle {
         self.query_sql(sql).await
     }
 
+    pub async fn remove_session(&self, session_id: u64) -> PolarsResult<u64> {
+        let (send_one, recv_one) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::RemoveSession {
+                session_id,
+                resp: send_one,
+            })
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+
+        recv_one
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?
+    }
+
+    pub async fn clear(&self, keep: Option<String>) -> PolarsResult<u64> {
+        let (send_one, recv_one) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::Clear {
+                keep,
+                resp: send_one,
+            })
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+
+        recv_one
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?
+    }
+
+    pub async fn annotate(
+        &self,
+        index: u64,
+        edit: AnnotationEdit,
+    ) -> PolarsResult<models::PacketAnnotation> {
+        let (send_one, recv_one) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::Annotate {
+                index,
+                edit,
+                resp: send_one,
+            })
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+
+        recv_one
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?
+    }
+
+    pub async fn persist_annotations(&self, path: PathBuf) -> u64 {
+        let (send_one, recv_one) = oneshot::channel();
+        let sent = self
+            .sender
+            .send(ActorMessage::PersistAnnotations {
+                path,
+                resp: send_one,
+            })
+            .await;
+        if sent.is_err() {
+            return 0;
+        }
+        recv_one.await.unwrap_or(0)
+    }
+
+    pub async fn set_redactor(&self, redactor: Option<Arc<Redactor>>) {
+        let _ = self.sender.send(ActorMessage::SetRedactor(redactor)).await;
+    }
+
+    pub async fn get_redaction_report(&self) -> PolarsResult<RedactionReport> {
+        let (send_one, recv_one) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::GetRedactionReport { resp: send_one })
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+
+        recv_one
+            .await
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))
+    }
+
+    /// Every packet with its payload, or those matching `condition`.
+    pub async fn get_packets_with_payload(
+        &self,
+        condition: Option<&str>,
+    ) -> PolarsResult<DataFrame> {
+        let sql = match condition {
+            Some(condition) => queries::packets_matching(condition),
+            None => queries::all_packets(),
+        };
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_annotations(&self) -> PolarsResult<DataFrame> {
+        self.query_sql(queries::all_annotations()).await
+    }
+
+    pub async fn get_session_packets_no_payload(&self, session_id: u64) -> PolarsResult<DataFrame> {
+        let sql = queries::session_packets_no_payload(session_id);
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_session_row_counts(&self) -> PolarsResult<DataFrame> {
+        self.query_sql(queries::session_row_counts()).await
+    }
+
     pub async fn get_packet_by_index(&self, index: u64) -> PolarsResult<DataFrame> {
         let sql = queries::get_packet_by_index(index);
         self.query_sql(sql).await
     }
 
+    pub async fn get_flows_updated_since(&self, last_seq: &u64) -> PolarsResult<DataFrame> {
+        let sql = queries::flows_updated_since(last_seq);
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_ws_messages_by_uuid(&self, uuid: &str) -> PolarsResult<DataFrame> {
+        let sql = queries::ws_messages_by_uuid(uuid);
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_db_queries_by_uuid(&self, uuid: &str) -> PolarsResult<DataFrame> {
+        let sql = queries::db_queries_by_uuid(uuid);
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_mail_messages_by_uuid(&self, uuid: &str) -> PolarsResult<DataFrame> {
+        let sql = queries::mail_messages_by_uuid(uuid);
+        self.query_sql(sql).await
+    }
+
+    pub async fn get_broker_messages_by_uuid(&self, uuid: &str) -> PolarsResult<DataFrame> {
+        let sql = queries::broker_messages_by_uuid(uuid);
+        self.query_sql(sql).await
+    }
+
     pub fn close(&self) {
         let _ = self.done.send(());
     }
@@ -82,9 +263,118 @@ impl DataFrameActorHandle {
     }
 }*/
 
+/// Tables derived from the packet stream, registered next to `packets`.
+struct DerivedTables {
+    flows: FlowTable,
+    ws_messages: WsMessageTable,
+    db_queries: DbQueryTable,
+    messaging: MessagingTables,
+}
+
+impl DerivedTables {
+    fn new() -> Self {
+        Self {
+            flows: FlowTable::new(),
+            ws_messages: WsMessageTable::new(),
+            db_queries: DbQueryTable::new(),
+            messaging: MessagingTables::new(),
+        }
+    }
+
+    /// Tables derived from `packets` alone, e.g. after rows were dropped.
+    /// Flows keep counting `update_seq` from `flows_seq`. Rows are redacted
+    /// again, since stored packets keep binary data no rule matched in.
+    fn rebuild(
+        packets: &[models::PacketData],
+        flows_seq: u64,
+        redactor: Option<&Redactor>,
+    ) -> Self {
+        let mut tables = Self {
+            flows: FlowTable::continuing_from(flows_seq),
+            ..Self::new()
+        };
+        // Derived tables expect consecutive indexes within a batch
+        for run in packets.chunk_by(|a, b| b.index == a.index + 1) {
+            match redactor {
+                // Counted when first stored
+                Some(redactor) => tables.update_redacted(
+                    run,
+                    run[0].index,
+                    redactor,
+                    &mut RedactionReport::default(),
+                ),
+                None => tables.update(run, run[0].index),
+            }
+        }
+        tables
+    }
+
+    fn update(&mut self, batch: &[models::PacketData], first_index: u64) {
+        self.flows.update(batch);
+        self.ws_messages.update(batch, first_index);
+        self.db_queries.update(batch, first_index);
+        self.messaging.update(batch, first_index);
+    }
+
+    /// Updates the tables with a batch as captured, then redacts the rows
+    /// it added. Binary protocols are parsed before any text is rewritten,
+    /// and WebSocket messages are redacted once unmasked and inflated.
+    fn update_redacted(
+        &mut self,
+        batch: &[models::PacketData],
+        first_index: u64,
+        redactor: &Redactor,
+        report: &mut RedactionReport,
+    ) {
+        let ws_messages = self.ws_messages.pending_mut().len();
+        let db_queries = self.db_queries.pending_mut().len();
+        let mail_messages = self.messaging.pending_mail_mut().len();
+        let broker_messages = self.messaging.pending_broker_mut().len();
+        self.update(batch, first_index);
+
+        for message in &mut self.ws_messages.pending_mut()[ws_messages..] {
+            redactor.redact_ws_message(message, report);
+        }
+        for query in &mut self.db_queries.pending_mut()[db_queries..] {
+            redactor.redact_db_query(query, report);
+        }
+        for message in &mut self.messaging.pending_mail_mut()[mail_messages..] {
+            redactor.redact_mail_message(message, report);
+        }
+        for message in &mut self.messaging.pending_broker_mut()[broker_messages..] {
+            redactor.redact_broker_message(message, report);
+        }
+    }
+
+    fn register(&mut self, ctx: &mut SQLContext) -> PolarsResult<()> {
+        ctx.register("flows", self.flows.as_df()?.lazy());
+        ctx.register("ws_messages", self.ws_messages.as_df()?.lazy());
+        ctx.register("db_queries", self.db_queries.as_df()?.lazy());
+        ctx.register("mail_messages", self.messaging.as_mail_df()?.lazy());
+        ctx.register("broker_messages", self.messaging.as_broker_df()?.lazy());
+        Ok(())
+    }
+}
+
+/// Registers empty versions of every table, e.g. to validate user SQL.
+pub fn register_empty_tables(ctx: &mut SQLContext) {
+    // Fails only to build the annotations of a non-empty store
+    let _ = AnnotationStore::new().register(ctx, create_capture_df().lazy());
+    ctx.register("flows", create_flows_df().lazy());
+    ctx.register("ws_messages", create_ws_messages_df().lazy());
+    ctx.register("db_queries", create_db_queries_df().lazy());
+    ctx.register("mail_messages", create_mail_messages_df().lazy());
+    ctx.register("broker_messages", create_broker_messages_df().lazy());
+}
+
 pub struct DataFrameActor {
     receiver: mpsc::Receiver<ActorMessage>,
     df: DataFrame,
+    app_proto: AppProtoDetector,
+    tables: DerivedTables,
+    annotations: AnnotationStore,
+    redactor: Option<Arc<Redactor>>,
+    redaction_report: RedactionReport,
     // ctx: SQLContext,
     done: watch::Receiver<()>,
     next_index: u64,
@@ -101,9 +391,14 @@ impl DataFrameActor {
         Ok(Self {
             receiver,
             df,
+            app_proto: AppProtoDetector::new(),
+            tables: DerivedTables::new(),
+            annotations: AnnotationStore::new(),
+            redactor: None,
+            redaction_report: RedactionReport::default(),
             // ctx,
             done,
-            next_index: 0,
+            next_index: FIRST_INDEX,
         })
     }
 
@@ -111,6 +406,11 @@ impl DataFrameActor {
         let Self {
             mut receiver,
             mut df,
+            mut app_proto,
+            mut tables,
+            mut annotations,
+            mut redactor,
+            mut redaction_report,
             // mut ctx,
             mut done,
             mut next_index,
@@ -129,15 +429,95 @@ impl DataFrameActor {
             };
             match event {
                 ActorEvent::Message(msg) => match msg {
-                    ActorMessage::UpdateBatch(batch) => {
+                    ActorMessage::UpdateBatch(mut batch) => {
+                        let first_index = next_index;
+                        app_proto.label(&mut batch);
+                        match &redactor {
+                            Some(redactor) => {
+                                tables.update_redacted(
+                                    &batch,
+                                    first_index,
+                                    redactor,
+                                    &mut redaction_report,
+                                );
+                                for packet in batch.iter_mut() {
+                                    redactor.redact_packet(packet, &mut redaction_report);
+                                }
+                            }
+                            None => tables.update(&batch, first_index),
+                        }
                         data_processing::write_batch_to_df(&batch, &mut df, &mut next_index)?;
                     }
                     ActorMessage::QuerySql { sql, resp } => {
                         // ctx.unregister("packets");
                         // ctx.register("packets", df.clone().lazy());
                         let mut ctx = SQLContext::new();
-                        ctx.register("packets", df.clone().lazy());
-                        if resp.send(ctx.execute(&sql)?.collect()).is_err() {
+                        // Errors go back to the caller; a bad query must not stop the actor
+                        let result = annotations
+                            .register(&mut ctx, df.clone().lazy())
+                            .and_then(|_| tables.register(&mut ctx))
+                            .and_then(|_| ctx.execute(&sql))
+                            .and_then(|lf| lf.collect());
+                        if resp.send(result).is_err() {
+                            eprintln!("Oneshot channel send failed");
+                        }
+                    }
+                    ActorMessage::RemoveSession { session_id, resp } => {
+                        let result =
+                            remove_session(&mut df, &mut tables, session_id, redactor.as_deref());
+                        if result.is_ok() {
+                            annotations.remove_session(session_id);
+                        }
+                        save_annotations(&annotations);
+                        if df.height() == 0 {
+                            next_index = FIRST_INDEX;
+                        }
+                        if resp.send(result).is_err() {
+                            eprintln!("Oneshot channel send failed");
+                        }
+                    }
+                    ActorMessage::Clear { keep, resp } => {
+                        let result = clear(
+                            &mut df,
+                            &mut tables,
+                            &annotations,
+                            keep.as_deref(),
+                            redactor.as_deref(),
+                        )
+                        .and_then(|dropped| {
+                            annotations.retain_rows(&df)?;
+                            Ok(dropped)
+                        });
+                        save_annotations(&annotations);
+                        if df.height() == 0 {
+                            next_index = FIRST_INDEX;
+                        }
+                        if resp.send(result).is_err() {
+                            eprintln!("Oneshot channel send failed");
+                        }
+                    }
+                    ActorMessage::Annotate { index, edit, resp } => {
+                        let result = session_of(&df, index)
+                            .map(|session_id| annotations.edit(index, session_id, edit));
+                        save_annotations(&annotations);
+                        if resp.send(result).is_err() {
+                            eprintln!("Oneshot channel send failed");
+                        }
+                    }
+                    ActorMessage::PersistAnnotations { path, resp } => {
+                        if let Err(e) = annotations.persist_to(path) {
+                            log::warn!("Failed to load annotations: {}", e);
+                        }
+                        if resp.send(annotations.last_session_id()).is_err() {
+                            eprintln!("Oneshot channel send failed");
+                        }
+                    }
+                    ActorMessage::SetRedactor(new_redactor) => {
+                        redactor = new_redactor;
+                        redaction_report = RedactionReport::default();
+                    }
+                    ActorMessage::GetRedactionReport { resp } => {
+                        if resp.send(redaction_report.clone()).is_err() {
                             eprintln!("Oneshot channel send failed");
                         }
                     }
@@ -150,9 +530,80 @@ impl DataFrameActor {
         }
     }
 }
+/// Saves the annotations after a change; the change stands if that fails.
+fn save_annotations(annotations: &AnnotationStore) {
+    if let Err(e) = annotations.save() {
+        log::warn!("Failed to save annotations: {}", e);
+    }
+}
+
+/// Drops the rows of a capture session and rebuilds the derived tables from
+/// the rows left.
+fn remove_session(
+    df: &mut DataFrame,
+    tables: &mut DerivedTables,
+    session_id: u64,
+    redactor: Option<&Redactor>,
+) -> PolarsResult<u64> {
+    let keep = df.column("session_id")?.u64()?.not_equal(session_id);
+    let kept = df.filter(&keep)?;
+    replace_rows(df, tables, kept, redactor)
+}
+
+/// Keeps only the rows matching `keep`, or none, and rebuilds the derived
+/// tables from them. Nothing changes if `keep` does not run.
+fn clear(
+    df: &mut DataFrame,
+    tables: &mut DerivedTables,
+    annotations: &AnnotationStore,
+    keep: Option<&str>,
+    redactor: Option<&Redactor>,
+) -> PolarsResult<u64> {
+    let kept = match keep {
+        Some(condition) => {
+            let mut ctx = SQLContext::new();
+            annotations.register(&mut ctx, df.clone().lazy())?;
+            tables.register(&mut ctx)?;
+            ctx.execute(&queries::packets_matching(condition))?
+                .collect()?
+                // Without the annotation columns joined in
+                .select(df.get_column_names_owned())?
+        }
+        None => create_capture_df(),
+    };
+    replace_rows(df, tables, kept, redactor)
+}
+
+/// Session of the packet at `index`.
+fn session_of(df: &DataFrame, index: u64) -> PolarsResult<u64> {
+    let mask = df.column("index")?.u64()?.equal(index);
+    df.filter(&mask)?
+        .column("session_id")?
+        .u64()?
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or_else(|| PolarsError::ComputeError(format!("No packet with index {}", index).into()))
+}
+
+/// Replaces the rows with `kept`, returning how many were dropped.
+fn replace_rows(
+    df: &mut DataFrame,
+    tables: &mut DerivedTables,
+    kept: DataFrame,
+    redactor: Option<&Redactor>,
+) -> PolarsResult<u64> {
+    let dropped = df.height().saturating_sub(kept.height()) as u64;
+    let packets = crate::tauri_bridge::converters::df_to_packet_data_vec(&kept)?;
+    *tables = DerivedTables::rebuild(&packets, tables.flows.seq(), redactor);
+    *df = kept;
+    Ok(dropped)
+}
+
 pub fn create_capture_df() -> DataFrame {
     let schema = Schema::from_iter(vec![
         Field::new("index".into(), DataType::UInt64),
+        Field::new("session_id".into(), DataType::UInt64),
         Field::new("timestamp".into(), DataType::Int64),
         Field::new("uuid".into(), DataType::String),
         Field::new("src_ip".into(), DataType::String),
@@ -161,12 +612,198 @@ pub fn create_capture_df() -> DataFrame {
         Field::new("dst_port".into(), DataType::UInt32),
         Field::new("pid".into(), DataType::Int32),
         Field::new("pname".into(), DataType::String),
+        Field::new("tid".into(), DataType::Int32),
+        Field::new("uid".into(), DataType::UInt32),
+        Field::new("exe_path".into(), DataType::String),
+        Field::new("cgroup_id".into(), DataType::UInt64),
+        Field::new("container_id".into(), DataType::String),
+        Field::new("package".into(), DataType::String),
+        Field::new("fd".into(), DataType::Int32),
         Field::new("type".into(), DataType::UInt32),
+        Field::new("type_name".into(), DataType::String),
+        Field::new("direction".into(), DataType::String),
+        Field::new("module".into(), DataType::String),
         Field::new("length".into(), DataType::UInt32),
         Field::new("is_binary".into(), DataType::Boolean),
+        Field::new("content_kind".into(), DataType::String),
+        Field::new("app_proto".into(), DataType::String),
+        Field::new("tls_version".into(), DataType::String),
+        Field::new("tls_cipher".into(), DataType::String),
+        Field::new("tls_sni".into(), DataType::String),
+        Field::new("tls_alpn".into(), DataType::String),
+        Field::new("tls_library".into(), DataType::String),
         Field::new("payload_utf8".into(), DataType::String),
         Field::new("payload_binary".into(), DataType::Binary),
+        Field::new("decoded_body_utf8".into(), DataType::String),
     ]);
 
     DataFrame::empty_with_schema(&schema)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::classify::classify_payload;
+    use crate::core::redaction::{RedactionConfig, RedactionMode};
+
+    const JWT: &str = "eyJhbGciOi.eyJzdWIi.c2lnbmF0dXJl";
+
+    fn spawn_actor() -> DataFrameActorHandle {
+        let (sender, receiver) = mpsc::channel(8);
+        let (done, done_rx) = watch::channel(());
+        tokio::spawn(DataFrameActor::new(receiver, done_rx).unwrap().run());
+        DataFrameActorHandle { sender, done }
+    }
+
+    async fn set_ingest_redaction(handle: &DataFrameActorHandle) {
+        let redactor = Redactor::new(&RedactionConfig {
+            mode: RedactionMode::Ingest,
+            ..Default::default()
+        })
+        .unwrap();
+        handle.set_redactor(redactor.map(Arc::new)).await;
+    }
+
+    /// A MySQL packet: 3-byte length, sequence id, payload.
+    fn mysql(seq: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// An event between a client on port 5000 and a server on `port`.
+    fn event(from_client: bool, port: u32, timestamp: i64, payload: &[u8]) -> models::PacketData {
+        let (src_port, dst_port) = if from_client {
+            (5000, port)
+        } else {
+            (port, 5000)
+        };
+        let classification = classify_payload(payload);
+        models::PacketData {
+            timestamp,
+            uuid: "conn".to_string(),
+            src_ip: if from_client { "10.0.0.1" } else { "10.0.0.2" }.to_string(),
+            src_port,
+            dst_ip: if from_client { "10.0.0.2" } else { "10.0.0.1" }.to_string(),
+            dst_port,
+            is_binary: classification.is_binary,
+            payload_utf8: classification.text,
+            payload_binary: if classification.text_is_exact {
+                Vec::new()
+            } else {
+                payload.to_vec()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn strings(df: &DataFrame, column: &str) -> Vec<String> {
+        df.column(column)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn ingest_redaction_keeps_binary_framing_for_db_queries() {
+        let handle = spawn_actor();
+        set_ingest_redaction(&handle).await;
+
+        let mut query = vec![0x03];
+        query.extend(format!("SELECT * FROM sessions WHERE jwt = '{JWT}'").as_bytes());
+        let ok = [0, 0, 0, 2, 0, 0, 0];
+        handle
+            .update_batch(vec![
+                event(false, 3306, 1, &mysql(0, b"\x0a8.0.33\0")),
+                event(true, 3306, 2, &mysql(1, b"auth")),
+                event(false, 3306, 3, &mysql(2, &ok)),
+                event(true, 3306, 4, &mysql(0, &query)),
+                event(false, 3306, 5, &mysql(1, &ok)),
+            ])
+            .await;
+
+        let df = handle
+            .query_sql("SELECT statement FROM db_queries".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            strings(&df, "statement"),
+            ["SELECT * FROM sessions WHERE jwt = '[REDACTED]'"]
+        );
+        let df = handle.query_sql(queries::all_packets()).await.unwrap();
+        assert!(
+            strings(&df, "payload_utf8")
+                .iter()
+                .all(|p| !p.contains(JWT))
+        );
+
+        let report = handle.get_redaction_report().await.unwrap();
+        assert_eq!(report.records, 2);
+        handle.close();
+    }
+
+    #[tokio::test]
+    async fn ingest_redaction_covers_ws_messages_after_clear() {
+        let handle = spawn_actor();
+        set_ingest_redaction(&handle).await;
+
+        let upgrade = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+        let text = format!(r#"{{"token":"{JWT}"}}"#);
+        // Masked, so the token is not in the captured bytes
+        let key = [1u8, 2, 3, 4];
+        let mut frame = vec![0x81, 0x80 | text.len() as u8];
+        frame.extend_from_slice(&key);
+        frame.extend(text.bytes().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        handle
+            .update_batch(vec![
+                event(false, 8080, 1, upgrade),
+                event(true, 8080, 2, &frame),
+            ])
+            .await;
+
+        let sql = "SELECT payload_utf8 FROM ws_messages".to_string();
+        let redacted = [r#"{"token":"[REDACTED]"}"#];
+        let df = handle.query_sql(sql.clone()).await.unwrap();
+        assert_eq!(strings(&df, "payload_utf8"), redacted);
+
+        // Rebuilt from the stored packets, which keep the masked frame
+        handle.clear(Some("index > 0".to_string())).await.unwrap();
+        let df = handle.query_sql(sql).await.unwrap();
+        assert_eq!(strings(&df, "payload_utf8"), redacted);
+        handle.close();
+    }
+
+    #[tokio::test]
+    async fn removing_the_last_session_restarts_indexes() {
+        let handle = spawn_actor();
+        let batch = |session_id| {
+            (1..=3)
+                .map(|ts| models::PacketData {
+                    session_id,
+                    ..event(true, 80, ts, b"GET / HTTP/1.1\r\n\r\n")
+                })
+                .collect::<Vec<_>>()
+        };
+        handle.update_batch(batch(1)).await;
+        assert_eq!(handle.remove_session(1).await.unwrap(), 3);
+
+        handle.update_batch(batch(2)).await;
+        let df = handle
+            .query_sql("SELECT index FROM packets".to_string())
+            .await
+            .unwrap();
+        let indexes: Vec<u64> = df
+            .column("index")
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(indexes, [FIRST_INDEX, FIRST_INDEX + 1, FIRST_INDEX + 2]);
+        handle.close();
+    }
+}
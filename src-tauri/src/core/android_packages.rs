@@ -0,0 +1,59 @@
+//! Installed Android packages and the uids they run as.
+
+use std::collections::HashMap;
+
+use crate::core::models::AndroidPackage;
+
+/// Parses the output of `pm list packages -U`, one
+/// `package:com.example uid:10123` line per package. A package installed for
+/// several users lists one uid per user, separated by commas.
+pub fn parse_package_list(output: &str) -> Vec<AndroidPackage> {
+    let mut packages: Vec<AndroidPackage> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?.strip_prefix("package:")?;
+            let uids = fields.find_map(|f| f.strip_prefix("uid:"))?;
+            Some(uids.split(',').filter_map(move |uid| {
+                Some(AndroidPackage {
+                    name: name.to_string(),
+                    uid: uid.parse().ok()?,
+                })
+            }))
+        })
+        .flatten()
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.uid.cmp(&b.uid)));
+    packages
+}
+
+/// Package names by uid, used to annotate captured events.
+#[derive(Debug, Clone, Default)]
+pub struct PackageIndex {
+    /// Packages sharing a uid are joined by `, `.
+    names: HashMap<u32, String>,
+}
+
+impl PackageIndex {
+    pub fn new(packages: &[AndroidPackage]) -> Self {
+        let mut names: HashMap<u32, String> = HashMap::new();
+        for package in packages {
+            names
+                .entry(package.uid)
+                .and_modify(|n| {
+                    n.push_str(", ");
+                    n.push_str(&package.name);
+                })
+                .or_insert_with(|| package.name.clone());
+        }
+        Self { names }
+    }
+
+    pub fn name(&self, uid: u32) -> Option<&str> {
+        self.names.get(&uid).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
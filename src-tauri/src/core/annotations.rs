@@ -0,0 +1,292 @@
+//! Tags and notes users put on captured packets, backing the `annotations`
+//! SQL table and the `tags` and `note` columns of `packets`.
+//!
+//! Annotations are keyed by session and index, and written to one file in
+//! the app data directory on every change. The file is loaded again on the
+//! next run, whose session ids continue after the ones annotated, and goes
+//! away once the annotated sessions are removed or cleared.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::core::models::PacketAnnotation;
+
+/// A change to the annotation of one packet.
+#[derive(Debug, Clone)]
+pub enum AnnotationEdit {
+    Tag(String),
+    Untag(String),
+    Note(String),
+}
+
+/// The file annotations are written to and loaded from.
+pub fn annotations_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("annotations.json")
+}
+
+/// Annotations by session id and packet index. Packets without tags and
+/// note have none.
+#[derive(Debug, Default)]
+pub struct AnnotationStore {
+    by_packet: BTreeMap<(u64, u64), PacketAnnotation>,
+    /// Written on every change once set.
+    path: Option<PathBuf>,
+}
+
+impl AnnotationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the annotations saved to `path`, if any, and writes them there
+    /// from now on.
+    pub fn persist_to(&mut self, path: PathBuf) -> Result<()> {
+        if path.exists() {
+            let saved: Vec<PacketAnnotation> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            for annotation in saved {
+                self.by_packet
+                    .entry((annotation.session_id, annotation.index))
+                    .or_insert(annotation);
+            }
+        }
+        self.path = Some(path);
+        self.save()
+    }
+
+    /// The highest session id with annotations, 0 without any.
+    pub fn last_session_id(&self) -> u64 {
+        self.by_packet
+            .keys()
+            .map(|(session_id, _)| *session_id)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Writes the annotations to the file set by `persist_to`, if any; the
+    /// file is removed once none are left.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.by_packet.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let annotations: Vec<&PacketAnnotation> = self.by_packet.values().collect();
+        // Replaced in one step, so a crash never leaves half a file
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&annotations)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Applies `edit` to the packet at `index` of `session_id` and returns
+    /// its annotation afterwards, empty once nothing is left.
+    pub fn edit(&mut self, index: u64, session_id: u64, edit: AnnotationEdit) -> PacketAnnotation {
+        let annotation = self
+            .by_packet
+            .entry((session_id, index))
+            .or_insert_with(|| PacketAnnotation {
+                index,
+                session_id,
+                ..Default::default()
+            });
+        match edit {
+            AnnotationEdit::Tag(tag) => {
+                let tag = tag.trim();
+                if !tag.is_empty() && !annotation.tags.iter().any(|t| t == tag) {
+                    annotation.tags.push(tag.to_string());
+                }
+            }
+            AnnotationEdit::Untag(tag) => annotation.tags.retain(|t| t != tag.trim()),
+            AnnotationEdit::Note(note) => annotation.note = note.trim().to_string(),
+        }
+
+        let annotation = annotation.clone();
+        if annotation.tags.is_empty() && annotation.note.is_empty() {
+            self.by_packet.remove(&(session_id, index));
+        }
+        annotation
+    }
+
+    /// Drops the annotations of the packets of `session_id`.
+    pub fn remove_session(&mut self, session_id: u64) {
+        self.by_packet.retain(|(id, _), _| *id != session_id);
+    }
+
+    /// Drops the annotations of packets no longer in `packets`, so that
+    /// none carries over to a later packet given the same index.
+    pub fn retain_rows(&mut self, packets: &DataFrame) -> PolarsResult<()> {
+        let kept: HashSet<(u64, u64)> = packets
+            .column("session_id")?
+            .u64()?
+            .into_no_null_iter()
+            .zip(packets.column("index")?.u64()?.into_no_null_iter())
+            .collect();
+        self.by_packet.retain(|key, _| kept.contains(key));
+        Ok(())
+    }
+
+    pub fn as_df(&self) -> PolarsResult<DataFrame> {
+        if self.by_packet.is_empty() {
+            return Ok(create_annotations_df());
+        }
+        let annotations = self.by_packet.values();
+        let tags: Vec<Series> = annotations
+            .clone()
+            .map(|a| Series::new(PlSmallStr::EMPTY, &a.tags).cast(&DataType::String))
+            .collect::<PolarsResult<_>>()?;
+
+        DataFrame::new(vec![
+            Column::new(
+                "index".into(),
+                annotations.clone().map(|a| a.index).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "session_id".into(),
+                annotations
+                    .clone()
+                    .map(|a| a.session_id)
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new("tags".into(), tags),
+            Column::new(
+                "note".into(),
+                annotations.map(|a| a.note.as_str()).collect::<Vec<_>>(),
+            ),
+        ])
+    }
+
+    /// Registers `annotations`, and `packets` with the `tags` and `note` of
+    /// each packet, null for packets without annotation.
+    pub fn register(&self, ctx: &mut SQLContext, packets: LazyFrame) -> PolarsResult<()> {
+        let packets = if self.by_packet.is_empty() {
+            packets.with_columns([
+                lit(NULL).cast(tags_dtype()).alias("tags"),
+                lit(NULL).cast(DataType::String).alias("note"),
+            ])
+        } else {
+            let annotations = self.as_df()?.lazy().select([
+                col("index"),
+                col("session_id"),
+                col("tags"),
+                col("note"),
+            ]);
+            packets.join(
+                annotations,
+                [col("index"), col("session_id")],
+                [col("index"), col("session_id")],
+                JoinArgs::new(JoinType::Left),
+            )
+        };
+        ctx.register("packets", packets);
+        ctx.register("annotations", self.as_df()?.lazy());
+        Ok(())
+    }
+}
+
+fn tags_dtype() -> DataType {
+    DataType::List(Box::new(DataType::String))
+}
+
+pub fn create_annotations_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("index".into(), DataType::UInt64),
+        Field::new("session_id".into(), DataType::UInt64),
+        Field::new("tags".into(), tags_dtype()),
+        Field::new("note".into(), DataType::String),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `packets` with just the columns annotations are keyed by.
+    fn packets(rows: &[(u64, u64)]) -> DataFrame {
+        df!(
+            "session_id" => rows.iter().map(|r| r.0).collect::<Vec<_>>(),
+            "index" => rows.iter().map(|r| r.1).collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    fn tagged(packets: &DataFrame, store: &AnnotationStore) -> Vec<bool> {
+        let mut ctx = SQLContext::new();
+        store.register(&mut ctx, packets.clone().lazy()).unwrap();
+        let df = ctx
+            .execute("SELECT tags FROM packets ORDER BY session_id")
+            .unwrap()
+            .collect()
+            .unwrap();
+        let tags = df.column("tags").unwrap().list().unwrap();
+        (0..df.height())
+            .map(|i| tags.get_as_series(i).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn reused_index_does_not_inherit_annotation() {
+        let mut store = AnnotationStore::new();
+        store.edit(1, 1, AnnotationEdit::Tag("suspicious".to_string()));
+        assert_eq!(tagged(&packets(&[(1, 1), (2, 1)]), &store), [true, false]);
+
+        // Indexes start over once every row was cleared
+        store.retain_rows(&packets(&[])).unwrap();
+        assert_eq!(tagged(&packets(&[(1, 1)]), &store), [false]);
+    }
+
+    #[test]
+    fn annotations_are_written_while_any_are_left() {
+        let path =
+            std::env::temp_dir().join(format!("ecaptureq-annotations-{}.json", std::process::id()));
+        let mut store = AnnotationStore::new();
+        store.persist_to(path.clone()).unwrap();
+        assert!(!path.exists());
+
+        store.edit(3, 2, AnnotationEdit::Note("login".to_string()));
+        store.save().unwrap();
+        let saved: Vec<PacketAnnotation> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((saved[0].session_id, saved[0].index), (2, 3));
+        assert_eq!(saved[0].note, "login");
+
+        store.edit(3, 2, AnnotationEdit::Note(String::new()));
+        store.save().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn saved_annotations_load_until_their_session_is_removed() {
+        let path = std::env::temp_dir().join(format!(
+            "ecaptureq-annotations-load-{}.json",
+            std::process::id()
+        ));
+        let mut store = AnnotationStore::new();
+        store.persist_to(path.clone()).unwrap();
+        store.edit(1, 4, AnnotationEdit::Tag("login".to_string()));
+        store.edit(2, 7, AnnotationEdit::Note("token".to_string()));
+        store.save().unwrap();
+
+        let mut loaded = AnnotationStore::new();
+        loaded.persist_to(path.clone()).unwrap();
+        assert_eq!(loaded.last_session_id(), 7);
+        assert_eq!(tagged(&packets(&[(4, 1), (7, 1)]), &loaded), [true, false]);
+
+        loaded.remove_session(7);
+        loaded.save().unwrap();
+        assert_eq!(loaded.last_session_id(), 4);
+        loaded.remove_session(4);
+        loaded.save().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use crate::core::content_decoding;
+use crate::core::db_queries::{mysql, postgres, redis};
+use crate::core::flows::flow_key;
+use crate::core::messaging::smtp;
+use crate::core::models::{ContentKind, PacketData};
+
+/// Application protocol stored in the `app_proto` column of `packets` and
+/// `flows`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppProto {
+    Http1,
+    Http2,
+    Grpc,
+    Websocket,
+    Mysql,
+    Postgres,
+    Redis,
+    Smtp,
+    Unknown,
+}
+
+impl AppProto {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppProto::Http1 => "http1",
+            AppProto::Http2 => "http2",
+            AppProto::Grpc => "grpc",
+            AppProto::Websocket => "websocket",
+            AppProto::Mysql => "mysql",
+            AppProto::Postgres => "postgres",
+            AppProto::Redis => "redis",
+            AppProto::Smtp => "smtp",
+            AppProto::Unknown => "unknown",
+        }
+    }
+}
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0";
+
+/// Well-known server ports, used when neither the payload nor the TLS
+/// handshake gave the protocol away.
+const PORTS: [(u32, AppProto); 9] = [
+    (80, AppProto::Http1),
+    (8080, AppProto::Http1),
+    (25, AppProto::Smtp),
+    (465, AppProto::Smtp),
+    (587, AppProto::Smtp),
+    (3306, AppProto::Mysql),
+    (5432, AppProto::Postgres),
+    (6379, AppProto::Redis),
+    (50051, AppProto::Grpc),
+];
+
+#[derive(Default)]
+struct ConnState {
+    /// Protocol recognized from a payload signature; sticks to the connection.
+    detected: Option<AppProto>,
+    /// Protocol negotiated with ALPN in the TLS handshake.
+    alpn: Option<AppProto>,
+}
+
+/// Labels events with their application protocol.
+///
+/// A connection is labeled by the first payload signature recognized on it
+/// (HTTP/2 preface, HTTP/1 start line, database and SMTP greetings), and
+/// until then by its ALPN (reported by ecapture or read from a captured
+/// handshake) or server port. HTTP/1 connections become
+/// `websocket` after an upgrade and HTTP/2 ones `grpc` once a gRPC content
+/// type is seen.
+pub struct AppProtoDetector {
+    connections: HashMap<String, ConnState>,
+}
+
+impl AppProtoDetector {
+    pub fn new() -> Self {
+        Self {
+            connections: HashMap::new(),
+        }
+    }
+
+    /// Sets `app_proto` on every event of the batch, in order.
+    pub fn label(&mut self, batch: &mut [PacketData]) {
+        for d in batch {
+            let state = self.connections.entry(flow_key(d)).or_default();
+            let payload = d.payload_bytes();
+
+            // ALPN reported by ecapture, else read from a captured handshake
+            let alpn = if !d.tls_alpn.is_empty() {
+                alpn_proto(d.tls_alpn.as_bytes())
+            } else if d.content_kind == ContentKind::TlsRecord.as_str() {
+                hello_alpn(payload).and_then(alpn_proto)
+            } else {
+                None
+            };
+            if alpn.is_some() {
+                state.alpn = alpn;
+            }
+
+            let proto = match state.detected {
+                Some(proto) => proto,
+                None => match sniff(payload) {
+                    Some(proto) => *state.detected.insert(proto),
+                    None => state
+                        .alpn
+                        .or_else(|| by_port(d))
+                        .unwrap_or(AppProto::Unknown),
+                },
+            };
+
+            let proto = match proto {
+                // The upgrade response itself is still HTTP
+                AppProto::Http1 if is_websocket_upgrade(payload) => {
+                    state.detected = Some(AppProto::Websocket);
+                    AppProto::Http1
+                }
+                AppProto::Http1 | AppProto::Http2 if mentions_grpc(proto, payload) => {
+                    *state.detected.insert(AppProto::Grpc)
+                }
+                proto => proto,
+            };
+            d.app_proto = proto.as_str().to_string();
+        }
+    }
+}
+
+impl Default for AppProtoDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recognizes an HTTP/1 request or status line, or the HTTP/2 preface.
+pub(crate) fn looks_like_http(payload: &[u8]) -> bool {
+    const METHODS: [&[u8]; 9] = [
+        b"GET ",
+        b"POST ",
+        b"PUT ",
+        b"DELETE ",
+        b"HEAD ",
+        b"OPTIONS ",
+        b"PATCH ",
+        b"CONNECT ",
+        b"TRACE ",
+    ];
+    payload.starts_with(b"HTTP/1.")
+        || payload.starts_with(HTTP2_PREFACE)
+        || METHODS.iter().any(|m| payload.starts_with(m))
+}
+
+/// Cheap signature check on a payload.
+fn sniff(payload: &[u8]) -> Option<AppProto> {
+    if payload.starts_with(HTTP2_PREFACE) {
+        Some(AppProto::Http2)
+    } else if looks_like_http(payload) {
+        Some(AppProto::Http1)
+    } else if redis::sniff_client(payload) {
+        Some(AppProto::Redis)
+    } else if postgres::sniff_client(payload) {
+        Some(AppProto::Postgres)
+    } else if mysql::sniff_client(payload) || mysql::sniff_server(payload) {
+        Some(AppProto::Mysql)
+    } else if smtp::sniff_client(payload) || smtp::sniff_server(payload) {
+        Some(AppProto::Smtp)
+    } else {
+        None
+    }
+}
+
+fn by_port(d: &PacketData) -> Option<AppProto> {
+    PORTS
+        .iter()
+        .find(|(port, _)| d.dst_port == *port)
+        .or_else(|| PORTS.iter().find(|(port, _)| d.src_port == *port))
+        .map(|(_, proto)| *proto)
+}
+
+fn is_websocket_upgrade(payload: &[u8]) -> bool {
+    let Some(msg) = content_decoding::parse_http_message(payload) else {
+        return false;
+    };
+    msg.head.starts_with("HTTP/1.")
+        && msg.head.split(' ').nth(1) == Some("101")
+        && msg
+            .header("Upgrade")
+            .is_some_and(|u| u.eq_ignore_ascii_case("websocket"))
+}
+
+/// HTTP/1 messages are checked by their Content-Type header (gRPC-Web);
+/// HTTP/2 header blocks are only searched for a literal content type, which
+/// misses Huffman-coded values.
+fn mentions_grpc(proto: AppProto, payload: &[u8]) -> bool {
+    const GRPC: &[u8] = b"application/grpc";
+    match proto {
+        AppProto::Http1 => content_decoding::parse_http_message(payload)
+            .and_then(|msg| msg.header("Content-Type"))
+            .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("application/grpc")),
+        _ => payload.windows(GRPC.len()).any(|w| w == GRPC),
+    }
+}
+
+/// Returns the first ALPN protocol of a TLS ClientHello or ServerHello. A
+/// ServerHello carries the negotiated protocol; a ClientHello lists the
+/// client's preferences, first one first.
+fn hello_alpn(payload: &[u8]) -> Option<&[u8]> {
+    const ALPN_EXTENSION: u16 = 0x0010;
+
+    // Record header: handshake, version, length; then handshake type and length
+    let [0x16, 0x03, _, _, _, kind @ (1 | 2), _, _, _, hello @ ..] = payload else {
+        return None;
+    };
+    let mut pos = 2 + 32; // version, random
+    let session_len = *hello.get(pos)? as usize;
+    pos += 1 + session_len;
+    if *kind == 1 {
+        let suites_len = be16(hello, pos)? as usize;
+        pos += 2 + suites_len;
+        let compression_len = *hello.get(pos)? as usize;
+        pos += 1 + compression_len;
+    } else {
+        pos += 2 + 1; // cipher suite, compression method
+    }
+
+    let extensions_end = pos + 2 + be16(hello, pos)? as usize;
+    pos += 2;
+    while pos + 4 <= extensions_end {
+        let ext_type = be16(hello, pos)?;
+        let ext_len = be16(hello, pos + 2)? as usize;
+        pos += 4;
+        if ext_type == ALPN_EXTENSION {
+            // Protocol name list length, then length-prefixed names
+            let name_len = *hello.get(pos + 2)? as usize;
+            return hello.get(pos + 3..pos + 3 + name_len);
+        }
+        pos += ext_len;
+    }
+    None
+}
+
+fn alpn_proto(name: &[u8]) -> Option<AppProto> {
+    match name {
+        b"h2" | b"h2c" => Some(AppProto::Http2),
+        b"http/1.1" | b"http/1.0" => Some(AppProto::Http1),
+        _ => None,
+    }
+}
+
+fn be16(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
@@ -0,0 +1,178 @@
+use crate::core::app_proto::looks_like_http;
+use crate::core::content_decoding;
+use crate::core::decoders::{PayloadDecoder, protobuf::ProtobufDecoder};
+use crate::core::models::ContentKind;
+
+/// Share of control characters above which decoded text is treated as binary.
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+pub struct Classification {
+    pub kind: ContentKind,
+    pub is_binary: bool,
+    /// Human readable rendering; empty for binary content.
+    pub text: String,
+    /// True when `text` is byte-for-byte the payload (plain UTF-8), in which
+    /// case the raw bytes do not need to be stored separately.
+    pub text_is_exact: bool,
+}
+
+impl Classification {
+    fn binary(kind: ContentKind) -> Self {
+        Self {
+            kind,
+            is_binary: true,
+            text: String::new(),
+            text_is_exact: false,
+        }
+    }
+
+    fn text(kind: ContentKind, text: String, text_is_exact: bool) -> Self {
+        Self {
+            kind,
+            is_binary: false,
+            text,
+            text_is_exact,
+        }
+    }
+}
+
+/// Classifies a captured payload and produces its text rendering.
+///
+/// Unlike a plain UTF-8 check this looks at the whole payload, splits HTTP
+/// messages so a binary body does not hide a textual header, and recognizes
+/// UTF-16 and Latin-1 text.
+pub fn classify_payload(payload: &[u8]) -> Classification {
+    if payload.is_empty() {
+        return Classification::text(ContentKind::Text, String::new(), true);
+    }
+
+    if is_tls_record(payload) {
+        return Classification::binary(ContentKind::TlsRecord);
+    }
+
+    if let Some(msg) = content_decoding::parse_http_message(payload) {
+        if let Ok(text) = std::str::from_utf8(payload) {
+            return Classification::text(ContentKind::Http, text.to_string(), true);
+        }
+        let body = match decode_text(msg.body) {
+            Some(body_text) => body_text,
+            None => format!("[binary body: {} bytes]", msg.body.len()),
+        };
+        return Classification::text(
+            ContentKind::Http,
+            format!("{}\r\n\r\n{}", msg.head, body),
+            false,
+        );
+    }
+
+    if let Ok(text) = std::str::from_utf8(payload)
+        && control_ratio(text) <= MAX_CONTROL_RATIO
+    {
+        let kind = if looks_like_http(payload) {
+            ContentKind::Http
+        } else {
+            ContentKind::Text
+        };
+        return Classification::text(kind, text.to_string(), true);
+    }
+
+    if let Some(text) = decode_text(payload) {
+        let kind = if looks_like_http(text.as_bytes()) {
+            ContentKind::Http
+        } else {
+            ContentKind::Text
+        };
+        return Classification::text(kind, text, false);
+    }
+
+    if payload.len() >= 2 && ProtobufDecoder.sniff(payload) {
+        return Classification::binary(ContentKind::Protobuf);
+    }
+
+    Classification::binary(ContentKind::Binary)
+}
+
+/// Tries UTF-8 (tolerating a character cut off at the end), UTF-16 and
+/// Latin-1, returning text only if it is mostly printable.
+fn decode_text(bytes: &[u8]) -> Option<String> {
+    let printable = |text: &String| control_ratio(text) <= MAX_CONTROL_RATIO;
+    decode_utf8(bytes)
+        .filter(printable)
+        .or_else(|| decode_utf16(bytes).filter(printable))
+        .or_else(|| decode_latin1(bytes).filter(printable))
+}
+
+fn decode_utf8(bytes: &[u8]) -> Option<String> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        // error_len() == None: the input ends in the middle of a character,
+        // which happens when ecapture splits a message across events.
+        Err(e) if e.error_len().is_none() => {
+            Some(String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let (little_endian, data) = match bytes {
+        [0xff, 0xfe, rest @ ..] => (true, rest),
+        [0xfe, 0xff, rest @ ..] => (false, rest),
+        _ => {
+            // Without a BOM, ASCII-heavy UTF-16 has a zero in every other byte.
+            let pairs = bytes.len() / 2;
+            if pairs < 2 {
+                return None;
+            }
+            let zeros_odd = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+            let zeros_even = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+            if zeros_odd * 10 >= pairs * 4 && zeros_even == 0 {
+                (true, bytes)
+            } else if zeros_even * 10 >= pairs * 4 && zeros_odd == 0 {
+                (false, bytes)
+            } else {
+                return None;
+            }
+        }
+    };
+
+    let units = data.chunks_exact(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+fn decode_latin1(bytes: &[u8]) -> Option<String> {
+    // C1 controls (0x80..0xA0) essentially never occur in real Latin-1 text.
+    if bytes.iter().any(|b| (0x80..0xa0).contains(b)) {
+        return None;
+    }
+    Some(bytes.iter().map(|&b| b as char).collect())
+}
+
+fn control_ratio(text: &str) -> f64 {
+    let mut total = 0usize;
+    let mut control = 0usize;
+    for c in text.chars() {
+        total += 1;
+        if c.is_control() && !matches!(c, '\t' | '\n' | '\r') {
+            control += 1;
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        control as f64 / total as f64
+    }
+}
+
+fn is_tls_record(payload: &[u8]) -> bool {
+    // ContentType (change_cipher_spec..heartbeat), then a 0x03xx legacy version
+    matches!(payload, [0x14..=0x18, 0x03, 0x00..=0x04, _, _, ..])
+}
@@ -0,0 +1,195 @@
+use std::io::Read;
+
+use crate::core::models::DecodedBody;
+
+/// Upper bound for a decompressed body. Anything beyond it is dropped and the
+/// body is flagged as truncated, which keeps decompression bombs in check.
+pub const MAX_DECODED_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// A minimally parsed HTTP/1.x message: start line, headers and raw body.
+pub struct HttpMessage<'a> {
+    pub head: &'a str,
+    pub headers: Vec<(&'a str, &'a str)>,
+    pub body: &'a [u8],
+}
+
+impl<'a> HttpMessage<'a> {
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| *v)
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding")
+            .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"))
+    }
+}
+
+/// Splits `payload` into HTTP head and body if it looks like an HTTP/1.x
+/// request or response.
+pub fn parse_http_message(payload: &[u8]) -> Option<HttpMessage<'_>> {
+    let head_end = find_subslice(payload, b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&payload[..head_end]).ok()?;
+
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next()?;
+    let is_response = start_line.starts_with("HTTP/1.");
+    let is_request = start_line
+        .rsplit_once(' ')
+        .is_some_and(|(_, version)| version.starts_with("HTTP/1."));
+    if !is_response && !is_request {
+        return None;
+    }
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect();
+
+    Some(HttpMessage {
+        head,
+        headers,
+        body: &payload[head_end + 4..],
+    })
+}
+
+/// Decodes the body of an HTTP message carrying a `Content-Encoding` (and
+/// optionally `Transfer-Encoding: chunked`).
+///
+/// Returns `None` when the payload is not HTTP or the body is not
+/// content-encoded, so callers can keep showing the raw payload.
+pub fn decode_http_body(payload: &[u8]) -> Option<DecodedBody> {
+    let msg = parse_http_message(payload)?;
+    let content_encoding = msg.header("Content-Encoding")?.to_ascii_lowercase();
+    let codings: Vec<&str> = content_encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty() && *c != "identity")
+        .collect();
+    if codings.is_empty() {
+        return None;
+    }
+
+    let mut error = None;
+    let mut truncated = false;
+    let mut body = if msg.is_chunked() {
+        dechunk(msg.body)
+    } else {
+        msg.body.to_vec()
+    };
+
+    // Codings are listed in the order they were applied, so undo them in reverse.
+    for coding in codings.iter().rev() {
+        match decompress(coding, &body) {
+            Ok((decoded, was_truncated)) => {
+                body = decoded;
+                truncated |= was_truncated;
+            }
+            Err((partial, e)) => {
+                error = Some(format!("{} decoding failed: {}", coding, e));
+                if !partial.is_empty() {
+                    body = partial;
+                }
+                break;
+            }
+        }
+    }
+
+    let (is_binary, body_utf8, body_binary) = match String::from_utf8(body) {
+        Ok(text) => (false, text, Vec::new()),
+        Err(e) => (true, String::new(), e.into_bytes()),
+    };
+
+    Some(DecodedBody {
+        content_encoding,
+        head: msg.head.to_string(),
+        is_binary,
+        body_utf8,
+        body_binary,
+        truncated,
+        error,
+    })
+}
+
+/// Decompresses `data` with a single content coding.
+///
+/// On failure the bytes produced so far are returned alongside the error, which
+/// is common for bodies split across several captured events.
+fn decompress(coding: &str, data: &[u8]) -> Result<(Vec<u8>, bool), (Vec<u8>, String)> {
+    match coding {
+        "gzip" | "x-gzip" => read_capped(flate2::read::MultiGzDecoder::new(data)),
+        "deflate" => {
+            // Per RFC 9110 "deflate" is zlib-wrapped, but many servers send raw DEFLATE.
+            read_capped(flate2::read::ZlibDecoder::new(data))
+                .or_else(|_| read_capped(flate2::read::DeflateDecoder::new(data)))
+        }
+        "br" => read_capped(brotli_decompressor::Decompressor::new(data, 4096)),
+        "zstd" => match zstd::stream::read::Decoder::new(data) {
+            Ok(decoder) => read_capped(decoder),
+            Err(e) => Err((Vec::new(), e.to_string())),
+        },
+        other => Err((
+            Vec::new(),
+            format!("unsupported content coding '{}'", other),
+        )),
+    }
+}
+
+fn read_capped<R: Read>(reader: R) -> Result<(Vec<u8>, bool), (Vec<u8>, String)> {
+    let mut out = Vec::new();
+    let mut limited = reader.take(MAX_DECODED_BODY_SIZE as u64 + 1);
+    match limited.read_to_end(&mut out) {
+        Ok(_) => {
+            let truncated = out.len() > MAX_DECODED_BODY_SIZE;
+            out.truncate(MAX_DECODED_BODY_SIZE);
+            Ok((out, truncated))
+        }
+        Err(e) => Err((out, e.to_string())),
+    }
+}
+
+/// Reassembles a `Transfer-Encoding: chunked` body. Stops quietly at the first
+/// malformed or incomplete chunk and returns what was collected so far.
+pub fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    while let Some(line_end) = find_subslice(data, b"\r\n") {
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_str, 16) else {
+            break;
+        };
+        if size == 0 {
+            break;
+        }
+        data = &data[line_end + 2..];
+        let take = size.min(data.len());
+        out.extend_from_slice(&data[..take]);
+        if take < size || data.len() < size + 2 {
+            break;
+        }
+        data = &data[size + 2..];
+    }
+    out
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Cuts `text` to at most `max_len` bytes on a character boundary, marking
+/// the cut with an ellipsis.
+pub fn truncate_text(mut text: String, max_len: usize) -> String {
+    if text.len() > max_len {
+        let mut end = max_len;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
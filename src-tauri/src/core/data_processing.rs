@@ -1,3 +1,7 @@
+use crate::core::app_proto::AppProto;
+use crate::core::classify;
+use crate::core::content_decoding;
+use crate::core::event_type::EventType;
 use crate::core::models::{self, ParsedMessage};
 use polars::prelude::*;
 use prost::Message;
@@ -15,7 +19,7 @@ use ecaptureq::events::{
     log_entry, // oneof payload 的内部模块
 };
 
-use anyhow::{Ok, Result, anyhow};
+use anyhow::{Ok, Result};
 
 pub fn write_batch_to_df(
     buffer: &[models::PacketData],
@@ -28,6 +32,8 @@ pub fn write_batch_to_df(
 
     let buffer_len = buffer.len();
     let mut index_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("index".into(), buffer_len);
+    let mut session_id_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("session_id".into(), buffer_len);
     let mut ts_builder = PrimitiveChunkedBuilder::<Int64Type>::new("timestamp".into(), buffer_len);
     let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), buffer_len);
     let mut src_ip_builder = StringChunkedBuilder::new("src_ip".into(), buffer_len);
@@ -38,16 +44,37 @@ pub fn write_batch_to_df(
         PrimitiveChunkedBuilder::<UInt32Type>::new("dst_port".into(), buffer_len);
     let mut pid_builder = PrimitiveChunkedBuilder::<Int32Type>::new("pid".into(), buffer_len);
     let mut pname_builder = StringChunkedBuilder::new("pname".into(), buffer_len);
+    let mut tid_builder = PrimitiveChunkedBuilder::<Int32Type>::new("tid".into(), buffer_len);
+    let mut uid_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("uid".into(), buffer_len);
+    let mut exe_path_builder = StringChunkedBuilder::new("exe_path".into(), buffer_len);
+    let mut cgroup_id_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("cgroup_id".into(), buffer_len);
+    let mut container_id_builder = StringChunkedBuilder::new("container_id".into(), buffer_len);
+    let mut package_builder = StringChunkedBuilder::new("package".into(), buffer_len);
+    let mut fd_builder = PrimitiveChunkedBuilder::<Int32Type>::new("fd".into(), buffer_len);
     let mut type_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("type".into(), buffer_len);
+    let mut type_name_builder = StringChunkedBuilder::new("type_name".into(), buffer_len);
+    let mut direction_builder = StringChunkedBuilder::new("direction".into(), buffer_len);
+    let mut module_builder = StringChunkedBuilder::new("module".into(), buffer_len);
     let mut length_builder =
         PrimitiveChunkedBuilder::<UInt32Type>::new("length".into(), buffer_len);
     let mut is_binary_builder = BooleanChunkedBuilder::new("is_binary".into(), buffer_len);
+    let mut content_kind_builder = StringChunkedBuilder::new("content_kind".into(), buffer_len);
+    let mut app_proto_builder = StringChunkedBuilder::new("app_proto".into(), buffer_len);
+    let mut tls_version_builder = StringChunkedBuilder::new("tls_version".into(), buffer_len);
+    let mut tls_cipher_builder = StringChunkedBuilder::new("tls_cipher".into(), buffer_len);
+    let mut tls_sni_builder = StringChunkedBuilder::new("tls_sni".into(), buffer_len);
+    let mut tls_alpn_builder = StringChunkedBuilder::new("tls_alpn".into(), buffer_len);
+    let mut tls_library_builder = StringChunkedBuilder::new("tls_library".into(), buffer_len);
     let mut payload_utf8_builder = StringChunkedBuilder::new("payload_utf8".into(), buffer_len);
     let mut payload_binary_builder = BinaryChunkedBuilder::new("payload_binary".into(), buffer_len);
+    let mut decoded_body_utf8_builder =
+        StringChunkedBuilder::new("decoded_body_utf8".into(), buffer_len);
 
     for d in buffer {
         index_builder.append_value(*next_index);
         *next_index += 1; // Increment index for next packet
+        session_id_builder.append_value(d.session_id);
         ts_builder.append_value(d.timestamp);
         uuid_builder.append_value(&d.uuid);
         src_ip_builder.append_value(&d.src_ip);
@@ -56,15 +83,40 @@ pub fn write_batch_to_df(
         dst_port_builder.append_value(d.dst_port);
         pid_builder.append_value(d.pid);
         pname_builder.append_value(&d.pname);
+        tid_builder.append_option(d.tid);
+        uid_builder.append_option(d.uid);
+        exe_path_builder.append_value(&d.exe_path);
+        cgroup_id_builder.append_option(d.cgroup_id);
+        container_id_builder.append_value(&d.container_id);
+        package_builder.append_value(&d.package);
+        fd_builder.append_option(d.fd);
         type_builder.append_value(d.r#type);
+        type_name_builder.append_value(&d.type_name);
+        direction_builder.append_value(&d.direction);
+        module_builder.append_option(d.module.map(|m| m.as_str()));
         length_builder.append_value(d.length);
         is_binary_builder.append_value(d.is_binary);
+        content_kind_builder.append_value(&d.content_kind);
+        app_proto_builder.append_value(&d.app_proto);
+        tls_version_builder.append_value(&d.tls_version);
+        tls_cipher_builder.append_value(&d.tls_cipher);
+        tls_sni_builder.append_value(&d.tls_sni);
+        tls_alpn_builder.append_value(&d.tls_alpn);
+        tls_library_builder.append_value(&d.tls_library);
         payload_utf8_builder.append_value(&d.payload_utf8);
         payload_binary_builder.append_value(&d.payload_binary);
+        // Only textual decoded bodies are kept, so they can be searched with SQL
+        let decoded_text = d
+            .decoded_body
+            .as_ref()
+            .filter(|b| !b.is_binary)
+            .map_or("", |b| b.body_utf8.as_str());
+        decoded_body_utf8_builder.append_value(decoded_text);
     }
 
     let column_vec = vec![
         index_builder.finish().into_column(),
+        session_id_builder.finish().into_column(),
         ts_builder.finish().into_column(),
         uuid_builder.finish().into_column(),
         src_ip_builder.finish().into_column(),
@@ -73,11 +125,29 @@ pub fn write_batch_to_df(
         dst_port_builder.finish().into_column(),
         pid_builder.finish().into_column(),
         pname_builder.finish().into_column(),
+        tid_builder.finish().into_column(),
+        uid_builder.finish().into_column(),
+        exe_path_builder.finish().into_column(),
+        cgroup_id_builder.finish().into_column(),
+        container_id_builder.finish().into_column(),
+        package_builder.finish().into_column(),
+        fd_builder.finish().into_column(),
         type_builder.finish().into_column(),
+        type_name_builder.finish().into_column(),
+        direction_builder.finish().into_column(),
+        module_builder.finish().into_column(),
         length_builder.finish().into_column(),
         is_binary_builder.finish().into_column(),
+        content_kind_builder.finish().into_column(),
+        app_proto_builder.finish().into_column(),
+        tls_version_builder.finish().into_column(),
+        tls_cipher_builder.finish().into_column(),
+        tls_sni_builder.finish().into_column(),
+        tls_alpn_builder.finish().into_column(),
+        tls_library_builder.finish().into_column(),
         payload_utf8_builder.finish().into_column(),
         payload_binary_builder.finish().into_column(),
+        decoded_body_utf8_builder.finish().into_column(),
     ];
 
     let batch_df = DataFrame::new(column_vec)?;
@@ -102,84 +172,86 @@ pub fn parse_eq_message<B: AsRef<[u8]>>(bytes: B) -> Result<ParsedMessage> {
             let log_message = models::ProcessLogMessage { log_info: runlog };
             Ok(ParsedMessage::ProcessLog(log_message))
         }
-        None => Err(anyhow!("missing payload in LogEntry")),
+        Some(log_entry::Payload::HandshakePayload(hs)) => {
+            Ok(ParsedMessage::Handshake(models::HandshakeMessage {
+                ecapture_version: hs.ecapture_version,
+                protocol_version: hs.protocol_version,
+            }))
+        }
+        // A payload variant added after this version is skipped by prost
+        None => Ok(ParsedMessage::Unsupported {
+            log_type: entry.log_type,
+        }),
     }
 }
 
 fn pb_event_to_packet(ev: PbEvent) -> models::PacketData {
-    // Saturating cast for pid (i64 -> i32)
-    let pid = if ev.pid > i32::MAX as i64 {
-        i32::MAX
-    } else if ev.pid < i32::MIN as i64 {
-        i32::MIN
-    } else {
-        ev.pid as i32
-    };
+    // tls and process are absent in events from older ecapture versions
+    let tls = ev.tls.unwrap_or_default();
+    let process = ev.process.unwrap_or_default();
 
-    // Check if payload is UTF-8 or binary
-    let is_binary = !is_utf8_prefix(&ev.payload);
+    // Undo Content-Encoding before the raw bytes are moved
+    let decoded_body = content_decoding::decode_http_body(&ev.payload);
 
-    let (payload_utf8, payload_binary) = if is_binary {
-        // Binary payload: store as Vec<u8>, empty string for UTF-8
-        (String::new(), ev.payload)
+    let classification = classify::classify_payload(&ev.payload);
+    let event_type = EventType::from_code(ev.r#type);
+    let payload_binary = if classification.text_is_exact {
+        Vec::new()
     } else {
-        // UTF-8 payload: store as string, empty Vec for binary
-        match String::from_utf8(ev.payload.clone()) {
-            std::result::Result::Ok(utf8_str) => (utf8_str, Vec::new()),
-            std::result::Result::Err(_) => {
-                // Fallback: treat as binary if conversion fails
-                (String::new(), ev.payload)
-            }
-        }
+        // Keep the original bytes whenever the text rendering is not exact
+        ev.payload
     };
 
     models::PacketData {
         index: 0, // Temporary: will be assigned in DataFrame
+        // Set by the websocket service
+        session_id: 0,
         timestamp: ev.timestamp,
         uuid: ev.uuid,
         src_ip: ev.src_ip,
         src_port: ev.src_port,
         dst_ip: ev.dst_ip,
         dst_port: ev.dst_port,
-        pid,
+        pid: saturating_i32(ev.pid),
         pname: ev.pname,
+        tid: process.tid.map(saturating_i32),
+        uid: process.uid,
+        exe_path: process.exe_path,
+        cgroup_id: process.cgroup_id,
+        container_id: process.container_id,
+        // Filled from the uid by the websocket service
+        package: String::new(),
+        fd: process.fd.map(saturating_i32),
         r#type: ev.r#type,
+        type_name: event_type.name().to_string(),
+        direction: event_type.direction().as_str().to_string(),
+        // Set by the websocket service
+        module: None,
         length: ev.length,
-        is_binary,
-        payload_utf8,
+        is_binary: classification.is_binary,
+        content_kind: classification.kind.as_str().to_string(),
+        // Labeled per connection by the actor, see AppProtoDetector
+        app_proto: AppProto::Unknown.as_str().to_string(),
+        tls_version: tls.version,
+        tls_cipher: tls.cipher,
+        tls_sni: tls.sni,
+        tls_alpn: tls.alpn,
+        tls_library: tls.library,
+        payload_utf8: classification.text,
         payload_binary,
+        decoded_body,
     }
 }
 
 fn pb_heartbeat_to_model(hb: PbHeartbeat) -> models::HeartbeatMessage {
-    let count = if hb.count > i32::MAX as i64 {
-        i32::MAX
-    } else if hb.count < i32::MIN as i64 {
-        i32::MIN
-    } else {
-        hb.count as i32
-    };
-
     models::HeartbeatMessage {
         timestamp: hb.timestamp,
-        count,
+        count: saturating_i32(hb.count),
         message: hb.message,
     }
 }
 
-pub fn is_utf8_prefix<T: AsRef<[u8]>>(data: T) -> bool {
-    let bytes = data.as_ref();
-
-    const MAX_CHECK_LEN: usize = 100;
-    let check_len = bytes.len().min(MAX_CHECK_LEN);
-    let prefix = &bytes[..check_len];
-
-    match std::str::from_utf8(prefix) {
-        std::result::Result::Ok(_) => true,
-        std::result::Result::Err(e) => {
-            // If error_len is None, it means the error is at the end of the input,
-            // indicating that what we have so far is valid UTF-8 (just incomplete)
-            e.error_len().is_none()
-        }
-    }
+/// Saturating cast for ids and counters sent as i64.
+fn saturating_i32(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
 }
@@ -0,0 +1,491 @@
+//! Database wire-protocol decoding backing the `db_queries` SQL table.
+//!
+//! Connections are recognized by well-known server port or by sniffing the
+//! first client message. Each protocol parser turns the client and server
+//! byte streams into statements and responses, which are paired in order to
+//! produce one row per statement.
+
+pub mod mysql;
+pub mod postgres;
+pub mod redis;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use polars::prelude::*;
+
+use crate::core::content_decoding::truncate_text;
+use crate::core::event_type::Direction;
+use crate::core::flows::{Orientation, flow_key};
+use crate::core::models::{ContentKind, DbQuery, PacketData};
+
+/// Connections whose unparsed bytes grow beyond this are considered desynced.
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// Unanswered statements beyond this are recorded without a response.
+const MAX_IN_FLIGHT: usize = 1024;
+
+/// Statements longer than this (bulk inserts, large Redis values) are cut.
+const MAX_STATEMENT_LEN: usize = 64 * 1024;
+
+/// Output of a protocol parser, in stream order.
+pub enum DbEvent {
+    /// The client sent a statement that expects a response.
+    Statement { kind: String, text: String },
+    /// The server finished responding to the oldest unanswered statement.
+    Response {
+        rows: Option<u64>,
+        error: Option<String>,
+    },
+}
+
+/// The stream no longer follows the protocol; the connection is dropped.
+#[derive(Debug)]
+pub struct Desync;
+
+pub trait WireParser: Send {
+    fn protocol(&self) -> &'static str;
+
+    /// Consumes complete client messages from the front of `buf`.
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync>;
+
+    /// Consumes complete server messages from the front of `buf`.
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync>;
+}
+
+struct DbConnection {
+    parser: Box<dyn WireParser>,
+    orientation: Orientation,
+    /// Way client data goes, seen from the hooked process.
+    client: Direction,
+    client_buf: Vec<u8>,
+    server_buf: Vec<u8>,
+    in_flight: VecDeque<InFlight>,
+}
+
+struct InFlight {
+    kind: String,
+    text: String,
+    packet_index: u64,
+    timestamp: i64,
+}
+
+pub struct DbQueryTable {
+    connections: HashMap<String, DbConnection>,
+    /// Connections known not to carry a supported database protocol.
+    ignored: HashSet<String>,
+    pending: Vec<DbQuery>,
+    next_id: u64,
+    df: DataFrame,
+}
+
+impl DbQueryTable {
+    pub fn new() -> Self {
+        Self {
+            connections: HashMap::new(),
+            ignored: HashSet::new(),
+            pending: Vec::new(),
+            next_id: 0,
+            df: create_db_queries_df(),
+        }
+    }
+
+    /// Feeds a batch of events. `first_index` is the packet index assigned to
+    /// the first event of the batch.
+    pub fn update(&mut self, batch: &[PacketData], first_index: u64) {
+        for (offset, d) in batch.iter().enumerate() {
+            let packet_index = first_index + offset as u64;
+            let key = flow_key(d);
+            if self.ignored.contains(&key) {
+                continue;
+            }
+            let payload = d.payload_bytes();
+
+            if !self.connections.contains_key(&key) {
+                match detect(d, payload) {
+                    Some(conn) => {
+                        self.connections.insert(key.clone(), conn);
+                    }
+                    None => {
+                        if d.content_kind == ContentKind::Http.as_str() {
+                            self.ignored.insert(key);
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let conn = self.connections.get_mut(&key).unwrap();
+            let mut events = Vec::new();
+            let result = if conn.orientation.direction(d) == conn.client {
+                conn.client_buf.extend_from_slice(payload);
+                conn.parser.client_data(&mut conn.client_buf, &mut events)
+            } else {
+                conn.server_buf.extend_from_slice(payload);
+                conn.parser.server_data(&mut conn.server_buf, &mut events)
+            };
+
+            let mut finished = Vec::new();
+            for event in events {
+                match event {
+                    DbEvent::Statement { kind, text } => {
+                        conn.in_flight.push_back(InFlight {
+                            kind,
+                            text,
+                            packet_index,
+                            timestamp: d.timestamp,
+                        });
+                        if conn.in_flight.len() > MAX_IN_FLIGHT {
+                            let stale = conn.in_flight.pop_front().unwrap();
+                            finished.push(query_row(d, conn.parser.protocol(), stale, None));
+                        }
+                    }
+                    DbEvent::Response { rows, error } => {
+                        if let Some(statement) = conn.in_flight.pop_front() {
+                            let response = (packet_index, d.timestamp, rows, error);
+                            finished.push(query_row(
+                                d,
+                                conn.parser.protocol(),
+                                statement,
+                                Some(response),
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let overflow = conn.client_buf.len() + conn.server_buf.len() > MAX_PENDING_BYTES;
+            if result.is_err() || overflow {
+                self.connections.remove(&key);
+                self.ignored.insert(key);
+            }
+
+            for mut row in finished {
+                row.query_id = self.next_id;
+                self.next_id += 1;
+                self.pending.push(row);
+            }
+        }
+    }
+
+    /// Queries decoded since the table was last returned, oldest first.
+    pub fn pending_mut(&mut self) -> &mut Vec<DbQuery> {
+        &mut self.pending
+    }
+
+    /// Returns the `db_queries` table, appending rows completed since the
+    /// last call.
+    pub fn as_df(&mut self) -> PolarsResult<DataFrame> {
+        if !self.pending.is_empty() {
+            let batch = db_queries_to_df(&std::mem::take(&mut self.pending))?;
+            self.df.vstack_mut(&batch)?;
+        }
+        Ok(self.df.clone())
+    }
+}
+
+impl Default for DbQueryTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type Response = (u64, i64, Option<u64>, Option<String>);
+
+type ParserFactory = fn() -> Box<dyn WireParser>;
+
+fn query_row(
+    d: &PacketData,
+    protocol: &str,
+    statement: InFlight,
+    response: Option<Response>,
+) -> DbQuery {
+    let (response_index, latency, rows, error) = match response {
+        Some((index, timestamp, rows, error)) => (
+            Some(index),
+            Some(timestamp - statement.timestamp),
+            rows,
+            error,
+        ),
+        None => (None, None, None, None),
+    };
+    DbQuery {
+        query_id: 0,
+        uuid: flow_key(d),
+        protocol: protocol.to_string(),
+        kind: statement.kind,
+        statement: truncate_text(statement.text, MAX_STATEMENT_LEN),
+        packet_index: statement.packet_index,
+        timestamp: statement.timestamp,
+        response_index,
+        latency,
+        rows,
+        error,
+    }
+}
+
+/// Picks a parser for a new connection, by server port first and by the
+/// shape of a client message second.
+fn detect(d: &PacketData, payload: &[u8]) -> Option<DbConnection> {
+    const PORTS: [(u32, ParserFactory); 3] = [
+        (3306, || Box::new(mysql::MysqlParser::new())),
+        (5432, || Box::new(postgres::PostgresParser::new())),
+        (6379, || Box::new(redis::RedisParser::new())),
+    ];
+
+    let orientation = Orientation::new(d);
+    let sent = orientation.direction(d);
+
+    let by_port = PORTS.iter().find_map(|(port, make)| {
+        if d.dst_port == *port {
+            Some((make(), sent))
+        } else if d.src_port == *port {
+            Some((make(), sent.opposite()))
+        } else {
+            None
+        }
+    });
+
+    let (parser, client): (Box<dyn WireParser>, Direction) = match by_port {
+        Some(found) => found,
+        None if redis::sniff_client(payload) => (Box::new(redis::RedisParser::new()), sent),
+        None if postgres::sniff_client(payload) => {
+            (Box::new(postgres::PostgresParser::new()), sent)
+        }
+        None if mysql::sniff_client(payload) => (Box::new(mysql::MysqlParser::new()), sent),
+        None => return None,
+    };
+
+    Some(DbConnection {
+        parser,
+        orientation,
+        client,
+        client_buf: Vec::new(),
+        server_buf: Vec::new(),
+        in_flight: VecDeque::new(),
+    })
+}
+
+/// Reads a NUL-terminated string starting at `pos`, returning it and the
+/// position after the terminator.
+pub(crate) fn read_cstr(data: &[u8], pos: usize) -> Option<(String, usize)> {
+    let len = data.get(pos..)?.iter().position(|&b| b == 0)?;
+    let text = String::from_utf8_lossy(&data[pos..pos + len]).into_owned();
+    Some((text, pos + len + 1))
+}
+
+fn db_queries_to_df(queries: &[DbQuery]) -> PolarsResult<DataFrame> {
+    let len = queries.len();
+    let mut query_id_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("query_id".into(), len);
+    let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), len);
+    let mut protocol_builder = StringChunkedBuilder::new("protocol".into(), len);
+    let mut kind_builder = StringChunkedBuilder::new("kind".into(), len);
+    let mut statement_builder = StringChunkedBuilder::new("statement".into(), len);
+    let mut packet_index_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("packet_index".into(), len);
+    let mut ts_builder = PrimitiveChunkedBuilder::<Int64Type>::new("timestamp".into(), len);
+    let mut response_index_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("response_index".into(), len);
+    let mut latency_builder = PrimitiveChunkedBuilder::<Int64Type>::new("latency".into(), len);
+    let mut rows_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("rows".into(), len);
+    let mut error_builder = StringChunkedBuilder::new("error".into(), len);
+
+    for q in queries {
+        query_id_builder.append_value(q.query_id);
+        uuid_builder.append_value(&q.uuid);
+        protocol_builder.append_value(&q.protocol);
+        kind_builder.append_value(&q.kind);
+        statement_builder.append_value(&q.statement);
+        packet_index_builder.append_value(q.packet_index);
+        ts_builder.append_value(q.timestamp);
+        response_index_builder.append_option(q.response_index);
+        latency_builder.append_option(q.latency);
+        rows_builder.append_option(q.rows);
+        error_builder.append_option(q.error.as_deref());
+    }
+
+    DataFrame::new(vec![
+        query_id_builder.finish().into_column(),
+        uuid_builder.finish().into_column(),
+        protocol_builder.finish().into_column(),
+        kind_builder.finish().into_column(),
+        statement_builder.finish().into_column(),
+        packet_index_builder.finish().into_column(),
+        ts_builder.finish().into_column(),
+        response_index_builder.finish().into_column(),
+        latency_builder.finish().into_column(),
+        rows_builder.finish().into_column(),
+        error_builder.finish().into_column(),
+    ])
+}
+
+pub fn create_db_queries_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("query_id".into(), DataType::UInt64),
+        Field::new("uuid".into(), DataType::String),
+        Field::new("protocol".into(), DataType::String),
+        Field::new("kind".into(), DataType::String),
+        Field::new("statement".into(), DataType::String),
+        Field::new("packet_index".into(), DataType::UInt64),
+        Field::new("timestamp".into(), DataType::Int64),
+        Field::new("response_index".into(), DataType::UInt64),
+        Field::new("latency".into(), DataType::Int64),
+        Field::new("rows".into(), DataType::UInt64),
+        Field::new("error".into(), DataType::String),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event between a client on port 5000 and a server on `port`, sent at
+    /// `timestamp`.
+    fn event(from_client: bool, port: u32, timestamp: i64, payload: &[u8]) -> PacketData {
+        let (src_port, dst_port) = if from_client {
+            (5000, port)
+        } else {
+            (port, 5000)
+        };
+        PacketData {
+            timestamp,
+            uuid: "conn".to_string(),
+            src_ip: if from_client { "10.0.0.1" } else { "10.0.0.2" }.to_string(),
+            src_port,
+            dst_ip: if from_client { "10.0.0.2" } else { "10.0.0.1" }.to_string(),
+            dst_port,
+            is_binary: true,
+            payload_binary: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// A MySQL packet: 3-byte length, sequence id, payload.
+    fn mysql(seq: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// A typed PostgreSQL message.
+    fn pg(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    /// PostgreSQL startup, authentication and the first ReadyForQuery.
+    fn pg_startup() -> Vec<PacketData> {
+        let params = b"user\0app\0\0";
+        let mut startup = (8 + params.len() as u32).to_be_bytes().to_vec();
+        startup.extend_from_slice(&(3u32 << 16).to_be_bytes());
+        startup.extend_from_slice(params);
+        let mut ready = pg(b'R', &[0, 0, 0, 0]);
+        ready.extend(pg(b'Z', b"I"));
+        vec![
+            event(true, 5432, 1, &startup),
+            event(false, 5432, 2, &ready),
+        ]
+    }
+
+    #[test]
+    fn mysql_query_round_trip() {
+        let mut table = DbQueryTable::new();
+        let mut result = mysql(1, &[1]);
+        result.extend(mysql(2, b"\x03def"));
+        result.extend(mysql(3, &[0xfe, 0, 0, 2, 0]));
+        result.extend(mysql(4, b"\x011"));
+        result.extend(mysql(5, &[0xfe, 0, 0, 2, 0]));
+        table.update(
+            &[
+                event(false, 3306, 1, &mysql(0, b"\x0a8.0.36\0")),
+                event(true, 3306, 10, &mysql(0, b"\x03SELECT 1")),
+                event(false, 3306, 15, &result),
+            ],
+            0,
+        );
+
+        let rows = table.pending_mut();
+        assert_eq!(rows.len(), 1);
+        let q = &rows[0];
+        assert_eq!((q.protocol.as_str(), q.kind.as_str()), ("mysql", "query"));
+        assert_eq!(q.statement, "SELECT 1");
+        assert_eq!((q.packet_index, q.response_index), (1, Some(2)));
+        assert_eq!(
+            (q.latency, q.rows, q.error.as_deref()),
+            (Some(5), Some(1), None)
+        );
+        assert_eq!(table.as_df().unwrap().height(), 1);
+    }
+
+    #[test]
+    fn truncated_statements_complete_in_a_later_event() {
+        let mut table = DbQueryTable::new();
+        let query = pg(b'Q', b"SELECT now()\0");
+        let mut events = pg_startup();
+        events.push(event(true, 5432, 3, &query[..3]));
+        events.push(event(true, 5432, 4, &query[3..]));
+        let mut reply = pg(b'D', &[0, 1, 0, 0, 0, 1, b'x']);
+        reply.extend(pg(b'C', b"SELECT 1\0"));
+        reply.extend(pg(b'Z', b"I"));
+        events.push(event(false, 5432, 6, &reply));
+        table.update(&events, 0);
+
+        let rows = table.pending_mut();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].statement, "SELECT now()");
+        assert_eq!((rows[0].packet_index, rows[0].rows), (3, Some(1)));
+        assert_eq!(rows[0].latency, Some(2));
+    }
+
+    #[test]
+    fn pipelined_commands_pair_with_replies_in_order() {
+        let mut table = DbQueryTable::new();
+        table.update(
+            &[
+                event(
+                    true,
+                    6379,
+                    1,
+                    b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$1\r\nk",
+                ),
+                event(false, 6379, 2, b"+PONG\r\n"),
+                event(true, 6379, 3, b"\r\n"),
+                event(false, 6379, 4, b"$-1\r\n"),
+            ],
+            0,
+        );
+
+        let rows: Vec<_> = table
+            .pending_mut()
+            .iter()
+            .map(|q| (q.statement.clone(), q.response_index, q.rows))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("PING".to_string(), Some(1), Some(1)),
+                ("GET k".to_string(), Some(3), Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_length_prefix_drops_the_connection() {
+        let mut table = DbQueryTable::new();
+        let mut events = pg_startup();
+        events.push(event(true, 5432, 3, &pg(b'Q', b"SELECT 1\0")));
+        let mut huge = vec![b'D'];
+        huge.extend_from_slice(&u32::MAX.to_be_bytes());
+        events.push(event(false, 5432, 4, &huge));
+        events.push(event(true, 5432, 5, &pg(b'Q', b"SELECT 2\0")));
+        table.update(&events, 0);
+
+        assert!(table.pending_mut().is_empty());
+        assert!(table.connections.is_empty());
+        assert!(table.ignored.contains("conn"));
+    }
+}
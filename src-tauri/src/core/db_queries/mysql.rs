@@ -0,0 +1,429 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{DbEvent, Desync, WireParser};
+
+/// Payload length marking a packet that continues in the next one.
+const MAX_PACKET_LEN: usize = 0xff_ffff;
+
+/// `SERVER_MORE_RESULTS_EXISTS` status flag.
+const MORE_RESULTS_EXISTS: u16 = 0x0008;
+
+const COM_INIT_DB: u8 = 0x02;
+const COM_QUERY: u8 = 0x03;
+const COM_PING: u8 = 0x0e;
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_STMT_EXECUTE: u8 = 0x17;
+const COM_STMT_CLOSE: u8 = 0x19;
+
+/// MySQL client/server protocol: text and prepared-statement commands with
+/// their OK, ERR and result set responses.
+pub struct MysqlParser {
+    /// Commands sent and not answered yet, oldest first.
+    awaiting: VecDeque<Command>,
+    server: ServerState,
+    /// Rows counted so far for the current command, across result sets.
+    rows: u64,
+    prepared: HashMap<u32, String>,
+    client_partial: Vec<u8>,
+    server_continuation: bool,
+}
+
+enum Command {
+    Query,
+    Prepare(String),
+}
+
+enum ServerState {
+    Idle,
+    /// Parameter and column definitions following a prepare response.
+    SkipDefinitions(u64),
+    Columns(u64),
+    /// After the column definitions: an EOF packet (old protocol), the first
+    /// row, or the end of an empty result set.
+    RowsStart,
+    Rows,
+}
+
+impl MysqlParser {
+    pub fn new() -> Self {
+        Self {
+            awaiting: VecDeque::new(),
+            server: ServerState::Idle,
+            rows: 0,
+            prepared: HashMap::new(),
+            client_partial: Vec::new(),
+            server_continuation: false,
+        }
+    }
+
+    fn client_command(&mut self, payload: &[u8], out: &mut Vec<DbEvent>) {
+        let Some((&command, args)) = payload.split_first() else {
+            return;
+        };
+        let (kind, text) = match command {
+            COM_QUERY => ("query", query_text(args)),
+            COM_INIT_DB => ("init_db", String::from_utf8_lossy(args).into_owned()),
+            COM_PING => ("ping", String::new()),
+            COM_STMT_PREPARE => {
+                let text = String::from_utf8_lossy(args).into_owned();
+                self.awaiting.push_back(Command::Prepare(text.clone()));
+                out.push(DbEvent::Statement {
+                    kind: "prepare".to_string(),
+                    text,
+                });
+                return;
+            }
+            COM_STMT_EXECUTE => {
+                let Some(id) = read_u32(args, 0) else {
+                    return;
+                };
+                let text = self
+                    .prepared
+                    .get(&id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("<statement {}>", id));
+                ("execute", text)
+            }
+            COM_STMT_CLOSE => {
+                if let Some(id) = read_u32(args, 0) {
+                    self.prepared.remove(&id);
+                }
+                return;
+            }
+            // COM_QUIT, COM_STMT_SEND_LONG_DATA and friends get no response;
+            // the rest start exchanges (e.g. COM_CHANGE_USER) not tracked here.
+            _ => return,
+        };
+        self.awaiting.push_back(Command::Query);
+        out.push(DbEvent::Statement {
+            kind: kind.to_string(),
+            text,
+        });
+    }
+
+    fn server_packet(&mut self, p: &[u8], out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let Some(&header) = p.first() else {
+            return Ok(());
+        };
+
+        match self.server {
+            ServerState::Idle => {
+                // Greeting and authentication exchange precede any command.
+                let Some(command) = self.awaiting.front() else {
+                    return Ok(());
+                };
+                match header {
+                    0xff => self.finish(None, Some(error_text(p)), out),
+                    0x00 if matches!(command, Command::Prepare(_)) && p.len() >= 12 => {
+                        let id = read_u32(p, 1).ok_or(Desync)?;
+                        let columns = u16::from_le_bytes([p[5], p[6]]) as u64;
+                        let params = u16::from_le_bytes([p[7], p[8]]) as u64;
+                        if let Some(Command::Prepare(text)) = self.awaiting.front() {
+                            self.prepared.insert(id, text.clone());
+                        }
+                        self.finish(None, None, out);
+                        if columns + params > 0 {
+                            self.server = ServerState::SkipDefinitions(columns + params);
+                        }
+                    }
+                    // A stray EOF, e.g. after prepared statement definitions
+                    0xfe if p.len() < 9 => {}
+                    0x00 | 0xfe => {
+                        let (affected, status) = ok_packet(p).ok_or(Desync)?;
+                        self.rows += affected;
+                        self.end_result(status, out);
+                    }
+                    // LOCAL INFILE request; the server sends an OK once the
+                    // client has uploaded the file.
+                    0xfb => {}
+                    _ => {
+                        let (columns, _) = read_lenenc(p, 0).ok_or(Desync)?;
+                        self.server = ServerState::Columns(columns);
+                    }
+                }
+            }
+            ServerState::SkipDefinitions(remaining) => {
+                if !(header == 0xfe && p.len() < 9) {
+                    self.server = match remaining - 1 {
+                        0 => ServerState::Idle,
+                        n => ServerState::SkipDefinitions(n),
+                    };
+                }
+            }
+            ServerState::Columns(remaining) => {
+                self.server = match remaining.saturating_sub(1) {
+                    0 => ServerState::RowsStart,
+                    n => ServerState::Columns(n),
+                };
+            }
+            ServerState::RowsStart | ServerState::Rows => {
+                let starting = matches!(self.server, ServerState::RowsStart);
+                match header {
+                    // EOF separating definitions from rows has exactly 5 bytes
+                    0xfe if starting && p.len() == 5 => self.server = ServerState::Rows,
+                    // A row never starts with 0xfe unless it fills a whole packet
+                    0xfe => {
+                        let status = if p.len() == 5 {
+                            u16::from_le_bytes([p[3], p[4]])
+                        } else {
+                            ok_packet(p).ok_or(Desync)?.1
+                        };
+                        self.end_result(status, out);
+                    }
+                    0xff => self.finish(None, Some(error_text(p)), out),
+                    _ => {
+                        self.rows += 1;
+                        self.server = ServerState::Rows;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_result(&mut self, status: u16, out: &mut Vec<DbEvent>) {
+        if status & MORE_RESULTS_EXISTS != 0 {
+            self.server = ServerState::Idle;
+        } else {
+            self.finish(Some(self.rows), None, out);
+        }
+    }
+
+    fn finish(&mut self, rows: Option<u64>, error: Option<String>, out: &mut Vec<DbEvent>) {
+        self.awaiting.pop_front();
+        self.server = ServerState::Idle;
+        self.rows = 0;
+        out.push(DbEvent::Response { rows, error });
+    }
+}
+
+impl Default for MysqlParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WireParser for MysqlParser {
+    fn protocol(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        while let Some((seq, payload)) = next_packet(buf, pos) {
+            pos += 4 + payload.len();
+            let continued = !self.client_partial.is_empty();
+            if payload.len() == MAX_PACKET_LEN {
+                self.client_partial.extend_from_slice(payload);
+                continue;
+            }
+            if continued {
+                let mut whole = std::mem::take(&mut self.client_partial);
+                whole.extend_from_slice(payload);
+                self.client_command(&whole, out);
+            } else if seq == 0 {
+                // Packets with a later sequence number belong to an exchange
+                // such as authentication, not to a new command.
+                self.client_command(payload, out);
+            }
+        }
+        buf.drain(..pos);
+        Ok(())
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        while let Some((_, payload)) = next_packet(buf, pos) {
+            pos += 4 + payload.len();
+            let continuation = self.server_continuation;
+            self.server_continuation = payload.len() == MAX_PACKET_LEN;
+            if continuation {
+                continue;
+            }
+            result = self.server_packet(payload, out);
+            if result.is_err() {
+                break;
+            }
+        }
+        buf.drain(..pos);
+        result
+    }
+}
+
+/// Recognizes a lone COM_QUERY packet carrying readable SQL.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    let [l0, l1, l2, 0, COM_QUERY, sql @ ..] = payload else {
+        return false;
+    };
+    let len = u32::from_le_bytes([*l0, *l1, *l2, 0]) as usize;
+    len == payload.len() - 4
+        && !sql.is_empty()
+        && std::str::from_utf8(sql)
+            .is_ok_and(|s| !s.chars().any(|c| c.is_control() && !c.is_whitespace()))
+}
+
+/// Recognizes the server's initial handshake (protocol version 10) packet.
+pub fn sniff_server(payload: &[u8]) -> bool {
+    let [l0, l1, l2, 0, 0x0a, rest @ ..] = payload else {
+        return false;
+    };
+    let len = u32::from_le_bytes([*l0, *l1, *l2, 0]) as usize;
+    // The server version string, e.g. "8.0.36", is NUL-terminated
+    len == payload.len() - 4
+        && rest
+            .iter()
+            .position(|&b| b == 0)
+            .is_some_and(|end| end > 0 && rest[..end].iter().all(u8::is_ascii_graphic))
+}
+
+fn next_packet(buf: &[u8], pos: usize) -> Option<(u8, &[u8])> {
+    let header = buf.get(pos..pos + 4)?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let payload = buf.get(pos + 4..pos + 4 + len)?;
+    Some((header[3], payload))
+}
+
+fn query_text(args: &[u8]) -> String {
+    // With CLIENT_QUERY_ATTRIBUTES the query is preceded by a parameter count
+    // and a parameter set count, which is 0 and 1 when no attributes are sent.
+    let sql = args.strip_prefix(&[0x00, 0x01]).unwrap_or(args);
+    String::from_utf8_lossy(sql).into_owned()
+}
+
+/// Affected rows and status flags of an OK packet.
+fn ok_packet(p: &[u8]) -> Option<(u64, u16)> {
+    let (affected, pos) = read_lenenc(p, 1)?;
+    let (_last_insert_id, pos) = read_lenenc(p, pos)?;
+    let status = p
+        .get(pos..pos + 2)
+        .map(|s| u16::from_le_bytes([s[0], s[1]]))?;
+    Some((affected, status))
+}
+
+fn error_text(p: &[u8]) -> String {
+    let code = p.get(1..3).map(|c| u16::from_le_bytes([c[0], c[1]]));
+    let rest = p.get(3..).unwrap_or_default();
+    let (state, message) = match rest {
+        [b'#', state @ ..] if state.len() >= 5 => (Some(&state[..5]), &state[5..]),
+        _ => (None, rest),
+    };
+    let message = String::from_utf8_lossy(message);
+    match (code, state) {
+        (Some(code), Some(state)) => {
+            format!(
+                "ERROR {} ({}): {}",
+                code,
+                String::from_utf8_lossy(state),
+                message
+            )
+        }
+        (Some(code), None) => format!("ERROR {}: {}", code, message),
+        _ => "ERROR".to_string(),
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a length-encoded integer, returning it and the position after it.
+fn read_lenenc(data: &[u8], pos: usize) -> Option<(u64, usize)> {
+    let width = match *data.get(pos)? {
+        n @ 0..=0xfa => return Some((n as u64, pos + 1)),
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => return None,
+    };
+    let bytes = data.get(pos + 1..pos + 1 + width)?;
+    let mut value = [0u8; 8];
+    value[..width].copy_from_slice(bytes);
+    Some((u64::from_le_bytes(value), pos + 1 + width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet: 3-byte length, sequence id, payload.
+    fn packet(seq: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Responses as `(rows, error)`.
+    fn responses(out: &[DbEvent]) -> Vec<(Option<u64>, Option<String>)> {
+        out.iter()
+            .filter_map(|e| match e {
+                DbEvent::Response { rows, error } => Some((*rows, error.clone())),
+                DbEvent::Statement { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prepared_statements_execute_with_their_text() {
+        let mut parser = MysqlParser::new();
+        let mut out = Vec::new();
+        let mut client = packet(0, b"\x16SELECT ?");
+        parser.client_data(&mut client, &mut out).unwrap();
+        // Statement 7, no columns, one parameter and its definition
+        let mut server = packet(1, &[0, 7, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        server.extend(packet(2, b"\x03def"));
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        let mut client = packet(0, &[COM_STMT_EXECUTE, 7, 0, 0, 0]);
+        parser.client_data(&mut client, &mut out).unwrap();
+        let mut server = packet(1, &[0, 3, 0, 2, 0, 0, 0]);
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        let statements: Vec<_> = out
+            .iter()
+            .filter_map(|e| match e {
+                DbEvent::Statement { kind, text } => Some((kind.as_str(), text.as_str())),
+                DbEvent::Response { .. } => None,
+            })
+            .collect();
+        assert_eq!(
+            statements,
+            [("prepare", "SELECT ?"), ("execute", "SELECT ?")]
+        );
+        assert_eq!(responses(&out), [(None, None), (Some(3), None)]);
+    }
+
+    #[test]
+    fn errors_carry_code_and_state() {
+        let mut parser = MysqlParser::new();
+        let mut out = Vec::new();
+        parser
+            .client_data(&mut packet(0, b"\x03SELEC"), &mut out)
+            .unwrap();
+        let mut server = packet(1, b"\xff\x28\x04#42000syntax error");
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        assert_eq!(
+            responses(&out),
+            [(None, Some("ERROR 1064 (42000): syntax error".to_string()))]
+        );
+    }
+
+    #[test]
+    fn truncated_packets_stay_buffered() {
+        let mut parser = MysqlParser::new();
+        let mut out = Vec::new();
+        let whole = packet(0, b"\x03SELECT 1");
+        let mut buf = whole[..6].to_vec();
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(buf.len(), 6);
+
+        buf.extend_from_slice(&whole[6..]);
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert_eq!(out.len(), 1);
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{DbEvent, Desync, WireParser, read_cstr};
+
+/// Messages larger than this are treated as a framing error.
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+const SSL_REQUEST: u32 = 80877103;
+const CANCEL_REQUEST: u32 = 80877102;
+const GSSENC_REQUEST: u32 = 80877104;
+
+/// PostgreSQL frontend/backend protocol, simple and extended query flow.
+///
+/// A statement is a simple `Query`, or the Parse/Bind/Execute messages sent
+/// up to a `Sync`; either is answered once the server reports
+/// `ReadyForQuery`.
+pub struct PostgresParser {
+    startup_done: bool,
+    /// Prepared statement name to query text.
+    statements: HashMap<String, String>,
+    /// Portal name to query text.
+    portals: HashMap<String, String>,
+    parsed: Vec<String>,
+    executed: Vec<String>,
+    /// One entry per Query or Sync sent; true if it produced a statement.
+    syncs: VecDeque<bool>,
+    data_rows: u64,
+    tag_rows: Option<u64>,
+    error: Option<String>,
+}
+
+impl PostgresParser {
+    pub fn new() -> Self {
+        Self {
+            startup_done: false,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+            parsed: Vec::new(),
+            executed: Vec::new(),
+            syncs: VecDeque::new(),
+            data_rows: 0,
+            tag_rows: None,
+            error: None,
+        }
+    }
+
+    fn typed_messages(
+        &mut self,
+        buf: &[u8],
+        pos: &mut usize,
+        out: &mut Vec<DbEvent>,
+        from_client: bool,
+    ) -> Result<(), Desync> {
+        if from_client && !self.startup_done {
+            return Ok(());
+        }
+        while let Some(header) = buf.get(*pos..*pos + 5) {
+            let tag = header[0];
+            let len = u32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+            if !tag.is_ascii_alphanumeric() || !(4..=MAX_MESSAGE_LEN).contains(&len) {
+                return Err(Desync);
+            }
+            let Some(body) = buf.get(*pos + 5..*pos + 1 + len) else {
+                break;
+            };
+            if from_client {
+                self.client_message(tag, body, out);
+            } else {
+                self.server_message(tag, body, out);
+            }
+            *pos += 1 + len;
+        }
+        Ok(())
+    }
+
+    fn client_message(&mut self, tag: u8, body: &[u8], out: &mut Vec<DbEvent>) {
+        match tag {
+            b'Q' => {
+                let text = read_cstr(body, 0).map(|(t, _)| t).unwrap_or_default();
+                self.syncs.push_back(true);
+                out.push(DbEvent::Statement {
+                    kind: "query".to_string(),
+                    text,
+                });
+            }
+            b'P' => {
+                if let Some((name, pos)) = read_cstr(body, 0)
+                    && let Some((query, _)) = read_cstr(body, pos)
+                {
+                    self.statements.insert(name, query.clone());
+                    self.parsed.push(query);
+                }
+            }
+            b'B' => {
+                if let Some((portal, pos)) = read_cstr(body, 0)
+                    && let Some((statement, _)) = read_cstr(body, pos)
+                {
+                    let query = self.statements.get(&statement).cloned();
+                    self.portals.insert(portal, query.unwrap_or_default());
+                }
+            }
+            b'E' => {
+                if let Some((portal, _)) = read_cstr(body, 0) {
+                    let query = self.portals.get(&portal).cloned();
+                    self.executed.push(query.unwrap_or_default());
+                }
+            }
+            b'C' => {
+                if let Some((&kind, rest)) = body.split_first()
+                    && let Some((name, _)) = read_cstr(rest, 0)
+                {
+                    match kind {
+                        b'S' => self.statements.remove(&name),
+                        _ => self.portals.remove(&name),
+                    };
+                }
+            }
+            b'S' => {
+                let executed = std::mem::take(&mut self.executed);
+                let parsed = std::mem::take(&mut self.parsed);
+                let statement = if !executed.is_empty() {
+                    Some(("execute", executed.join("; ")))
+                } else if !parsed.is_empty() {
+                    Some(("prepare", parsed.join("; ")))
+                } else {
+                    None
+                };
+                self.syncs.push_back(statement.is_some());
+                if let Some((kind, text)) = statement {
+                    out.push(DbEvent::Statement {
+                        kind: kind.to_string(),
+                        text,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn server_message(&mut self, tag: u8, body: &[u8], out: &mut Vec<DbEvent>) {
+        match tag {
+            b'D' => self.data_rows += 1,
+            b'C' => {
+                // Command tags end with the row count, e.g. "INSERT 0 5"
+                let count = read_cstr(body, 0)
+                    .and_then(|(t, _)| t.rsplit(' ').next().and_then(|n| n.parse::<u64>().ok()));
+                if let Some(count) = count {
+                    self.tag_rows = Some(self.tag_rows.unwrap_or(0) + count);
+                }
+            }
+            b'E' => self.error = Some(error_text(body)),
+            b'Z' => {
+                let rows = self.tag_rows.take().unwrap_or(self.data_rows);
+                let error = self.error.take();
+                self.data_rows = 0;
+                // The first ReadyForQuery follows authentication
+                if self.syncs.pop_front() == Some(true) {
+                    let rows = if error.is_some() { None } else { Some(rows) };
+                    out.push(DbEvent::Response { rows, error });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for PostgresParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WireParser for PostgresParser {
+    fn protocol(&self) -> &'static str {
+        "postgresql"
+    }
+
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        // Startup-phase messages have no type byte.
+        while !self.startup_done {
+            let Some(header) = buf.get(pos..pos + 8) else {
+                break;
+            };
+            let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+            let code = u32::from_be_bytes(header[4..].try_into().unwrap());
+            if !is_startup_code(code) || !(8..=10_000).contains(&len) {
+                self.startup_done = true;
+                break;
+            }
+            if buf.len() < pos + len {
+                break;
+            }
+            pos += len;
+            if code >> 16 == 3 {
+                self.startup_done = true;
+            }
+        }
+
+        let result = self.typed_messages(buf, &mut pos, out, true);
+        buf.drain(..pos);
+        result
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let result = self.typed_messages(buf, &mut pos, out, false);
+        buf.drain(..pos);
+        result
+    }
+}
+
+/// Recognizes a StartupMessage or a simple Query message.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    if let Some(header) = payload.get(..8) {
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let code = u32::from_be_bytes(header[4..].try_into().unwrap());
+        if code >> 16 == 3 && len == payload.len() {
+            return true;
+        }
+    }
+    match payload {
+        [b'Q', l0, l1, l2, l3, .., 0] => {
+            u32::from_be_bytes([*l0, *l1, *l2, *l3]) as usize == payload.len() - 1
+        }
+        _ => false,
+    }
+}
+
+fn is_startup_code(code: u32) -> bool {
+    code >> 16 == 3 || matches!(code, SSL_REQUEST | CANCEL_REQUEST | GSSENC_REQUEST)
+}
+
+/// Formats an ErrorResponse as "SEVERITY CODE: message".
+fn error_text(body: &[u8]) -> String {
+    let mut severity = String::new();
+    let mut code = String::new();
+    let mut message = String::new();
+    let mut pos = 0;
+    while let Some(&field) = body.get(pos)
+        && field != 0
+    {
+        let Some((value, next)) = read_cstr(body, pos + 1) else {
+            break;
+        };
+        match field {
+            b'V' => severity = value,
+            b'S' if severity.is_empty() => severity = value,
+            b'C' => code = value,
+            b'M' => message = value,
+            _ => {}
+        }
+        pos = next;
+    }
+    format!("{} {}: {}", severity, code, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A typed message.
+    fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    /// A parser past the startup phase.
+    fn started() -> PostgresParser {
+        PostgresParser {
+            startup_done: true,
+            ..PostgresParser::new()
+        }
+    }
+
+    #[test]
+    fn extended_query_is_one_statement_per_sync() {
+        let mut parser = started();
+        let mut out = Vec::new();
+        let mut client = message(b'P', b"s1\0SELECT $1\0\0\0");
+        client.extend(message(b'B', b"\0s1\0\0\0\0\0\0\0"));
+        client.extend(message(b'E', b"\0\0\0\0\0"));
+        client.extend(message(b'S', b""));
+        parser.client_data(&mut client, &mut out).unwrap();
+
+        let mut server = message(b'E', b"SERROR\0VERROR\0C22P02\0Mbad input\0\0");
+        server.extend(message(b'Z', b"I"));
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        assert!(matches!(
+            &out[..],
+            [
+                DbEvent::Statement { kind, text },
+                DbEvent::Response { rows: None, error: Some(error) },
+            ] if kind == "execute" && text == "SELECT $1" && error == "ERROR 22P02: bad input"
+        ));
+    }
+
+    #[test]
+    fn oversized_length_prefix_is_a_desync() {
+        let mut parser = started();
+        let mut out = Vec::new();
+        let mut buf = vec![b'Q'];
+        buf.extend_from_slice(&(MAX_MESSAGE_LEN as u32 + 1).to_be_bytes());
+        assert!(parser.client_data(&mut buf, &mut out).is_err());
+
+        let mut buf = vec![b'Q', 0, 0, 0, 2];
+        assert!(parser.client_data(&mut buf, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn sniffs_startup_and_simple_query() {
+        let params = b"user\0app\0\0";
+        let mut startup = (8 + params.len() as u32).to_be_bytes().to_vec();
+        startup.extend_from_slice(&(3u32 << 16).to_be_bytes());
+        startup.extend_from_slice(params);
+        assert!(sniff_client(&startup));
+        assert!(!sniff_client(&startup[..startup.len() - 1]));
+        assert!(sniff_client(&message(b'Q', b"SELECT 1\0")));
+        assert!(!sniff_client(&message(b'Q', b"SELECT 1")));
+    }
+}
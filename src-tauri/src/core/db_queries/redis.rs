@@ -0,0 +1,316 @@
+use super::{DbEvent, Desync, WireParser};
+
+/// Nesting deeper than this is treated as a framing error.
+const MAX_DEPTH: usize = 32;
+
+/// Declared lengths above this are treated as garbage.
+const MAX_LEN: i64 = 1 << 24;
+
+/// Commands after which the server pushes messages instead of replying.
+const SUBSCRIBE_COMMANDS: [&str; 4] = ["SUBSCRIBE", "PSUBSCRIBE", "SSUBSCRIBE", "MONITOR"];
+
+/// Redis RESP2/RESP3: one reply per command, in order.
+pub struct RedisParser {
+    /// Replies still expected from the server.
+    awaiting: usize,
+    /// Set once the connection entered pub/sub or monitor mode; only the
+    /// reply to the command that started it is paired after that.
+    subscribed: bool,
+}
+
+/// Command name followed by its arguments.
+type Args = Vec<Vec<u8>>;
+
+enum Value {
+    Text(Vec<u8>),
+    Error(Vec<u8>),
+    Null,
+    /// Logical element count (pairs for maps) and the elements themselves.
+    Aggregate(u64, Vec<Value>),
+    /// RESP3 attributes and out-of-band pushes, which are not replies.
+    OutOfBand,
+}
+
+impl RedisParser {
+    pub fn new() -> Self {
+        Self {
+            awaiting: 0,
+            subscribed: false,
+        }
+    }
+
+    fn command(&mut self, args: Args, out: &mut Vec<DbEvent>) {
+        if self.subscribed || args.is_empty() {
+            return;
+        }
+        let kind = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+        if SUBSCRIBE_COMMANDS.contains(&kind.as_str()) {
+            self.subscribed = true;
+        }
+        self.awaiting += 1;
+        out.push(DbEvent::Statement {
+            kind,
+            text: render_command(&args),
+        });
+    }
+}
+
+impl Default for RedisParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WireParser for RedisParser {
+    fn protocol(&self) -> &'static str {
+        "redis"
+    }
+
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        while pos < buf.len() {
+            let parsed = if buf[pos] == b'*' {
+                match parse_value(buf, pos, 0) {
+                    Ok(Some((Value::Aggregate(_, items), end))) => {
+                        let args = items.into_iter().map(|item| match item {
+                            Value::Text(bytes) => Ok(bytes),
+                            _ => Err(Desync),
+                        });
+                        args.collect::<Result<Vec<_>, _>>()
+                            .map(|args| Some((args, end)))
+                    }
+                    Ok(Some(_)) => Err(Desync),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            } else {
+                parse_inline(buf, pos)
+            };
+            match parsed {
+                Ok(Some((args, end))) => {
+                    self.command(args, out);
+                    pos = end;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        buf.drain(..pos);
+        result
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<DbEvent>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        while pos < buf.len() {
+            match parse_value(buf, pos, 0) {
+                Ok(Some((value, end))) => {
+                    pos = end;
+                    if matches!(value, Value::OutOfBand) || self.awaiting == 0 {
+                        continue;
+                    }
+                    self.awaiting -= 1;
+                    let (rows, error) = match value {
+                        Value::Error(message) => {
+                            (None, Some(String::from_utf8_lossy(&message).into_owned()))
+                        }
+                        Value::Null => (Some(0), None),
+                        Value::Aggregate(len, _) => (Some(len), None),
+                        _ => (Some(1), None),
+                    };
+                    out.push(DbEvent::Response { rows, error });
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        buf.drain(..pos);
+        result
+    }
+}
+
+/// Recognizes a command sent as a RESP array of bulk strings.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    matches!(payload, [b'*', b'1'..=b'9', ..])
+        && content_line(payload, 0).is_some_and(|(_, next)| payload.get(next) == Some(&b'$'))
+}
+
+/// Parses one value starting at `pos`. Returns `Ok(None)` when the buffer
+/// ends before the value does.
+fn parse_value(buf: &[u8], pos: usize, depth: usize) -> Result<Option<(Value, usize)>, Desync> {
+    if depth > MAX_DEPTH {
+        return Err(Desync);
+    }
+    let Some((line, next)) = content_line(buf, pos) else {
+        return Ok(None);
+    };
+    let (&kind, rest) = line.split_first().ok_or(Desync)?;
+
+    let value = match kind {
+        b'+' | b',' | b'#' | b'(' => Value::Text(rest.to_vec()),
+        b'-' => Value::Error(rest.to_vec()),
+        b':' => Value::Text(rest.to_vec()),
+        b'_' => Value::Null,
+        b'$' | b'!' | b'=' => {
+            let len = parse_len(rest)?;
+            if len < 0 {
+                return Ok(Some((Value::Null, next)));
+            }
+            let end = next + len as usize;
+            let Some(bytes) = buf.get(next..end) else {
+                return Ok(None);
+            };
+            match buf.get(end..end + 2) {
+                None => return Ok(None),
+                Some(b"\r\n") => {}
+                Some(_) => return Err(Desync),
+            }
+            let bytes = match kind {
+                b'!' => return Ok(Some((Value::Error(bytes.to_vec()), end + 2))),
+                // Verbatim strings start with a three letter format, e.g. "txt:"
+                b'=' => bytes.get(4..).unwrap_or_default(),
+                _ => bytes,
+            };
+            return Ok(Some((Value::Text(bytes.to_vec()), end + 2)));
+        }
+        b'*' | b'~' | b'%' | b'|' | b'>' => {
+            let len = parse_len(rest)?;
+            if len < 0 {
+                return Ok(Some((Value::Null, next)));
+            }
+            let count = if matches!(kind, b'%' | b'|') {
+                len * 2
+            } else {
+                len
+            };
+            let mut items = Vec::new();
+            let mut pos = next;
+            for _ in 0..count {
+                match parse_value(buf, pos, depth + 1)? {
+                    Some((item, end)) => {
+                        items.push(item);
+                        pos = end;
+                    }
+                    None => return Ok(None),
+                }
+            }
+            let value = match kind {
+                b'|' | b'>' => Value::OutOfBand,
+                _ => Value::Aggregate(len as u64, items),
+            };
+            return Ok(Some((value, pos)));
+        }
+        _ => return Err(Desync),
+    };
+    Ok(Some((value, next)))
+}
+
+/// Parses an inline command such as `PING\r\n`.
+fn parse_inline(buf: &[u8], pos: usize) -> Result<Option<(Args, usize)>, Desync> {
+    let Some((line, next)) = content_line(buf, pos) else {
+        return Ok(None);
+    };
+    if line.iter().any(|b| b.is_ascii_control() && *b != b'\t') {
+        return Err(Desync);
+    }
+    let args = line
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect();
+    Ok(Some((args, next)))
+}
+
+fn parse_len(digits: &[u8]) -> Result<i64, Desync> {
+    let len = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or(Desync)?;
+    if len > MAX_LEN {
+        return Err(Desync);
+    }
+    Ok(len)
+}
+
+/// Returns the line at `pos` without its CRLF and the position after it.
+fn content_line(buf: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let len = buf.get(pos..)?.windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[pos..pos + len], pos + len + 2))
+}
+
+fn render_command(args: &[Vec<u8>]) -> String {
+    args.iter()
+        .map(|arg| match std::str::from_utf8(arg) {
+            Ok(s) if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '"') => {
+                s.to_string()
+            }
+            _ => format!("{:?}", String::from_utf8_lossy(arg)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_wait_for_their_whole_value() {
+        let mut parser = RedisParser::new();
+        let mut out = Vec::new();
+        let mut client = b"*2\r\n$4\r\nKEYS\r\n$1\r\n*\r\n".to_vec();
+        parser.client_data(&mut client, &mut out).unwrap();
+
+        let reply = b"*2\r\n$1\r\na\r\n$1\r\nb\r\n";
+        let mut server = reply[..12].to_vec();
+        parser.server_data(&mut server, &mut out).unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!(server.len(), 12);
+
+        server.extend_from_slice(&reply[12..]);
+        parser.server_data(&mut server, &mut out).unwrap();
+        assert!(matches!(
+            &out[..],
+            [
+                DbEvent::Statement { kind, text },
+                DbEvent::Response { rows: Some(2), error: None },
+            ] if kind == "KEYS" && text == "KEYS *"
+        ));
+    }
+
+    #[test]
+    fn pushes_between_replies_are_skipped() {
+        let mut parser = RedisParser::new();
+        let mut out = Vec::new();
+        parser
+            .client_data(&mut b"GET k\r\n".to_vec(), &mut out)
+            .unwrap();
+        let mut server = b">2\r\n+invalidate\r\n*1\r\n$1\r\nk\r\n-ERR wrong type\r\n".to_vec();
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        assert!(matches!(
+            &out[1..],
+            [DbEvent::Response { rows: None, error: Some(error) }] if error == "ERR wrong type"
+        ));
+    }
+
+    #[test]
+    fn oversized_or_deep_values_are_a_desync() {
+        let mut parser = RedisParser::new();
+        let mut out = Vec::new();
+        let mut huge = format!("*1\r\n${}\r\n", MAX_LEN + 1).into_bytes();
+        assert!(parser.client_data(&mut huge, &mut out).is_err());
+
+        let mut deep = "*1\r\n".repeat(MAX_DEPTH + 2).into_bytes();
+        deep.extend_from_slice(b":1\r\n");
+        assert!(parser.server_data(&mut deep, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+}
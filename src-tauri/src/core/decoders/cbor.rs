@@ -0,0 +1,68 @@
+use anyhow::{Result, anyhow};
+use ciborium::Value as CborValue;
+use serde_json::{Map, Value, json};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+/// Self-describe CBOR tag (55799), often used as a magic number.
+const SELF_DESCRIBE_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// CBOR documents. Byte strings are hex encoded and tags are kept as
+/// `{ "$tag": n, "value": ... }`.
+pub struct CborDecoder;
+
+impl PayloadDecoder for CborDecoder {
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        mime == "application/cbor" || mime.ends_with("+cbor")
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        // Top-level maps only, the array range collides with MessagePack.
+        body.starts_with(&SELF_DESCRIBE_TAG) || matches!(body.first(), Some(0xa0..=0xbf))
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        let mut rest = input.body;
+        let value: CborValue = ciborium::de::from_reader(&mut rest)?;
+        if !rest.is_empty() {
+            return Err(anyhow!("{} trailing bytes after CBOR value", rest.len()));
+        }
+        Ok(to_json(value))
+    }
+}
+
+fn to_json(value: CborValue) -> Value {
+    match value {
+        CborValue::Null => Value::Null,
+        CborValue::Bool(b) => Value::Bool(b),
+        CborValue::Integer(i) => {
+            let n = i128::from(i);
+            match (u64::try_from(n), i64::try_from(n)) {
+                (Ok(u), _) => Value::from(u),
+                (_, Ok(s)) => Value::from(s),
+                _ => Value::String(n.to_string()),
+            }
+        }
+        CborValue::Float(f) => Value::from(f),
+        CborValue::Text(s) => Value::String(s),
+        CborValue::Bytes(bytes) => json!({ "$hex": hex::encode(bytes) }),
+        CborValue::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        CborValue::Map(entries) => {
+            let mut map = Map::new();
+            for (k, v) in entries {
+                let key = match to_json(k) {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                map.insert(key, to_json(v));
+            }
+            Value::Object(map)
+        }
+        CborValue::Tag(tag, inner) => json!({ "$tag": tag, "value": to_json(*inner) }),
+        _ => Value::Null,
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+/// `application/x-www-form-urlencoded` bodies. Repeated keys become arrays.
+pub struct FormDecoder;
+
+impl PayloadDecoder for FormDecoder {
+    fn name(&self) -> &'static str {
+        "form"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        mime == "application/x-www-form-urlencoded"
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        !body.is_empty()
+            && body.contains(&b'=')
+            && body
+                .iter()
+                .all(|b| b.is_ascii_alphanumeric() || b"=&%+-_.~*[]".contains(b))
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        let text =
+            std::str::from_utf8(input.body).map_err(|_| anyhow!("form body is not valid UTF-8"))?;
+
+        let mut fields = Map::new();
+        for (key, value) in form_urlencoded::parse(text.trim().as_bytes()) {
+            let value = Value::String(value.into_owned());
+            match fields.get_mut(key.as_ref()) {
+                Some(Value::Array(values)) => values.push(value),
+                Some(existing) => {
+                    let first = existing.take();
+                    *existing = Value::Array(vec![first, value]);
+                }
+                None => {
+                    fields.insert(key.into_owned(), value);
+                }
+            }
+        }
+        Ok(Value::Object(fields))
+    }
+}
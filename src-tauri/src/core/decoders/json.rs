@@ -0,0 +1,27 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+pub struct JsonDecoder;
+
+impl PayloadDecoder for JsonDecoder {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        mime == "application/json" || mime.ends_with("+json") || mime == "text/json"
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        matches!(
+            body.iter().find(|b| !b.is_ascii_whitespace()),
+            Some(b'{') | Some(b'[')
+        )
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        Ok(serde_json::from_slice(input.body)?)
+    }
+}
@@ -0,0 +1,169 @@
+//! Structured payload decoders.
+//!
+//! Each decoder turns a body into a `serde_json::Value` tree that the detail
+//! view can render. Decoders are picked by `Content-Type` first and by content
+//! sniffing second; callers may also ask for one explicitly by name.
+
+pub mod cbor;
+pub mod form;
+pub mod json;
+pub mod msgpack;
+pub mod multipart;
+pub mod protobuf;
+pub mod xml;
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+use crate::core::content_decoding;
+use crate::core::models::DecodedPayload;
+
+/// Body to decode plus the media type it was declared with, if any.
+pub struct DecodeInput<'a> {
+    /// Full `Content-Type` value, parameters included (e.g. multipart boundary).
+    pub content_type: Option<&'a str>,
+    pub body: &'a [u8],
+    /// Multipart parts the body is nested in, 0 for a whole payload.
+    pub depth: usize,
+}
+
+impl DecodeInput<'_> {
+    /// Lowercased media type without parameters.
+    pub fn mime(&self) -> Option<String> {
+        self.content_type.map(|ct| {
+            ct.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        })
+    }
+
+    /// Looks up a `Content-Type` parameter such as `boundary` or `charset`.
+    pub fn content_type_param(&self, name: &str) -> Option<&str> {
+        self.content_type?.split(';').skip(1).find_map(|param| {
+            let (k, v) = param.split_once('=')?;
+            k.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| v.trim().trim_matches('"'))
+        })
+    }
+}
+
+pub trait PayloadDecoder: Send + Sync {
+    /// Stable identifier, also accepted by the `decode_payload` command.
+    fn name(&self) -> &'static str;
+
+    /// Whether the declared media type is handled by this decoder.
+    fn matches_mime(&self, mime: &str) -> bool;
+
+    /// Cheap check on the body itself, used when no media type matched.
+    fn sniff(&self, body: &[u8]) -> bool;
+
+    fn decode(&self, input: &DecodeInput, registry: &DecoderRegistry) -> Result<Value>;
+}
+
+pub struct DecoderRegistry {
+    decoders: Vec<Box<dyn PayloadDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self {
+            decoders: Vec::new(),
+        }
+    }
+
+    /// Registers a decoder. Sniffing tries decoders in registration order, so
+    /// strict formats should be registered before permissive ones.
+    pub fn register(&mut self, decoder: Box<dyn PayloadDecoder>) {
+        self.decoders.push(decoder);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.decoders.iter().map(|d| d.name()).collect()
+    }
+
+    pub fn decode(&self, input: &DecodeInput, requested: Option<&str>) -> Result<DecodedPayload> {
+        if let Some(name) = requested {
+            let decoder = self
+                .decoders
+                .iter()
+                .find(|d| d.name() == name)
+                .ok_or_else(|| anyhow!("unknown decoder '{}'", name))?;
+            return Ok(decoded(
+                decoder.as_ref(),
+                input,
+                decoder.decode(input, self)?,
+            ));
+        }
+
+        if let Some(mime) = input.mime()
+            && let Some(decoder) = self.decoders.iter().find(|d| d.matches_mime(&mime))
+        {
+            return Ok(decoded(
+                decoder.as_ref(),
+                input,
+                decoder.decode(input, self)?,
+            ));
+        }
+
+        for decoder in self.decoders.iter().filter(|d| d.sniff(input.body)) {
+            if let Ok(tree) = decoder.decode(input, self) {
+                return Ok(decoded(decoder.as_ref(), input, tree));
+            }
+        }
+
+        Err(anyhow!("no decoder recognized the payload"))
+    }
+}
+
+impl Default for DecoderRegistry {
+    /// Registry with all built-in decoders.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(json::JsonDecoder));
+        registry.register(Box::new(xml::XmlDecoder));
+        registry.register(Box::new(multipart::MultipartDecoder));
+        registry.register(Box::new(form::FormDecoder));
+        registry.register(Box::new(cbor::CborDecoder));
+        registry.register(Box::new(msgpack::MsgpackDecoder));
+        registry.register(Box::new(protobuf::ProtobufDecoder));
+        registry
+    }
+}
+
+fn decoded(decoder: &dyn PayloadDecoder, input: &DecodeInput, tree: Value) -> DecodedPayload {
+    DecodedPayload {
+        decoder: decoder.name().to_string(),
+        content_type: input.content_type.map(str::to_string),
+        tree,
+    }
+}
+
+/// Extracts the body to decode from a captured payload.
+///
+/// HTTP messages are split into headers and body, with chunked transfer and
+/// content encodings undone; anything else is decoded as a whole.
+pub fn body_from_payload(payload: &[u8]) -> (Option<String>, Vec<u8>) {
+    let Some(msg) = content_decoding::parse_http_message(payload) else {
+        return (None, payload.to_vec());
+    };
+
+    let content_type = msg.header("Content-Type").map(str::to_string);
+    let body = match content_decoding::decode_http_body(payload) {
+        Some(decoded) if decoded.is_binary => decoded.body_binary,
+        Some(decoded) => decoded.body_utf8.into_bytes(),
+        None if msg.is_chunked() => content_decoding::dechunk(msg.body),
+        None => msg.body.to_vec(),
+    };
+    (content_type, body)
+}
+
+/// Renders bytes that have no better representation.
+pub(crate) fn bytes_value(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Value::String(text.to_string()),
+        Err(_) => serde_json::json!({ "$hex": hex::encode(bytes) }),
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, anyhow};
+use rmpv::Value as MsgpackValue;
+use serde_json::{Map, Value, json};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+/// MessagePack documents. Binary and extension values are hex encoded.
+pub struct MsgpackDecoder;
+
+impl PayloadDecoder for MsgpackDecoder {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        matches!(
+            mime,
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack"
+        )
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        // Only top-level maps and arrays; scalars are too ambiguous to sniff.
+        matches!(body.first(), Some(0x80..=0x9f | 0xdc..=0xdf))
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        let mut rest = input.body;
+        let value = rmpv::decode::read_value(&mut rest)?;
+        if !rest.is_empty() {
+            return Err(anyhow!(
+                "{} trailing bytes after MessagePack value",
+                rest.len()
+            ));
+        }
+        Ok(to_json(value))
+    }
+}
+
+fn to_json(value: MsgpackValue) -> Value {
+    match value {
+        MsgpackValue::Nil => Value::Null,
+        MsgpackValue::Boolean(b) => Value::Bool(b),
+        MsgpackValue::Integer(i) => match (i.as_u64(), i.as_i64()) {
+            (Some(u), _) => Value::from(u),
+            (None, Some(s)) => Value::from(s),
+            (None, None) => Value::Null,
+        },
+        MsgpackValue::F32(f) => Value::from(f64::from(f)),
+        MsgpackValue::F64(f) => Value::from(f),
+        MsgpackValue::String(s) => match s.into_str() {
+            Some(text) => Value::String(text),
+            None => Value::Null,
+        },
+        MsgpackValue::Binary(bytes) => json!({ "$hex": hex::encode(bytes) }),
+        MsgpackValue::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        MsgpackValue::Map(entries) => {
+            let mut map = Map::new();
+            for (k, v) in entries {
+                let key = match to_json(k) {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                map.insert(key, to_json(v));
+            }
+            Value::Object(map)
+        }
+        MsgpackValue::Ext(kind, data) => json!({ "$ext": kind, "$hex": hex::encode(data) }),
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value, json};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder, bytes_value};
+
+/// Binary parts larger than this are summarized instead of hex-dumped.
+const MAX_INLINE_BINARY_PART: usize = 4096;
+/// Parts nested deeper than this are shown raw, so that a crafted body
+/// cannot recurse without bound.
+const MAX_DEPTH: usize = 8;
+
+/// `multipart/*` bodies. Each part's content is decoded again through the
+/// registry, so a JSON part shows up as a JSON tree.
+pub struct MultipartDecoder;
+
+impl PayloadDecoder for MultipartDecoder {
+    fn name(&self) -> &'static str {
+        "multipart"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        mime.starts_with("multipart/")
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        body.starts_with(b"--") && sniff_boundary(body).is_some()
+    }
+
+    fn decode(&self, input: &DecodeInput, registry: &DecoderRegistry) -> Result<Value> {
+        let boundary = match input.content_type_param("boundary") {
+            Some(b) => b.to_string(),
+            None => sniff_boundary(input.body).ok_or_else(|| anyhow!("missing boundary"))?,
+        };
+        let delimiter = format!("--{}", boundary).into_bytes();
+
+        let mut sections = split_on(input.body, &delimiter);
+        // Everything before the first delimiter is preamble.
+        if sections.len() < 2 {
+            return Err(anyhow!("no multipart delimiter found"));
+        }
+        sections.remove(0);
+
+        let mut parts = Vec::new();
+        for section in sections {
+            // The closing delimiter is followed by "--"
+            if section.starts_with(b"--") {
+                break;
+            }
+            let section = section.strip_prefix(b"\r\n").unwrap_or(section);
+            let section = section.strip_suffix(b"\r\n").unwrap_or(section);
+            parts.push(decode_part(section, registry, input.depth + 1));
+        }
+        Ok(Value::Array(parts))
+    }
+}
+
+/// Decodes a part nested `depth` multipart bodies deep.
+fn decode_part(section: &[u8], registry: &DecoderRegistry, depth: usize) -> Value {
+    let (head, body) = match find(section, b"\r\n\r\n") {
+        Some(pos) => (&section[..pos], &section[pos + 4..]),
+        None => (&b""[..], section),
+    };
+
+    let mut headers = Map::new();
+    for line in String::from_utf8_lossy(head).split("\r\n") {
+        if let Some((k, v)) = line.split_once(':') {
+            headers.insert(
+                k.trim().to_ascii_lowercase(),
+                Value::String(v.trim().to_string()),
+            );
+        }
+    }
+
+    let content_type = headers.get("content-type").and_then(Value::as_str);
+    let disposition = headers.get("content-disposition").and_then(Value::as_str);
+    let name = disposition.and_then(|d| disposition_param(d, "name"));
+    let filename = disposition.and_then(|d| disposition_param(d, "filename"));
+
+    let nested = if depth < MAX_DEPTH {
+        let input = DecodeInput {
+            content_type,
+            body,
+            depth,
+        };
+        registry.decode(&input, None)
+    } else {
+        Err(anyhow!(
+            "multipart nested more than {} levels deep",
+            MAX_DEPTH
+        ))
+    };
+    let (decoder, content) = match nested {
+        Ok(decoded) => (Value::String(decoded.decoder), decoded.tree),
+        Err(_) if body.len() > MAX_INLINE_BINARY_PART && std::str::from_utf8(body).is_err() => {
+            (Value::Null, json!({ "$binary_length": body.len() }))
+        }
+        Err(_) => (Value::Null, bytes_value(body)),
+    };
+
+    json!({
+        "name": name,
+        "filename": filename,
+        "headers": headers,
+        "decoder": decoder,
+        "content": content,
+    })
+}
+
+fn disposition_param(disposition: &str, param: &str) -> Option<String> {
+    disposition.split(';').skip(1).find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case(param)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+fn sniff_boundary(body: &[u8]) -> Option<String> {
+    let line_end = find(body, b"\r\n")?;
+    let boundary = std::str::from_utf8(body.get(2..line_end)?).ok()?;
+    (!boundary.is_empty() && boundary.len() <= 70).then(|| boundary.to_string())
+}
+
+fn split_on<'a>(data: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut sections = Vec::new();
+    let mut rest = data;
+    while let Some(pos) = find(rest, delimiter) {
+        sections.push(&rest[..pos]);
+        rest = &rest[pos + delimiter.len()..];
+    }
+    sections.push(rest);
+    sections
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A body with a multipart part nested `levels` times, around a JSON part.
+    fn nested_body(levels: usize) -> Vec<u8> {
+        let mut body = b"Content-Type: application/json\r\n\r\n{\"a\":1}".to_vec();
+        for level in 0..levels {
+            let boundary = format!("b{}", level);
+            let mut outer = format!(
+                "Content-Type: multipart/mixed; boundary={}\r\n\r\n--{}\r\n",
+                boundary, boundary
+            )
+            .into_bytes();
+            outer.extend_from_slice(&body);
+            outer.extend(format!("\r\n--{}--\r\n", boundary).bytes());
+            body = outer;
+        }
+        body
+    }
+
+    /// Decodes `levels` nested multipart bodies as a payload.
+    fn decode_nested(levels: usize) -> Value {
+        let body = nested_body(levels);
+        let (head, body) = body.split_at(find(&body, b"\r\n\r\n").unwrap() + 4);
+        let content_type = std::str::from_utf8(head).unwrap();
+        let input = DecodeInput {
+            content_type: content_type.trim_end().strip_prefix("Content-Type: "),
+            body,
+            depth: 0,
+        };
+        DecoderRegistry::default()
+            .decode(&input, None)
+            .unwrap()
+            .tree
+    }
+
+    /// Multipart levels decoded, following the first part of each.
+    fn decoded_depth(tree: &Value) -> usize {
+        let mut depth = 0;
+        let mut parts = tree;
+        while let Some(part) = parts.get(0) {
+            if part["decoder"] != "multipart" {
+                break;
+            }
+            depth += 1;
+            parts = &part["content"];
+        }
+        depth
+    }
+
+    #[test]
+    fn nested_parts_are_decoded() {
+        assert_eq!(decoded_depth(&decode_nested(3)), 2);
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let tree = decode_nested(MAX_DEPTH * 4);
+        assert_eq!(decoded_depth(&tree), MAX_DEPTH - 1);
+
+        // The part past the cap is kept raw
+        let mut part = &tree[0];
+        for _ in 1..MAX_DEPTH {
+            part = &part["content"][0];
+        }
+        assert_eq!(part["decoder"], Value::Null);
+        assert!(part["content"].is_string());
+    }
+}
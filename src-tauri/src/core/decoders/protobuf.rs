@@ -0,0 +1,145 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value, json};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+/// Nested length-delimited fields deeper than this are left as bytes.
+const MAX_NESTING_DEPTH: usize = 16;
+
+/// Schema-less protobuf wire format. Fields are keyed by their number;
+/// repeated fields become arrays. gRPC bodies are split into their
+/// length-prefixed messages first.
+pub struct ProtobufDecoder;
+
+impl PayloadDecoder for ProtobufDecoder {
+    fn name(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        matches!(
+            mime,
+            "application/protobuf"
+                | "application/x-protobuf"
+                | "application/x-google-protobuf"
+                | "application/vnd.google.protobuf"
+        ) || mime.starts_with("application/grpc")
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        !body.is_empty() && parse_message(body, 0).is_some()
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        let is_grpc = input
+            .mime()
+            .is_some_and(|m| m.starts_with("application/grpc") && !m.contains("web-text"));
+        if is_grpc {
+            return decode_grpc_frames(input.body);
+        }
+        parse_message(input.body, 0)
+            .map(Value::Object)
+            .ok_or_else(|| anyhow!("invalid protobuf wire format"))
+    }
+}
+
+fn decode_grpc_frames(mut data: &[u8]) -> Result<Value> {
+    let mut messages = Vec::new();
+    while data.len() >= 5 {
+        let compressed = data[0] == 1;
+        let len = u32::from_be_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        let frame = data
+            .get(5..5 + len)
+            .ok_or_else(|| anyhow!("truncated gRPC frame"))?;
+        let message = if compressed {
+            json!({ "$compressed_length": len })
+        } else {
+            parse_message(frame, 0)
+                .map(Value::Object)
+                .ok_or_else(|| anyhow!("invalid protobuf in gRPC frame"))?
+        };
+        messages.push(json!({ "compressed": compressed, "message": message }));
+        data = &data[5 + len..];
+    }
+    Ok(Value::Array(messages))
+}
+
+/// Parses a complete message, returning `None` unless every byte is consumed
+/// by well-formed fields.
+fn parse_message(mut data: &[u8], depth: usize) -> Option<Map<String, Value>> {
+    let mut fields = Map::new();
+    while !data.is_empty() {
+        let key = read_varint(&mut data)?;
+        let field = key >> 3;
+        if field == 0 {
+            return None;
+        }
+        let value = match key & 0x7 {
+            0 => Value::from(read_varint(&mut data)?),
+            1 => {
+                let (bytes, rest) = data.split_first_chunk::<8>()?;
+                data = rest;
+                Value::from(u64::from_le_bytes(*bytes))
+            }
+            2 => {
+                let len = usize::try_from(read_varint(&mut data)?).ok()?;
+                if len > data.len() {
+                    return None;
+                }
+                let (bytes, rest) = data.split_at(len);
+                data = rest;
+                length_delimited_value(bytes, depth)
+            }
+            5 => {
+                let (bytes, rest) = data.split_first_chunk::<4>()?;
+                data = rest;
+                Value::from(u32::from_le_bytes(*bytes))
+            }
+            // Groups (3, 4) are deprecated and anything else is invalid
+            _ => return None,
+        };
+
+        match fields.get_mut(&field.to_string()) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, value]);
+            }
+            None => {
+                fields.insert(field.to_string(), value);
+            }
+        }
+    }
+    Some(fields)
+}
+
+/// Length-delimited fields may be strings, bytes or nested messages; the wire
+/// format does not say which, so prefer readable text, then a nested message.
+fn length_delimited_value(bytes: &[u8], depth: usize) -> Value {
+    if let Ok(text) = std::str::from_utf8(bytes)
+        && !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+    {
+        return Value::String(text.to_string());
+    }
+    if depth < MAX_NESTING_DEPTH
+        && !bytes.is_empty()
+        && let Some(nested) = parse_message(bytes, depth + 1)
+    {
+        return Value::Object(nested);
+    }
+    json!({ "$hex": hex::encode(bytes) })
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
@@ -0,0 +1,113 @@
+use anyhow::{Result, anyhow};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde_json::{Map, Value};
+
+use super::{DecodeInput, DecoderRegistry, PayloadDecoder};
+
+/// XML documents, rendered as `{ name, attributes, children }` element nodes
+/// with text content as plain strings.
+pub struct XmlDecoder;
+
+impl PayloadDecoder for XmlDecoder {
+    fn name(&self) -> &'static str {
+        "xml"
+    }
+
+    fn matches_mime(&self, mime: &str) -> bool {
+        mime == "application/xml" || mime == "text/xml" || mime.ends_with("+xml")
+    }
+
+    fn sniff(&self, body: &[u8]) -> bool {
+        body.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+    }
+
+    fn decode(&self, input: &DecodeInput, _registry: &DecoderRegistry) -> Result<Value> {
+        let mut reader = Reader::from_reader(input.body);
+        let mut stack: Vec<Map<String, Value>> = Vec::new();
+        let mut roots = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => {
+                    flush_text(&mut text, &mut stack);
+                    stack.push(element(&e)?);
+                }
+                Event::Empty(e) => {
+                    flush_text(&mut text, &mut stack);
+                    push_child(Value::Object(element(&e)?), &mut stack, &mut roots);
+                }
+                Event::End(_) => {
+                    flush_text(&mut text, &mut stack);
+                    let node = stack
+                        .pop()
+                        .ok_or_else(|| anyhow!("unbalanced closing tag"))?;
+                    push_child(Value::Object(node), &mut stack, &mut roots);
+                }
+                Event::Text(e) => text.push_str(&e.decode()?),
+                Event::CData(e) => text.push_str(&e.decode()?),
+                Event::GeneralRef(e) => {
+                    if let Some(ch) = e.resolve_char_ref()? {
+                        text.push(ch);
+                    } else {
+                        let name = e.decode()?;
+                        match resolve_predefined_entity(&name) {
+                            Some(resolved) => text.push_str(resolved),
+                            None => text.push_str(&format!("&{};", name)),
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !stack.is_empty() {
+            return Err(anyhow!("unexpected end of document"));
+        }
+        match roots.len() {
+            0 => Err(anyhow!("no XML element found")),
+            1 => Ok(roots.remove(0)),
+            _ => Ok(Value::Array(roots)),
+        }
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Map<String, Value>> {
+    let mut attributes = Map::new();
+    for attr in start.attributes() {
+        let attr = attr?;
+        attributes.insert(
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            Value::String(attr.unescape_value()?.into_owned()),
+        );
+    }
+
+    let mut node = Map::new();
+    node.insert(
+        "name".to_string(),
+        Value::String(String::from_utf8_lossy(start.name().as_ref()).into_owned()),
+    );
+    node.insert("attributes".to_string(), Value::Object(attributes));
+    node.insert("children".to_string(), Value::Array(Vec::new()));
+    Ok(node)
+}
+
+fn flush_text(text: &mut String, stack: &mut [Map<String, Value>]) {
+    let trimmed = text.trim();
+    if !trimmed.is_empty()
+        && let Some(Value::Array(children)) = stack.last_mut().and_then(|n| n.get_mut("children"))
+    {
+        children.push(Value::String(trimmed.to_string()));
+    }
+    text.clear();
+}
+
+fn push_child(node: Value, stack: &mut [Map<String, Value>], roots: &mut Vec<Value>) {
+    match stack.last_mut().and_then(|n| n.get_mut("children")) {
+        Some(Value::Array(children)) => children.push(node),
+        _ => roots.push(node),
+    }
+}
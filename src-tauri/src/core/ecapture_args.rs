@@ -0,0 +1,367 @@
+//! Command line of the bundled ecapture binary.
+//!
+//! Arguments are either built from [`EcaptureOptions`] or, as an escape
+//! hatch, taken from a raw string split like a shell would.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// ecapture module to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Subcommand {
+    /// OpenSSL and BoringSSL.
+    #[default]
+    Tls,
+    /// Go crypto/tls.
+    Gotls,
+    Gnutls,
+    Nss,
+    Bash,
+    Mysqld,
+    Postgres,
+}
+
+impl Subcommand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Subcommand::Tls => "tls",
+            Subcommand::Gotls => "gotls",
+            Subcommand::Gnutls => "gnutls",
+            Subcommand::Nss => "nss",
+            Subcommand::Bash => "bash",
+            Subcommand::Mysqld => "mysqld",
+            Subcommand::Postgres => "postgres",
+        }
+    }
+
+    /// The module named `name`; ecapture also takes `openssl` for `tls`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tls" | "openssl" => Some(Subcommand::Tls),
+            "gotls" => Some(Subcommand::Gotls),
+            "gnutls" => Some(Subcommand::Gnutls),
+            "nss" => Some(Subcommand::Nss),
+            "bash" => Some(Subcommand::Bash),
+            "mysqld" => Some(Subcommand::Mysqld),
+            "postgres" => Some(Subcommand::Postgres),
+            _ => None,
+        }
+    }
+
+    /// The module run by `argv`, the first argument naming one before the
+    /// pcap filter.
+    pub fn from_argv(argv: &[String]) -> Option<Self> {
+        argv.iter()
+            .take_while(|arg| *arg != "--")
+            .find_map(|arg| Self::from_name(arg))
+    }
+
+    /// TLS modules, which capture network traffic and accept the interface,
+    /// port and pcap filter options.
+    fn captures_network(&self) -> bool {
+        matches!(
+            self,
+            Subcommand::Tls | Subcommand::Gotls | Subcommand::Gnutls | Subcommand::Nss
+        )
+    }
+}
+
+/// What a process-targeted capture is filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureScope {
+    /// Only the process itself (`--pid`).
+    Process,
+    /// Every process of its user (`--uid`).
+    User,
+}
+
+/// Typed ecapture options; unset fields are left to ecapture's defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct EcaptureOptions {
+    pub subcommand: Subcommand,
+    /// Only hook this process (`--pid`).
+    pub pid: Option<u32>,
+    /// Only hook processes of this user (`--uid`).
+    pub uid: Option<u32>,
+    /// Path of the OpenSSL or BoringSSL library to hook (`--libssl`), `tls` only.
+    pub libssl: Option<String>,
+    /// Go binary to hook (`--elfpath`), `gotls` only.
+    pub elfpath: Option<String>,
+    /// Network interface (`-i`).
+    pub interface: Option<String>,
+    /// Only capture this port (`--port`).
+    pub port: Option<u16>,
+    /// pcap filter expression, e.g. `tcp port 443`, passed as the last
+    /// argument after `--`.
+    pub pcap_filter: Option<String>,
+}
+
+impl EcaptureOptions {
+    /// Checks the options against each other and the selected module.
+    pub fn validate(&self) -> Result<()> {
+        let module = self.subcommand.as_str();
+        if self.pid == Some(0) {
+            return Err(anyhow!(
+                "pid must be positive; leave it empty to hook every process"
+            ));
+        }
+        if let Some(libssl) = &self.libssl {
+            if self.subcommand != Subcommand::Tls {
+                return Err(anyhow!(
+                    "--libssl only applies to the tls module, not {}",
+                    module
+                ));
+            }
+            if !libssl.starts_with('/') {
+                return Err(anyhow!("libssl must be an absolute path: {}", libssl));
+            }
+        }
+        if let Some(elfpath) = &self.elfpath {
+            if self.subcommand != Subcommand::Gotls {
+                return Err(anyhow!(
+                    "--elfpath only applies to the gotls module, not {}",
+                    module
+                ));
+            }
+            if !elfpath.starts_with('/') {
+                return Err(anyhow!("elfpath must be an absolute path: {}", elfpath));
+            }
+        }
+        if let Some(interface) = &self.interface {
+            // IFNAMSIZ includes the terminating NUL
+            if interface.is_empty()
+                || interface.len() > 15
+                || interface.starts_with('-')
+                || interface.contains(|c: char| c.is_whitespace() || c == '/')
+            {
+                return Err(anyhow!("invalid network interface name: {:?}", interface));
+            }
+        }
+        if self.port == Some(0) {
+            return Err(anyhow!("port must be between 1 and 65535"));
+        }
+        if let Some(filter) = &self.pcap_filter {
+            let filter = filter.trim();
+            if filter.is_empty() {
+                return Err(anyhow!("pcap filter is empty"));
+            }
+            // Would be taken for an ecapture flag, e.g. --pcapfile
+            if filter.starts_with('-') {
+                return Err(anyhow!("pcap filter must not start with '-': {}", filter));
+            }
+        }
+        let network = self.interface.is_some() || self.port.is_some() || self.pcap_filter.is_some();
+        if network && !self.subcommand.captures_network() {
+            return Err(anyhow!(
+                "interface, port and pcap filter only apply to the TLS modules, not {}",
+                module
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validates the options and builds the argv, without the program name.
+    /// ecapture streams events to `ws_url`.
+    pub fn to_argv(&self, ws_url: &str) -> Result<Vec<String>> {
+        self.validate()?;
+
+        let mut argv = vec![self.subcommand.as_str().to_string()];
+        if let Some(pid) = self.pid {
+            argv.push(format!("--pid={}", pid));
+        }
+        if let Some(uid) = self.uid {
+            argv.push(format!("--uid={}", uid));
+        }
+        if let Some(libssl) = &self.libssl {
+            argv.push(format!("--libssl={}", libssl));
+        }
+        if let Some(elfpath) = &self.elfpath {
+            argv.push(format!("--elfpath={}", elfpath));
+        }
+        if let Some(interface) = &self.interface {
+            argv.push("-i".to_string());
+            argv.push(interface.clone());
+        }
+        if let Some(port) = self.port {
+            argv.push(format!("--port={}", port));
+        }
+        argv.push("--ecaptureq".to_string());
+        argv.push(ws_url.to_string());
+        // ecapture joins the remaining arguments into the filter expression;
+        // after `--` none of it is parsed as a flag
+        if let Some(filter) = &self.pcap_filter {
+            argv.push("--".to_string());
+            argv.push(filter.trim().to_string());
+        }
+        Ok(argv)
+    }
+}
+
+/// Value of the `--ecaptureq` flag in `argv`, given as `--ecaptureq URL` or
+/// `--ecaptureq=URL`. Arguments after `--` are not flags.
+pub fn ecaptureq_url(argv: &[String]) -> Result<Option<&str>> {
+    let mut url = None;
+    let mut args = argv.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--" => break,
+            "--ecaptureq" => args
+                .next()
+                .map(String::as_str)
+                .ok_or_else(|| anyhow!("--ecaptureq needs a value"))?,
+            arg => match arg.strip_prefix("--ecaptureq=") {
+                Some(value) => value,
+                None => continue,
+            },
+        };
+        if url.replace(value).is_some() {
+            return Err(anyhow!("--ecaptureq is given more than once"));
+        }
+    }
+    Ok(url)
+}
+
+/// Splits a raw argument string like a POSIX shell: whitespace separates
+/// arguments, single quotes keep everything literally, double quotes keep
+/// whitespace and backslash escapes the next character. Variables, globs and
+/// other expansions are not performed.
+pub fn split_args(raw: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    // An argument was started, possibly an empty quoted one
+    let mut in_arg = false;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Inside double quotes a backslash only escapes these
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(anyhow!("unterminated double quote")),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(c) => current.push(c),
+                    None => return Err(anyhow!("trailing backslash")),
+                }
+            }
+            c => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WS_URL: &str = "ws://127.0.0.1:28257";
+
+    fn with_filter(filter: &str) -> EcaptureOptions {
+        EcaptureOptions {
+            pcap_filter: Some(filter.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pcap_filter_follows_end_of_options() {
+        let argv = with_filter(" tcp port 443 ").to_argv(WS_URL).unwrap();
+        assert_eq!(argv, ["tls", "--ecaptureq", WS_URL, "--", "tcp port 443"]);
+    }
+
+    #[test]
+    fn pcap_filter_cannot_inject_flags() {
+        for filter in ["--pcapfile=/etc/shadow", " -w /tmp/out", "-i lo"] {
+            assert!(with_filter(filter).to_argv(WS_URL).is_err(), "{}", filter);
+        }
+        let options = EcaptureOptions {
+            interface: Some("-w".to_string()),
+            ..Default::default()
+        };
+        assert!(options.to_argv(WS_URL).is_err());
+    }
+
+    fn url_of(raw: &str) -> Result<Option<String>> {
+        let argv = split_args(raw)?;
+        Ok(ecaptureq_url(&argv)?.map(str::to_string))
+    }
+
+    #[test]
+    fn ecaptureq_url_in_either_form() {
+        let expected = Some(WS_URL.to_string());
+        assert_eq!(
+            url_of(&format!("tls --ecaptureq {}", WS_URL)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            url_of(&format!("tls --ecaptureq={}", WS_URL)).unwrap(),
+            expected
+        );
+        // Part of the pcap filter
+        assert_eq!(url_of("tls -- --ecaptureq ws://10.0.0.1:1").unwrap(), None);
+        assert!(url_of("tls --ecaptureq").is_err());
+        assert!(
+            url_of(&format!(
+                "tls --ecaptureq={} --ecaptureq {}",
+                WS_URL, WS_URL
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn module_of_typed_and_raw_arguments() {
+        let options = EcaptureOptions {
+            subcommand: Subcommand::Gotls,
+            ..Default::default()
+        };
+        let argv = options.to_argv(WS_URL).unwrap();
+        assert_eq!(Subcommand::from_argv(&argv), Some(Subcommand::Gotls));
+
+        let module = |raw: &str| Subcommand::from_argv(&split_args(raw).unwrap());
+        assert_eq!(module("-d openssl --pid=1"), Some(Subcommand::Tls));
+        assert_eq!(module("--pid=1 -- bash"), None);
+        assert_eq!(module(""), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Meaning of the event `type` field.
+//!
+//! eCapture fills it with the type of the parser that recognized the payload
+//! (`ParserType` in its event processor). The capture module (tls, gotls,
+//! gnutls, nss, bash, mysqld, postgres) is not part of the event; it is the
+//! [`Subcommand`] of the capture session, recorded in the `module` column.
+//!
+//! [`Subcommand`]: crate::core::ecapture_args::Subcommand
+
+/// Parser type of a captured event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    /// No parser matched; the payload is passed through as is.
+    Raw,
+    HttpRequest,
+    Http2Request,
+    HttpResponse,
+    Http2Response,
+    WebSocket,
+    /// A code this version does not know.
+    Unknown,
+}
+
+/// Which way the payload went, seen from the hooked process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Received by the process (SSL_read and friends): ingress.
+    Read,
+    /// Sent by the process (SSL_write and friends): egress.
+    Write,
+    Unknown,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Read => "read",
+            Direction::Write => "write",
+            Direction::Unknown => "unknown",
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::Read => Direction::Write,
+            Direction::Write => Direction::Read,
+            Direction::Unknown => Direction::Unknown,
+        }
+    }
+}
+
+struct EventTypeInfo {
+    code: u32,
+    kind: EventType,
+    name: &'static str,
+    direction: Direction,
+}
+
+/// Requests are assumed to be written and responses read, which holds for
+/// the usual case of hooking the client side of a TLS library.
+const EVENT_TYPES: [EventTypeInfo; 6] = [
+    EventTypeInfo {
+        code: 0,
+        kind: EventType::Raw,
+        name: "raw",
+        direction: Direction::Unknown,
+    },
+    EventTypeInfo {
+        code: 1,
+        kind: EventType::HttpRequest,
+        name: "http_request",
+        direction: Direction::Write,
+    },
+    EventTypeInfo {
+        code: 2,
+        kind: EventType::Http2Request,
+        name: "http2_request",
+        direction: Direction::Write,
+    },
+    EventTypeInfo {
+        code: 3,
+        kind: EventType::HttpResponse,
+        name: "http_response",
+        direction: Direction::Read,
+    },
+    EventTypeInfo {
+        code: 4,
+        kind: EventType::Http2Response,
+        name: "http2_response",
+        direction: Direction::Read,
+    },
+    EventTypeInfo {
+        code: 5,
+        kind: EventType::WebSocket,
+        name: "websocket",
+        direction: Direction::Unknown,
+    },
+];
+
+impl EventType {
+    pub fn from_code(code: u32) -> Self {
+        EVENT_TYPES
+            .iter()
+            .find(|info| info.code == code)
+            .map_or(EventType::Unknown, |info| info.kind)
+    }
+
+    /// Value of the `type_name` column.
+    pub fn name(&self) -> &'static str {
+        self.info().map_or("unknown", |info| info.name)
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.info()
+            .map_or(Direction::Unknown, |info| info.direction)
+    }
+
+    fn info(&self) -> Option<&'static EventTypeInfo> {
+        EVENT_TYPES.iter().find(|info| info.kind == *self)
+    }
+}
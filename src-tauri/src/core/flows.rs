@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use polars::prelude::*;
+
+use crate::core::app_proto::AppProto;
+use crate::core::event_type::{Direction, EventType};
+use crate::core::models::{FlowRecord, PacketData};
+
+/// Incrementally maintained connection table backing the `flows` SQL table.
+///
+/// Flows are keyed by the event `uuid`; events without one fall back to their
+/// 5-tuple. The materialized DataFrame is rebuilt lazily, only after a batch
+/// actually touched the table.
+pub struct FlowTable {
+    records: Vec<FlowRecord>,
+    /// Indexed like `records`.
+    orientations: Vec<Orientation>,
+    lookup: HashMap<String, usize>,
+    seq: u64,
+    df: DataFrame,
+    dirty: bool,
+}
+
+impl FlowTable {
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            orientations: Vec::new(),
+            lookup: HashMap::new(),
+            seq: 0,
+            df: create_flows_df(),
+            dirty: false,
+        }
+    }
+
+    /// An empty table whose `update_seq` continues after `seq`, so that the
+    /// flows of a rebuilt table are all pushed again.
+    pub fn continuing_from(seq: u64) -> Self {
+        Self { seq, ..Self::new() }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn update(&mut self, batch: &[PacketData]) {
+        if batch.is_empty() {
+            return;
+        }
+
+        // Every flow touched by this batch shares the same update_seq so the
+        // push service can fetch changed rows with a single `update_seq > n`.
+        self.seq += 1;
+
+        for d in batch {
+            let key = flow_key(d);
+            let bytes = d.length as u64;
+
+            let slot = match self.lookup.get(&key) {
+                Some(&slot) => slot,
+                None => {
+                    let slot = self.records.len();
+                    self.records.push(FlowRecord {
+                        flow_id: slot as u64,
+                        uuid: key.clone(),
+                        src_ip: d.src_ip.clone(),
+                        src_port: d.src_port,
+                        dst_ip: d.dst_ip.clone(),
+                        dst_port: d.dst_port,
+                        pid: d.pid,
+                        pname: d.pname.clone(),
+                        first_seen: d.timestamp,
+                        last_seen: d.timestamp,
+                        app_proto: AppProto::Unknown.as_str().to_string(),
+                        ..Default::default()
+                    });
+                    self.orientations.push(Orientation::new(d));
+                    self.lookup.insert(key, slot);
+                    slot
+                }
+            };
+
+            let flow = &mut self.records[slot];
+            if self.orientations[slot].direction(d) == Direction::Write {
+                flow.bytes_out += bytes;
+            } else {
+                flow.bytes_in += bytes;
+            }
+            flow.first_seen = flow.first_seen.min(d.timestamp);
+            flow.last_seen = flow.last_seen.max(d.timestamp);
+            flow.duration = flow.last_seen - flow.first_seen;
+            flow.event_count += 1;
+            if flow.pname.is_empty() && !d.pname.is_empty() {
+                flow.pid = d.pid;
+                flow.pname = d.pname.clone();
+            }
+            // Labels only get more specific over a connection (http1 to
+            // websocket, http2 to grpc), so the latest known one wins
+            if d.app_proto != AppProto::Unknown.as_str() && !d.app_proto.is_empty() {
+                flow.app_proto.clone_from(&d.app_proto);
+            }
+            flow.update_seq = self.seq;
+        }
+
+        self.dirty = true;
+    }
+
+    /// Returns the `flows` table, rebuilding it if it changed since the last call.
+    pub fn as_df(&mut self) -> PolarsResult<DataFrame> {
+        if self.dirty {
+            self.df = flows_to_df(&self.records)?;
+            self.dirty = false;
+        }
+        Ok(self.df.clone())
+    }
+}
+
+impl Default for FlowTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key used to group events of one connection across the derived tables.
+pub(crate) fn flow_key(d: &PacketData) -> String {
+    if !d.uuid.is_empty() {
+        return d.uuid.clone();
+    }
+    format!(
+        "{}:{}-{}:{}-{}",
+        d.src_ip, d.src_port, d.dst_ip, d.dst_port, d.pid
+    )
+}
+
+/// Tells the two directions of a connection apart, seen from the hooked
+/// process.
+///
+/// The event type decides when it tells whether the payload was read or
+/// written. Other events count as written when sent from the side that was
+/// the process in the first event of the connection.
+pub(crate) struct Orientation {
+    /// `ip:port` of the hooked process.
+    local: String,
+}
+
+impl Orientation {
+    /// Orientation of the connection whose first event is `d`.
+    pub(crate) fn new(d: &PacketData) -> Self {
+        let local = match EventType::from_code(d.r#type).direction() {
+            Direction::Read => endpoint(&d.dst_ip, d.dst_port),
+            _ => endpoint(&d.src_ip, d.src_port),
+        };
+        Self { local }
+    }
+
+    /// Which way `d` went; never `Direction::Unknown`.
+    pub(crate) fn direction(&self, d: &PacketData) -> Direction {
+        match EventType::from_code(d.r#type).direction() {
+            Direction::Unknown if endpoint(&d.src_ip, d.src_port) == self.local => Direction::Write,
+            Direction::Unknown => Direction::Read,
+            known => known,
+        }
+    }
+}
+
+/// `ip:port` of one side of a connection.
+fn endpoint(ip: &str, port: u32) -> String {
+    format!("{}:{}", ip, port)
+}
+
+fn flows_to_df(records: &[FlowRecord]) -> PolarsResult<DataFrame> {
+    let len = records.len();
+    let mut flow_id_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("flow_id".into(), len);
+    let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), len);
+    let mut src_ip_builder = StringChunkedBuilder::new("src_ip".into(), len);
+    let mut src_port_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("src_port".into(), len);
+    let mut dst_ip_builder = StringChunkedBuilder::new("dst_ip".into(), len);
+    let mut dst_port_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("dst_port".into(), len);
+    let mut pid_builder = PrimitiveChunkedBuilder::<Int32Type>::new("pid".into(), len);
+    let mut pname_builder = StringChunkedBuilder::new("pname".into(), len);
+    let mut first_seen_builder =
+        PrimitiveChunkedBuilder::<Int64Type>::new("first_seen".into(), len);
+    let mut last_seen_builder = PrimitiveChunkedBuilder::<Int64Type>::new("last_seen".into(), len);
+    let mut duration_builder = PrimitiveChunkedBuilder::<Int64Type>::new("duration".into(), len);
+    let mut bytes_out_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("bytes_out".into(), len);
+    let mut bytes_in_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("bytes_in".into(), len);
+    let mut event_count_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("event_count".into(), len);
+    let mut app_proto_builder = StringChunkedBuilder::new("app_proto".into(), len);
+    let mut update_seq_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("update_seq".into(), len);
+
+    for f in records {
+        flow_id_builder.append_value(f.flow_id);
+        uuid_builder.append_value(&f.uuid);
+        src_ip_builder.append_value(&f.src_ip);
+        src_port_builder.append_value(f.src_port);
+        dst_ip_builder.append_value(&f.dst_ip);
+        dst_port_builder.append_value(f.dst_port);
+        pid_builder.append_value(f.pid);
+        pname_builder.append_value(&f.pname);
+        first_seen_builder.append_value(f.first_seen);
+        last_seen_builder.append_value(f.last_seen);
+        duration_builder.append_value(f.duration);
+        bytes_out_builder.append_value(f.bytes_out);
+        bytes_in_builder.append_value(f.bytes_in);
+        event_count_builder.append_value(f.event_count);
+        app_proto_builder.append_value(&f.app_proto);
+        update_seq_builder.append_value(f.update_seq);
+    }
+
+    DataFrame::new(vec![
+        flow_id_builder.finish().into_column(),
+        uuid_builder.finish().into_column(),
+        src_ip_builder.finish().into_column(),
+        src_port_builder.finish().into_column(),
+        dst_ip_builder.finish().into_column(),
+        dst_port_builder.finish().into_column(),
+        pid_builder.finish().into_column(),
+        pname_builder.finish().into_column(),
+        first_seen_builder.finish().into_column(),
+        last_seen_builder.finish().into_column(),
+        duration_builder.finish().into_column(),
+        bytes_out_builder.finish().into_column(),
+        bytes_in_builder.finish().into_column(),
+        event_count_builder.finish().into_column(),
+        app_proto_builder.finish().into_column(),
+        update_seq_builder.finish().into_column(),
+    ])
+}
+
+pub fn create_flows_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("flow_id".into(), DataType::UInt64),
+        Field::new("uuid".into(), DataType::String),
+        Field::new("src_ip".into(), DataType::String),
+        Field::new("src_port".into(), DataType::UInt32),
+        Field::new("dst_ip".into(), DataType::String),
+        Field::new("dst_port".into(), DataType::UInt32),
+        Field::new("pid".into(), DataType::Int32),
+        Field::new("pname".into(), DataType::String),
+        Field::new("first_seen".into(), DataType::Int64),
+        Field::new("last_seen".into(), DataType::Int64),
+        Field::new("duration".into(), DataType::Int64),
+        Field::new("bytes_out".into(), DataType::UInt64),
+        Field::new("bytes_in".into(), DataType::UInt64),
+        Field::new("event_count".into(), DataType::UInt64),
+        Field::new("app_proto".into(), DataType::String),
+        Field::new("update_seq".into(), DataType::UInt64),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event of `type` on connection `conn`, always reported with the
+    /// socket's tuple as ecapture does.
+    fn event(r#type: u32, length: u32) -> PacketData {
+        PacketData {
+            uuid: "conn".to_string(),
+            src_ip: "10.0.0.1".to_string(),
+            src_port: 5000,
+            dst_ip: "10.0.0.2".to_string(),
+            dst_port: 443,
+            r#type,
+            length,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bytes_follow_the_event_type_over_the_tuple() {
+        let mut table = FlowTable::new();
+        // An HTTP request, its response and a raw event
+        table.update(&[event(1, 100), event(3, 2000), event(0, 7)]);
+
+        let flow = &table.records[0];
+        assert_eq!((flow.bytes_out, flow.bytes_in), (107, 2000));
+        assert_eq!(flow.event_count, 3);
+    }
+
+    #[test]
+    fn unknown_directions_fall_back_to_the_first_sender() {
+        let mut table = FlowTable::new();
+        let reply = PacketData {
+            src_ip: "10.0.0.2".to_string(),
+            src_port: 443,
+            dst_ip: "10.0.0.1".to_string(),
+            dst_port: 5000,
+            ..event(0, 50)
+        };
+        table.update(&[event(0, 10), reply]);
+
+        let flow = &table.records[0];
+        assert_eq!((flow.bytes_out, flow.bytes_in), (10, 50));
+    }
+}
@@ -0,0 +1,99 @@
+//! Messages between the app and the privileged helper that runs ecapture as
+//! root, one JSON object per line over a Unix socket.
+//!
+//! A connection either carries one capture session, opened by
+//! [`Request::Start`] and ended by [`Request::Stop`] or by closing it, or is
+//! turned by [`Request::Relay`] into a plain byte pipe to the WebSocket of
+//! the running ecapture, or answers one other request such as
+//! [`Request::Info`].
+
+use anyhow::{Result, anyhow};
+use http::Uri;
+use serde::{Deserialize, Serialize};
+
+use crate::core::ecapture_args::EcaptureOptions;
+use crate::core::models::{EcaptureInfo, ProcessInfo};
+use crate::core::session_timeouts::SessionTimeouts;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Runs ecapture with typed options only; raw arguments could make root
+    /// write arbitrary files.
+    Start {
+        options: EcaptureOptions,
+        ws_url: String,
+        #[serde(default)]
+        timeouts: SessionTimeouts,
+    },
+    Stop,
+    Relay,
+    /// The ecapture the helper runs, answered by [`Reply::Info`].
+    Info,
+    /// ecapture processes left behind by an earlier helper, answered by
+    /// [`Reply::Processes`].
+    Orphans,
+    /// Stops the processes [`Request::Orphans`] lists.
+    StopOrphans {
+        #[serde(default)]
+        timeouts: SessionTimeouts,
+    },
+    /// Kills the ecapture of the running session at once, for an app that
+    /// gave up waiting for it to stop.
+    Kill,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    /// The relay is open and raw bytes follow, or the request is done.
+    Ok,
+    Error {
+        message: String,
+    },
+    /// An event for the frontend, e.g. a line of ecapture output.
+    Event {
+        event: String,
+        payload: serde_json::Value,
+    },
+    /// The session is over, with the error if it did not stop on request.
+    Exited {
+        error: Option<String>,
+    },
+    Info {
+        info: EcaptureInfo,
+    },
+    Processes {
+        processes: Vec<ProcessInfo>,
+    },
+}
+
+/// Host and port of a WebSocket URL ecapture may listen on. Only loopback
+/// addresses are accepted, so the helper never relays to another host.
+pub fn loopback_address(ws_url: &str) -> Result<(String, u16)> {
+    let uri: Uri = ws_url
+        .parse()
+        .map_err(|e| anyhow!("invalid WebSocket URL {:?}: {}", ws_url, e))?;
+    if uri.scheme_str() != Some("ws") {
+        return Err(anyhow!("only ws:// URLs are supported: {}", ws_url));
+    }
+    let host = uri
+        .host()
+        .ok_or_else(|| anyhow!("WebSocket URL has no host: {}", ws_url))?;
+    let loopback = host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    if !loopback {
+        return Err(anyhow!(
+            "eCapture may only stream to a loopback address, not {}",
+            host
+        ));
+    }
+    let port = uri
+        .port_u16()
+        .ok_or_else(|| anyhow!("WebSocket URL has no port: {}", ws_url))?;
+    Ok((host.to_string(), port))
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+
+use crate::core::models::BrokerMessage;
+
+use super::{Desync, MAX_CONTENT_LEN, MessageParser, Reader, Record, set_payload};
+
+const PROTOCOL_HEADER: &[u8] = b"AMQP\x00\x00\x09\x01";
+
+const FRAME_METHOD: u8 = 1;
+const FRAME_HEADER: u8 = 2;
+const FRAME_BODY: u8 = 3;
+const FRAME_END: u8 = 0xce;
+
+/// Frames announced larger than this are treated as garbage.
+const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// AMQP 0-9-1: one record per method frame. Content-carrying methods
+/// (basic.publish, basic.deliver, basic.get-ok, basic.return) are recorded
+/// once their content header and body frames have arrived.
+pub struct AmqpParser {
+    header_checked: bool,
+    /// Content-carrying method per direction (true for the client) and
+    /// channel, waiting for its content.
+    content: HashMap<(bool, u16), PendingContent>,
+}
+
+struct PendingContent {
+    message: BrokerMessage,
+    /// Announced body size, known once the content header arrived.
+    body_size: Option<u64>,
+    received: u64,
+    body: Vec<u8>,
+}
+
+impl AmqpParser {
+    pub fn new() -> Self {
+        Self {
+            header_checked: false,
+            content: HashMap::new(),
+        }
+    }
+
+    fn frame(&mut self, key: (bool, u16), kind: u8, payload: &[u8], out: &mut Vec<Record>) {
+        let channel = key.1;
+        match kind {
+            FRAME_METHOD => {
+                let Some(message) = method(channel, payload) else {
+                    return;
+                };
+                if carries_content(&message.method) {
+                    self.content.insert(
+                        key,
+                        PendingContent {
+                            message,
+                            body_size: None,
+                            received: 0,
+                            body: Vec::new(),
+                        },
+                    );
+                } else {
+                    out.push(Record::Broker(message));
+                }
+            }
+            FRAME_HEADER => {
+                let Some(pending) = self.content.get_mut(&key) else {
+                    return;
+                };
+                let mut r = Reader::new(payload);
+                let (Some(_class), Some(_weight), Some(size), Some(flags)) =
+                    (r.u16(), r.u16(), r.u64(), r.u16())
+                else {
+                    return;
+                };
+                // content-type is the first property
+                if flags & 0x8000 != 0 {
+                    pending.message.content_type = r.short_str().unwrap_or_default();
+                }
+                pending.body_size = Some(size);
+                if size == 0 {
+                    self.finish(key, out);
+                }
+            }
+            FRAME_BODY => {
+                let Some(pending) = self.content.get_mut(&key) else {
+                    return;
+                };
+                let room = MAX_CONTENT_LEN.saturating_sub(pending.body.len());
+                pending
+                    .body
+                    .extend_from_slice(&payload[..payload.len().min(room)]);
+                pending.received += payload.len() as u64;
+                if pending
+                    .body_size
+                    .is_some_and(|size| pending.received >= size)
+                {
+                    self.finish(key, out);
+                }
+            }
+            // Heartbeats carry nothing
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self, key: (bool, u16), out: &mut Vec<Record>) {
+        if let Some(mut pending) = self.content.remove(&key) {
+            let length = pending.body_size.unwrap_or(pending.received);
+            set_payload(&mut pending.message, pending.body, length);
+            out.push(Record::Broker(pending.message));
+        }
+    }
+
+    fn frames(
+        &mut self,
+        buf: &mut Vec<u8>,
+        from_client: bool,
+        out: &mut Vec<Record>,
+    ) -> Result<(), Desync> {
+        let mut pos = 0;
+        // Only the client sends the protocol header
+        if from_client && !self.header_checked {
+            if buf.len() < PROTOCOL_HEADER.len() {
+                return Ok(());
+            }
+            if buf.starts_with(PROTOCOL_HEADER) {
+                pos = PROTOCOL_HEADER.len();
+            }
+            self.header_checked = true;
+        }
+
+        let mut result = Ok(());
+        while let Some(header) = buf.get(pos..pos + 7) {
+            let kind = header[0];
+            let channel = u16::from_be_bytes([header[1], header[2]]);
+            let size = u32::from_be_bytes([header[3], header[4], header[5], header[6]]) as usize;
+            if !matches!(kind, 1..=4 | 8) || size > MAX_FRAME_LEN {
+                result = Err(Desync);
+                break;
+            }
+            let Some(frame_end) = buf.get(pos + 7 + size) else {
+                break;
+            };
+            if *frame_end != FRAME_END {
+                result = Err(Desync);
+                break;
+            }
+            let payload = &buf[pos + 7..pos + 7 + size];
+            self.frame((from_client, channel), kind, payload, out);
+            pos += 8 + size;
+        }
+        buf.drain(..pos);
+        result
+    }
+}
+
+impl Default for AmqpParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageParser for AmqpParser {
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        self.frames(buf, true, out)
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        self.frames(buf, false, out)
+    }
+}
+
+/// Recognizes the AMQP 0-9-1 protocol header sent first by clients.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    payload.starts_with(PROTOCOL_HEADER)
+}
+
+fn carries_content(method: &str) -> bool {
+    matches!(
+        method,
+        "basic.publish" | "basic.return" | "basic.deliver" | "basic.get-ok"
+    )
+}
+
+/// Decodes a method frame into a record with the fields most useful for
+/// filtering; other arguments are left out.
+fn method(channel: u16, payload: &[u8]) -> Option<BrokerMessage> {
+    let mut r = Reader::new(payload);
+    let class_id = r.u16()?;
+    let method_id = r.u16()?;
+    let mut m = BrokerMessage {
+        protocol: "amqp".to_string(),
+        method: method_name(class_id, method_id),
+        channel: channel as u32,
+        ..Default::default()
+    };
+
+    match (class_id, method_id) {
+        // exchange.declare: reserved, exchange, type
+        (40, 10) => {
+            r.u16()?;
+            m.exchange = r.short_str()?;
+            m.detail = format!("type={}", r.short_str()?);
+        }
+        // queue.declare / basic.consume: reserved, queue
+        (50, 10) | (60, 20) => {
+            r.u16()?;
+            m.queue = r.short_str()?;
+        }
+        // queue.declare-ok: queue, message count, consumer count
+        (50, 11) => {
+            m.queue = r.short_str()?;
+            m.detail = format!("messages={} consumers={}", r.u32()?, r.u32()?);
+        }
+        // queue.bind / queue.unbind: reserved, queue, exchange, routing key
+        (50, 20) | (50, 50) => {
+            r.u16()?;
+            m.queue = r.short_str()?;
+            m.exchange = r.short_str()?;
+            m.topic = r.short_str()?;
+        }
+        // basic.publish: reserved, exchange, routing key, flags
+        (60, 40) => {
+            r.u16()?;
+            m.exchange = r.short_str()?;
+            m.topic = r.short_str()?;
+            let flags = r.u8()?;
+            m.detail = format!("mandatory={} immediate={}", flags & 1 != 0, flags & 2 != 0);
+        }
+        // basic.return: reply code, reply text, exchange, routing key
+        (60, 50) => {
+            let code = r.u16()?;
+            let reply = r.short_str()?;
+            m.exchange = r.short_str()?;
+            m.topic = r.short_str()?;
+            m.detail = format!("reply_code={} reply_text={}", code, reply);
+        }
+        // basic.deliver: consumer tag, delivery tag, redelivered, exchange, routing key
+        (60, 60) => {
+            let consumer = r.short_str()?;
+            let delivery = r.u64()?;
+            let redelivered = r.u8()? & 1 != 0;
+            m.exchange = r.short_str()?;
+            m.topic = r.short_str()?;
+            m.detail = format!(
+                "consumer_tag={} delivery_tag={} redelivered={}",
+                consumer, delivery, redelivered
+            );
+        }
+        // basic.get-ok: delivery tag, redelivered, exchange, routing key, message count
+        (60, 71) => {
+            let delivery = r.u64()?;
+            let redelivered = r.u8()? & 1 != 0;
+            m.exchange = r.short_str()?;
+            m.topic = r.short_str()?;
+            m.detail = format!("delivery_tag={} redelivered={}", delivery, redelivered);
+        }
+        // basic.ack / basic.nack: delivery tag, flags
+        (60, 80) | (60, 120) => {
+            m.detail = format!("delivery_tag={} multiple={}", r.u64()?, r.u8()? & 1 != 0);
+        }
+        // connection.close / channel.close: reply code, reply text
+        (10, 50) | (20, 40) => {
+            m.detail = format!("reply_code={} reply_text={}", r.u16()?, r.short_str()?);
+        }
+        _ => {}
+    }
+    Some(m)
+}
+
+fn method_name(class_id: u16, method_id: u16) -> String {
+    let class = match class_id {
+        10 => "connection",
+        20 => "channel",
+        40 => "exchange",
+        50 => "queue",
+        60 => "basic",
+        85 => "confirm",
+        90 => "tx",
+        _ => return format!("{}.{}", class_id, method_id),
+    };
+    let method = match (class_id, method_id) {
+        (10, 10) => "start",
+        (10, 11) => "start-ok",
+        (10, 20) => "secure",
+        (10, 21) => "secure-ok",
+        (10, 30) => "tune",
+        (10, 31) => "tune-ok",
+        (10, 40) => "open",
+        (10, 41) => "open-ok",
+        (10, 50) => "close",
+        (10, 51) => "close-ok",
+        (10, 60) => "blocked",
+        (10, 61) => "unblocked",
+        (20, 10) => "open",
+        (20, 11) => "open-ok",
+        (20, 20) => "flow",
+        (20, 21) => "flow-ok",
+        (20, 40) => "close",
+        (20, 41) => "close-ok",
+        (40, 10) | (50, 10) => "declare",
+        (40, 11) | (50, 11) => "declare-ok",
+        (40, 20) | (50, 40) => "delete",
+        (40, 21) | (50, 41) => "delete-ok",
+        (40, 30) | (50, 20) => "bind",
+        (40, 31) | (50, 21) => "bind-ok",
+        (40, 40) | (50, 50) => "unbind",
+        (40, 51) | (50, 51) => "unbind-ok",
+        (50, 30) => "purge",
+        (50, 31) => "purge-ok",
+        (60, 10) => "qos",
+        (60, 11) => "qos-ok",
+        (60, 20) => "consume",
+        (60, 21) => "consume-ok",
+        (60, 30) => "cancel",
+        (60, 31) => "cancel-ok",
+        (60, 40) => "publish",
+        (60, 50) => "return",
+        (60, 60) => "deliver",
+        (60, 70) => "get",
+        (60, 71) => "get-ok",
+        (60, 72) => "get-empty",
+        (60, 80) => "ack",
+        (60, 90) => "reject",
+        (60, 100) => "recover-async",
+        (60, 110) => "recover",
+        (60, 111) => "recover-ok",
+        (60, 120) => "nack",
+        (85, 10) | (90, 10) => "select",
+        (85, 11) | (90, 11) => "select-ok",
+        (90, 20) => "commit",
+        (90, 21) => "commit-ok",
+        (90, 30) => "rollback",
+        (90, 31) => "rollback-ok",
+        _ => return format!("{}.{}", class, method_id),
+    };
+    format!("{}.{}", class, method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: u8, channel: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&channel.to_be_bytes());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame.push(FRAME_END);
+        frame
+    }
+
+    fn short_str(s: &str) -> Vec<u8> {
+        let mut out = vec![s.len() as u8];
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    /// basic.publish to `exchange` with `routing_key`.
+    fn publish(channel: u16, exchange: &str, routing_key: &str) -> Vec<u8> {
+        let mut payload = vec![0, 60, 0, 40, 0, 0];
+        payload.extend(short_str(exchange));
+        payload.extend(short_str(routing_key));
+        payload.push(0);
+        frame(FRAME_METHOD, channel, &payload)
+    }
+
+    /// Content header announcing `size` body bytes of `content_type`.
+    fn header(channel: u16, size: u64, content_type: &str) -> Vec<u8> {
+        let mut payload = vec![0, 60, 0, 0];
+        payload.extend_from_slice(&size.to_be_bytes());
+        payload.extend_from_slice(&0x8000u16.to_be_bytes());
+        payload.extend(short_str(content_type));
+        frame(FRAME_HEADER, channel, &payload)
+    }
+
+    fn broker(out: &[Record]) -> Vec<&BrokerMessage> {
+        out.iter()
+            .filter_map(|r| match r {
+                Record::Broker(m) => Some(m),
+                Record::Mail(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn content_of_interleaved_channels_is_kept_apart() {
+        let mut parser = AmqpParser::new();
+        let mut out = Vec::new();
+        let mut buf = PROTOCOL_HEADER.to_vec();
+        buf.extend(publish(1, "orders", "created"));
+        buf.extend(publish(2, "audit", "login"));
+        buf.extend(header(2, 2, "text/plain"));
+        buf.extend(header(1, 7, "application/json"));
+        buf.extend(frame(FRAME_BODY, 1, b"{\"id\""));
+        buf.extend(frame(FRAME_BODY, 2, b"ok"));
+        buf.extend(frame(FRAME_BODY, 1, b":1}"));
+        parser.client_data(&mut buf, &mut out).unwrap();
+
+        let messages: Vec<_> = broker(&out)
+            .iter()
+            .map(|m| {
+                (
+                    m.channel,
+                    m.topic.as_str(),
+                    m.content_type.as_str(),
+                    m.payload_utf8.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "login", "text/plain", "ok"),
+                (1, "created", "application/json", "{\"id\":1}"),
+            ]
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn truncated_frames_stay_buffered() {
+        let mut parser = AmqpParser::new();
+        let mut out = Vec::new();
+        let whole = publish(1, "orders", "created");
+        let mut buf = whole[..whole.len() - 1].to_vec();
+        parser.server_data(&mut buf, &mut out).unwrap();
+        assert_eq!(buf.len(), whole.len() - 1);
+
+        buf.push(FRAME_END);
+        buf.extend(header(1, 0, "text/plain"));
+        parser.server_data(&mut buf, &mut out).unwrap();
+        assert_eq!(broker(&out)[0].method, "basic.publish");
+    }
+
+    #[test]
+    fn oversized_or_unterminated_frames_are_a_desync() {
+        let mut out = Vec::new();
+        let mut huge = vec![FRAME_BODY, 0, 1];
+        huge.extend_from_slice(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes());
+        assert!(AmqpParser::new().server_data(&mut huge, &mut out).is_err());
+
+        let mut bad_end = publish(1, "orders", "created");
+        *bad_end.last_mut().unwrap() = 0;
+        assert!(
+            AmqpParser::new()
+                .server_data(&mut bad_end, &mut out)
+                .is_err()
+        );
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::core::content_decoding::truncate_text;
+use crate::core::models::MailMessage;
+
+use super::{Desync, MAX_TEXT_LEN, MessageParser, Record, next_line};
+
+/// Unterminated lines longer than this mean the stream is not IMAP.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Literals announced larger than this are treated as garbage.
+const MAX_LITERAL_LEN: usize = 64 * 1024 * 1024;
+
+/// Commands a client typically opens a session with.
+const GREETING_COMMANDS: [&str; 6] = [
+    "CAPABILITY",
+    "LOGIN",
+    "AUTHENTICATE",
+    "ID",
+    "STARTTLS",
+    "NOOP",
+];
+
+/// IMAP4: tagged client commands and untagged, continuation and tagged
+/// server responses. Literals are inlined when they are text.
+pub struct ImapParser;
+
+impl ImapParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for ImapParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageParser for ImapParser {
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        parse_lines(buf, out, client_record)
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        parse_lines(buf, out, server_record)
+    }
+}
+
+fn parse_lines(
+    buf: &mut Vec<u8>,
+    out: &mut Vec<Record>,
+    to_record: fn(&str) -> Result<MailMessage, Desync>,
+) -> Result<(), Desync> {
+    let mut pos = 0;
+    let mut result = Ok(());
+    loop {
+        match read_line(buf, pos) {
+            Ok(Some((line, next))) => {
+                match to_record(&line) {
+                    Ok(m) => out.push(Record::Mail(m)),
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                pos = next;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    buf.drain(..pos);
+    result
+}
+
+/// Reads one logical line starting at `pos`, following `{n}` / `{n+}`
+/// literals into the next physical line.
+fn read_line(buf: &[u8], pos: usize) -> Result<Option<(String, usize)>, Desync> {
+    let mut line = String::new();
+    let mut cursor = pos;
+    loop {
+        let Some((segment, next)) = next_line(buf, cursor) else {
+            if buf.len() - cursor > MAX_LINE_LEN {
+                return Err(Desync);
+            }
+            return Ok(None);
+        };
+        match literal_len(segment) {
+            Some((len, marker)) => {
+                if len > MAX_LITERAL_LEN {
+                    return Err(Desync);
+                }
+                let Some(literal) = buf.get(next..next + len) else {
+                    return Ok(None);
+                };
+                line.push_str(&String::from_utf8_lossy(&segment[..segment.len() - marker]));
+                match std::str::from_utf8(literal) {
+                    Ok(s) => line.push_str(s),
+                    Err(_) => line.push_str(&format!("{{{} bytes}}", len)),
+                }
+                cursor = next + len;
+            }
+            None => {
+                line.push_str(&String::from_utf8_lossy(segment));
+                return Ok(Some((truncate_text(line, MAX_TEXT_LEN), next)));
+            }
+        }
+    }
+}
+
+/// Length of a literal announced at the end of a line, and the length of
+/// the announcement itself.
+fn literal_len(segment: &[u8]) -> Option<(usize, usize)> {
+    let inner = segment.strip_suffix(b"}")?;
+    let start = inner.iter().rposition(|&b| b == b'{')?;
+    let digits = &inner[start + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    let len = std::str::from_utf8(digits).ok()?.parse().ok()?;
+    Some((len, segment.len() - start))
+}
+
+fn client_record(line: &str) -> Result<MailMessage, Desync> {
+    let Some((tag, rest)) = line.split_once(' ') else {
+        // IDLE is ended by a bare DONE; AUTHENTICATE exchanges send bare
+        // base64 lines.
+        let command = if line.eq_ignore_ascii_case("DONE") {
+            "DONE"
+        } else {
+            "CONTINUATION"
+        };
+        return Ok(mail("", command, line.to_string()));
+    };
+    if tag.is_empty() || !tag.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(Desync);
+    }
+
+    let (command, args) = split_word(rest);
+    // UID FETCH, UID STORE, ... are reported as one command
+    let (command, args) = if command.eq_ignore_ascii_case("UID") {
+        let (sub, args) = split_word(args);
+        (format!("UID {}", sub.to_ascii_uppercase()), args)
+    } else {
+        (command.to_ascii_uppercase(), args)
+    };
+    Ok(mail(tag, &command, args.to_string()))
+}
+
+fn server_record(line: &str) -> Result<MailMessage, Desync> {
+    let (tag, rest) = split_word(line);
+    if tag.is_empty() || !tag.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(Desync);
+    }
+    if tag == "+" {
+        return Ok(mail("+", "CONTINUATION", rest.to_string()));
+    }
+
+    let (first, args) = split_word(rest);
+    // Untagged data such as "* 3 EXISTS" or "* 1 FETCH (...)" lead with a number
+    if tag == "*" && !first.is_empty() && first.bytes().all(|b| b.is_ascii_digit()) {
+        let (name, args) = split_word(args);
+        let args = if args.is_empty() {
+            first.to_string()
+        } else {
+            format!("{} {}", first, args)
+        };
+        return Ok(mail(tag, &name.to_ascii_uppercase(), args));
+    }
+    Ok(mail(tag, &first.to_ascii_uppercase(), args.to_string()))
+}
+
+/// Recognizes a typical first client command such as `a1 CAPABILITY`.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    let Some((line, next)) = next_line(payload, 0) else {
+        return false;
+    };
+    if next != payload.len() {
+        return false;
+    }
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.split(' ');
+    let tag = words.next().unwrap_or("");
+    let command = words.next().unwrap_or("");
+    !tag.is_empty()
+        && tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'.')
+        && GREETING_COMMANDS
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(command))
+}
+
+/// Recognizes the server greeting, e.g. `* OK [CAPABILITY IMAP4rev1] ready`.
+pub fn sniff_server(payload: &[u8]) -> bool {
+    (payload.starts_with(b"* OK") || payload.starts_with(b"* PREAUTH"))
+        && next_line(payload, 0).is_some_and(|(line, _)| {
+            String::from_utf8_lossy(line)
+                .to_ascii_uppercase()
+                .contains("IMAP")
+        })
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    s.split_once(' ').unwrap_or((s, ""))
+}
+
+fn mail(tag: &str, command: &str, arguments: String) -> MailMessage {
+    MailMessage {
+        protocol: "imap".to_string(),
+        tag: tag.to_string(),
+        command: command.to_string(),
+        arguments,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mails(out: &[Record]) -> Vec<(String, String, String)> {
+        out.iter()
+            .filter_map(|r| match r {
+                Record::Mail(m) => Some((m.tag.clone(), m.command.clone(), m.arguments.clone())),
+                Record::Broker(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn literals_are_inlined_once_complete() {
+        let mut parser = ImapParser::new();
+        let mut out = Vec::new();
+        let mut buf = b"a1 LOGIN {4}\r\nus".to_vec();
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert!(out.is_empty());
+
+        buf.extend_from_slice(b"er secret\r\na2 uid fetch 1 FLAGS\r\n");
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert_eq!(
+            mails(&out),
+            [
+                ("a1".into(), "LOGIN".into(), "user secret".into()),
+                ("a2".into(), "UID FETCH".into(), "1 FLAGS".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn untagged_data_leads_with_its_number() {
+        let mut out = Vec::new();
+        let mut buf = b"* 3 EXISTS\r\n+ idling\r\na1 OK done\r\n".to_vec();
+        ImapParser::new().server_data(&mut buf, &mut out).unwrap();
+        assert_eq!(
+            mails(&out),
+            [
+                ("*".into(), "EXISTS".into(), "3".into()),
+                ("+".into(), "CONTINUATION".into(), "idling".into()),
+                ("a1".into(), "OK".into(), "done".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_literals_are_a_desync() {
+        let mut out = Vec::new();
+        let mut buf = format!("a1 APPEND INBOX {{{}}}\r\n", MAX_LITERAL_LEN + 1).into_bytes();
+        assert!(ImapParser::new().client_data(&mut buf, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+}
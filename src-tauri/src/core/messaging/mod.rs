@@ -0,0 +1,566 @@
+//! Mail and message broker protocol decoding backing the `mail_messages`
+//! (SMTP, IMAP) and `broker_messages` (AMQP 0-9-1, MQTT) SQL tables.
+//!
+//! Connections are recognized by well-known port or by sniffing the first
+//! message of either side; the parser then emits one record per command,
+//! reply, method or control packet.
+
+pub mod amqp;
+pub mod imap;
+pub mod mqtt;
+pub mod smtp;
+
+use std::collections::{HashMap, HashSet};
+
+use polars::prelude::*;
+
+use crate::core::content_decoding::{MAX_DECODED_BODY_SIZE, truncate_text};
+use crate::core::event_type::Direction;
+use crate::core::flows::{Orientation, flow_key};
+use crate::core::models::{BrokerMessage, ContentKind, MailMessage, PacketData};
+
+/// Connections whose unparsed bytes grow beyond this are considered desynced.
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// Command arguments and details longer than this are cut.
+pub(crate) const MAX_TEXT_LEN: usize = 64 * 1024;
+
+/// Stored message bodies are cut at this size.
+pub(crate) const MAX_CONTENT_LEN: usize = MAX_DECODED_BODY_SIZE;
+
+/// Output of a protocol parser. Connection and packet fields are filled in
+/// by the table.
+pub enum Record {
+    Mail(MailMessage),
+    Broker(BrokerMessage),
+}
+
+/// The stream no longer follows the protocol; the connection is dropped.
+#[derive(Debug)]
+pub struct Desync;
+
+pub trait MessageParser: Send {
+    /// Consumes complete client messages from the front of `buf`.
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync>;
+
+    /// Consumes complete server messages from the front of `buf`.
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync>;
+}
+
+struct Connection {
+    parser: Box<dyn MessageParser>,
+    orientation: Orientation,
+    /// Way client data goes, seen from the hooked process.
+    client: Direction,
+    client_buf: Vec<u8>,
+    server_buf: Vec<u8>,
+}
+
+pub struct MessagingTables {
+    connections: HashMap<String, Connection>,
+    /// Connections known not to carry a supported protocol.
+    ignored: HashSet<String>,
+    pending_mail: Vec<MailMessage>,
+    pending_broker: Vec<BrokerMessage>,
+    next_mail_id: u64,
+    next_broker_id: u64,
+    mail_df: DataFrame,
+    broker_df: DataFrame,
+}
+
+impl MessagingTables {
+    pub fn new() -> Self {
+        Self {
+            connections: HashMap::new(),
+            ignored: HashSet::new(),
+            pending_mail: Vec::new(),
+            pending_broker: Vec::new(),
+            next_mail_id: 0,
+            next_broker_id: 0,
+            mail_df: create_mail_messages_df(),
+            broker_df: create_broker_messages_df(),
+        }
+    }
+
+    /// Feeds a batch of events. `first_index` is the packet index assigned to
+    /// the first event of the batch.
+    pub fn update(&mut self, batch: &[PacketData], first_index: u64) {
+        for (offset, d) in batch.iter().enumerate() {
+            let packet_index = first_index + offset as u64;
+            let key = flow_key(d);
+            if self.ignored.contains(&key) {
+                continue;
+            }
+            let payload = d.payload_bytes();
+
+            if !self.connections.contains_key(&key) {
+                match detect(d, payload) {
+                    Some(conn) => {
+                        self.connections.insert(key.clone(), conn);
+                    }
+                    None => {
+                        if d.content_kind == ContentKind::Http.as_str() {
+                            self.ignored.insert(key);
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let conn = self.connections.get_mut(&key).unwrap();
+            let mut records = Vec::new();
+            let from_client = conn.orientation.direction(d) == conn.client;
+            let result = if from_client {
+                conn.client_buf.extend_from_slice(payload);
+                conn.parser.client_data(&mut conn.client_buf, &mut records)
+            } else {
+                conn.server_buf.extend_from_slice(payload);
+                conn.parser.server_data(&mut conn.server_buf, &mut records)
+            };
+
+            let overflow = conn.client_buf.len() + conn.server_buf.len() > MAX_PENDING_BYTES;
+            if result.is_err() || overflow {
+                self.connections.remove(&key);
+                self.ignored.insert(key.clone());
+            }
+
+            let direction = if from_client {
+                "client_to_server"
+            } else {
+                "server_to_client"
+            };
+            for record in records {
+                match record {
+                    Record::Mail(mut m) => {
+                        m.message_id = self.next_mail_id;
+                        m.uuid = key.clone();
+                        m.packet_index = packet_index;
+                        m.timestamp = d.timestamp;
+                        m.direction = direction.to_string();
+                        self.next_mail_id += 1;
+                        self.pending_mail.push(m);
+                    }
+                    Record::Broker(mut m) => {
+                        m.message_id = self.next_broker_id;
+                        m.uuid = key.clone();
+                        m.packet_index = packet_index;
+                        m.timestamp = d.timestamp;
+                        m.direction = direction.to_string();
+                        self.next_broker_id += 1;
+                        self.pending_broker.push(m);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mail records decoded since the table was last returned, oldest first.
+    pub fn pending_mail_mut(&mut self) -> &mut Vec<MailMessage> {
+        &mut self.pending_mail
+    }
+
+    /// Broker records decoded since the table was last returned, oldest
+    /// first.
+    pub fn pending_broker_mut(&mut self) -> &mut Vec<BrokerMessage> {
+        &mut self.pending_broker
+    }
+
+    /// Returns the `mail_messages` table, appending records decoded since
+    /// the last call.
+    pub fn as_mail_df(&mut self) -> PolarsResult<DataFrame> {
+        if !self.pending_mail.is_empty() {
+            let batch = mail_messages_to_df(&std::mem::take(&mut self.pending_mail))?;
+            self.mail_df.vstack_mut(&batch)?;
+        }
+        Ok(self.mail_df.clone())
+    }
+
+    /// Returns the `broker_messages` table, appending records decoded since
+    /// the last call.
+    pub fn as_broker_df(&mut self) -> PolarsResult<DataFrame> {
+        if !self.pending_broker.is_empty() {
+            let batch = broker_messages_to_df(&std::mem::take(&mut self.pending_broker))?;
+            self.broker_df.vstack_mut(&batch)?;
+        }
+        Ok(self.broker_df.clone())
+    }
+}
+
+impl Default for MessagingTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type ParserFactory = fn() -> Box<dyn MessageParser>;
+type Sniffer = fn(&[u8]) -> bool;
+
+/// Picks a parser for a new connection, by server port first and by the
+/// shape of the first client or server message second.
+fn detect(d: &PacketData, payload: &[u8]) -> Option<Connection> {
+    const PORTS: [(&[u32], ParserFactory); 4] = [
+        (&[25, 465, 587], || Box::new(smtp::SmtpParser::new())),
+        (&[143, 993], || Box::new(imap::ImapParser::new())),
+        (&[5671, 5672], || Box::new(amqp::AmqpParser::new())),
+        (&[1883, 8883], || Box::new(mqtt::MqttParser::new())),
+    ];
+    const SNIFFERS: [(Sniffer, Sniffer, ParserFactory); 4] = [
+        (smtp::sniff_client, smtp::sniff_server, || {
+            Box::new(smtp::SmtpParser::new())
+        }),
+        (imap::sniff_client, imap::sniff_server, || {
+            Box::new(imap::ImapParser::new())
+        }),
+        (
+            amqp::sniff_client,
+            |_| false,
+            || Box::new(amqp::AmqpParser::new()),
+        ),
+        (
+            mqtt::sniff_client,
+            |_| false,
+            || Box::new(mqtt::MqttParser::new()),
+        ),
+    ];
+
+    let orientation = Orientation::new(d);
+    let sent = orientation.direction(d);
+
+    let by_port = PORTS.iter().find_map(|(ports, make)| {
+        if ports.contains(&d.dst_port) {
+            Some((make(), sent))
+        } else if ports.contains(&d.src_port) {
+            Some((make(), sent.opposite()))
+        } else {
+            None
+        }
+    });
+    let by_content = || {
+        SNIFFERS.iter().find_map(|(client, server, make)| {
+            if client(payload) {
+                Some((make(), sent))
+            } else if server(payload) {
+                Some((make(), sent.opposite()))
+            } else {
+                None
+            }
+        })
+    };
+
+    let (parser, client) = by_port.or_else(by_content)?;
+    Some(Connection {
+        parser,
+        orientation,
+        client,
+        client_buf: Vec::new(),
+        server_buf: Vec::new(),
+    })
+}
+
+/// Lossy text rendering of protocol fields, cut at `MAX_TEXT_LEN`.
+pub(crate) fn text(bytes: &[u8]) -> String {
+    truncate_text(String::from_utf8_lossy(bytes).into_owned(), MAX_TEXT_LEN)
+}
+
+/// Fills the payload columns of a broker message from a message body.
+pub(crate) fn set_payload(m: &mut BrokerMessage, body: Vec<u8>, length: u64) {
+    m.length = length;
+    match String::from_utf8(body) {
+        Ok(text) => m.payload_utf8 = text,
+        Err(e) => {
+            m.is_binary = true;
+            m.payload_binary = e.into_bytes();
+        }
+    }
+}
+
+/// Finds the next CRLF-terminated line at `pos`, returning it without the
+/// terminator and the position after it.
+pub(crate) fn next_line(buf: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let len = buf.get(pos..)?.windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[pos..pos + len], pos + len + 2))
+}
+
+/// Big-endian field reader over a frame or packet body.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(crate) fn short_str(&mut self) -> Option<String> {
+        let len = self.u8()? as usize;
+        Some(text(self.take(len)?))
+    }
+
+    /// Everything not read yet.
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = self.data.get(self.pos..).unwrap_or_default();
+        self.pos = self.data.len();
+        rest
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+fn mail_messages_to_df(messages: &[MailMessage]) -> PolarsResult<DataFrame> {
+    let len = messages.len();
+    let mut message_id_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("message_id".into(), len);
+    let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), len);
+    let mut protocol_builder = StringChunkedBuilder::new("protocol".into(), len);
+    let mut packet_index_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("packet_index".into(), len);
+    let mut ts_builder = PrimitiveChunkedBuilder::<Int64Type>::new("timestamp".into(), len);
+    let mut direction_builder = StringChunkedBuilder::new("direction".into(), len);
+    let mut tag_builder = StringChunkedBuilder::new("tag".into(), len);
+    let mut command_builder = StringChunkedBuilder::new("command".into(), len);
+    let mut arguments_builder = StringChunkedBuilder::new("arguments".into(), len);
+    let mut mail_from_builder = StringChunkedBuilder::new("mail_from".into(), len);
+    let mut rcpt_to_builder = StringChunkedBuilder::new("rcpt_to".into(), len);
+    let mut subject_builder = StringChunkedBuilder::new("subject".into(), len);
+    let mut content_builder = StringChunkedBuilder::new("content".into(), len);
+
+    for m in messages {
+        message_id_builder.append_value(m.message_id);
+        uuid_builder.append_value(&m.uuid);
+        protocol_builder.append_value(&m.protocol);
+        packet_index_builder.append_value(m.packet_index);
+        ts_builder.append_value(m.timestamp);
+        direction_builder.append_value(&m.direction);
+        tag_builder.append_value(&m.tag);
+        command_builder.append_value(&m.command);
+        arguments_builder.append_value(&m.arguments);
+        mail_from_builder.append_value(&m.mail_from);
+        rcpt_to_builder.append_value(&m.rcpt_to);
+        subject_builder.append_value(&m.subject);
+        content_builder.append_value(&m.content);
+    }
+
+    DataFrame::new(vec![
+        message_id_builder.finish().into_column(),
+        uuid_builder.finish().into_column(),
+        protocol_builder.finish().into_column(),
+        packet_index_builder.finish().into_column(),
+        ts_builder.finish().into_column(),
+        direction_builder.finish().into_column(),
+        tag_builder.finish().into_column(),
+        command_builder.finish().into_column(),
+        arguments_builder.finish().into_column(),
+        mail_from_builder.finish().into_column(),
+        rcpt_to_builder.finish().into_column(),
+        subject_builder.finish().into_column(),
+        content_builder.finish().into_column(),
+    ])
+}
+
+fn broker_messages_to_df(messages: &[BrokerMessage]) -> PolarsResult<DataFrame> {
+    let len = messages.len();
+    let mut message_id_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("message_id".into(), len);
+    let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), len);
+    let mut protocol_builder = StringChunkedBuilder::new("protocol".into(), len);
+    let mut packet_index_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("packet_index".into(), len);
+    let mut ts_builder = PrimitiveChunkedBuilder::<Int64Type>::new("timestamp".into(), len);
+    let mut direction_builder = StringChunkedBuilder::new("direction".into(), len);
+    let mut method_builder = StringChunkedBuilder::new("method".into(), len);
+    let mut channel_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("channel".into(), len);
+    let mut exchange_builder = StringChunkedBuilder::new("exchange".into(), len);
+    let mut topic_builder = StringChunkedBuilder::new("topic".into(), len);
+    let mut queue_builder = StringChunkedBuilder::new("queue".into(), len);
+    let mut packet_id_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("packet_id".into(), len);
+    let mut qos_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("qos".into(), len);
+    let mut detail_builder = StringChunkedBuilder::new("detail".into(), len);
+    let mut content_type_builder = StringChunkedBuilder::new("content_type".into(), len);
+    let mut length_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("length".into(), len);
+    let mut is_binary_builder = BooleanChunkedBuilder::new("is_binary".into(), len);
+    let mut payload_utf8_builder = StringChunkedBuilder::new("payload_utf8".into(), len);
+    let mut payload_binary_builder = BinaryChunkedBuilder::new("payload_binary".into(), len);
+
+    for m in messages {
+        message_id_builder.append_value(m.message_id);
+        uuid_builder.append_value(&m.uuid);
+        protocol_builder.append_value(&m.protocol);
+        packet_index_builder.append_value(m.packet_index);
+        ts_builder.append_value(m.timestamp);
+        direction_builder.append_value(&m.direction);
+        method_builder.append_value(&m.method);
+        channel_builder.append_value(m.channel);
+        exchange_builder.append_value(&m.exchange);
+        topic_builder.append_value(&m.topic);
+        queue_builder.append_value(&m.queue);
+        packet_id_builder.append_option(m.packet_id);
+        qos_builder.append_option(m.qos);
+        detail_builder.append_value(&m.detail);
+        content_type_builder.append_value(&m.content_type);
+        length_builder.append_value(m.length);
+        is_binary_builder.append_value(m.is_binary);
+        payload_utf8_builder.append_value(&m.payload_utf8);
+        payload_binary_builder.append_value(&m.payload_binary);
+    }
+
+    DataFrame::new(vec![
+        message_id_builder.finish().into_column(),
+        uuid_builder.finish().into_column(),
+        protocol_builder.finish().into_column(),
+        packet_index_builder.finish().into_column(),
+        ts_builder.finish().into_column(),
+        direction_builder.finish().into_column(),
+        method_builder.finish().into_column(),
+        channel_builder.finish().into_column(),
+        exchange_builder.finish().into_column(),
+        topic_builder.finish().into_column(),
+        queue_builder.finish().into_column(),
+        packet_id_builder.finish().into_column(),
+        qos_builder.finish().into_column(),
+        detail_builder.finish().into_column(),
+        content_type_builder.finish().into_column(),
+        length_builder.finish().into_column(),
+        is_binary_builder.finish().into_column(),
+        payload_utf8_builder.finish().into_column(),
+        payload_binary_builder.finish().into_column(),
+    ])
+}
+
+pub fn create_mail_messages_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("message_id".into(), DataType::UInt64),
+        Field::new("uuid".into(), DataType::String),
+        Field::new("protocol".into(), DataType::String),
+        Field::new("packet_index".into(), DataType::UInt64),
+        Field::new("timestamp".into(), DataType::Int64),
+        Field::new("direction".into(), DataType::String),
+        Field::new("tag".into(), DataType::String),
+        Field::new("command".into(), DataType::String),
+        Field::new("arguments".into(), DataType::String),
+        Field::new("mail_from".into(), DataType::String),
+        Field::new("rcpt_to".into(), DataType::String),
+        Field::new("subject".into(), DataType::String),
+        Field::new("content".into(), DataType::String),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+pub fn create_broker_messages_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("message_id".into(), DataType::UInt64),
+        Field::new("uuid".into(), DataType::String),
+        Field::new("protocol".into(), DataType::String),
+        Field::new("packet_index".into(), DataType::UInt64),
+        Field::new("timestamp".into(), DataType::Int64),
+        Field::new("direction".into(), DataType::String),
+        Field::new("method".into(), DataType::String),
+        Field::new("channel".into(), DataType::UInt32),
+        Field::new("exchange".into(), DataType::String),
+        Field::new("topic".into(), DataType::String),
+        Field::new("queue".into(), DataType::String),
+        Field::new("packet_id".into(), DataType::UInt32),
+        Field::new("qos".into(), DataType::UInt32),
+        Field::new("detail".into(), DataType::String),
+        Field::new("content_type".into(), DataType::String),
+        Field::new("length".into(), DataType::UInt64),
+        Field::new("is_binary".into(), DataType::Boolean),
+        Field::new("payload_utf8".into(), DataType::String),
+        Field::new("payload_binary".into(), DataType::Binary),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event between a client on port 5000 and a server on `port`.
+    fn event(from_client: bool, port: u32, payload: &[u8]) -> PacketData {
+        let (src_port, dst_port) = if from_client {
+            (5000, port)
+        } else {
+            (port, 5000)
+        };
+        PacketData {
+            uuid: "conn".to_string(),
+            src_ip: if from_client { "10.0.0.1" } else { "10.0.0.2" }.to_string(),
+            src_port,
+            dst_ip: if from_client { "10.0.0.2" } else { "10.0.0.1" }.to_string(),
+            dst_port,
+            is_binary: true,
+            payload_binary: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn smtp_exchange_round_trip() {
+        let mut tables = MessagingTables::new();
+        // Sniffed from the greeting; 2525 is no well-known port
+        tables.update(
+            &[
+                event(false, 2525, b"220 mail.example.com ESMTP\r\n"),
+                event(true, 2525, b"EHLO client\r\n"),
+                event(false, 2525, b"250 mail.example.com\r\n"),
+            ],
+            0,
+        );
+
+        let rows: Vec<_> = tables
+            .pending_mail_mut()
+            .iter()
+            .map(|m| (m.packet_index, m.direction.as_str(), m.command.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (0, "server_to_client", "220"),
+                (1, "client_to_server", "EHLO"),
+                (2, "server_to_client", "250"),
+            ]
+        );
+        assert_eq!(tables.as_mail_df().unwrap().height(), 3);
+    }
+
+    #[test]
+    fn oversized_length_prefix_drops_the_connection() {
+        let mut tables = MessagingTables::new();
+        let mut huge = vec![3 << 4, 0x80, 0x80, 0x80, 0x40];
+        huge.extend_from_slice(b"topic");
+        tables.update(
+            &[event(true, 1883, &huge), event(true, 1883, &[0xc0, 0])],
+            0,
+        );
+
+        assert!(tables.pending_broker_mut().is_empty());
+        assert!(tables.connections.is_empty());
+        assert!(tables.ignored.contains("conn"));
+    }
+}
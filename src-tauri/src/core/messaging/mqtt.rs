@@ -0,0 +1,400 @@
+use crate::core::models::BrokerMessage;
+
+use super::{Desync, MessageParser, Reader, Record, set_payload, text};
+
+/// Packets announced larger than this are treated as garbage (the protocol
+/// maximum is 256 MiB).
+const MAX_PACKET_LEN: usize = 64 * 1024 * 1024;
+
+const CONNECT: u8 = 1;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const UNSUBSCRIBE: u8 = 10;
+
+const PACKET_NAMES: [&str; 16] = [
+    "RESERVED",
+    "CONNECT",
+    "CONNACK",
+    "PUBLISH",
+    "PUBACK",
+    "PUBREC",
+    "PUBREL",
+    "PUBCOMP",
+    "SUBSCRIBE",
+    "SUBACK",
+    "UNSUBSCRIBE",
+    "UNSUBACK",
+    "PINGREQ",
+    "PINGRESP",
+    "DISCONNECT",
+    "AUTH",
+];
+
+/// MQTT 3.1, 3.1.1 and 5: one record per control packet. The protocol
+/// level announced in CONNECT decides whether packets carry v5 properties.
+pub struct MqttParser {
+    version: u8,
+}
+
+impl MqttParser {
+    pub fn new() -> Self {
+        Self { version: 4 }
+    }
+
+    fn packets(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        while let Some(&first) = buf.get(pos) {
+            let kind = first >> 4;
+            if kind == 0 {
+                result = Err(Desync);
+                break;
+            }
+            let (len, header_len) = match remaining_length(&buf[pos + 1..]) {
+                Ok(Some(v)) => v,
+                Ok(None) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            if len > MAX_PACKET_LEN {
+                result = Err(Desync);
+                break;
+            }
+            let start = pos + 1 + header_len;
+            let Some(body) = buf.get(start..start + len) else {
+                break;
+            };
+            if kind == CONNECT
+                && let Some(version) = connect_version(body)
+            {
+                self.version = version;
+            }
+            // Malformed bodies are skipped; framing is still intact
+            if let Some(m) = packet(first, body, self.version >= 5) {
+                out.push(Record::Broker(m));
+            }
+            pos = start + len;
+        }
+        buf.drain(..pos);
+        result
+    }
+}
+
+impl Default for MqttParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageParser for MqttParser {
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        self.packets(buf, out)
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        self.packets(buf, out)
+    }
+}
+
+/// Recognizes a CONNECT packet naming the `MQTT` (or 3.1 `MQIsdp`) protocol.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    if payload.first() != Some(&(CONNECT << 4)) {
+        return false;
+    }
+    let Ok(Some((_, header_len))) = remaining_length(&payload[1..]) else {
+        return false;
+    };
+    let body = &payload[1 + header_len..];
+    body.starts_with(b"\x00\x04MQTT") || body.starts_with(b"\x00\x06MQIsdp")
+}
+
+/// Decodes the variable-length "remaining length" of the fixed header,
+/// returning it with the number of bytes it took.
+fn remaining_length(data: &[u8]) -> Result<Option<(usize, usize)>, Desync> {
+    let mut value = 0usize;
+    for i in 0..4 {
+        let Some(&b) = data.get(i) else {
+            return Ok(None);
+        };
+        value |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Err(Desync)
+}
+
+fn connect_version(body: &[u8]) -> Option<u8> {
+    let mut r = Reader::new(body);
+    utf8(&mut r)?;
+    r.u8()
+}
+
+/// Decodes a control packet into a record with the fields most useful for
+/// filtering; other fields are left out.
+fn packet(first: u8, body: &[u8], v5: bool) -> Option<BrokerMessage> {
+    let kind = first >> 4;
+    let mut r = Reader::new(body);
+    let mut m = BrokerMessage {
+        protocol: "mqtt".to_string(),
+        method: PACKET_NAMES[kind as usize].to_string(),
+        ..Default::default()
+    };
+
+    match kind {
+        CONNECT => {
+            utf8(&mut r)?;
+            let version = r.u8()?;
+            let flags = r.u8()?;
+            let keepalive = r.u16()?;
+            if version >= 5 {
+                properties(&mut r)?;
+            }
+            let client_id = utf8(&mut r)?;
+            m.detail = format!(
+                "client_id={} version={} keepalive={} clean={}",
+                client_id,
+                version,
+                keepalive,
+                flags & 0x02 != 0
+            );
+            // Will message: properties (v5), topic, payload
+            if flags & 0x04 != 0 {
+                if version >= 5 {
+                    properties(&mut r)?;
+                }
+                m.topic = utf8(&mut r)?;
+                m.qos = Some(((flags >> 3) & 0x03) as u32);
+                let len = r.u16()? as usize;
+                let will = r.take(len)?;
+                set_payload(&mut m, will.to_vec(), len as u64);
+            }
+            if flags & 0x80 != 0 {
+                m.detail.push_str(&format!(" username={}", utf8(&mut r)?));
+            }
+        }
+        // CONNACK: flags, return (v3) or reason (v5) code
+        2 => {
+            let flags = r.u8()?;
+            let code = r.u8()?;
+            m.detail = format!("session_present={} code={}", flags & 1 != 0, code);
+        }
+        PUBLISH => {
+            let qos = (first >> 1) & 0x03;
+            m.topic = utf8(&mut r)?;
+            m.qos = Some(qos as u32);
+            if qos > 0 {
+                m.packet_id = Some(r.u16()? as u32);
+            }
+            if v5 {
+                m.content_type = properties(&mut r)?.unwrap_or_default();
+            }
+            m.detail = format!("retain={} dup={}", first & 1 != 0, first & 0x08 != 0);
+            let payload = r.rest();
+            set_payload(&mut m, payload.to_vec(), payload.len() as u64);
+        }
+        // PUBACK, PUBREC, PUBREL, PUBCOMP, UNSUBACK: packet id, then (v5)
+        // an optional reason code
+        4..=7 | 11 => {
+            m.packet_id = Some(r.u16()? as u32);
+            if v5 && kind != 11 && !r.is_empty() {
+                m.detail = format!("code={}", r.u8()?);
+            }
+        }
+        SUBSCRIBE | UNSUBSCRIBE => {
+            m.packet_id = Some(r.u16()? as u32);
+            if v5 {
+                properties(&mut r)?;
+            }
+            let mut filters = Vec::new();
+            let mut qos = Vec::new();
+            while !r.is_empty() {
+                filters.push(utf8(&mut r)?);
+                if kind == SUBSCRIBE {
+                    qos.push((r.u8()? & 0x03).to_string());
+                }
+            }
+            m.topic = filters.join(", ");
+            if kind == SUBSCRIBE {
+                m.detail = format!("qos={}", qos.join(","));
+            }
+        }
+        // SUBACK: packet id, properties (v5), one return code per filter
+        9 => {
+            m.packet_id = Some(r.u16()? as u32);
+            if v5 {
+                properties(&mut r)?;
+            }
+            let codes: Vec<String> = r.rest().iter().map(u8::to_string).collect();
+            m.detail = format!("codes={}", codes.join(","));
+        }
+        // DISCONNECT and AUTH: optional reason code (v5)
+        14 | 15 => {
+            if let Some(code) = r.u8() {
+                m.detail = format!("code={}", code);
+            }
+        }
+        _ => {}
+    }
+    Some(m)
+}
+
+/// Reads a length-prefixed UTF-8 string.
+fn utf8(r: &mut Reader) -> Option<String> {
+    let len = r.u16()? as usize;
+    Some(text(r.take(len)?))
+}
+
+fn variable_int(r: &mut Reader) -> Option<usize> {
+    let mut value = 0usize;
+    for i in 0..4 {
+        let b = r.u8()?;
+        value |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Skips an MQTT 5 property list, returning the Content Type property if
+/// it is present.
+fn properties(r: &mut Reader) -> Option<Option<String>> {
+    let len = variable_int(r)?;
+    let mut props = Reader::new(r.take(len)?);
+    let mut content_type = None;
+    while !props.is_empty() {
+        match props.u8()? {
+            0x03 => content_type = Some(utf8(&mut props)?),
+            0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2a => {
+                props.u8()?;
+            }
+            0x13 | 0x21 | 0x22 | 0x23 => {
+                props.u16()?;
+            }
+            0x02 | 0x11 | 0x18 | 0x27 => {
+                props.u32()?;
+            }
+            0x0b => {
+                variable_int(&mut props)?;
+            }
+            0x08 | 0x09 | 0x12 | 0x15 | 0x16 | 0x1a | 0x1c | 0x1f => {
+                utf8(&mut props)?;
+            }
+            // User property: name and value
+            0x26 => {
+                utf8(&mut props)?;
+                utf8(&mut props)?;
+            }
+            // Unknown property; the rest of the list cannot be walked
+            _ => break,
+        }
+    }
+    Some(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet with a one-byte remaining length.
+    fn packet(first: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![first, body.len() as u8];
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    fn utf8_field(s: &str) -> Vec<u8> {
+        let mut out = (s.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    /// CONNECT at protocol `level` from client `client_id`.
+    fn connect(level: u8, client_id: &str) -> Vec<u8> {
+        let mut body = utf8_field("MQTT");
+        body.extend_from_slice(&[level, 0x02, 0, 60]);
+        if level >= 5 {
+            body.push(0);
+        }
+        body.extend(utf8_field(client_id));
+        packet(CONNECT << 4, &body)
+    }
+
+    fn broker(out: &[Record]) -> Vec<&BrokerMessage> {
+        out.iter()
+            .filter_map(|r| match r {
+                Record::Broker(m) => Some(m),
+                Record::Mail(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn v5_publish_after_connect_reads_properties() {
+        let mut parser = MqttParser::new();
+        let mut out = Vec::new();
+        let mut body = utf8_field("sensors/1");
+        body.extend_from_slice(&[0, 7]);
+        let mut properties = vec![0x03];
+        properties.extend(utf8_field("text/plain"));
+        body.push(properties.len() as u8);
+        body.extend(properties);
+        body.extend_from_slice(b"21.5");
+
+        let mut buf = connect(5, "probe");
+        buf.extend(packet(PUBLISH << 4 | 0x02, &body));
+        parser.client_data(&mut buf, &mut out).unwrap();
+
+        let messages = broker(&out);
+        assert_eq!(
+            messages[0].detail,
+            "client_id=probe version=5 keepalive=60 clean=true"
+        );
+        let publish = messages[1];
+        assert_eq!(
+            (publish.topic.as_str(), publish.qos, publish.packet_id),
+            ("sensors/1", Some(1), Some(7))
+        );
+        assert_eq!(
+            (publish.content_type.as_str(), publish.payload_utf8.as_str()),
+            ("text/plain", "21.5")
+        );
+    }
+
+    #[test]
+    fn truncated_packets_stay_buffered() {
+        let mut parser = MqttParser::new();
+        let mut out = Vec::new();
+        let whole = connect(4, "probe");
+        // Cut inside the remaining length of a second packet too
+        let mut buf = whole.clone();
+        buf.push(0xc0);
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!(buf, [0xc0]);
+
+        buf.push(0);
+        parser.client_data(&mut buf, &mut out).unwrap();
+        assert_eq!(broker(&out)[1].method, "PINGREQ");
+    }
+
+    #[test]
+    fn oversized_remaining_length_is_a_desync() {
+        let mut out = Vec::new();
+        // A fifth length byte is not allowed
+        let mut endless = vec![PUBLISH << 4, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(
+            MqttParser::new()
+                .client_data(&mut endless, &mut out)
+                .is_err()
+        );
+
+        // 128 MiB, within the encoding but beyond the limit
+        let mut huge = vec![PUBLISH << 4, 0x80, 0x80, 0x80, 0x40];
+        assert!(MqttParser::new().client_data(&mut huge, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+}
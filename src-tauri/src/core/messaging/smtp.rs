@@ -0,0 +1,365 @@
+use std::collections::VecDeque;
+
+use crate::core::content_decoding::truncate_text;
+use crate::core::models::MailMessage;
+
+use super::{Desync, MAX_CONTENT_LEN, MessageParser, Record, next_line, text};
+
+/// Command and reply lines are limited to 1000 bytes (RFC 5321 §4.5.3.1);
+/// a longer unterminated line means this is not SMTP.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// SMTP submission: commands, replies and the message content sent with
+/// DATA or BDAT, which is recorded as a `MESSAGE` row carrying the envelope.
+pub struct SmtpParser {
+    /// Commands waiting for a reply, oldest first.
+    awaiting: VecDeque<String>,
+    /// The server accepted DATA; the client is sending content.
+    in_data: bool,
+    /// Bytes of content already searched for the terminating dot.
+    data_scanned: usize,
+    /// Remaining chunk size of a BDAT command and whether it is the last.
+    bdat: Option<(usize, bool)>,
+    /// The server asked for the next AUTH exchange line (334).
+    auth_continuation: bool,
+    mail_from: String,
+    rcpt_to: Vec<String>,
+    content: Vec<u8>,
+}
+
+impl SmtpParser {
+    pub fn new() -> Self {
+        Self {
+            awaiting: VecDeque::new(),
+            in_data: false,
+            data_scanned: 0,
+            bdat: None,
+            auth_continuation: false,
+            mail_from: String::new(),
+            rcpt_to: Vec::new(),
+            content: Vec::new(),
+        }
+    }
+
+    fn command(&mut self, line: &[u8], out: &mut Vec<Record>) -> Result<(), Desync> {
+        if self.auth_continuation {
+            self.auth_continuation = false;
+            self.awaiting.push_back("AUTH_DATA".to_string());
+            out.push(Record::Mail(mail("AUTH_DATA", text(line))));
+            return Ok(());
+        }
+
+        let line = text(line);
+        let (verb, args) = line.split_once(' ').unwrap_or((&line, ""));
+        if verb.is_empty() || !verb.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(Desync);
+        }
+        let verb = verb.to_ascii_uppercase();
+        match verb.as_str() {
+            "MAIL" => {
+                self.mail_from = address(args);
+                self.rcpt_to.clear();
+            }
+            "RCPT" => self.rcpt_to.push(address(args)),
+            "RSET" => self.reset_envelope(),
+            "BDAT" => {
+                let mut parts = args.split_whitespace();
+                let size = parts.next().and_then(|s| s.parse().ok()).ok_or(Desync)?;
+                let last = parts.next().is_some_and(|p| p.eq_ignore_ascii_case("LAST"));
+                self.bdat = Some((size, last));
+            }
+            _ => {}
+        }
+        self.awaiting.push_back(verb.clone());
+        out.push(Record::Mail(mail(&verb, args.to_string())));
+        Ok(())
+    }
+
+    fn reply(&mut self, code: &str, lines: Vec<String>, out: &mut Vec<Record>) {
+        let command = self.awaiting.pop_front();
+        if code == "354" && command.as_deref() == Some("DATA") {
+            self.in_data = true;
+            self.data_scanned = 0;
+        }
+        self.auth_continuation = code == "334";
+        out.push(Record::Mail(mail(code, lines.join("\n"))));
+    }
+
+    fn message(&mut self, out: &mut Vec<Record>) {
+        let content = std::mem::take(&mut self.content);
+        let mut m = mail("MESSAGE", format!("{} bytes", content.len()));
+        m.mail_from = self.mail_from.clone();
+        m.rcpt_to = self.rcpt_to.join(", ");
+        m.subject = subject(&content);
+        m.content = truncate_text(
+            String::from_utf8_lossy(&content).into_owned(),
+            MAX_CONTENT_LEN,
+        );
+        out.push(Record::Mail(m));
+        self.reset_envelope();
+    }
+
+    fn push_content(&mut self, data: &[u8]) {
+        let room = MAX_CONTENT_LEN.saturating_sub(self.content.len());
+        self.content
+            .extend_from_slice(&data[..data.len().min(room)]);
+    }
+
+    fn reset_envelope(&mut self) {
+        self.mail_from.clear();
+        self.rcpt_to.clear();
+        self.content.clear();
+    }
+}
+
+impl Default for SmtpParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageParser for SmtpParser {
+    fn client_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        while pos < buf.len() {
+            if let Some((remaining, last)) = self.bdat {
+                let take = remaining.min(buf.len() - pos);
+                self.push_content(&buf[pos..pos + take]);
+                pos += take;
+                if take < remaining {
+                    self.bdat = Some((remaining - take, last));
+                    break;
+                }
+                self.bdat = None;
+                if last {
+                    self.message(out);
+                }
+                continue;
+            }
+
+            if self.in_data {
+                // The content ends with a line holding a single dot; the
+                // CRLF before it belongs to the content.
+                let data = &buf[pos..];
+                let end = if data.starts_with(b".\r\n") {
+                    Some(0)
+                } else {
+                    let from = self.data_scanned.saturating_sub(4);
+                    data[from..]
+                        .windows(5)
+                        .position(|w| w == b"\r\n.\r\n")
+                        .map(|i| from + i + 2)
+                };
+                let Some(end) = end else {
+                    self.data_scanned = data.len();
+                    break;
+                };
+                let content = unstuff(&data[..end]);
+                self.push_content(&content);
+                pos += end + 3;
+                self.in_data = false;
+                self.awaiting.push_back("MESSAGE".to_string());
+                self.message(out);
+                continue;
+            }
+
+            let Some((line, next)) = next_line(buf, pos) else {
+                if buf.len() - pos > MAX_LINE_LEN {
+                    result = Err(Desync);
+                }
+                break;
+            };
+            if let Err(e) = self.command(line, out) {
+                result = Err(e);
+                break;
+            }
+            pos = next;
+        }
+        buf.drain(..pos);
+        result
+    }
+
+    fn server_data(&mut self, buf: &mut Vec<u8>, out: &mut Vec<Record>) -> Result<(), Desync> {
+        let mut pos = 0;
+        let mut result = Ok(());
+        'replies: loop {
+            // A reply is complete once its last line ("250 ...") arrived;
+            // earlier lines use a hyphen ("250-...").
+            let mut cursor = pos;
+            let mut lines = Vec::new();
+            loop {
+                let Some((line, next)) = next_line(buf, cursor) else {
+                    if buf.len() - pos > MAX_LINE_LEN {
+                        result = Err(Desync);
+                    }
+                    break 'replies;
+                };
+                let valid = line.len() >= 3
+                    && line[..3].iter().all(u8::is_ascii_digit)
+                    && matches!(line.get(3), None | Some(b' ') | Some(b'-'));
+                if !valid {
+                    result = Err(Desync);
+                    break 'replies;
+                }
+                lines.push(text(line.get(4..).unwrap_or_default()));
+                cursor = next;
+                if line.get(3) != Some(&b'-') {
+                    let code = String::from_utf8_lossy(&line[..3]).into_owned();
+                    self.reply(&code, lines, out);
+                    pos = cursor;
+                    continue 'replies;
+                }
+            }
+        }
+        buf.drain(..pos);
+        result
+    }
+}
+
+/// Recognizes an EHLO or HELO greeting from the client.
+pub fn sniff_client(payload: &[u8]) -> bool {
+    let head = payload.get(..5).unwrap_or_default();
+    (head.eq_ignore_ascii_case(b"EHLO ") || head.eq_ignore_ascii_case(b"HELO "))
+        && payload.ends_with(b"\r\n")
+}
+
+/// Recognizes the server's 220 greeting.
+pub fn sniff_server(payload: &[u8]) -> bool {
+    payload.starts_with(b"220")
+        && matches!(payload.get(3), Some(b' ') | Some(b'-'))
+        && next_line(payload, 0).is_some_and(|(line, _)| {
+            String::from_utf8_lossy(line)
+                .to_ascii_uppercase()
+                .contains("SMTP")
+        })
+}
+
+fn mail(command: &str, arguments: String) -> MailMessage {
+    MailMessage {
+        protocol: "smtp".to_string(),
+        command: command.to_string(),
+        arguments,
+        ..Default::default()
+    }
+}
+
+/// Extracts the address from `FROM:<a@b> SIZE=10` or `TO:<a@b>`.
+fn address(args: &str) -> String {
+    let value = args.split_once(':').map_or(args, |(_, v)| v).trim();
+    match (value.find('<'), value.find('>')) {
+        (Some(start), Some(end)) if start < end => value[start + 1..end].to_string(),
+        _ => value.split_whitespace().next().unwrap_or("").to_string(),
+    }
+}
+
+/// Removes the extra leading dot from lines that start with one.
+fn unstuff(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut line_start = true;
+    for (i, &b) in data.iter().enumerate() {
+        if !(line_start && b == b'.' && data.get(i + 1) == Some(&b'.')) {
+            out.push(b);
+        }
+        line_start = b == b'\n';
+    }
+    out
+}
+
+/// Reads the (possibly folded) Subject header of a message.
+fn subject(content: &[u8]) -> String {
+    let head_end = content
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(content.len());
+    let head = String::from_utf8_lossy(&content[..head_end]);
+
+    let mut subject: Option<String> = None;
+    for line in head.split("\r\n") {
+        match subject.as_mut() {
+            Some(s) if line.starts_with([' ', '\t']) => s.push_str(line),
+            Some(_) => break,
+            None => {
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("Subject")
+                {
+                    subject = Some(value.trim().to_string());
+                }
+            }
+        }
+    }
+    subject.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mails(out: &[Record]) -> Vec<&MailMessage> {
+        out.iter()
+            .filter_map(|r| match r {
+                Record::Mail(m) => Some(m),
+                Record::Broker(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn data_content_may_span_events() {
+        let mut parser = SmtpParser::new();
+        let mut out = Vec::new();
+        let mut client =
+            b"MAIL FROM:<a@example.com> SIZE=10\r\nRCPT TO:<b@example.com>\r\nDATA\r\n".to_vec();
+        parser.client_data(&mut client, &mut out).unwrap();
+        let mut server = b"250 OK\r\n250 OK\r\n354 Go ahead\r\n".to_vec();
+        parser.server_data(&mut server, &mut out).unwrap();
+
+        let mut client = b"Subject: hello\r\n\r\n..dotted\r\n".to_vec();
+        parser.client_data(&mut client, &mut out).unwrap();
+        client.extend_from_slice(b".\r\nQUIT\r\n");
+        parser.client_data(&mut client, &mut out).unwrap();
+
+        let records = mails(&out);
+        let message = records.iter().find(|m| m.command == "MESSAGE").unwrap();
+        assert_eq!(message.mail_from, "a@example.com");
+        assert_eq!(message.rcpt_to, "b@example.com");
+        assert_eq!(message.subject, "hello");
+        assert_eq!(message.content, "Subject: hello\r\n\r\n.dotted\r\n");
+        assert_eq!(records.last().unwrap().command, "QUIT");
+    }
+
+    #[test]
+    fn multiline_replies_wait_for_their_last_line() {
+        let mut parser = SmtpParser::new();
+        let mut out = Vec::new();
+        let mut server = b"250-mail.example.com\r\n250-PIPELINING\r\n".to_vec();
+        parser.server_data(&mut server, &mut out).unwrap();
+        assert!(out.is_empty());
+
+        server.extend_from_slice(b"250 CHUNKING\r\n");
+        parser.server_data(&mut server, &mut out).unwrap();
+        let records = mails(&out);
+        assert_eq!(records[0].command, "250");
+        assert_eq!(
+            records[0].arguments,
+            "mail.example.com\nPIPELINING\nCHUNKING"
+        );
+    }
+
+    #[test]
+    fn overlong_lines_are_a_desync() {
+        let mut out = Vec::new();
+        let mut client = vec![b'A'; MAX_LINE_LEN + 1];
+        assert!(
+            SmtpParser::new()
+                .client_data(&mut client, &mut out)
+                .is_err()
+        );
+        let mut server = b"OK\r\n".to_vec();
+        assert!(
+            SmtpParser::new()
+                .server_data(&mut server, &mut out)
+                .is_err()
+        );
+    }
+}
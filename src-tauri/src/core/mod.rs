@@ -1,4 +1,21 @@
 pub mod actor;
+pub mod android_packages;
+pub mod annotations;
+pub mod app_proto;
+pub mod classify;
+pub mod content_decoding;
 pub mod data_processing;
+pub mod db_queries;
+pub mod decoders;
+pub mod ecapture_args;
+pub mod event_type;
+pub mod flows;
+pub mod helper_protocol;
+pub mod messaging;
 pub mod models;
+pub mod protocol;
 pub mod queries;
+pub mod redaction;
+pub mod session_timeouts;
+pub mod sessions;
+pub mod ws_messages;
@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+use crate::core::ecapture_args::Subcommand;
+use crate::core::redaction::RedactionReport;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogType {
     Heartbeat = 0,
     ProcessLog = 1,
     Event = 2,
+    Handshake = 3,
 }
 
 impl From<u8> for LogType {
@@ -13,21 +17,53 @@ impl From<u8> for LogType {
             0 => LogType::Heartbeat,
             1 => LogType::ProcessLog,
             2 => LogType::Event,
+            3 => LogType::Handshake,
             _ => LogType::Event, // 默认当作事件处理
         }
     }
 }
 
+/// Coarse payload classification stored in the `content_kind` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Http,
+    TlsRecord,
+    Protobuf,
+    Text,
+    Binary,
+}
+
+impl ContentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Http => "http",
+            ContentKind::TlsRecord => "tls-record",
+            ContentKind::Protobuf => "protobuf",
+            ContentKind::Text => "text",
+            ContentKind::Binary => "binary",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ParsedMessage {
     Heartbeat(HeartbeatMessage),
     ProcessLog(ProcessLogMessage),
     Event(PacketData),
+    Handshake(HandshakeMessage),
+    /// An entry with a log type or payload this version cannot decode, e.g.
+    /// from a newer ecapture.
+    Unsupported {
+        log_type: i32,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct PacketData {
     pub index: u64,
+    /// Capture session the event was received in, see `list_sessions`.
+    #[serde(default)]
+    pub session_id: u64,
     pub timestamp: i64,
     pub uuid: String,
     pub src_ip: String,
@@ -38,17 +74,79 @@ pub struct PacketData {
     pub pid: i32,
     #[serde(alias = "process_name", alias = "proc_name", alias = "comm")]
     pub pname: String,
+    /// Process metadata; `None` / empty when the sender does not report it.
+    #[serde(default)]
+    pub tid: Option<i32>,
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub exe_path: String,
+    #[serde(default)]
+    pub cgroup_id: Option<u64>,
+    #[serde(default)]
+    pub container_id: String,
+    /// Android package(s) running as `uid`; empty when unknown.
+    #[serde(default)]
+    pub package: String,
+    #[serde(default)]
+    pub fd: Option<i32>,
     #[serde(rename = "type")]
     pub r#type: u32,
+    /// Name of `type`, see `core::event_type`.
+    #[serde(default)]
+    pub type_name: String,
+    /// `read`, `write` or `unknown`, seen from the hooked process.
+    #[serde(default)]
+    pub direction: String,
+    /// ecapture module of the capture session; `None` when not known, as
+    /// for an external ecapture.
+    #[serde(default)]
+    pub module: Option<Subcommand>,
     pub length: u32,
     pub is_binary: bool,
+    #[serde(default)]
+    pub content_kind: String,
+    /// Application protocol label, see `core::app_proto`.
+    #[serde(default)]
+    pub app_proto: String,
+    /// TLS session metadata; empty when unknown.
+    #[serde(default)]
+    pub tls_version: String,
+    #[serde(default)]
+    pub tls_cipher: String,
+    #[serde(default)]
+    pub tls_sni: String,
+    #[serde(default)]
+    pub tls_alpn: String,
+    /// TLS library the event was captured from, e.g. `openssl` or `gotls`.
+    #[serde(default)]
+    pub tls_library: String,
+    /// Text rendering of the payload (empty for binary content).
     pub payload_utf8: String,
+    /// Raw payload bytes; empty when `payload_utf8` already is the exact payload.
     pub payload_binary: Vec<u8>,
+    #[serde(default)]
+    pub decoded_body: Option<DecodedBody>,
+}
+
+impl PacketData {
+    /// Returns the captured payload bytes regardless of how they were stored.
+    pub fn payload_bytes(&self) -> &[u8] {
+        // payload_binary is only left empty when payload_utf8 is lossless
+        if self.payload_binary.is_empty() {
+            self.payload_utf8.as_bytes()
+        } else {
+            &self.payload_binary
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PacketDataFrontend {
     pub index: u64,
+    /// Capture session the event was received in, see `list_sessions`.
+    #[serde(default)]
+    pub session_id: u64,
     pub timestamp: i64,
     pub uuid: String,
     pub src_ip: String,
@@ -59,10 +157,190 @@ pub struct PacketDataFrontend {
     pub pid: i32,
     #[serde(alias = "process_name", alias = "proc_name", alias = "comm")]
     pub pname: String,
+    /// Process metadata; `None` / empty when the sender does not report it.
+    #[serde(default)]
+    pub tid: Option<i32>,
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub exe_path: String,
+    #[serde(default)]
+    pub cgroup_id: Option<u64>,
+    #[serde(default)]
+    pub container_id: String,
+    /// Android package(s) running as `uid`; empty when unknown.
+    #[serde(default)]
+    pub package: String,
+    #[serde(default)]
+    pub fd: Option<i32>,
     #[serde(rename = "type")]
     pub r#type: u32,
+    /// Name of `type`, see `core::event_type`.
+    #[serde(default)]
+    pub type_name: String,
+    /// `read`, `write` or `unknown`, seen from the hooked process.
+    #[serde(default)]
+    pub direction: String,
+    /// ecapture module of the capture session; `None` when not known, as
+    /// for an external ecapture.
+    #[serde(default)]
+    pub module: Option<Subcommand>,
     pub length: u32,
     pub is_binary: bool,
+    #[serde(default)]
+    pub content_kind: String,
+    #[serde(default)]
+    pub app_proto: String,
+    /// TLS session metadata; empty when unknown.
+    #[serde(default)]
+    pub tls_version: String,
+    #[serde(default)]
+    pub tls_cipher: String,
+    #[serde(default)]
+    pub tls_sni: String,
+    #[serde(default)]
+    pub tls_alpn: String,
+    /// TLS library the event was captured from, e.g. `openssl` or `gotls`.
+    #[serde(default)]
+    pub tls_library: String,
+}
+
+/// HTTP body after undoing its `Content-Encoding` (and chunked transfer coding).
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct DecodedBody {
+    pub content_encoding: String,
+    pub head: String,
+    pub is_binary: bool,
+    pub body_utf8: String,
+    pub body_binary: Vec<u8>,
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+/// Structured view of a payload produced by one of the `core::decoders`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DecodedPayload {
+    pub decoder: String,
+    pub content_type: Option<String>,
+    pub tree: serde_json::Value,
+}
+
+/// One row of the `flows` table: all events sharing a `uuid` (or 5-tuple).
+///
+/// `src_*` / `dst_*` describe the endpoints as seen on the first event of the
+/// flow; `bytes_out` counts payload sent from src to dst, `bytes_in` the reverse.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct FlowRecord {
+    pub flow_id: u64,
+    pub uuid: String,
+    pub src_ip: String,
+    pub src_port: u32,
+    pub dst_ip: String,
+    pub dst_port: u32,
+    pub pid: i32,
+    pub pname: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub duration: i64,
+    pub bytes_out: u64,
+    pub bytes_in: u64,
+    pub event_count: u64,
+    pub app_proto: String,
+    pub update_seq: u64,
+}
+
+/// One reassembled WebSocket message from the `ws_messages` table.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct WsMessage {
+    pub message_id: u64,
+    pub uuid: String,
+    /// Index of the packet that completed the message.
+    pub packet_index: u64,
+    pub timestamp: i64,
+    pub direction: String,
+    pub opcode: u32,
+    pub opcode_name: String,
+    pub compressed: bool,
+    pub length: u64,
+    pub is_binary: bool,
+    pub payload_utf8: String,
+    pub payload_binary: Vec<u8>,
+}
+
+/// One database statement and its response from the `db_queries` table.
+///
+/// The response fields are `None` when no response was seen for the statement.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct DbQuery {
+    pub query_id: u64,
+    pub uuid: String,
+    /// `mysql`, `postgresql` or `redis`.
+    pub protocol: String,
+    /// Command type, e.g. `query`, `prepare`, `execute` or a Redis command name.
+    pub kind: String,
+    pub statement: String,
+    /// Index and timestamp of the packet carrying the statement.
+    pub packet_index: u64,
+    pub timestamp: i64,
+    pub response_index: Option<u64>,
+    /// Response timestamp minus statement timestamp, in timestamp units.
+    pub latency: Option<i64>,
+    pub rows: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// One SMTP or IMAP command, reply or delivered message from the
+/// `mail_messages` table.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct MailMessage {
+    pub message_id: u64,
+    pub uuid: String,
+    /// `smtp` or `imap`.
+    pub protocol: String,
+    pub packet_index: u64,
+    pub timestamp: i64,
+    pub direction: String,
+    /// IMAP command tag, `*` for untagged and `+` for continuation responses.
+    pub tag: String,
+    /// Command verb, reply code or response name; `MESSAGE` for SMTP content.
+    pub command: String,
+    pub arguments: String,
+    /// SMTP envelope, filled on `MESSAGE` records.
+    pub mail_from: String,
+    pub rcpt_to: String,
+    pub subject: String,
+    pub content: String,
+}
+
+/// One AMQP method or MQTT control packet from the `broker_messages` table.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct BrokerMessage {
+    pub message_id: u64,
+    pub uuid: String,
+    /// `amqp` or `mqtt`.
+    pub protocol: String,
+    pub packet_index: u64,
+    pub timestamp: i64,
+    pub direction: String,
+    /// AMQP method such as `basic.publish`, or MQTT packet type such as `PUBLISH`.
+    pub method: String,
+    /// AMQP channel; 0 for MQTT.
+    pub channel: u32,
+    pub exchange: String,
+    /// AMQP routing key or MQTT topic (filters joined by `, ` for SUBSCRIBE).
+    pub topic: String,
+    pub queue: String,
+    /// MQTT packet identifier.
+    pub packet_id: Option<u32>,
+    pub qos: Option<u32>,
+    /// Remaining method or packet fields, e.g. `client_id=app keepalive=60`.
+    pub detail: String,
+    pub content_type: String,
+    /// Full message body length, even when the stored payload was cut.
+    pub length: u64,
+    pub is_binary: bool,
+    pub payload_utf8: String,
+    pub payload_binary: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -77,8 +355,148 @@ pub struct ProcessLogMessage {
     pub log_info: String,
 }
 
+/// A line printed by the bundled ecapture process.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EcaptureOutputLine {
+    /// Milliseconds since the epoch, when the line was read.
+    pub timestamp: i64,
+    /// `stdout` or `stderr`.
+    pub stream: String,
+    pub line: String,
+}
+
+/// Sent when the bundled ecapture exited unexpectedly; events between the
+/// exit and the restart are missing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaptureRestartEvent {
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    /// Number of unexpected exits in this session.
+    pub attempt: u32,
+    pub reason: String,
+    /// Delay before the restart; 0 when giving up.
+    pub delay_ms: u64,
+    /// No restart follows; the capture session is stopped.
+    pub gave_up: bool,
+}
+
+/// Tags and note a user put on a packet, a row of the `annotations` table.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct PacketAnnotation {
+    pub index: u64,
+    pub session_id: u64,
+    pub tags: Vec<String>,
+    pub note: String,
+}
+
+/// A capture session of this app run, returned by `list_sessions`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionInfo {
+    pub session_id: u64,
+    /// Milliseconds since the epoch.
+    pub started_at: i64,
+    /// `None` while the session runs.
+    pub stopped_at: Option<i64>,
+    /// Arguments of the bundled ecapture; empty for an external one.
+    pub ecapture_args: Vec<String>,
+    /// ecapture module run by `ecapture_args`.
+    pub module: Option<Subcommand>,
+    /// WebSocket URL the events were received from.
+    pub source_url: String,
+    /// Rows of the session in the `packets` table.
+    pub rows: u64,
+    /// `stopped` when stopped on request, else why the session ended;
+    /// `None` while it runs.
+    pub exit_reason: Option<String>,
+}
+
+/// The ecapture binary a capture session would run, returned by
+/// `get_ecapture_info`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EcaptureInfo {
+    /// `bundled` or `external`.
+    pub source: String,
+    pub path: String,
+    /// SHA-256 the binary was verified against, hex encoded.
+    pub sha256: String,
+    /// Version printed by `ecapture --version`, if it could be read.
+    pub version: Option<String>,
+    pub compatibility: String,
+    pub warning: Option<String>,
+}
+
+/// An installed Android package, returned by `list_packages`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AndroidPackage {
+    pub name: String,
+    pub uid: u32,
+}
+
+/// A running process, returned by `list_processes`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub uid: u32,
+    pub name: String,
+    /// Arguments joined by spaces; empty for kernel threads.
+    pub cmdline: String,
+    /// Executable path, if readable.
+    pub exe: Option<String>,
+    /// TLS implementations it uses: `openssl`, `boringssl`, `gnutls`, `nss`
+    /// or `gotls`.
+    pub tls_libraries: Vec<String>,
+    /// Path of the mapped OpenSSL or BoringSSL library.
+    pub libssl: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HandshakeMessage {
+    pub ecapture_version: String,
+    pub protocol_version: u32,
+}
+
+/// State of the LogEntry stream, returned by `get_protocol_status` and sent
+/// with `protocol-warning` events.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ProtocolStatus {
+    pub connected: bool,
+    pub ecapture_version: Option<String>,
+    /// `None` until a handshake arrives; older ecapture releases send none.
+    pub protocol_version: Option<u32>,
+    /// Highest protocol version this build decodes.
+    pub supported_protocol_version: u32,
+    /// `supported`, `untested`, `unsupported` or `unknown`.
+    pub compatibility: String,
+    /// Version mismatches and undecodable entries, oldest first.
+    pub warnings: Vec<String>,
+    /// Entries that could not be decoded, and their distinct log types.
+    pub unsupported_messages: u64,
+    pub unsupported_log_types: Vec<i32>,
+    /// Binary messages that were not valid LogEntry protobuf.
+    pub decode_errors: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EqMessage {
     pub log_type: u8,
     pub payload: serde_json::Value,
 }
+
+/// File written by `export_packets`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PacketExport {
+    /// Milliseconds since the epoch.
+    pub exported_at: i64,
+    pub packets: Vec<PacketData>,
+    pub annotations: Vec<PacketAnnotation>,
+    /// What was redacted; `None` if redaction is off.
+    pub redaction: Option<RedactionReport>,
+}
+
+/// Result of `export_packets`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub packets: u64,
+    pub redaction: Option<RedactionReport>,
+}
@@ -0,0 +1,163 @@
+//! Versioning of the LogEntry stream sent by ecapture.
+//!
+//! Recent ecapture releases open the stream with a `Handshake` entry giving
+//! their version and the protocol version of the stream. Older releases send
+//! no handshake; their version can only be read from the banner they log at
+//! startup, and their stream is protocol version 1.
+
+use crate::core::models::ProtocolStatus;
+
+/// Highest protocol version this build decodes completely.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Protocol version assumed when ecapture sends no handshake.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// How well a given ecapture release is known to work with this build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Supported,
+    /// Newer than this build; probably works, new data is ignored.
+    Untested,
+    Unsupported,
+    /// The version could not be determined.
+    Unknown,
+}
+
+impl Compatibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compatibility::Supported => "supported",
+            Compatibility::Untested => "untested",
+            Compatibility::Unsupported => "unsupported",
+            Compatibility::Unknown => "unknown",
+        }
+    }
+}
+
+/// Status before ecapture has connected.
+pub fn initial_status() -> ProtocolStatus {
+    ProtocolStatus {
+        supported_protocol_version: PROTOCOL_VERSION,
+        compatibility: Compatibility::Unknown.as_str().to_string(),
+        ..Default::default()
+    }
+}
+
+type Version = (u32, u32, u32);
+
+struct VersionRange {
+    /// Inclusive.
+    from: Version,
+    /// Exclusive; `None` for no upper bound.
+    until: Option<Version>,
+    compatibility: Compatibility,
+    note: &'static str,
+}
+
+/// ecapture releases and how this build handles them, oldest first.
+const COMPATIBILITY_TABLE: [VersionRange; 3] = [
+    VersionRange {
+        from: (0, 0, 0),
+        until: Some((1, 4, 0)),
+        compatibility: Compatibility::Unsupported,
+        note: "it predates the protobuf LogEntry stream",
+    },
+    VersionRange {
+        from: (1, 4, 0),
+        until: Some((2, 0, 0)),
+        compatibility: Compatibility::Supported,
+        note: "",
+    },
+    VersionRange {
+        from: (2, 0, 0),
+        until: None,
+        compatibility: Compatibility::Untested,
+        note: "it is newer than this build; data it adds is ignored",
+    },
+];
+
+/// Checks an ecapture release against the compatibility table, returning a
+/// warning for anything that is not known to work.
+pub fn check(
+    ecapture_version: Option<&str>,
+    protocol_version: Option<u32>,
+) -> (Compatibility, Option<String>) {
+    let protocol = protocol_version.unwrap_or(LEGACY_PROTOCOL_VERSION);
+    if protocol > PROTOCOL_VERSION {
+        return (
+            Compatibility::Untested,
+            Some(format!(
+                "ecapture sends protocol version {}, this build understands up to {}; \
+                 new fields and message types are ignored",
+                protocol, PROTOCOL_VERSION
+            )),
+        );
+    }
+
+    let Some(text) = ecapture_version else {
+        return (
+            Compatibility::Unknown,
+            Some("ecapture did not report its version; compatibility was not checked".to_string()),
+        );
+    };
+    let Some(version) = parse_version(text) else {
+        return (
+            Compatibility::Unknown,
+            Some(format!("unrecognized ecapture version \"{}\"", text)),
+        );
+    };
+    let range = COMPATIBILITY_TABLE
+        .iter()
+        .find(|r| version >= r.from && r.until.is_none_or(|until| version < until))
+        .expect("compatibility table covers every version");
+    match range.compatibility {
+        Compatibility::Supported => (Compatibility::Supported, None),
+        compatibility => (
+            compatibility,
+            Some(format!(
+                "ecapture {}.{}.{} is {}: {}",
+                version.0,
+                version.1,
+                version.2,
+                compatibility.as_str(),
+                range.note
+            )),
+        ),
+    }
+}
+
+/// Finds the first `major.minor.patch` in a version string such as `v1.4.3`
+/// or `linux_arm64:v1.4.3-20250812-4b5f1a2:6.8.0`.
+pub fn parse_version(text: &str) -> Option<Version> {
+    text.match_indices(|c: char| c.is_ascii_digit())
+        .filter(|(i, _)| {
+            // Start of a number, not the middle of one such as "arm64"
+            !text[..*i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_alphanumeric() && c != 'v')
+        })
+        .find_map(|(i, _)| {
+            let mut parts = text[i..].splitn(3, '.');
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next()?.parse().ok()?;
+            let patch = parts.next()?;
+            let end = patch
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(patch.len());
+            Some((major, minor, patch[..end].parse().ok()?))
+        })
+}
+
+/// Reads the ecapture version from its startup banner, for releases that
+/// send no handshake.
+pub fn version_from_log(line: &str) -> Option<String> {
+    let start = line.to_ascii_lowercase().find("version")?;
+    let rest = &line[start + "version".len()..];
+    let rest = rest.trim_start_matches(|c: char| c == ':' || c == '=' || c.is_whitespace());
+    let token = rest
+        .split(|c: char| c.is_whitespace() || c == '"' || c == ',')
+        .next()?;
+    parse_version(token).map(|_| token.to_string())
+}
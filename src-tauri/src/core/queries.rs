@@ -13,18 +13,22 @@ pub fn new_packets_since_index(last_index: u64) -> String {
     )
 }
 
+/// Columns of `PacketDataFrontend`, i.e. everything but the payload.
+const FRONTEND_COLS: &str = "index, session_id, timestamp, uuid, src_ip, src_port, dst_ip, dst_port, \
+     pid, pname, tid, uid, exe_path, cgroup_id, container_id, package, fd, type, type_name, \
+     direction, module, length, is_binary, content_kind, app_proto, tls_version, tls_cipher, \
+     tls_sni, tls_alpn, tls_library";
+
 pub fn new_packets_since_index_no_payload(last_index: &u64) -> String {
     format!(
-        "SELECT index, timestamp, uuid, src_ip, src_port, dst_ip, dst_port, pid, pname, type, length, is_binary FROM packets WHERE index > {} ORDER BY index",
-        last_index
+        "SELECT {} FROM packets WHERE index > {} ORDER BY index",
+        FRONTEND_COLS, last_index
     )
 }
 
 pub fn new_packets_customized_no_payload(last_index: &u64, user_sql: &str) -> String {
     let trimmed_sql = user_sql.trim();
 
-    const TARGET_COLS: &str = "index, timestamp, uuid, src_ip, src_port, dst_ip, dst_port, pid, pname, type, length, is_binary";
-
     let is_full_select = trimmed_sql.to_lowercase().starts_with("select");
 
     if is_full_select {
@@ -32,7 +36,7 @@ pub fn new_packets_customized_no_payload(last_index: &u64, user_sql: &str) -> St
 
         format!(
             "SELECT {} FROM ({}) AS user_view WHERE index > {} ORDER BY index ASC",
-            TARGET_COLS, clean_sql, last_index
+            FRONTEND_COLS, clean_sql, last_index
         )
     } else {
         let condition = if trimmed_sql.is_empty() {
@@ -43,11 +47,71 @@ pub fn new_packets_customized_no_payload(last_index: &u64, user_sql: &str) -> St
 
         format!(
             "SELECT {} FROM packets WHERE ({}) AND index > {} ORDER BY index ASC",
-            TARGET_COLS, condition, last_index
+            FRONTEND_COLS, condition, last_index
         )
     }
 }
 
+/// Packets of one capture session, without their payload.
+pub fn session_packets_no_payload(session_id: u64) -> String {
+    format!(
+        "SELECT {} FROM packets WHERE session_id = {} ORDER BY index",
+        FRONTEND_COLS, session_id
+    )
+}
+
+/// Every column of the packets matching `condition`.
+pub fn packets_matching(condition: &str) -> String {
+    format!(
+        "SELECT * FROM packets WHERE ({}) ORDER BY index",
+        condition.trim().trim_end_matches(';')
+    )
+}
+
+pub fn all_annotations() -> String {
+    "SELECT * FROM annotations ORDER BY index".to_string()
+}
+
+/// Number of `packets` rows of each capture session.
+pub fn session_row_counts() -> String {
+    "SELECT session_id, COUNT(*) AS row_count FROM packets GROUP BY session_id".to_string()
+}
+
 pub fn get_packet_by_index(index: u64) -> String {
     format!("SELECT * FROM packets WHERE index = {} LIMIT 1", index)
 }
+
+pub fn flows_updated_since(last_seq: &u64) -> String {
+    format!(
+        "SELECT * FROM flows WHERE update_seq > {} ORDER BY flow_id",
+        last_seq
+    )
+}
+
+pub fn ws_messages_by_uuid(uuid: &str) -> String {
+    format!(
+        "SELECT * FROM ws_messages WHERE uuid = '{}' ORDER BY message_id",
+        uuid.replace('\'', "''")
+    )
+}
+
+pub fn db_queries_by_uuid(uuid: &str) -> String {
+    format!(
+        "SELECT * FROM db_queries WHERE uuid = '{}' ORDER BY query_id",
+        uuid.replace('\'', "''")
+    )
+}
+
+pub fn mail_messages_by_uuid(uuid: &str) -> String {
+    format!(
+        "SELECT * FROM mail_messages WHERE uuid = '{}' ORDER BY message_id",
+        uuid.replace('\'', "''")
+    )
+}
+
+pub fn broker_messages_by_uuid(uuid: &str) -> String {
+    format!(
+        "SELECT * FROM broker_messages WHERE uuid = '{}' ORDER BY message_id",
+        uuid.replace('\'', "''")
+    )
+}
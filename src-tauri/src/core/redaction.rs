@@ -0,0 +1,493 @@
+//! Redaction of secrets such as tokens, cookies and passwords in captured
+//! payloads, configured in the settings and applied when packets are stored,
+//! shown or exported.
+//!
+//! Rules rewrite text: HTTP/1 headers, JSON in any payload and user
+//! patterns. Binary data is only scanned, and left out if a rule matches,
+//! since rewriting it would break the framing of binary protocols. HTTP/2
+//! headers are HPACK compressed and compressed bodies are only seen once
+//! decoded, so exports leave out raw bytes altogether.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Context, Result};
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::content_decoding;
+use crate::core::models::{
+    BrokerMessage, DbQuery, DecodedPayload, MailMessage, PacketData, WsMessage,
+};
+
+/// Replaces every redacted value.
+pub const MASK: &str = "[REDACTED]";
+/// Replaces binary payloads left out.
+const BINARY_MASK: &str = "[REDACTED binary payload]";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMode {
+    #[default]
+    Off,
+    /// Payloads are redacted before they are stored, so SQL no longer
+    /// matches the secrets. What is compressed on the wire is redacted
+    /// once decoded for display.
+    Ingest,
+    /// Payloads are stored as captured, so SQL still matches them, but
+    /// redacted when shown or exported.
+    Display,
+    /// Payloads are only redacted when exported.
+    Export,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub mode: RedactionMode,
+    /// HTTP headers whose values are redacted, case-insensitive.
+    pub headers: Vec<String>,
+    /// JSON keys whose values are redacted, case-insensitive.
+    pub json_keys: Vec<String>,
+    /// Regular expressions whose matches are redacted.
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            mode: RedactionMode::Off,
+            headers: [
+                "Authorization",
+                "Proxy-Authorization",
+                "Cookie",
+                "Set-Cookie",
+            ]
+            .map(String::from)
+            .to_vec(),
+            json_keys: [
+                "password",
+                "passwd",
+                "secret",
+                "token",
+                "access_token",
+                "refresh_token",
+                "api_key",
+            ]
+            .map(String::from)
+            .to_vec(),
+            // JSON Web Tokens
+            patterns: vec![r"eyJ[\w-]+\.[\w-]+\.[\w-]+".to_string()],
+        }
+    }
+}
+
+/// What was redacted.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RedactionReport {
+    /// Packets or messages with at least one value redacted.
+    pub records: u64,
+    /// Values redacted by rule, e.g. `header:Authorization`.
+    pub matches: BTreeMap<String, u64>,
+    /// Binary payloads left out, since a rule matched in them or, when
+    /// exported, since they could not be scanned.
+    pub binary_payloads_removed: u64,
+}
+
+impl RedactionReport {
+    /// Adds the matches of one record.
+    fn add(&mut self, hits: BTreeMap<String, u64>) {
+        if hits.is_empty() {
+            return;
+        }
+        self.records += 1;
+        for (rule, count) in hits {
+            *self.matches.entry(rule).or_default() += count;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    regex: Regex,
+    replacement: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Redactor {
+    mode: RedactionMode,
+    rules: Vec<Rule>,
+    json_keys: HashSet<String>,
+}
+
+impl Redactor {
+    /// Compiles the rules of `config`; `None` if redaction is off.
+    pub fn new(config: &RedactionConfig) -> Result<Option<Self>> {
+        if config.mode == RedactionMode::Off {
+            return Ok(None);
+        }
+
+        let mut rules = Vec::new();
+        for header in config
+            .headers
+            .iter()
+            .map(|h| h.trim())
+            .filter(|h| !h.is_empty())
+        {
+            rules.push(Rule {
+                name: format!("header:{}", header),
+                regex: Regex::new(&format!(
+                    r"(?im)^({}[ \t]*:[ \t]*)[^\r\n]*",
+                    regex::escape(header)
+                ))?,
+                replacement: format!("${{1}}{}", MASK).into_bytes(),
+            });
+        }
+        for key in config
+            .json_keys
+            .iter()
+            .map(|k| k.trim())
+            .filter(|k| !k.is_empty())
+        {
+            rules.push(Rule {
+                name: format!("json:{}", key),
+                regex: Regex::new(&format!(
+                    r#"(?i)("{}"\s*:\s*)("(?:[^"\\]|\\.)*"|[^,}}\]\s]+)"#,
+                    regex::escape(key)
+                ))?,
+                replacement: format!("${{1}}\"{}\"", MASK).into_bytes(),
+            });
+        }
+        for pattern in config.patterns.iter().filter(|p| !p.trim().is_empty()) {
+            rules.push(Rule {
+                name: format!("pattern:{}", pattern),
+                regex: Regex::new(pattern)
+                    .with_context(|| format!("invalid redaction pattern {:?}", pattern))?,
+                // Not expanded, so `$` in the mask stays literal
+                replacement: MASK.as_bytes().to_vec(),
+            });
+        }
+
+        Ok(Some(Self {
+            mode: config.mode,
+            rules,
+            json_keys: config
+                .json_keys
+                .iter()
+                .map(|k| k.trim().to_lowercase())
+                .collect(),
+        }))
+    }
+
+    pub fn mode(&self) -> RedactionMode {
+        self.mode
+    }
+
+    /// Whether packets are redacted before they are stored.
+    pub fn redacts_ingest(&self) -> bool {
+        self.mode == RedactionMode::Ingest
+    }
+
+    /// Whether packets are redacted when shown. Also done after ingest, for
+    /// what only shows once decoded, like compressed bodies.
+    pub fn redacts_display(&self) -> bool {
+        matches!(self.mode, RedactionMode::Ingest | RedactionMode::Display)
+    }
+
+    fn redact_bytes<'a>(&self, data: &'a [u8], hits: &mut BTreeMap<String, u64>) -> Cow<'a, [u8]> {
+        let mut data = Cow::Borrowed(data);
+        for rule in &self.rules {
+            let count = rule.regex.find_iter(&data).count() as u64;
+            if count == 0 {
+                continue;
+            }
+            *hits.entry(rule.name.clone()).or_default() += count;
+            let redacted = if rule.replacement == MASK.as_bytes() {
+                rule.regex
+                    .replace_all(&data, regex::bytes::NoExpand(&rule.replacement))
+                    .into_owned()
+            } else {
+                rule.regex
+                    .replace_all(&data, rule.replacement.as_slice())
+                    .into_owned()
+            };
+            data = Cow::Owned(redacted);
+        }
+        data
+    }
+
+    fn redact_string(&self, text: &mut String, hits: &mut BTreeMap<String, u64>) {
+        if let Cow::Owned(redacted) = self.redact_bytes(text.as_bytes(), hits) {
+            *text = String::from_utf8_lossy(&redacted).into_owned();
+        }
+    }
+
+    /// Whether a rule matches in binary data. Bytes are never rewritten,
+    /// since that would break the framing of binary protocols.
+    fn scan_bytes(&self, data: &[u8], hits: &mut BTreeMap<String, u64>) -> bool {
+        let mut found = false;
+        for rule in &self.rules {
+            let count = rule.regex.find_iter(data).count() as u64;
+            if count > 0 {
+                *hits.entry(rule.name.clone()).or_default() += count;
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// Redacts fields that render the same data, e.g. a payload as text and
+    /// as bytes, counting each match once. Binary fields a rule matches in
+    /// are cleared.
+    fn redact_views(
+        &self,
+        texts: Vec<&mut String>,
+        binaries: Vec<&mut Vec<u8>>,
+        report: &mut RedactionReport,
+    ) -> BTreeMap<String, u64> {
+        let mut hits = BTreeMap::new();
+        let mut merge = |field: BTreeMap<String, u64>| {
+            for (rule, count) in field {
+                let total: &mut u64 = hits.entry(rule).or_default();
+                *total = count.max(*total);
+            }
+        };
+        for text in texts {
+            let mut field = BTreeMap::new();
+            self.redact_string(text, &mut field);
+            merge(field);
+        }
+        for data in binaries {
+            let mut field = BTreeMap::new();
+            if self.scan_bytes(data, &mut field) {
+                data.clear();
+                report.binary_payloads_removed += 1;
+            }
+            merge(field);
+        }
+        hits
+    }
+
+    pub fn redact_packet(&self, packet: &mut PacketData, report: &mut RedactionReport) {
+        let mut hits = self.redact_views(
+            vec![&mut packet.payload_utf8],
+            vec![&mut packet.payload_binary],
+            report,
+        );
+        if let Some(body) = &mut packet.decoded_body {
+            let body_hits = self.redact_views(
+                vec![&mut body.head, &mut body.body_utf8],
+                vec![&mut body.body_binary],
+                report,
+            );
+            // The raw bytes hold what was found in the decoded body
+            if !body_hits.is_empty() && !packet.payload_binary.is_empty() {
+                packet.payload_binary.clear();
+                report.binary_payloads_removed += 1;
+            }
+            for (rule, count) in body_hits {
+                let total = hits.entry(rule).or_default();
+                *total = count.max(*total);
+            }
+        }
+        report.add(hits);
+        if packet.is_binary && packet.payload_binary.is_empty() && packet.payload_utf8.is_empty() {
+            packet.payload_utf8 = BINARY_MASK.to_string();
+        }
+    }
+
+    /// Redacts a packet for export. Raw bytes are left out unless the text
+    /// is an exact copy of them, since rules cannot see into e.g. compressed
+    /// bodies; an HTTP body is exported decoded instead.
+    pub fn redact_export(&self, packet: &mut PacketData, report: &mut RedactionReport) {
+        if packet.decoded_body.is_none() {
+            packet.decoded_body = content_decoding::decode_http_body(packet.payload_bytes());
+        }
+        let mut removed = !packet.payload_binary.is_empty();
+        packet.payload_binary.clear();
+        if let Some(body) = &mut packet.decoded_body {
+            removed |= !body.body_binary.is_empty();
+            body.body_binary.clear();
+        }
+        if removed {
+            report.binary_payloads_removed += 1;
+        }
+        self.redact_packet(packet, report);
+    }
+
+    pub fn redact_ws_message(&self, message: &mut WsMessage, report: &mut RedactionReport) {
+        let hits = self.redact_views(
+            vec![&mut message.payload_utf8],
+            vec![&mut message.payload_binary],
+            report,
+        );
+        report.add(hits);
+    }
+
+    pub fn redact_db_query(&self, query: &mut DbQuery, report: &mut RedactionReport) {
+        let mut hits = BTreeMap::new();
+        self.redact_string(&mut query.statement, &mut hits);
+        report.add(hits);
+    }
+
+    pub fn redact_mail_message(&self, message: &mut MailMessage, report: &mut RedactionReport) {
+        let mut hits = BTreeMap::new();
+        self.redact_string(&mut message.arguments, &mut hits);
+        self.redact_string(&mut message.content, &mut hits);
+        report.add(hits);
+    }
+
+    pub fn redact_broker_message(&self, message: &mut BrokerMessage, report: &mut RedactionReport) {
+        let mut hits = self.redact_views(
+            vec![&mut message.payload_utf8],
+            vec![&mut message.payload_binary],
+            report,
+        );
+        self.redact_string(&mut message.detail, &mut hits);
+        report.add(hits);
+    }
+
+    /// Redacts the values of configured keys and the strings of a decoded
+    /// payload tree.
+    pub fn redact_decoded(&self, payload: &mut DecodedPayload, report: &mut RedactionReport) {
+        let mut hits = BTreeMap::new();
+        self.redact_value(&mut payload.tree, &mut hits);
+        report.add(hits);
+    }
+
+    fn redact_value(&self, value: &mut serde_json::Value, hits: &mut BTreeMap<String, u64>) {
+        match value {
+            serde_json::Value::String(text) => self.redact_string(text, hits),
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact_value(item, hits);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    let key = key.to_lowercase();
+                    if self.json_keys.contains(&key) && !field.is_object() && !field.is_array() {
+                        *field = serde_json::Value::String(MASK.to_string());
+                        *hits.entry(format!("json:{}", key)).or_default() += 1;
+                    } else {
+                        self.redact_value(field, hits);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::classify::classify_payload;
+    use std::io::Write;
+
+    const SECRET: &str = "s3cr3t-value";
+
+    fn redactor(mode: RedactionMode) -> Redactor {
+        Redactor::new(&RedactionConfig {
+            mode,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    /// A packet as `pb_event_to_packet` builds it.
+    fn captured(payload: &[u8]) -> PacketData {
+        let classification = classify_payload(payload);
+        PacketData {
+            is_binary: classification.is_binary,
+            payload_utf8: classification.text,
+            payload_binary: if classification.text_is_exact {
+                Vec::new()
+            } else {
+                payload.to_vec()
+            },
+            decoded_body: content_decoding::decode_http_body(payload),
+            ..Default::default()
+        }
+    }
+
+    fn gzip_response(body: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let mut payload =
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\n\r\n"
+                .to_vec();
+        payload.extend(encoder.finish().unwrap());
+        payload
+    }
+
+    #[test]
+    fn export_leaves_out_compressed_body_bytes() {
+        let mut packet = captured(&gzip_response(&format!(r#"{{"token":"{SECRET}"}}"#)));
+        assert!(!packet.is_binary);
+        assert!(!packet.payload_binary.is_empty());
+        packet.decoded_body = None;
+
+        let mut report = RedactionReport::default();
+        redactor(RedactionMode::Export).redact_export(&mut packet, &mut report);
+
+        assert!(packet.payload_binary.is_empty());
+        let body = packet.decoded_body.as_ref().unwrap();
+        assert_eq!(body.body_utf8, r#"{"token":"[REDACTED]"}"#);
+        assert!(!serde_json::to_string(&packet).unwrap().contains(SECRET));
+        assert_eq!(report.binary_payloads_removed, 1);
+        assert_eq!(report.matches.get("json:token"), Some(&1));
+    }
+
+    #[test]
+    fn export_keeps_exact_text() {
+        let payload = format!("GET / HTTP/1.1\r\nAuthorization: Bearer {SECRET}\r\n\r\n");
+        let mut packet = captured(payload.as_bytes());
+
+        let mut report = RedactionReport::default();
+        redactor(RedactionMode::Export).redact_export(&mut packet, &mut report);
+
+        assert_eq!(
+            packet.payload_utf8,
+            "GET / HTTP/1.1\r\nAuthorization: [REDACTED]\r\n\r\n"
+        );
+        assert_eq!(report.binary_payloads_removed, 0);
+        assert_eq!(report.records, 1);
+    }
+
+    #[test]
+    fn binary_payloads_are_scanned_not_rewritten() {
+        let redactor = redactor(RedactionMode::Ingest);
+        let mut report = RedactionReport::default();
+
+        let clean = [0x00, 0x05, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        let mut packet = captured(&clean);
+        redactor.redact_packet(&mut packet, &mut report);
+        assert_eq!(packet.payload_binary, clean);
+
+        let mut framed = vec![0x00; 12];
+        framed.push(b'\n');
+        framed.extend(format!("Cookie: {SECRET}").as_bytes());
+        let mut packet = captured(&framed);
+        assert!(packet.is_binary);
+        redactor.redact_packet(&mut packet, &mut report);
+        assert!(packet.payload_binary.is_empty());
+        assert_eq!(packet.payload_utf8, BINARY_MASK);
+        assert_eq!(report.binary_payloads_removed, 1);
+        assert_eq!(report.matches.get("header:Cookie"), Some(&1));
+    }
+
+    #[test]
+    fn compressed_secret_drops_raw_bytes() {
+        let mut packet = captured(&gzip_response(&format!(r#"{{"password":"{SECRET}"}}"#)));
+
+        let mut report = RedactionReport::default();
+        redactor(RedactionMode::Ingest).redact_packet(&mut packet, &mut report);
+
+        assert!(packet.payload_binary.is_empty());
+        assert!(!serde_json::to_string(&packet).unwrap().contains(SECRET));
+        assert_eq!(report.matches.get("json:password"), Some(&1));
+    }
+}
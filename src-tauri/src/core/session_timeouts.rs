@@ -0,0 +1,49 @@
+//! How long a capture session waits for ecapture to come up and to go away,
+//! configurable in the settings.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionTimeouts {
+    /// Time ecapture gets to accept the WebSocket connection before the
+    /// launch counts as failed.
+    pub launch_ms: u64,
+    /// Time ecapture gets to exit after SIGINT before it is sent SIGTERM.
+    pub interrupt_grace_ms: u64,
+    /// Time ecapture gets to exit after SIGTERM before it is killed.
+    pub terminate_grace_ms: u64,
+    /// Time quitting the app waits for the session to shut down.
+    pub exit_ms: u64,
+}
+
+impl Default for SessionTimeouts {
+    fn default() -> Self {
+        Self {
+            launch_ms: 10_000,
+            interrupt_grace_ms: 2_000,
+            terminate_grace_ms: 2_000,
+            exit_ms: 8_000,
+        }
+    }
+}
+
+impl SessionTimeouts {
+    pub fn launch(&self) -> Duration {
+        Duration::from_millis(self.launch_ms)
+    }
+
+    pub fn interrupt_grace(&self) -> Duration {
+        Duration::from_millis(self.interrupt_grace_ms)
+    }
+
+    pub fn terminate_grace(&self) -> Duration {
+        Duration::from_millis(self.terminate_grace_ms)
+    }
+
+    pub fn exit(&self) -> Duration {
+        Duration::from_millis(self.exit_ms)
+    }
+}
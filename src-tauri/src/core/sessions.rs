@@ -0,0 +1,89 @@
+//! The capture sessions of this app run, so that rows of the shared
+//! `packets` table can be told apart by the session that produced them.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::core::ecapture_args::Subcommand;
+use crate::core::models::SessionInfo;
+
+/// Reason recorded for a session stopped on request.
+pub const STOPPED: &str = "stopped";
+
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    /// Oldest first.
+    sessions: Vec<SessionInfo>,
+    /// The running session, if any.
+    running: Option<u64>,
+    last_id: u64,
+}
+
+impl SessionRegistry {
+    /// Records a new running session and returns its id. Ids start at 1, or
+    /// after the one given to `continue_after`, and are never reused.
+    pub fn begin(&mut self, ecapture_args: Vec<String>, source_url: String) -> u64 {
+        self.last_id += 1;
+        self.sessions.push(SessionInfo {
+            session_id: self.last_id,
+            started_at: now_millis(),
+            stopped_at: None,
+            module: Subcommand::from_argv(&ecapture_args),
+            ecapture_args,
+            source_url,
+            rows: 0,
+            exit_reason: None,
+        });
+        self.running = Some(self.last_id);
+        self.last_id
+    }
+
+    /// Makes later sessions take ids after `session_id`, one of an earlier
+    /// run still referenced by saved data.
+    pub fn continue_after(&mut self, session_id: u64) {
+        self.last_id = self.last_id.max(session_id);
+    }
+
+    /// Ends the running session, if there is one.
+    pub fn end_running(&mut self, exit_reason: impl Into<String>) {
+        let Some(id) = self.running.take() else {
+            return;
+        };
+        if let Some(session) = self.sessions.iter_mut().find(|s| s.session_id == id) {
+            session.stopped_at = Some(now_millis());
+            session.exit_reason = Some(exit_reason.into());
+        }
+    }
+
+    pub fn running(&self) -> Option<u64> {
+        self.running
+    }
+
+    pub fn contains(&self, session_id: u64) -> bool {
+        self.sessions.iter().any(|s| s.session_id == session_id)
+    }
+
+    /// Forgets a session whose rows were cleared.
+    pub fn remove(&mut self, session_id: u64) {
+        self.sessions.retain(|s| s.session_id != session_id);
+    }
+
+    /// The sessions, oldest first, with their row counts filled in from
+    /// `rows` by session id.
+    pub fn list(&self, rows: &HashMap<u64, u64>) -> Vec<SessionInfo> {
+        self.sessions
+            .iter()
+            .map(|s| SessionInfo {
+                rows: rows.get(&s.session_id).copied().unwrap_or(0),
+                ..s.clone()
+            })
+            .collect()
+    }
+}
+
+/// Milliseconds since the epoch.
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+
+use flate2::{Decompress, FlushDecompress, Status};
+use polars::prelude::*;
+
+use crate::core::content_decoding::{self, MAX_DECODED_BODY_SIZE};
+use crate::core::event_type::Direction;
+use crate::core::flows::{Orientation, flow_key};
+use crate::core::models::{PacketData, WsMessage};
+
+/// Connections whose unparsed bytes, or the fragments of a message not
+/// finished yet, grow beyond this are considered desynced.
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// Trailer stripped from every permessage-deflate message (RFC 7692 §7.2.1).
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// WebSocket message reassembly backing the `ws_messages` SQL table.
+///
+/// A connection (`uuid`) is tracked once its `101 Switching Protocols`
+/// response has been seen; everything after it is parsed as frames, per
+/// direction, since one event may hold several frames or only part of one.
+pub struct WsMessageTable {
+    connections: HashMap<String, WsConnection>,
+    pending: Vec<WsMessage>,
+    next_id: u64,
+    df: DataFrame,
+}
+
+struct WsConnection {
+    flags: ConnFlags,
+    orientation: Orientation,
+    directions: HashMap<Direction, DirectionState>,
+}
+
+/// Negotiated permessage-deflate parameters.
+#[derive(Clone, Copy)]
+struct ConnFlags {
+    deflate: bool,
+    client_no_context_takeover: bool,
+    server_no_context_takeover: bool,
+}
+
+#[derive(Default)]
+struct DirectionState {
+    buffer: Vec<u8>,
+    fragments: Vec<u8>,
+    fragment_opcode: u8,
+    fragment_compressed: bool,
+    inflater: Option<Decompress>,
+}
+
+struct Frame {
+    fin: bool,
+    compressed: bool,
+    opcode: u8,
+    masked: bool,
+    payload: Vec<u8>,
+}
+
+enum FrameError {
+    Incomplete,
+    Invalid,
+}
+
+impl WsMessageTable {
+    pub fn new() -> Self {
+        Self {
+            connections: HashMap::new(),
+            pending: Vec::new(),
+            next_id: 0,
+            df: create_ws_messages_df(),
+        }
+    }
+
+    /// Feeds a batch of events. `first_index` is the packet index assigned to
+    /// the first event of the batch.
+    pub fn update(&mut self, batch: &[PacketData], first_index: u64) {
+        for (offset, d) in batch.iter().enumerate() {
+            let packet_index = first_index + offset as u64;
+            let key = flow_key(d);
+            let payload = d.payload_bytes();
+
+            let frames_start = if self.connections.contains_key(&key) {
+                0
+            } else {
+                match parse_upgrade(d, payload) {
+                    Some((conn, body_offset)) => {
+                        self.connections.insert(key.clone(), conn);
+                        body_offset
+                    }
+                    None => continue,
+                }
+            };
+
+            let conn = self.connections.get_mut(&key).unwrap();
+            let flags = conn.flags;
+            let direction = conn.orientation.direction(d);
+            let state = conn.directions.entry(direction).or_default();
+            state.buffer.extend_from_slice(&payload[frames_start..]);
+
+            let mut desynced = state.buffer.len() > MAX_PENDING_BYTES;
+            let mut completed = Vec::new();
+            while !desynced {
+                match take_frame(&mut state.buffer) {
+                    Ok(frame) => match assemble(flags, state, frame) {
+                        Ok(Some(message)) => completed.push(message),
+                        Ok(None) => continue,
+                        Err(_) => desynced = true,
+                    },
+                    Err(FrameError::Incomplete) => break,
+                    Err(FrameError::Invalid) => desynced = true,
+                }
+            }
+
+            for (opcode, masked, compressed, data) in completed {
+                self.push_message(d, packet_index, opcode, masked, compressed, data);
+            }
+            if desynced {
+                self.connections.remove(&key);
+            }
+        }
+    }
+
+    fn push_message(
+        &mut self,
+        d: &PacketData,
+        packet_index: u64,
+        opcode: u8,
+        masked: bool,
+        compressed: bool,
+        data: Vec<u8>,
+    ) {
+        let length = data.len() as u64;
+        let (is_binary, payload_utf8, payload_binary) = match opcode {
+            0x1 => match String::from_utf8(data) {
+                Ok(text) => (false, text, Vec::new()),
+                Err(e) => (true, String::new(), e.into_bytes()),
+            },
+            // Close: 2-byte status code followed by an optional UTF-8 reason
+            0x8 if data.len() >= 2 => {
+                let code = u16::from_be_bytes([data[0], data[1]]);
+                let reason = String::from_utf8_lossy(&data[2..]);
+                (
+                    false,
+                    format!("{} {}", code, reason).trim_end().to_string(),
+                    data,
+                )
+            }
+            _ => match String::from_utf8(data) {
+                Ok(text) if opcode != 0x2 => (false, text, Vec::new()),
+                Ok(text) => (true, String::new(), text.into_bytes()),
+                Err(e) => (true, String::new(), e.into_bytes()),
+            },
+        };
+
+        self.pending.push(WsMessage {
+            message_id: self.next_id,
+            uuid: flow_key(d),
+            packet_index,
+            timestamp: d.timestamp,
+            // RFC 6455 requires clients to mask every frame they send
+            direction: if masked {
+                "client_to_server".to_string()
+            } else {
+                "server_to_client".to_string()
+            },
+            opcode: opcode as u32,
+            opcode_name: opcode_name(opcode).to_string(),
+            compressed,
+            length,
+            is_binary,
+            payload_utf8,
+            payload_binary,
+        });
+        self.next_id += 1;
+    }
+
+    /// Messages decoded since the table was last returned, oldest first.
+    pub fn pending_mut(&mut self) -> &mut Vec<WsMessage> {
+        &mut self.pending
+    }
+
+    /// Returns the `ws_messages` table, appending messages decoded since the
+    /// last call.
+    pub fn as_df(&mut self) -> PolarsResult<DataFrame> {
+        if !self.pending.is_empty() {
+            let batch = ws_messages_to_df(&std::mem::take(&mut self.pending))?;
+            self.df.vstack_mut(&batch)?;
+        }
+        Ok(self.df.clone())
+    }
+}
+
+impl Default for WsMessageTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recognizes a `101 Switching Protocols` response upgrading to WebSocket,
+/// carried by `d`, and returns the negotiated extensions and the offset where
+/// frames start.
+fn parse_upgrade(d: &PacketData, payload: &[u8]) -> Option<(WsConnection, usize)> {
+    let msg = content_decoding::parse_http_message(payload)?;
+    let status_line = msg.head.lines().next()?;
+    if !status_line.starts_with("HTTP/1.") || status_line.split(' ').nth(1) != Some("101") {
+        return None;
+    }
+    if !msg
+        .header("Upgrade")
+        .is_some_and(|u| u.eq_ignore_ascii_case("websocket"))
+    {
+        return None;
+    }
+
+    let extensions = msg
+        .header("Sec-WebSocket-Extensions")
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let conn = WsConnection {
+        flags: ConnFlags {
+            deflate: extensions.contains("permessage-deflate"),
+            client_no_context_takeover: extensions.contains("client_no_context_takeover"),
+            server_no_context_takeover: extensions.contains("server_no_context_takeover"),
+        },
+        orientation: Orientation::new(d),
+        directions: HashMap::new(),
+    };
+    Some((conn, payload.len() - msg.body.len()))
+}
+
+fn take_frame(buffer: &mut Vec<u8>) -> Result<Frame, FrameError> {
+    let [b0, b1, ..] = buffer[..] else {
+        return Err(FrameError::Incomplete);
+    };
+
+    let opcode = b0 & 0x0f;
+    if matches!(opcode, 0x3..=0x7 | 0xb..=0xf) || b0 & 0x30 != 0 {
+        return Err(FrameError::Invalid);
+    }
+    let masked = b1 & 0x80 != 0;
+
+    let mut pos = 2;
+    let len = match b1 & 0x7f {
+        126 => {
+            let bytes = buffer.get(2..4).ok_or(FrameError::Incomplete)?;
+            pos = 4;
+            u16::from_be_bytes([bytes[0], bytes[1]]) as u64
+        }
+        127 => {
+            let bytes = buffer.get(2..10).ok_or(FrameError::Incomplete)?;
+            pos = 10;
+            u64::from_be_bytes(bytes.try_into().unwrap())
+        }
+        n => n as u64,
+    };
+    if len > MAX_PENDING_BYTES as u64 {
+        return Err(FrameError::Invalid);
+    }
+    let len = len as usize;
+
+    let mask = if masked {
+        let key = buffer.get(pos..pos + 4).ok_or(FrameError::Incomplete)?;
+        pos += 4;
+        Some([key[0], key[1], key[2], key[3]])
+    } else {
+        None
+    };
+
+    if buffer.len() < pos + len {
+        return Err(FrameError::Incomplete);
+    }
+    let mut payload: Vec<u8> = buffer.drain(..pos + len).skip(pos).collect();
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    Ok(Frame {
+        fin: b0 & 0x80 != 0,
+        compressed: b0 & 0x40 != 0,
+        opcode,
+        masked,
+        payload,
+    })
+}
+
+/// A complete message as `(opcode, masked, compressed, data)`.
+type Message = (u8, bool, bool, Vec<u8>);
+
+/// Folds a frame into the direction state, returning a complete message once
+/// one is available. A message whose fragments grow too large is dropped as
+/// invalid.
+fn assemble(
+    flags: ConnFlags,
+    state: &mut DirectionState,
+    frame: Frame,
+) -> Result<Option<Message>, FrameError> {
+    // Control frames may be interleaved with fragments and are never compressed.
+    if frame.opcode >= 0x8 {
+        return Ok(Some((frame.opcode, frame.masked, false, frame.payload)));
+    }
+
+    if frame.opcode != 0x0 {
+        state.fragments.clear();
+        state.fragment_opcode = frame.opcode;
+        state.fragment_compressed = frame.compressed && flags.deflate;
+    }
+    if state.fragments.len() + frame.payload.len() > MAX_PENDING_BYTES {
+        state.fragments = Vec::new();
+        return Err(FrameError::Invalid);
+    }
+    state.fragments.extend_from_slice(&frame.payload);
+    if !frame.fin {
+        return Ok(None);
+    }
+
+    let data = std::mem::take(&mut state.fragments);
+    let compressed = state.fragment_compressed;
+    let data = if compressed {
+        let no_context_takeover = if frame.masked {
+            flags.client_no_context_takeover
+        } else {
+            flags.server_no_context_takeover
+        };
+        inflate_message(state, &data, no_context_takeover)
+    } else {
+        data
+    };
+    Ok(Some((
+        state.fragment_opcode,
+        frame.masked,
+        compressed,
+        data,
+    )))
+}
+
+fn inflate_message(state: &mut DirectionState, data: &[u8], no_context_takeover: bool) -> Vec<u8> {
+    let inflater = state.inflater.get_or_insert_with(|| Decompress::new(false));
+    if no_context_takeover {
+        inflater.reset(false);
+    }
+
+    let mut input = data.to_vec();
+    input.extend_from_slice(&DEFLATE_TAIL);
+    let mut input = &input[..];
+    let mut out = Vec::with_capacity(input.len() * 4 + 64);
+
+    loop {
+        let before_in = inflater.total_in();
+        let before_out = out.len();
+        let status = inflater.decompress_vec(input, &mut out, FlushDecompress::Sync);
+        let consumed = (inflater.total_in() - before_in) as usize;
+        input = &input[consumed..];
+
+        match status {
+            Ok(Status::StreamEnd) => break,
+            Ok(_) if out.len() >= MAX_DECODED_BODY_SIZE => {
+                out.truncate(MAX_DECODED_BODY_SIZE);
+                break;
+            }
+            Ok(_) if input.is_empty() && out.len() < out.capacity() => break,
+            Ok(_) if consumed == 0 && out.len() == before_out && out.len() < out.capacity() => {
+                break;
+            }
+            Ok(_) => out.reserve(out.capacity().max(4096)),
+            Err(_) => {
+                // A broken dictionary would corrupt every following message too
+                state.inflater = None;
+                break;
+            }
+        }
+    }
+    out
+}
+
+fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        0x0 => "continuation",
+        0x1 => "text",
+        0x2 => "binary",
+        0x8 => "close",
+        0x9 => "ping",
+        0xa => "pong",
+        _ => "unknown",
+    }
+}
+
+fn ws_messages_to_df(messages: &[WsMessage]) -> PolarsResult<DataFrame> {
+    let len = messages.len();
+    let mut message_id_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("message_id".into(), len);
+    let mut uuid_builder = StringChunkedBuilder::new("uuid".into(), len);
+    let mut packet_index_builder =
+        PrimitiveChunkedBuilder::<UInt64Type>::new("packet_index".into(), len);
+    let mut ts_builder = PrimitiveChunkedBuilder::<Int64Type>::new("timestamp".into(), len);
+    let mut direction_builder = StringChunkedBuilder::new("direction".into(), len);
+    let mut opcode_builder = PrimitiveChunkedBuilder::<UInt32Type>::new("opcode".into(), len);
+    let mut opcode_name_builder = StringChunkedBuilder::new("opcode_name".into(), len);
+    let mut compressed_builder = BooleanChunkedBuilder::new("compressed".into(), len);
+    let mut length_builder = PrimitiveChunkedBuilder::<UInt64Type>::new("length".into(), len);
+    let mut is_binary_builder = BooleanChunkedBuilder::new("is_binary".into(), len);
+    let mut payload_utf8_builder = StringChunkedBuilder::new("payload_utf8".into(), len);
+    let mut payload_binary_builder = BinaryChunkedBuilder::new("payload_binary".into(), len);
+
+    for m in messages {
+        message_id_builder.append_value(m.message_id);
+        uuid_builder.append_value(&m.uuid);
+        packet_index_builder.append_value(m.packet_index);
+        ts_builder.append_value(m.timestamp);
+        direction_builder.append_value(&m.direction);
+        opcode_builder.append_value(m.opcode);
+        opcode_name_builder.append_value(&m.opcode_name);
+        compressed_builder.append_value(m.compressed);
+        length_builder.append_value(m.length);
+        is_binary_builder.append_value(m.is_binary);
+        payload_utf8_builder.append_value(&m.payload_utf8);
+        payload_binary_builder.append_value(&m.payload_binary);
+    }
+
+    DataFrame::new(vec![
+        message_id_builder.finish().into_column(),
+        uuid_builder.finish().into_column(),
+        packet_index_builder.finish().into_column(),
+        ts_builder.finish().into_column(),
+        direction_builder.finish().into_column(),
+        opcode_builder.finish().into_column(),
+        opcode_name_builder.finish().into_column(),
+        compressed_builder.finish().into_column(),
+        length_builder.finish().into_column(),
+        is_binary_builder.finish().into_column(),
+        payload_utf8_builder.finish().into_column(),
+        payload_binary_builder.finish().into_column(),
+    ])
+}
+
+pub fn create_ws_messages_df() -> DataFrame {
+    let schema = Schema::from_iter(vec![
+        Field::new("message_id".into(), DataType::UInt64),
+        Field::new("uuid".into(), DataType::String),
+        Field::new("packet_index".into(), DataType::UInt64),
+        Field::new("timestamp".into(), DataType::Int64),
+        Field::new("direction".into(), DataType::String),
+        Field::new("opcode".into(), DataType::UInt32),
+        Field::new("opcode_name".into(), DataType::String),
+        Field::new("compressed".into(), DataType::Boolean),
+        Field::new("length".into(), DataType::UInt64),
+        Field::new("is_binary".into(), DataType::Boolean),
+        Field::new("payload_utf8".into(), DataType::String),
+        Field::new("payload_binary".into(), DataType::Binary),
+    ]);
+
+    DataFrame::empty_with_schema(&schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UPGRADE: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\n\
+        Upgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+
+    /// An event on connection `conn` between a client on port 5000 and a
+    /// server on port 80.
+    fn event(from_client: bool, payload: &[u8]) -> PacketData {
+        let (src_port, dst_port) = if from_client { (5000, 80) } else { (80, 5000) };
+        PacketData {
+            uuid: "conn".to_string(),
+            src_ip: if from_client { "10.0.0.1" } else { "10.0.0.2" }.to_string(),
+            src_port,
+            dst_ip: if from_client { "10.0.0.2" } else { "10.0.0.1" }.to_string(),
+            dst_port,
+            is_binary: true,
+            payload_binary: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// Encodes a frame, masked when sent by the client.
+    fn frame(fin: bool, opcode: u8, from_client: bool, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![if fin { 0x80 } else { 0 } | opcode];
+        let mask_bit = if from_client { 0x80 } else { 0 };
+        match payload.len() {
+            n if n < 126 => out.push(mask_bit | n as u8),
+            n if n <= u16::MAX as usize => {
+                out.push(mask_bit | 126);
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                out.push(mask_bit | 127);
+                out.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        if from_client {
+            let key = [1, 2, 3, 4];
+            out.extend_from_slice(&key);
+            out.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        } else {
+            out.extend_from_slice(payload);
+        }
+        out
+    }
+
+    /// Returns `(direction, opcode_name, payload_utf8, packet_index)` of the
+    /// messages decoded so far.
+    fn messages(table: &mut WsMessageTable) -> Vec<(String, String, String, u64)> {
+        table
+            .pending_mut()
+            .iter()
+            .map(|m| {
+                (
+                    m.direction.clone(),
+                    m.opcode_name.clone(),
+                    m.payload_utf8.clone(),
+                    m.packet_index,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_after_the_upgrade_become_messages() {
+        let mut table = WsMessageTable::new();
+        let mut response = UPGRADE.to_vec();
+        response.extend(frame(true, 0x1, false, b"welcome"));
+        table.update(
+            &[
+                event(false, &response),
+                event(true, &frame(true, 0x1, true, b"hello")),
+            ],
+            0,
+        );
+
+        assert_eq!(
+            messages(&mut table),
+            [
+                (
+                    "server_to_client".into(),
+                    "text".into(),
+                    "welcome".into(),
+                    0
+                ),
+                ("client_to_server".into(), "text".into(), "hello".into(), 1),
+            ]
+        );
+        assert_eq!(table.as_df().unwrap().height(), 2);
+    }
+
+    #[test]
+    fn truncated_frames_wait_for_the_next_event() {
+        let mut table = WsMessageTable::new();
+        let bytes = frame(true, 0x1, true, &[b'a'; 300]);
+        table.update(
+            &[
+                event(false, UPGRADE),
+                event(true, &bytes[..3]),
+                event(true, &bytes[3..100]),
+            ],
+            0,
+        );
+        assert!(messages(&mut table).is_empty());
+
+        table.update(&[event(true, &bytes[100..])], 3);
+        let decoded = messages(&mut table);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].2, "a".repeat(300));
+        assert_eq!(decoded[0].3, 3);
+    }
+
+    #[test]
+    fn control_frames_interleave_with_fragments() {
+        let mut table = WsMessageTable::new();
+        table.update(
+            &[
+                event(false, UPGRADE),
+                event(true, &frame(false, 0x1, true, b"hel")),
+                event(true, &frame(true, 0x9, true, b"are you there")),
+                event(true, &frame(true, 0x0, true, b"lo")),
+            ],
+            0,
+        );
+
+        assert_eq!(
+            messages(&mut table),
+            [
+                (
+                    "client_to_server".into(),
+                    "ping".into(),
+                    "are you there".into(),
+                    2
+                ),
+                ("client_to_server".into(), "text".into(), "hello".into(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_length_prefix_drops_the_connection() {
+        let mut table = WsMessageTable::new();
+        let mut huge = vec![0x81, 127];
+        huge.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        table.update(&[event(false, UPGRADE), event(false, &huge)], 0);
+        table.update(&[event(false, &frame(true, 0x1, false, b"lost"))], 2);
+
+        assert!(messages(&mut table).is_empty());
+        assert!(table.connections.is_empty());
+    }
+
+    #[test]
+    fn unfinished_fragments_are_bounded() {
+        let mut table = WsMessageTable::new();
+        table.update(&[event(false, UPGRADE)], 0);
+        let chunk = vec![b'x'; 8 * 1024 * 1024];
+        let mut index = 1;
+        table.update(&[event(false, &frame(false, 0x2, false, &chunk))], index);
+        while !table.connections.is_empty() {
+            index += 1;
+            assert!(index < 16, "fragments grew past the limit");
+            table.update(&[event(false, &frame(false, 0x0, false, &chunk))], index);
+        }
+        table.update(&[event(false, &frame(true, 0x0, false, b"end"))], index + 1);
+
+        assert!(messages(&mut table).is_empty());
+    }
+}
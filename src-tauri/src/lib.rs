@@ -2,11 +2,14 @@ mod core;
 mod services;
 mod tauri_bridge;
 
-use std::{sync::Arc, thread, time::Duration};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
 
 use log::{error, info};
-#[cfg(target_os = "linux")]
-use nix::unistd::geteuid;
+#[cfg(all(target_os = "linux", not(decoupled)))]
+use nix::unistd::{geteuid, getuid};
 use tauri::{Manager, RunEvent};
 use tauri_plugin_log::{Builder as LogBuilder, Target, TargetKind};
 use tokio::sync::{Mutex, RwLock, mpsc, watch};
@@ -20,16 +23,77 @@ use crate::tauri_bridge::{
 use wg::WaitGroup;
 // use tokio::signal;
 
+#[cfg(all(target_os = "linux", not(decoupled)))]
+pub use services::helper::HELPER_FLAG;
+
+/// Runs the privileged helper instead of the app, returning the exit code.
+/// Arguments after [`HELPER_FLAG`] configure it.
+#[cfg(all(target_os = "linux", not(decoupled)))]
+pub async fn run_privileged_helper() -> i32 {
+    services::helper::init_logging();
+    let config = services::helper::HelperConfig::from_args(std::env::args().skip(2));
+    match config.map(services::helper::serve) {
+        Ok(serve) => match serve.await {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("Privileged helper failed: {:#}", e);
+                1
+            }
+        },
+        Err(e) => {
+            error!("Invalid privileged helper arguments: {:#}", e);
+            2
+        }
+    }
+}
+
+/// Kills the ecapture processes of a session that did not stop in time,
+/// with their process groups, so that none outlives the app. Without root
+/// the privileged helper kills the ones it runs.
+#[cfg(all(target_os = "linux", not(decoupled)))]
+async fn kill_capture(app_handle: &tauri::AppHandle) {
+    if let Some(socket) = &app_handle.state::<AppState>().helper_socket {
+        if let Err(e) = services::helper::kill_remote(socket).await {
+            error!("Failed to kill eCapture in the privileged helper: {:#}", e);
+        }
+        return;
+    }
+    let Ok(data_dir) = app_handle.path().app_data_dir() else {
+        return;
+    };
+    let children = services::pidfile::PidFile::new(data_dir).children();
+    if children.is_empty() {
+        return;
+    }
+    error!(
+        "Killing eCapture processes {:?}",
+        children.iter().map(|p| p.pid).collect::<Vec<_>>()
+    );
+    services::capture::kill_processes(&children);
+}
+
+#[cfg(all(target_os = "linux", not(decoupled)))]
+fn warn_orphans(pids: impl Iterator<Item = u32>) {
+    let pids: Vec<u32> = pids.collect();
+    if !pids.is_empty() {
+        log::warn!(
+            "eCapture processes {:?} are still running from an earlier session",
+            pids
+        );
+    }
+}
+
+#[cfg(not(all(target_os = "linux", not(decoupled))))]
+async fn kill_capture(_app_handle: &tauri::AppHandle) {}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub async fn run() {
-    // On Linux, only root user can run this program
+    // On Linux, a normal user leaves ecapture to the privileged helper
     #[cfg(all(target_os = "linux", not(decoupled)))]
-    {
-        if !geteuid().is_root() {
-            error!("NEED TO RUN WITH ROOT PERMISSION");
-            std::process::exit(1);
-        }
-    }
+    let helper_socket =
+        (!geteuid().is_root()).then(|| services::helper::socket_path(getuid().as_raw()));
+    #[cfg(not(all(target_os = "linux", not(decoupled))))]
+    let helper_socket = None;
 
     let (actor_tx, actor_rx) = mpsc::channel(128);
     let (done_tx, done_rx) = watch::channel(());
@@ -45,10 +109,19 @@ pub async fn run() {
         df_actor_handle,
         done: Mutex::new(done_tx),
         shutdown_tx: Mutex::new(None),
+        session_wg: Mutex::new(None),
+        pending_tasks: Default::default(),
         configs: Mutex::new(None),
         user_sql: Mutex::new(None),
         shared_last_index: Arc::new(Mutex::new(0)),
         status: Arc::new(RwLock::new(RunState::NotCapturing)),
+        sessions: Arc::new(RwLock::new(Default::default())),
+        protocol_status: Arc::new(RwLock::new(core::protocol::initial_status())),
+        packages: Arc::new(RwLock::new(Default::default())),
+        ecapture_output: services::ecapture_output::OutputRing::new(),
+        decoders: core::decoders::DecoderRegistry::default(),
+        redactor: RwLock::new(None),
+        helper_socket,
     };
 
     let log_plugin = LogBuilder::new()
@@ -75,15 +148,41 @@ pub async fn run() {
                 std::fs::create_dir_all(&data_dir)?;
             }
             config_check(&data_dir)?;
+
+            #[cfg(all(target_os = "linux", not(decoupled)))]
+            match app_handle.state::<AppState>().helper_socket.clone() {
+                // Only a helper that already runs is asked, so that no
+                // password prompt shows at startup
+                Some(socket) => {
+                    tokio::spawn(async move {
+                        if services::helper::is_running(&socket).await
+                            && let Ok(orphans) = services::helper::remote_orphans(&socket).await
+                        {
+                            warn_orphans(orphans.iter().map(|p| p.pid));
+                        }
+                    });
+                }
+                None => {
+                    let orphans = services::pidfile::PidFile::new(&data_dir).orphans();
+                    warn_orphans(orphans.iter().map(|p| p.pid));
+                }
+            }
             let configs = Configs::get_json_from_app_dir(&data_dir)?;
 
             let config_init_wg = WaitGroup::new();
             let config_init_wg_clone = config_init_wg.clone();
             config_init_wg.add(1);
 
+            let annotations_path = core::annotations::annotations_file(&data_dir);
             tokio::spawn(async move {
                 let state = app_handle_state.state::<AppState>();
                 state.init_configs(configs).await;
+                let last_session_id = state
+                    .df_actor_handle
+                    .persist_annotations(annotations_path)
+                    .await;
+                // Saved annotations must not attach to new sessions' packets
+                state.sessions.write().await.continue_after(last_session_id);
                 config_init_wg_clone.done();
             });
 
@@ -106,28 +205,80 @@ pub async fn run() {
             commands::modify_configs,
             commands::verify_user_sql,
             commands::get_packet_with_payload,
+            commands::decode_payload,
+            commands::get_ws_messages,
+            commands::get_db_queries,
+            commands::get_mail_messages,
+            commands::get_broker_messages,
+            commands::list_sessions,
+            commands::get_session_packets,
+            commands::clear_session,
+            commands::clear_packets,
+            commands::tag_packet,
+            commands::untag_packet,
+            commands::set_packet_note,
+            commands::list_annotations,
+            commands::export_packets,
+            commands::get_redaction_report,
+            commands::get_flows,
+            commands::get_protocol_status,
+            commands::get_ecapture_output,
+            commands::get_ecapture_info,
+            commands::list_processes,
+            commands::capture_process,
+            commands::list_packages,
+            commands::capture_package,
+            commands::get_orphaned_processes,
+            commands::stop_orphaned_processes,
         ]);
 
     let app = builder
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
 
-    app.run(|app_handle, event| {
+    // Set once the capture session is shut down and the app may exit
+    let shut_down = Arc::new(AtomicBool::new(false));
+    app.run(move |app_handle, event| {
         match event {
             RunEvent::ExitRequested { api, .. } => {
+                if shut_down.load(Ordering::Acquire) {
+                    return;
+                }
                 api.prevent_exit();
                 info!("Exit requested. Starting graceful shutdown of capture session...");
                 let app_handle_clone = app_handle.clone();
+                let shut_down = shut_down.clone();
 
                 tauri::async_runtime::spawn(async move {
                     let state: tauri::State<AppState> = app_handle_clone.state();
-                    // state.session_handles.lock().await.take();
-                    if commands::stop_capture(state).await.is_err() {
-                        error!("Error stopping capture");
-                    }
+                    let exit_timeout = state
+                        .configs
+                        .lock()
+                        .await
+                        .as_ref()
+                        .map(Configs::timeouts)
+                        .unwrap_or_default()
+                        .exit();
+                    // Not running is fine, only a session that hangs is not
+                    let code =
+                        match tokio::time::timeout(exit_timeout, commands::stop_capture(state))
+                            .await
+                        {
+                            Ok(_) => 0,
+                            Err(_) => {
+                                let state: tauri::State<AppState> = app_handle_clone.state();
+                                error!(
+                                    "Capture session did not stop within {:?}, still running: {}",
+                                    exit_timeout,
+                                    state.pending_tasks.names().join(", ")
+                                );
+                                kill_capture(&app_handle_clone).await;
+                                1
+                            }
+                        };
+                    shut_down.store(true, Ordering::Release);
+                    app_handle_clone.exit(code);
                 });
-                thread::sleep(Duration::from_millis(500));
-                std::process::exit(1);
             }
             _ => {}
         }
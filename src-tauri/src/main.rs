@@ -7,6 +7,11 @@ fn main() {
         .build()
         .expect("Failed to create Tokio runtime.");
 
+    #[cfg(all(target_os = "linux", not(decoupled)))]
+    if std::env::args().nth(1).as_deref() == Some(ecaptureq_lib::HELPER_FLAG) {
+        std::process::exit(rt.block_on(ecaptureq_lib::run_privileged_helper()));
+    }
+
     rt.block_on(async {
         ecaptureq_lib::run().await;
     });
@@ -0,0 +1,27 @@
+//! Installed Android packages, listed by the package manager.
+
+use anyhow::{Result, anyhow};
+use tokio::process::Command;
+
+use crate::core::android_packages;
+use crate::core::models::AndroidPackage;
+
+/// Lists installed packages through `su`, which also sees the packages
+/// hidden from this app by package visibility filtering.
+pub async fn list_installed_packages() -> Result<Vec<AndroidPackage>> {
+    let output = Command::new("su")
+        .arg("-c")
+        .arg("pm list packages -U")
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "pm list packages failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(android_packages::parse_package_list(
+        &String::from_utf8_lossy(&output.stdout),
+    ))
+}
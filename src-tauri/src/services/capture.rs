@@ -1,62 +1,124 @@
 use anyhow::{Error, Result, anyhow};
 use futures_util::future;
-use log::{error, info};
+use log::{error, info, warn};
+#[cfg(target_os = "linux")]
+use nix::errno::Errno;
+#[cfg(target_os = "linux")]
+use nix::sys::prctl;
 #[cfg(not(target_os = "windows"))]
 use nix::sys::signal::{Signal, kill as send_signal};
 #[cfg(not(target_os = "windows"))]
 use nix::unistd::Pid;
+#[cfg(target_os = "linux")]
+use nix::unistd::{geteuid, getpid, getppid};
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::fs;
 use std::io::Write;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::OnceLock;
 use std::time::Duration;
-use std::{fs, process};
 use tokio::process::{Child, Command}; // Use Tokio's Command and Child
 use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::core::models::EcaptureInfo;
+use crate::core::protocol;
+use crate::core::session_timeouts::SessionTimeouts;
+use crate::services::ecapture_output::{self, OutputRing};
+use crate::services::events::EventSink;
+#[cfg(target_os = "linux")]
+use crate::services::pidfile::PidFile;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use crate::services::processes;
+#[cfg(target_os = "linux")]
+use crate::services::processes::FoundProcess;
 
 fn get_cli_binary_name() -> String {
     // Android x86_64
     #[cfg(all(target_os = "android", target_arch = "x86_64"))]
     {
-        let mut hasher = Sha256::new();
-        hasher.update(get_ecapture_bytes());
-        let hash_string = hex::encode(hasher.finalize());
+        let hash_string = embedded_sha256();
         return format!("android_ecapture_amd64_{}", hash_string);
     }
 
     // Android arm64
     #[cfg(all(target_os = "android", target_arch = "aarch64"))]
     {
-        let mut hasher = Sha256::new();
-        hasher.update(get_ecapture_bytes());
-        let hash_string = hex::encode(hasher.finalize());
+        let hash_string = embedded_sha256();
         return format!("android_ecapture_arm64_{}", hash_string);
     }
 
     // Linux x86_64
     #[cfg(all(target_os = "linux", target_arch = "x86_64", not(decoupled)))]
     {
-        let mut hasher = Sha256::new();
-        hasher.update(get_ecapture_bytes());
-        let hash_string = hex::encode(hasher.finalize());
+        let hash_string = embedded_sha256();
         return format!("linux_ecapture_amd64_{}", hash_string);
     }
 
     // Linux arm64
     #[cfg(all(target_os = "linux", target_arch = "aarch64", not(decoupled)))]
     {
-        let mut hasher = Sha256::new();
-        hasher.update(get_ecapture_bytes());
-        let hash_string = hex::encode(hasher.finalize());
+        let hash_string = embedded_sha256();
         return format!("linux_ecapture_arm64_{}", hash_string);
     }
 
     "ecapture".to_string()
 }
 
+/// SHA-256 of the embedded binary, hex encoded.
+fn embedded_sha256() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hex::encode(Sha256::digest(get_ecapture_bytes())))
+}
+
+/// Reads an external binary, once, and checks it against its pinned hash.
+fn read_external(path: &Path, sha256: &str) -> Result<Vec<u8>> {
+    let bytes =
+        fs::read(path).map_err(|e| anyhow!("cannot read eCapture binary {:?}: {}", path, e))?;
+    let actual = hex::encode(Sha256::digest(&bytes));
+    if actual != sha256 {
+        return Err(anyhow!(
+            "SHA-256 of {:?} is {}, expected {}; refusing to run it",
+            path,
+            actual,
+            sha256
+        ));
+    }
+    info!("Using external eCapture binary {:?}", path);
+    Ok(bytes)
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut file = fs::File::open(path)?;
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Prefixes of the names bundled binaries are written under, see
+/// `get_cli_binary_name`.
+const BUNDLED_BINARY_PREFIXES: [&str; 2] = ["linux_ecapture_", "android_ecapture_"];
+/// Prefix of the copies external binaries are run from, followed by their
+/// SHA-256.
+const EXTERNAL_BINARY_PREFIX: &str = "external_ecapture_";
+
+/// The ecapture binary to run.
+#[derive(Debug, Clone)]
+pub enum BinarySource {
+    /// The binary embedded in this build, written to the data directory.
+    Bundled,
+    /// A binary provided by the user, only run if its SHA-256 matches. It
+    /// is run from a copy in the data directory, as `path` may be replaced
+    /// between checking and running it.
+    External { path: PathBuf, sha256: String },
+}
+
 // Android x86_64
 #[cfg(all(target_os = "android", target_arch = "x86_64"))]
 fn get_ecapture_bytes() -> &'static [u8] {
@@ -89,27 +151,92 @@ fn get_ecapture_bytes() -> &'static [u8] {
 
 pub struct CaptureManager {
     executable_path: PathBuf,
+    source: BinarySource,
     child: Option<Child>,
+    output: OutputRing,
+    events: EventSink,
+    timeouts: SessionTimeouts,
+    #[cfg(target_os = "linux")]
+    pidfile: PidFile,
 }
 
 impl CaptureManager {
-    pub fn new(base_path: impl AsRef<Path>) -> Self {
-        let executable_path = base_path.as_ref().join(get_cli_binary_name());
+    pub fn new(
+        base_path: impl AsRef<Path>,
+        source: BinarySource,
+        output: OutputRing,
+        events: impl Into<EventSink>,
+    ) -> Self {
+        let executable_path = match &source {
+            BinarySource::Bundled => base_path.as_ref().join(get_cli_binary_name()),
+            BinarySource::External { sha256, .. } => base_path.as_ref().join(format!(
+                "{}{}",
+                EXTERNAL_BINARY_PREFIX,
+                sha256.to_ascii_lowercase()
+            )),
+        };
         Self {
             executable_path,
+            source,
             child: None,
+            output,
+            events: events.into(),
+            timeouts: SessionTimeouts::default(),
+            #[cfg(target_os = "linux")]
+            pidfile: PidFile::new(base_path),
         }
     }
 
-    #[cfg(target_os = "android")]
+    /// Sets the grace periods ecapture gets when it is stopped.
+    pub fn set_timeouts(&mut self, timeouts: SessionTimeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Builds the error for an unexpected exit, with the last output lines
+    /// once the readers have drained the pipes.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    async fn exit_error(&self, reason: String, readers: Vec<JoinHandle<()>>) -> Error {
+        let _ = tokio::time::timeout(Duration::from_millis(500), future::join_all(readers)).await;
+        let tail = self.output.tail();
+        if tail.is_empty() {
+            anyhow!("{}", reason)
+        } else {
+            anyhow!("{}, last output:\n{}", reason, tail)
+        }
+    }
+
+    /// Makes sure the binary about to be run is the expected one. The
+    /// bundled binary is checked against the embedded bytes and rewritten if
+    /// it differs. An external binary is read once, checked against its
+    /// pinned hash and copied to the data directory; only the copy is run.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
     fn prepare_binary(&self) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        self.check_data_dir()?;
+
+        let expected = match &self.source {
+            BinarySource::Bundled => embedded_sha256().to_string(),
+            BinarySource::External { sha256, .. } => sha256.to_ascii_lowercase(),
+        };
         if self.executable_path.exists() {
-            info!("Found existing binary file");
-            return Ok(());
+            if file_sha256(&self.executable_path)? == expected {
+                info!("Found existing binary file");
+                self.remove_stale_binaries();
+                return Ok(());
+            }
+            warn!(
+                "eCapture binary at {:?} does not match the expected one, rewriting it",
+                self.executable_path
+            );
+            fs::remove_file(&self.executable_path)?;
         }
 
+        let bytes = match &self.source {
+            BinarySource::Bundled => Cow::Borrowed(get_ecapture_bytes()),
+            BinarySource::External { path, .. } => Cow::Owned(read_external(path, &expected)?),
+        };
         let mut dest_file = fs::File::create(&self.executable_path)?;
-        dest_file.write_all(get_ecapture_bytes())?;
+        dest_file.write_all(&bytes)?;
         dest_file.sync_all()?; // Ensure content is flushed to disk
 
         let mut perms = fs::metadata(&self.executable_path)?.permissions();
@@ -117,68 +244,143 @@ impl CaptureManager {
         fs::set_permissions(&self.executable_path, perms)?;
 
         info!("eCapture binary prepared at: {:?}", self.executable_path);
+        self.remove_stale_binaries();
         Ok(())
     }
 
+    /// Refuses to run a binary as root from a data directory another user
+    /// could swap it in.
     #[cfg(target_os = "linux")]
-    fn prepare_binary(&self) -> Result<()> {
-        if self.executable_path.exists() {
-            // fs::remove_file(&self.executable_path)?;
-            info!("Found existing binary file");
+    fn check_data_dir(&self) -> Result<()> {
+        if !geteuid().is_root() {
             return Ok(());
         }
+        let dir = self
+            .executable_path
+            .parent()
+            .ok_or_else(|| anyhow!("eCapture binary has no parent directory"))?;
+        let metadata = fs::metadata(dir)?;
+        if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+            return Err(anyhow!(
+                "data directory {:?} must be owned and only writable by root to run eCapture from it",
+                dir
+            ));
+        }
+        Ok(())
+    }
 
-        let mut dest_file = fs::File::create(&self.executable_path)?;
-        dest_file.write_all(get_ecapture_bytes())?;
-        dest_file.sync_all()?; // Ensure content is flushed to disk
-
-        let mut perms = fs::metadata(&self.executable_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&self.executable_path, perms)?;
+    /// Deletes binaries left in the data directory by other versions of the
+    /// app or by other external binaries.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn remove_stale_binaries(&self) {
+        let (Some(dir), Some(current)) = (
+            self.executable_path.parent(),
+            self.executable_path.file_name(),
+        ) else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let is_stale = name != current
+                && name.to_str().is_some_and(|n| {
+                    n.starts_with(EXTERNAL_BINARY_PREFIX)
+                        || BUNDLED_BINARY_PREFIXES.iter().any(|p| n.starts_with(p))
+                });
+            if !is_stale {
+                continue;
+            }
+            match fs::remove_file(entry.path()) {
+                Ok(()) => info!("Removed stale eCapture binary {:?}", name),
+                Err(e) => error!("Failed to remove stale eCapture binary {:?}: {}", name, e),
+            }
+        }
+    }
 
-        info!("eCapture binary prepared at: {:?}", self.executable_path);
-        Ok(())
+    /// Describes the binary that would be run, preparing it first.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub async fn info(&self) -> Result<EcaptureInfo> {
+        self.prepare_binary()?;
+        let (source, sha256) = match &self.source {
+            BinarySource::Bundled => ("bundled", embedded_sha256().to_string()),
+            BinarySource::External { sha256, .. } => ("external", sha256.to_ascii_lowercase()),
+        };
+        let version = self.binary_version().await;
+        let (compatibility, warning) = protocol::check(version.as_deref(), None);
+        Ok(EcaptureInfo {
+            source: source.to_string(),
+            path: self.executable_path.to_string_lossy().to_string(),
+            sha256,
+            version,
+            compatibility: compatibility.as_str().to_string(),
+            warning,
+        })
     }
 
+    /// Asks the binary for its version.
     #[cfg(any(target_os = "android", target_os = "linux"))]
-    fn cleanup(&self) -> std::io::Result<()> {
-        info!("Cleaning up eCapture binary...");
-        fs::remove_file(&self.executable_path)
+    async fn binary_version(&self) -> Option<String> {
+        #[cfg(target_os = "android")]
+        let mut command = {
+            let mut command = Command::new("su");
+            command.arg("-c").arg(format!(
+                "{} --version",
+                shell_quote(&self.executable_path.to_string_lossy())
+            ));
+            command
+        };
+        #[cfg(target_os = "linux")]
+        let mut command = {
+            let mut command = Command::new(&self.executable_path);
+            command.arg("--version");
+            command
+        };
+        let output = tokio::time::timeout(Duration::from_secs(5), command.output())
+            .await
+            .ok()?
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .chain(String::from_utf8_lossy(&output.stderr).lines())
+            .find_map(protocol::version_from_log)
     }
 
     #[cfg(target_os = "android")]
     pub async fn run(
         &mut self,
         mut shutdown_rx: watch::Receiver<()>,
-        ecapture_args: String,
+        ecapture_args: Vec<String>,
     ) -> Result<()> {
         // --- 1. Initial Cleanup ---
         // Clean up any orphaned processes from previous runs.
-        let cleaner = AndroidCleaner::new();
-        let cleanup_targets = ["android_ecapture"];
-        let cleanup_exempt_names = ["com.gojue.ecaptureq"];
-        let mut exempt_pids = HashSet::new();
-        exempt_pids.insert(process::id()); // Always exempt the main app process itself.
+        let cleaner = AndroidCleaner::new(
+            self.executable_path.clone(),
+            matches!(self.source, BinarySource::Bundled),
+            self.timeouts,
+        );
 
         info!("Running initial cleanup before starting process...");
-        if let Err(e) = cleaner
-            .cleanup_processes(&cleanup_targets, &exempt_pids, &cleanup_exempt_names)
-            .await
-        {
+        if let Err(e) = cleaner.cleanup_processes().await {
             error!("Initial cleanup failed, continuing anyway: {}", e);
         }
 
         self.prepare_binary()?;
 
-        let binary_command = self.executable_path.to_string_lossy().to_string();
-        let args_vec: Vec<&str> = ecapture_args.split_whitespace().collect();
+        // `su -c` takes a single command string, interpreted by a shell
+        let mut command_line = shell_quote(&self.executable_path.to_string_lossy());
+        for arg in &ecapture_args {
+            command_line.push(' ');
+            command_line.push_str(&shell_quote(arg));
+        }
         let mut child = Command::new("su")
             .arg("-c")
-            .arg(&binary_command)
-            .args(&args_vec)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
+            .arg(&command_line)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()?;
+        let readers = ecapture_output::forward(&mut child, &self.output, &self.events);
 
         info!("eCapture process started via 'su -c'.");
         self.child = Some(child);
@@ -191,10 +393,7 @@ impl CaptureManager {
             _ = shutdown_rx.changed() => {
                 info!("Shutdown signal received, cleaning up eCapture process(es)...");
 
-                if let Err(e) = cleaner
-                    .cleanup_processes(&cleanup_targets, &exempt_pids, &cleanup_exempt_names)
-                    .await
-                {
+                if let Err(e) = cleaner.cleanup_processes().await {
                     error!("Cleanup on shutdown signal failed: {}", e);
                 } else {
                     info!("Cleanup on shutdown signal completed.");
@@ -206,21 +405,19 @@ impl CaptureManager {
 
             // Branch for unexpected process exit
             result = self.child.as_mut().unwrap().wait() => {
-                match result {
-                    Ok(status) => error!("eCapture process exited unexpectedly with status: {}", status),
-                    Err(e) => error!("Error waiting for eCapture process: {}", e),
-                }
-                return Err(anyhow!("eCapture process exited unexpectedly"));
+                let reason = match result {
+                    Ok(status) => format!("eCapture process exited unexpectedly with status: {}", status),
+                    Err(e) => format!("Error waiting for eCapture process: {}", e),
+                };
+                error!("{}", reason);
+                return Err(self.exit_error(reason, readers).await);
             }
         }
 
         // This part is reached after a graceful shutdown.
         // It is slightly redundant but acts as a final safety net to ensure a clean state.
         info!("Running final cleanup before exiting...");
-        if let Err(e) = cleaner
-            .cleanup_processes(&cleanup_targets, &exempt_pids, &cleanup_exempt_names)
-            .await
-        {
+        if let Err(e) = cleaner.cleanup_processes().await {
             error!("Final cleanup failed: {}", e);
         }
 
@@ -230,49 +427,99 @@ impl CaptureManager {
     #[cfg(target_os = "linux")]
     pub async fn run(
         &mut self,
-        mut shutdown_rx: watch::Receiver<()>,
-        ecapture_args: String,
+        shutdown_rx: watch::Receiver<()>,
+        ecapture_args: Vec<String>,
     ) -> Result<()> {
+        // Before preparing, as a running binary cannot be rewritten
+        if let Err(e) = self.stop_leftovers().await {
+            error!(
+                "Stopping leftover eCapture processes failed, continuing anyway: {}",
+                e
+            );
+        }
         self.prepare_binary()?;
-        let binary_command = self.executable_path.to_string_lossy().to_string();
-        let args_vec: Vec<&str> = ecapture_args.split_whitespace().collect();
-        let child = Command::new(&binary_command)
-            .args(args_vec)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()?;
+        let mut command = Command::new(&self.executable_path);
+        command
+            .args(&ecapture_args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Its own group, so stopping it reaches anything it spawns
+            .process_group(0);
+        let parent = getpid();
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                // Stop with the app even when it aborts. The signal follows
+                // the spawning thread, a runtime worker living as long as
+                // the app.
+                prctl::set_pdeathsig(Signal::SIGTERM)?;
+                if getppid() != parent {
+                    // The app is already gone
+                    return Err(Errno::ESRCH.into());
+                }
+                Ok(())
+            });
+        }
+        let mut child = command.spawn()?;
+        let readers = ecapture_output::forward(&mut child, &self.output, &self.events);
 
         info!("eCapture process spawned with PID: {:?}", child.id());
+        let pid = child.id().ok_or_else(|| anyhow!("can not get child pid"))?;
+        if let Err(e) = self.pidfile.record(pid, &self.executable_path) {
+            warn!("Failed to record eCapture PID {}: {}", pid, e);
+        }
         self.child = Some(child); // 将 child 存入 struct
+        let result = self.wait_child(shutdown_rx, pid, readers).await;
+        if let Err(e) = self.pidfile.remove(pid) {
+            warn!("Failed to forget eCapture PID {}: {}", pid, e);
+        }
+        result
+    }
 
+    /// Waits for the child to exit, or stops it on shutdown.
+    #[cfg(target_os = "linux")]
+    async fn wait_child(
+        &mut self,
+        mut shutdown_rx: watch::Receiver<()>,
+        pid: u32,
+        readers: Vec<JoinHandle<()>>,
+    ) -> Result<()> {
         tokio::select! {
             biased;
 
             _ = shutdown_rx.changed() => {
 
                 if let Some(child) = self.child.as_mut() {
-                    let pid = child.id().ok_or_else(|| anyhow!("can not get child pid"))?;
-
-                    send_signal(Pid::from_raw(pid as i32), Signal::SIGINT)?;
-
-                    tokio::select! {
-                        result = child.wait() => {
-                            info!("eCapture process exited gracefully with result: {:?}", result);
+                    for (signal, grace) in processes::escalation(&self.timeouts) {
+                        match send_signal(Pid::from_raw(-(pid as i32)), signal) {
+                            // The group is gone already, only the reap is left
+                            Ok(()) | Err(Errno::ESRCH) => {}
+                            Err(e) => return Err(e.into()),
                         }
-                        _ = tokio::time::sleep(Duration::from_secs(1)) => {
-                            error!("Process did not exit gracefully after 1s. Forcing kill...");
-                            self.child.as_mut().unwrap().kill().await?;
+                        match tokio::time::timeout(grace, child.wait()).await {
+                            Ok(result) => {
+                                info!(
+                                    "eCapture process exited after {} with result: {:?}",
+                                    signal.as_str(),
+                                    result
+                                );
+                                break;
+                            }
+                            Err(_) => {
+                                warn!("eCapture process ignored {} for {:?}", signal.as_str(), grace)
+                            }
                         }
                     }
                 }
             }
 
             result = self.child.as_mut().unwrap().wait() => {
-                match result {
-                    Ok(status) => error!("eCapture process exited unexpectedly with status: {}", status),
-                    Err(e) => error!("Error waiting for eCapture process: {}", e),
-                }
-                return Err(anyhow!("ecapture failed launch"))
+                let reason = match result {
+                    Ok(status) => format!("eCapture process exited unexpectedly with status: {}", status),
+                    Err(e) => format!("Error waiting for eCapture process: {}", e),
+                };
+                error!("{}", reason);
+                return Err(self.exit_error(reason, readers).await);
             }
         }
 
@@ -281,13 +528,98 @@ impl CaptureManager {
     }
 }
 
+#[cfg(target_os = "linux")]
+impl CaptureManager {
+    /// Stops processes of our binary that outlived an earlier session, e.g.
+    /// after the app crashed, escalating from SIGINT to SIGKILL. Only the
+    /// processes in the pidfile, with the start time recorded there, are
+    /// stopped; others running our binary are merely reported.
+    async fn stop_leftovers(&self) -> Result<()> {
+        let found = self.pidfile.orphans();
+        self.report_unrecorded(&found);
+        if found.is_empty() {
+            return Ok(());
+        }
+
+        let pids: Vec<u32> = found.iter().map(|p| p.pid).collect();
+        warn!("Stopping leftover eCapture processes {:?}", pids);
+        stop_processes(&found, &self.timeouts).await
+    }
+
+    /// Logs processes running our binary that are not in the pidfile,
+    /// without signalling them.
+    fn report_unrecorded(&self, recorded: &[FoundProcess]) {
+        let bundled = matches!(self.source, BinarySource::Bundled);
+        let found = match processes::find_by_exe(processes::PROC_ROOT, |exe| {
+            is_our_binary(exe, &self.executable_path, bundled)
+        }) {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Failed to look for other eCapture processes: {}", e);
+                return;
+            }
+        };
+        let unrecorded: Vec<u32> = found
+            .iter()
+            .map(|p| p.pid)
+            .filter(|pid| !recorded.iter().any(|r| r.pid == *pid))
+            .collect();
+        if !unrecorded.is_empty() {
+            warn!(
+                "eCapture processes {:?} run our binary but are not in the pidfile, leaving them alone",
+                unrecorded
+            );
+        }
+    }
+}
+
+/// Stops processes that are not our children, escalating from SIGINT to
+/// SIGKILL while they keep running.
+#[cfg(target_os = "linux")]
+pub async fn stop_processes(found: &[FoundProcess], timeouts: &SessionTimeouts) -> Result<()> {
+    let alive = || {
+        found
+            .iter()
+            .map(|p| p.pid)
+            .filter(|p| processes::read_stat(processes::PROC_ROOT, *p).is_some())
+            .collect::<Vec<_>>()
+    };
+    for (signal, grace) in processes::escalation(timeouts) {
+        for target in processes::signal_targets(found) {
+            if let Err(e) = send_signal(Pid::from_raw(target), signal) {
+                warn!("Sending {} to {} failed: {}", signal.as_str(), target, e);
+            }
+        }
+        let deadline = tokio::time::Instant::now() + grace;
+        while !alive().is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if alive().is_empty() {
+            info!("eCapture processes stopped after {}", signal.as_str());
+            return Ok(());
+        }
+    }
+    Err(anyhow!("eCapture processes {:?} survived SIGKILL", alive()))
+}
+
+/// Kills processes at once, with the groups they lead, when there is no
+/// time left to stop them gracefully.
+#[cfg(target_os = "linux")]
+pub fn kill_processes(found: &[FoundProcess]) {
+    for target in processes::signal_targets(found) {
+        if let Err(e) = send_signal(Pid::from_raw(target), Signal::SIGKILL) {
+            warn!("Sending SIGKILL to {} failed: {}", target, e);
+        }
+    }
+}
+
 #[cfg(target_os = "linux")]
 impl Drop for CaptureManager {
     // new*
     fn drop(&mut self) {
         if let Some(child) = self.child.as_mut() {
             if let Some(pid) = child.id() {
-                if let Err(_e) = send_signal(Pid::from_raw(pid as i32), Signal::SIGINT) {
+                if let Err(_e) = send_signal(Pid::from_raw(-(pid as i32)), Signal::SIGINT) {
                     error!("Can not kill ecapture in drop trait");
                     return;
                 }
@@ -298,133 +630,122 @@ impl Drop for CaptureManager {
     }
 }
 
-#[cfg(target_os = "android")]
-pub struct AndroidCleaner;
-
-#[cfg(target_os = "android")]
-impl AndroidCleaner {
-    /// Creates a new cleaner instance.
-    pub fn new() -> Self {
-        AndroidCleaner
+/// Whether `exe` is the binary we launch, or a bundled binary left running
+/// by an earlier version of the app.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn is_our_binary(exe: &Path, executable_path: &Path, bundled: bool) -> bool {
+    if exe == executable_path {
+        return true;
     }
+    bundled
+        && exe.parent() == executable_path.parent()
+        && exe
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| BUNDLED_BINARY_PREFIXES.iter().any(|p| n.starts_with(p)))
+}
 
-    pub async fn cleanup_processes(
-        &self,
-        targets: &[&str],
-        exempt_pids: &HashSet<u32>,
-        exempt_names: &[&str],
-    ) -> Result<()> {
-        info!(
-            "Starting Android process cleanup for targets: {:?}",
-            targets
-        );
+/// Quotes an argument for `sh`.
+#[cfg(target_os = "android")]
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
 
-        let find_futures = targets
-            .iter()
-            .map(|name| self.find_pids_by_name(name, exempt_names));
-        let find_results = future::join_all(find_futures).await;
-
-        let mut pids_to_kill = HashSet::new();
-        for result in find_results {
-            match result {
-                Ok(pids) => {
-                    for pid in pids {
-                        if !exempt_pids.contains(&pid) {
-                            pids_to_kill.insert(pid);
-                        }
-                    }
-                }
-                Err(e) => error!("Error finding processes during cleanup: {}", e),
-            }
-        }
+/// Stops the ecapture processes started through `su`, which run as root and
+/// are hidden from the app's own view of `/proc`.
+#[cfg(target_os = "android")]
+pub struct AndroidCleaner {
+    executable_path: PathBuf,
+    /// Whether older bundled binaries next to `executable_path` are ours too.
+    bundled: bool,
+    timeouts: SessionTimeouts,
+}
 
-        if pids_to_kill.is_empty() {
-            info!("No orphaned processes found to clean up.");
-            return Ok(());
+#[cfg(target_os = "android")]
+impl AndroidCleaner {
+    pub fn new(executable_path: PathBuf, bundled: bool, timeouts: SessionTimeouts) -> Self {
+        Self {
+            executable_path,
+            bundled,
+            timeouts,
         }
-
-        info!("Found processes to kill: {:?}", pids_to_kill);
-
-        let kill_futures = pids_to_kill.iter().map(|&pid| self.kill_process(pid));
-        let kill_results = future::join_all(kill_futures).await;
-
-        let killed_count = kill_results.into_iter().filter(|res| res.is_ok()).count();
-        info!(
-            "Cleanup finished. Attempted to kill {} processes, {} succeeded.",
-            pids_to_kill.len(),
-            killed_count
-        );
-
-        Ok(())
     }
 
-    /// Finds PIDs by searching the full command line arguments.
-    async fn find_pids_by_name(&self, keyword: &str, exempt_names: &[&str]) -> Result<Vec<u32>> {
-        let ps_command = "ps -A -o PID,ARGS";
-
-        // Execute the `ps` command as root to see all processes.
-        let output = Command::new("su")
-            .arg("-c")
-            .arg(ps_command)
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!(
-                "Root `ps` command failed with status: {}. Stderr: {}",
-                output.status, stderr
-            );
-            return Err(anyhow!("Failed to execute 'ps' as root"));
+    /// Stops every process running one of our binaries, escalating from
+    /// SIGINT to SIGKILL while they keep running.
+    pub async fn cleanup_processes(&self) -> Result<()> {
+        let dir = self
+            .executable_path
+            .parent()
+            .ok_or_else(|| anyhow!("eCapture binary has no parent directory"))?;
+        let listing = su(&processes::exe_listing_script(dir)).await?;
+        let found = processes::parse_exe_listing(&listing, |exe| {
+            is_our_binary(exe, &self.executable_path, self.bundled)
+        });
+        if found.is_empty() {
+            info!("No eCapture processes found to clean up.");
+            return Ok(());
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut pids = Vec::new();
-
-        for line in stdout.lines().skip(1) {
-            // Skip header
-            let trimmed_line = line.trim();
-            // Split into PID and the rest of the line (ARGS)
-            let parts: Vec<&str> = trimmed_line.splitn(2, char::is_whitespace).collect();
-
-            if parts.len() == 2 {
-                let pid_str = parts[0];
-                let full_args = parts[1];
-
-                let is_self_ps_command = full_args.contains(ps_command);
-
-                if full_args.contains(keyword) && !is_self_ps_command {
-                    let is_exempted_by_name =
-                        exempt_names.iter().any(|&name| full_args.contains(name));
-
-                    if !is_exempted_by_name {
-                        if let Ok(pid) = pid_str.parse::<u32>() {
-                            pids.push(pid);
-                        }
-                    }
+        let pids: Vec<u32> = found.iter().map(|p| p.pid).collect();
+        let targets = processes::signal_targets(&found)
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        info!("Stopping eCapture processes {:?} ({})", pids, targets);
+
+        for (signal, grace) in processes::escalation(&self.timeouts) {
+            // Negative targets are process groups
+            if let Err(e) = su(&format!("kill -{} -- {}", signal as i32, targets)).await {
+                warn!("Sending {} failed: {}", signal.as_str(), e);
+            }
+            let deadline = tokio::time::Instant::now() + grace;
+            loop {
+                let alive = self.alive(&pids).await?;
+                if alive.is_empty() {
+                    info!("eCapture processes stopped after {}", signal.as_str());
+                    return Ok(());
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    warn!("eCapture processes {:?} ignored {}", alive, signal.as_str());
+                    break;
                 }
+                tokio::time::sleep(Duration::from_millis(100)).await;
             }
         }
-        Ok(pids)
+        Err(anyhow!("eCapture processes {:?} survived SIGKILL", pids))
     }
 
-    /// Kills the specified process by PID using `su`.
-    async fn kill_process(&self, pid: u32) -> Result<()> {
-        let status = Command::new("su")
-            .arg("-c")
-            .arg(format!("kill -9 {}", pid))
-            .status()
-            .await?;
+    /// The given processes that still exist.
+    async fn alive(&self, pids: &[u32]) -> Result<Vec<u32>> {
+        let pids = pids
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let output = su(&format!(
+            "for p in {}; do [ -d /proc/$p ] && echo $p; done; true",
+            pids
+        ))
+        .await?;
+        Ok(output
+            .lines()
+            .filter_map(|l| l.trim().parse().ok())
+            .collect())
+    }
+}
 
-        if status.success() {
-            info!("Successfully terminated process with PID: {}", pid);
-            Ok(())
-        } else {
-            error!(
-                "Failed to kill process with PID: {}. It might have already exited.",
-                pid
-            );
-            Err(anyhow!("Failed to kill PID: {}", pid))
-        }
+/// Runs a shell command as root, returning its stdout.
+#[cfg(target_os = "android")]
+async fn su(command: &str) -> Result<String> {
+    let output = Command::new("su").arg("-c").arg(command).output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "su command failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+
+use crate::core::models::EcaptureOutputLine;
+use crate::services::events::EventSink;
+
+/// Lines kept in the ring; older ones are dropped.
+const MAX_LINES: usize = 500;
+/// Lines appended to the error of a failed launch.
+const ERROR_TAIL_LINES: usize = 20;
+const OUTPUT_EVENT: &str = "ecapture-output";
+
+/// Recent stdout and stderr lines of the ecapture process, shared between
+/// the capture manager and `get_ecapture_output`.
+#[derive(Clone, Default)]
+pub struct OutputRing {
+    lines: Arc<Mutex<VecDeque<EcaptureOutputLine>>>,
+}
+
+impl OutputRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, line: EcaptureOutputLine) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == MAX_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn snapshot(&self) -> Vec<EcaptureOutputLine> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap().clear();
+    }
+
+    /// The last lines, one per line, for error messages; empty if there was
+    /// no output.
+    pub fn tail(&self) -> String {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(ERROR_TAIL_LINES);
+        lines
+            .iter()
+            .skip(skip)
+            .map(|l| format!("[{}] {}", l.stream, l.line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Takes the piped stdout and stderr of `child` and forwards each line to
+/// the log, the ring and the frontend. The returned tasks end when the
+/// streams close.
+pub fn forward(child: &mut Child, ring: &OutputRing, events: &EventSink) -> Vec<JoinHandle<()>> {
+    let mut tasks = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        tasks.push(tokio::spawn(read_lines(
+            stdout,
+            "stdout",
+            ring.clone(),
+            events.clone(),
+        )));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tasks.push(tokio::spawn(read_lines(
+            stderr,
+            "stderr",
+            ring.clone(),
+            events.clone(),
+        )));
+    }
+    tasks
+}
+
+async fn read_lines(
+    stream: impl AsyncRead + Unpin,
+    name: &'static str,
+    ring: OutputRing,
+    events: EventSink,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to read eCapture {}: {}", name, e);
+                break;
+            }
+        }
+        // ecapture may print raw payload bytes; keep what is readable
+        let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        match name {
+            "stderr" => warn!("[eCapture] {}", line),
+            _ => info!("[eCapture] {}", line),
+        }
+
+        let line = EcaptureOutputLine {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64),
+            stream: name.to_string(),
+            line,
+        };
+        if let Err(e) = events.emit(OUTPUT_EVENT, &line) {
+            error!("Failed to emit eCapture output: {}", e);
+        }
+        ring.push(line);
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
+
+/// An event for the frontend, as passed from the privileged helper to the
+/// app.
+pub type ForwardedEvent = (String, serde_json::Value);
+
+/// Where the capture services report events: the frontend directly, or the
+/// client of the privileged helper, which passes them on to its frontend.
+#[derive(Clone)]
+pub enum EventSink {
+    App(AppHandle),
+    Forward(mpsc::UnboundedSender<ForwardedEvent>),
+}
+
+impl EventSink {
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: &S) -> Result<()> {
+        match self {
+            EventSink::App(app_handle) => app_handle.emit(event, payload)?,
+            EventSink::Forward(tx) => tx
+                .send((event.to_string(), serde_json::to_value(payload)?))
+                .map_err(|_| anyhow::anyhow!("event receiver is gone"))?,
+        }
+        Ok(())
+    }
+}
+
+impl From<AppHandle> for EventSink {
+    fn from(app_handle: AppHandle) -> Self {
+        EventSink::App(app_handle)
+    }
+}
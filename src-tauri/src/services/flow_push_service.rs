@@ -0,0 +1,83 @@
+use crate::core::actor::DataFrameActorHandle;
+use anyhow::Result;
+use log::{error, info};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Periodically pushes flows changed since the last tick to the frontend.
+///
+/// Unlike packets, flow rows are updated in place, so progress is tracked by
+/// the table's `update_seq` rather than by `index`. The frontend merges rows
+/// by `uuid`.
+pub struct FlowPushService {
+    df_actor_handle: DataFrameActorHandle,
+    tauri_interface: String,
+    done: watch::Receiver<()>,
+    last_seq: u64,
+    app_handle: AppHandle,
+}
+
+impl FlowPushService {
+    pub fn new(
+        handle: DataFrameActorHandle,
+        interface: String,
+        done: watch::Receiver<()>,
+        app_handle: AppHandle,
+    ) -> Result<JoinHandle<()>> {
+        let mut worker = Self {
+            df_actor_handle: handle,
+            tauri_interface: interface,
+            done,
+            last_seq: 0,
+            app_handle,
+        };
+
+        Ok(tokio::spawn(async move {
+            worker.run().await;
+        }))
+    }
+
+    pub async fn run(&mut self) {
+        let mut flush_timer = tokio::time::interval(Duration::from_millis(300));
+        info!("Flow push service started.");
+
+        loop {
+            tokio::select! {
+                biased;
+
+                _ = self.done.changed() => {
+                    info!("Flow push service shutting down");
+                    break;
+                }
+
+                _ = flush_timer.tick() => {
+                    let new_df = match self.df_actor_handle.get_flows_updated_since(&self.last_seq).await {
+                        Ok(df) => df,
+                        Err(e) => {
+                            error!("Error fetching flows: {}", e);
+                            continue;
+                        }
+                    };
+
+                    if new_df.height() == 0 {
+                        continue;
+                    }
+
+                    match crate::tauri_bridge::converters::df_to_flow_record_vec(&new_df) {
+                        Ok(flows) => {
+                            if let Some(max_seq) = flows.iter().map(|f| f.update_seq).max() {
+                                self.last_seq = max_seq;
+                            }
+                            if let Err(e) = self.app_handle.emit(self.tauri_interface.as_str(), &flows) {
+                                error!("Failed to send flows to frontend: {}", e);
+                            }
+                        }
+                        Err(e) => error!("Error converting flows: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,662 @@
+//! The privileged helper: a small root process that only runs ecapture and
+//! relays its WebSocket, so that the app itself can run as a normal user.
+//! The app talks to it over a Unix socket with the messages of
+//! [`helper_protocol`].
+//!
+//! The helper is this executable run with [`HELPER_FLAG`], either launched by
+//! the app through pkexec, started by hand with sudo, or socket-activated by
+//! systemd.
+
+use std::fs;
+use std::os::fd::FromRawFd;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt, chown};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use log::{error, info, warn};
+use nix::unistd::geteuid;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UnixListener, UnixStream};
+use tokio::process::Command;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{Mutex, mpsc, watch};
+use tokio::task::JoinSet;
+
+use crate::core::ecapture_args::EcaptureOptions;
+use crate::core::helper_protocol::{self, Reply, Request};
+use crate::core::models::{EcaptureInfo, ProcessInfo};
+use crate::core::session_timeouts::SessionTimeouts;
+use crate::services::capture::{self, BinarySource, CaptureManager};
+use crate::services::ecapture_output::OutputRing;
+use crate::services::events::EventSink;
+use crate::services::pidfile::PidFile;
+use crate::services::processes;
+use crate::services::supervisor::{self, RestartPolicy};
+
+/// First argument that runs the executable as the helper instead of the app.
+pub const HELPER_FLAG: &str = "--privileged-helper";
+/// Owned by root, so that the socket cannot be swapped under the helper.
+const SOCKET_DIR: &str = "/run/ecaptureq";
+/// Where the helper writes the bundled ecapture and its pidfile.
+const DATA_DIR: &str = "/var/lib/ecaptureq";
+/// Time the app waits for a helper it launched, including the password
+/// prompt of pkexec.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(120);
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+/// Time sessions get to stop ecapture when the helper exits.
+const EXIT_GRACE: Duration = Duration::from_secs(6);
+
+/// Socket of the helper serving the user `uid`.
+pub fn socket_path(uid: u32) -> PathBuf {
+    Path::new(SOCKET_DIR).join(format!("helper-{}.sock", uid))
+}
+
+/// The session the helper runs, at most one at a time.
+struct ActiveSession {
+    ws_url: String,
+    /// Set once a relay has reached ecapture.
+    ready: watch::Sender<bool>,
+}
+
+pub struct HelperConfig {
+    socket: PathBuf,
+    /// The only user besides root allowed to connect.
+    client_uid: Option<u32>,
+    binary: BinarySource,
+    /// The app that launched the helper; the helper exits with it.
+    parent_pid: Option<u32>,
+}
+
+impl HelperConfig {
+    /// Reads `--uid`, `--socket`, `--parent-pid`, `--ecapture` and
+    /// `--ecapture-sha256`. The uid defaults to the user who ran pkexec or
+    /// sudo, the socket to the one of that user.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut client_uid = None;
+        let mut socket = None;
+        let mut parent_pid = None;
+        let mut ecapture = None;
+        let mut sha256 = None;
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("{} needs a value", flag))?;
+            match flag.as_str() {
+                "--uid" => client_uid = Some(value.parse()?),
+                "--socket" => socket = Some(PathBuf::from(value)),
+                "--parent-pid" => parent_pid = Some(value.parse()?),
+                "--ecapture" => ecapture = Some(PathBuf::from(value)),
+                "--ecapture-sha256" => sha256 = Some(value),
+                _ => return Err(anyhow!("unknown option {}", flag)),
+            }
+        }
+        let client_uid = client_uid.or_else(|| {
+            ["PKEXEC_UID", "SUDO_UID"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok()?.parse().ok())
+        });
+        let socket = match (socket, client_uid) {
+            (Some(socket), _) => socket,
+            (None, Some(uid)) => socket_path(uid),
+            (None, None) => return Err(anyhow!("--socket or --uid is required")),
+        };
+        let binary = match (ecapture, sha256) {
+            (Some(path), Some(sha256)) => BinarySource::External { path, sha256 },
+            (None, None) => BinarySource::Bundled,
+            _ => return Err(anyhow!("--ecapture and --ecapture-sha256 go together")),
+        };
+        Ok(Self {
+            socket,
+            client_uid,
+            binary,
+            parent_pid,
+        })
+    }
+}
+
+/// Accepts connections until the app that launched the helper exits or the
+/// helper is interrupted, then stops the running session.
+pub async fn serve(config: HelperConfig) -> Result<()> {
+    if !geteuid().is_root() {
+        return Err(anyhow!("the privileged helper must run as root"));
+    }
+    if let BinarySource::External { path, .. } = &config.binary {
+        check_root_owned(path)?;
+    }
+    fs::create_dir_all(DATA_DIR)?;
+    fs::set_permissions(DATA_DIR, fs::Permissions::from_mode(0o700))?;
+
+    let (listener, bound) = match systemd_listener()? {
+        Some(listener) => (listener, false),
+        None => (bind(&config)?, true),
+    };
+    info!(
+        "Privileged helper listening on {:?} for uid {:?}",
+        config.socket, config.client_uid
+    );
+
+    let active: Arc<Mutex<Option<ActiveSession>>> = Arc::default();
+    let (exit_tx, exit_rx) = watch::channel(());
+    let mut sessions = JoinSet::new();
+    let parent_exited = parent_exited(config.parent_pid);
+    tokio::pin!(parent_exited);
+    let mut terminate = signal(SignalKind::terminate())?;
+    loop {
+        while sessions.try_join_next().is_some() {}
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("Failed to accept a connection: {}", e);
+                        continue;
+                    }
+                };
+                match stream.peer_cred() {
+                    Ok(cred) if cred.uid() == 0 || Some(cred.uid()) == config.client_uid => {}
+                    Ok(cred) => {
+                        warn!("Refused a connection from uid {}", cred.uid());
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Refused a connection without credentials: {}", e);
+                        continue;
+                    }
+                }
+                let binary = config.binary.clone();
+                let active = active.clone();
+                let exit_rx = exit_rx.clone();
+                sessions.spawn(async move {
+                    if let Err(e) = handle(stream, binary, active, exit_rx).await {
+                        warn!("Connection failed: {:#}", e);
+                    }
+                });
+            }
+            _ = &mut parent_exited => {
+                info!("eCaptureQ exited, stopping the privileged helper");
+                break;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Interrupted, stopping the privileged helper");
+                break;
+            }
+            _ = terminate.recv() => {
+                info!("Terminated, stopping the privileged helper");
+                break;
+            }
+        }
+    }
+
+    let _ = exit_tx.send(());
+    let _ = tokio::time::timeout(EXIT_GRACE, sessions.join_all()).await;
+    if bound {
+        let _ = fs::remove_file(&config.socket);
+    }
+    Ok(())
+}
+
+/// Refuses an external binary the client user could change, as the helper
+/// runs it as root.
+fn check_root_owned(path: &Path) -> Result<()> {
+    let metadata =
+        fs::metadata(path).map_err(|e| anyhow!("cannot read eCapture binary {:?}: {}", path, e))?;
+    if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+        return Err(anyhow!(
+            "eCapture binary {:?} must be owned and only writable by root to run it in the privileged helper",
+            path
+        ));
+    }
+    Ok(())
+}
+
+/// The socket passed by systemd socket activation, if any.
+fn systemd_listener() -> Result<Option<UnixListener>> {
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    if !for_us || std::env::var("LISTEN_FDS").as_deref() != Ok("1") {
+        return Ok(None);
+    }
+    // SAFETY: systemd passes the listening socket as the first fd after stderr
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(3) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(UnixListener::from_std(listener)?))
+}
+
+/// Creates the socket, readable and writable by the client user only.
+fn bind(config: &HelperConfig) -> Result<UnixListener> {
+    if let Some(dir) = config.socket.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Left behind by a helper that did not exit cleanly
+    if fs::symlink_metadata(&config.socket).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(&config.socket)?;
+    }
+    let listener = UnixListener::bind(&config.socket)?;
+    fs::set_permissions(&config.socket, fs::Permissions::from_mode(0o600))?;
+    if let Some(uid) = config.client_uid {
+        chown(&config.socket, Some(uid), None)?;
+    }
+    Ok(listener)
+}
+
+/// Resolves once `pid` has exited; never without one.
+async fn parent_exited(pid: Option<u32>) {
+    let Some(pid) = pid else {
+        return std::future::pending().await;
+    };
+    while processes::read_stat(processes::PROC_ROOT, pid).is_some() {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn handle(
+    mut stream: UnixStream,
+    binary: BinarySource,
+    active: Arc<Mutex<Option<ActiveSession>>>,
+    exit_rx: watch::Receiver<()>,
+) -> Result<()> {
+    let Some(request) = read_message::<Request>(&mut stream).await? else {
+        return Ok(());
+    };
+    match request {
+        Request::Start {
+            options,
+            ws_url,
+            timeouts,
+        } => run_session(stream, options, ws_url, timeouts, binary, active, exit_rx).await,
+        Request::Relay => relay(stream, &active).await,
+        Request::Stop => {
+            let message = "no capture session on this connection".to_string();
+            write_message(&mut stream, &Reply::Error { message }).await
+        }
+        Request::Info => {
+            let (events, _) = mpsc::unbounded_channel();
+            let manager = CaptureManager::new(
+                DATA_DIR,
+                binary,
+                OutputRing::new(),
+                EventSink::Forward(events),
+            );
+            let reply = match manager.info().await {
+                Ok(info) => Reply::Info { info },
+                Err(e) => Reply::Error {
+                    message: format!("{:#}", e),
+                },
+            };
+            write_message(&mut stream, &reply).await
+        }
+        Request::Orphans => {
+            let processes = PidFile::new(DATA_DIR)
+                .orphans()
+                .iter()
+                .filter_map(|p| processes::find_process(processes::PROC_ROOT, p.pid).ok())
+                .collect();
+            write_message(&mut stream, &Reply::Processes { processes }).await
+        }
+        Request::StopOrphans { timeouts } => {
+            let orphans = PidFile::new(DATA_DIR).orphans();
+            info!(
+                "Stopping orphaned eCapture processes {:?}",
+                orphans.iter().map(|p| p.pid).collect::<Vec<_>>()
+            );
+            let reply = match capture::stop_processes(&orphans, &timeouts).await {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::Error {
+                    message: format!("{:#}", e),
+                },
+            };
+            write_message(&mut stream, &reply).await
+        }
+        Request::Kill => {
+            let children = PidFile::new(DATA_DIR).children();
+            if !children.is_empty() {
+                warn!(
+                    "Killing eCapture processes {:?}",
+                    children.iter().map(|p| p.pid).collect::<Vec<_>>()
+                );
+                capture::kill_processes(&children);
+            }
+            write_message(&mut stream, &Reply::Ok).await
+        }
+    }
+}
+
+/// Runs ecapture until the client stops the session or hangs up, sending
+/// it the events of the session.
+async fn run_session(
+    stream: UnixStream,
+    options: EcaptureOptions,
+    ws_url: String,
+    timeouts: SessionTimeouts,
+    binary: BinarySource,
+    active: Arc<Mutex<Option<ActiveSession>>>,
+    mut exit_rx: watch::Receiver<()>,
+) -> Result<()> {
+    let (mut read, mut write) = stream.into_split();
+    let argv = helper_protocol::loopback_address(&ws_url).and_then(|_| options.to_argv(&ws_url));
+    let argv = match argv {
+        Ok(argv) => argv,
+        Err(e) => {
+            let error = Some(format!("{:#}", e));
+            return write_message(&mut write, &Reply::Exited { error }).await;
+        }
+    };
+    let (ready_tx, ready_rx) = watch::channel(false);
+    {
+        let mut active = active.lock().await;
+        if active.is_some() {
+            let error = Some("a capture session is already running".to_string());
+            return write_message(&mut write, &Reply::Exited { error }).await;
+        }
+        *active = Some(ActiveSession {
+            ws_url,
+            ready: ready_tx,
+        });
+    }
+
+    info!("Starting capture session: {:?}", argv);
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let events = EventSink::Forward(events_tx);
+    let mut manager = CaptureManager::new(DATA_DIR, binary, OutputRing::new(), events.clone());
+    manager.set_timeouts(timeouts);
+    let capture = tokio::spawn(async move {
+        supervisor::supervise(
+            &mut manager,
+            shutdown_rx,
+            argv,
+            RestartPolicy::default(),
+            &ready_rx,
+            &events,
+        )
+        .await
+    });
+
+    let stop_requested = async move {
+        // Anything but a stop is ignored; a closed connection stops too
+        while let Ok(Some(request)) = read_message::<Request>(&mut read).await {
+            if matches!(request, Request::Stop) {
+                break;
+            }
+        }
+    };
+    tokio::pin!(stop_requested);
+    let mut stopping = false;
+    let mut client_gone = false;
+    loop {
+        tokio::select! {
+            event = events_rx.recv() => {
+                // Closed once the capture task has ended
+                let Some((event, payload)) = event else {
+                    break;
+                };
+                if !client_gone
+                    && write_message(&mut write, &Reply::Event { event, payload }).await.is_err()
+                {
+                    client_gone = true;
+                    stopping = true;
+                    let _ = shutdown_tx.send(());
+                }
+            }
+            _ = &mut stop_requested, if !stopping => {
+                stopping = true;
+                let _ = shutdown_tx.send(());
+            }
+            _ = exit_rx.changed(), if !stopping => {
+                stopping = true;
+                let _ = shutdown_tx.send(());
+            }
+        }
+    }
+
+    let result = capture.await?;
+    *active.lock().await = None;
+    info!("Capture session ended");
+    let error = result.err().map(|e| format!("{:#}", e));
+    write_message(&mut write, &Reply::Exited { error }).await
+}
+
+/// Pipes the connection to the WebSocket of the running ecapture.
+async fn relay(mut stream: UnixStream, active: &Mutex<Option<ActiveSession>>) -> Result<()> {
+    let Some((ws_url, ready)) = active
+        .lock()
+        .await
+        .as_ref()
+        .map(|session| (session.ws_url.clone(), session.ready.clone()))
+    else {
+        let message = "no capture session is running".to_string();
+        return write_message(&mut stream, &Reply::Error { message }).await;
+    };
+    let (host, port) = helper_protocol::loopback_address(&ws_url)?;
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        // ecapture may not listen yet; the app retries
+        Err(e) => {
+            let message = format!("cannot connect to eCapture at {}: {}", ws_url, e);
+            return write_message(&mut stream, &Reply::Error { message }).await;
+        }
+    };
+    ready.send_replace(true);
+    write_message(&mut stream, &Reply::Ok).await?;
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+    Ok(())
+}
+
+/// Makes sure a helper listens on `socket`, launching one through pkexec,
+/// which asks for an administrator password, if none does. A running helper
+/// keeps the binary it was started with.
+pub async fn ensure_running(socket: &Path, binary: &BinarySource) -> Result<()> {
+    if is_running(socket).await {
+        return Ok(());
+    }
+
+    let mut command = Command::new("pkexec");
+    command
+        .arg(std::env::current_exe()?)
+        .arg(HELPER_FLAG)
+        .arg("--socket")
+        .arg(socket)
+        .arg("--parent-pid")
+        .arg(std::process::id().to_string());
+    if let BinarySource::External { path, sha256 } = binary {
+        command
+            .arg("--ecapture")
+            .arg(path)
+            .arg("--ecapture-sha256")
+            .arg(sha256);
+    }
+    info!("Launching the privileged helper through pkexec");
+    let mut child = command
+        .spawn()
+        .map_err(|e| anyhow!("cannot run pkexec to start the privileged helper: {}", e))?;
+
+    let deadline = tokio::time::Instant::now() + LAUNCH_TIMEOUT;
+    loop {
+        if is_running(socket).await {
+            return Ok(());
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow!(
+                "the privileged helper exited with {} before it was ready",
+                status
+            ));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow!("timed out waiting for the privileged helper"));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Whether a helper listens on `socket`, or systemd starts one for it.
+pub async fn is_running(socket: &Path) -> bool {
+    UnixStream::connect(socket).await.is_ok()
+}
+
+/// Runs a capture session in the helper, like [`supervisor::supervise`]
+/// does locally, passing its events on to the frontend.
+pub async fn supervise_remote(
+    socket: &Path,
+    options: EcaptureOptions,
+    ws_url: String,
+    timeouts: SessionTimeouts,
+    mut shutdown_rx: watch::Receiver<()>,
+    app_handle: &AppHandle,
+) -> Result<()> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| anyhow!("cannot connect to the privileged helper: {}", e))?;
+    let request = Request::Start {
+        options,
+        ws_url,
+        timeouts,
+    };
+    write_message(&mut stream, &request).await?;
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut stop_sent = false;
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed(), if !stop_sent => {
+                stop_sent = true;
+                write_message(&mut write, &Request::Stop).await?;
+            }
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Err(anyhow!("the privileged helper closed the connection"));
+                };
+                match serde_json::from_str::<Reply>(&line)? {
+                    Reply::Event { event, payload } => {
+                        if let Err(e) = app_handle.emit(&event, payload) {
+                            error!("Failed to emit {}: {}", event, e);
+                        }
+                    }
+                    Reply::Exited { error: None } => return Ok(()),
+                    Reply::Exited { error: Some(error) } | Reply::Error { message: error } => {
+                        return Err(anyhow!("{}", error));
+                    }
+                    Reply::Ok | Reply::Info { .. } | Reply::Processes { .. } => {}
+                }
+            }
+        }
+    }
+}
+
+/// Opens a byte pipe to the WebSocket of the ecapture run by the helper.
+pub async fn connect_relay(socket: &Path) -> Result<UnixStream> {
+    let mut stream = UnixStream::connect(socket).await?;
+    write_message(&mut stream, &Request::Relay).await?;
+    match read_message::<Reply>(&mut stream).await? {
+        Some(Reply::Ok) => Ok(stream),
+        Some(Reply::Error { message }) => Err(anyhow!("{}", message)),
+        _ => Err(anyhow!("unexpected reply from the privileged helper")),
+    }
+}
+
+/// The ecapture a running helper runs.
+pub async fn remote_info(socket: &Path) -> Result<EcaptureInfo> {
+    match ask(socket, &Request::Info).await? {
+        Reply::Info { info } => Ok(info),
+        _ => Err(anyhow!("unexpected reply from the privileged helper")),
+    }
+}
+
+/// ecapture processes a running helper finds left behind by an earlier one.
+pub async fn remote_orphans(socket: &Path) -> Result<Vec<ProcessInfo>> {
+    match ask(socket, &Request::Orphans).await? {
+        Reply::Processes { processes } => Ok(processes),
+        _ => Err(anyhow!("unexpected reply from the privileged helper")),
+    }
+}
+
+pub async fn stop_remote_orphans(socket: &Path, timeouts: SessionTimeouts) -> Result<()> {
+    ask(socket, &Request::StopOrphans { timeouts }).await?;
+    Ok(())
+}
+
+/// Kills the ecapture of the session a running helper runs.
+pub async fn kill_remote(socket: &Path) -> Result<()> {
+    ask(socket, &Request::Kill).await?;
+    Ok(())
+}
+
+/// Sends `request` on a connection of its own and returns the reply.
+async fn ask(socket: &Path, request: &Request) -> Result<Reply> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| anyhow!("cannot connect to the privileged helper: {}", e))?;
+    write_message(&mut stream, request).await?;
+    match read_message::<Reply>(&mut stream).await? {
+        Some(Reply::Error { message }) => Err(anyhow!("{}", message)),
+        Some(reply) => Ok(reply),
+        None => Err(anyhow!("the privileged helper closed the connection")),
+    }
+}
+
+/// Reads one message, a byte at a time so that nothing after it is
+/// consumed. `None` if the connection was closed before one.
+async fn read_message<T: DeserializeOwned>(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<T>> {
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0u8];
+        if stream.read(&mut byte).await? == 0 {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            return Err(anyhow!("connection closed in the middle of a message"));
+        }
+        if byte[0] == b'\n' {
+            break;
+        }
+        if line.len() == MAX_MESSAGE_LEN {
+            return Err(anyhow!("message longer than {} bytes", MAX_MESSAGE_LEN));
+        }
+        line.push(byte[0]);
+    }
+    Ok(Some(serde_json::from_slice(&line)?))
+}
+
+async fn write_message<T: Serialize>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    message: &T,
+) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line).await?;
+    Ok(())
+}
+
+/// The helper runs without the app and its log plugin; its log goes to
+/// stderr, which pkexec, sudo and systemd all keep.
+pub fn init_logging() {
+    struct StderrLogger;
+
+    impl log::Log for StderrLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Info
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                eprintln!("[{}] {}", record.level(), record.args());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: StderrLogger = StderrLogger;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+}
@@ -1,3 +1,16 @@
+#[cfg(target_os = "android")]
+pub mod android_packages;
 pub mod capture;
+pub mod ecapture_output;
+pub mod events;
+pub mod flow_push_service;
+#[cfg(all(not(decoupled), target_os = "linux"))]
+pub mod helper;
+#[cfg(target_os = "linux")]
+pub mod pidfile;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod processes;
 pub mod push_service;
+#[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+pub mod supervisor;
 pub mod websocket;
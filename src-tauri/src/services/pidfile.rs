@@ -0,0 +1,113 @@
+//! The ecapture processes we launched, kept on disk so that processes
+//! outliving the app can be found on its next start. The release build
+//! aborts on panic, so `Drop for CaptureManager` cannot be relied on.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::services::processes::{self, FoundProcess, PROC_ROOT};
+
+const PIDFILE_NAME: &str = "ecapture_pids.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PidEntry {
+    pid: u32,
+    /// From `/proc/<pid>/stat`, to tell our process from a later one that
+    /// was given the same pid.
+    start_time: u64,
+    exe: PathBuf,
+}
+
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: data_dir.as_ref().join(PIDFILE_NAME),
+        }
+    }
+
+    /// Records a process we just launched, dropping the entries of
+    /// processes that have exited.
+    pub fn record(&self, pid: u32, exe: &Path) -> Result<()> {
+        let stat = processes::read_stat(PROC_ROOT, pid)
+            .ok_or_else(|| anyhow!("process {} exited before it was recorded", pid))?;
+        let mut entries = self.live_entries();
+        entries.push(PidEntry {
+            pid,
+            start_time: stat.start_time,
+            exe: exe.to_path_buf(),
+        });
+        self.write(&entries)
+    }
+
+    /// Forgets a process once it has been reaped.
+    pub fn remove(&self, pid: u32) -> Result<()> {
+        let mut entries = self.live_entries();
+        entries.retain(|e| e.pid != pid);
+        self.write(&entries)
+    }
+
+    /// Recorded processes that still run but are not children of this app,
+    /// i.e. were left behind by an earlier run of it.
+    pub fn orphans(&self) -> Vec<FoundProcess> {
+        let parent = std::process::id();
+        self.running(|ppid| ppid != parent)
+    }
+
+    /// Recorded processes that still run and are children of this app.
+    pub fn children(&self) -> Vec<FoundProcess> {
+        let parent = std::process::id();
+        self.running(|ppid| ppid == parent)
+    }
+
+    /// Recorded processes that still run, with a parent `ppid` matches.
+    fn running(&self, ppid: impl Fn(u32) -> bool) -> Vec<FoundProcess> {
+        self.live_entries()
+            .into_iter()
+            .filter_map(|entry| {
+                let stat = processes::read_stat(PROC_ROOT, entry.pid)?;
+                ppid(stat.ppid).then_some(FoundProcess {
+                    pid: entry.pid,
+                    pgid: stat.pgid,
+                    exe: entry.exe,
+                })
+            })
+            .collect()
+    }
+
+    /// Entries whose process still runs. A missing or unreadable file has
+    /// none.
+    fn live_entries(&self) -> Vec<PidEntry> {
+        let Ok(json) = fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        let entries: Vec<PidEntry> = serde_json::from_str(&json).unwrap_or_default();
+        entries
+            .into_iter()
+            .filter(|entry| {
+                processes::read_stat(PROC_ROOT, entry.pid)
+                    .is_some_and(|stat| stat.start_time == entry.start_time)
+            })
+            .collect()
+    }
+
+    fn write(&self, entries: &[PidEntry]) -> Result<()> {
+        if entries.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)?;
+            }
+            return Ok(());
+        }
+        // Replaced in one step, so a crash never leaves half a file
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(entries)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
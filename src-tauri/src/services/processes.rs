@@ -0,0 +1,639 @@
+//! Running processes, read from `/proc`: the TLS libraries they use, and
+//! the processes running one of our ecapture binaries so they can be stopped.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use nix::sys::signal::Signal;
+
+use crate::core::ecapture_args::{CaptureScope, EcaptureOptions, Subcommand};
+use crate::core::models::ProcessInfo;
+use crate::core::session_timeouts::SessionTimeouts;
+
+pub const PROC_ROOT: &str = "/proc";
+
+/// Time a killed process gets to disappear before it is reported as stuck.
+const KILL_WAIT: Duration = Duration::from_secs(1);
+
+/// Signals sent to stop an ecapture process, each followed by the time it
+/// is given to exit before the next one.
+pub fn escalation(timeouts: &SessionTimeouts) -> [(Signal, Duration); 3] {
+    [
+        (Signal::SIGINT, timeouts.interrupt_grace()),
+        (Signal::SIGTERM, timeouts.terminate_grace()),
+        (Signal::SIGKILL, KILL_WAIT),
+    ]
+}
+
+/// Suffix the kernel appends to the `exe` link of a deleted binary.
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// TLS implementations ecapture can hook, as reported in
+/// `ProcessInfo::tls_libraries`.
+const OPENSSL: &str = "openssl";
+const BORINGSSL: &str = "boringssl";
+const GNUTLS: &str = "gnutls";
+const NSS: &str = "nss";
+const GOTLS: &str = "gotls";
+
+/// Lists the processes under `proc_root`, sorted by pid. Processes that exit
+/// while being read are skipped.
+pub fn list_processes(proc_root: impl AsRef<Path>) -> Result<Vec<ProcessInfo>> {
+    let proc_root = proc_root.as_ref();
+    let mut go_binaries = HashMap::new();
+    let mut processes: Vec<ProcessInfo> = fs::read_dir(proc_root)?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| read_process(proc_root, pid, &mut go_binaries))
+        .collect();
+    processes.sort_by_key(|p| p.pid);
+    Ok(processes)
+}
+
+/// Reads a single process.
+pub fn find_process(proc_root: impl AsRef<Path>, pid: u32) -> Result<ProcessInfo> {
+    read_process(proc_root.as_ref(), pid, &mut HashMap::new())
+        .ok_or_else(|| anyhow!("process {} not found", pid))
+}
+
+/// `go_binaries` caches whether an executable is a Go binary, as many
+/// processes share one.
+fn read_process(
+    proc_root: &Path,
+    pid: u32,
+    go_binaries: &mut HashMap<PathBuf, bool>,
+) -> Option<ProcessInfo> {
+    let dir = proc_root.join(pid.to_string());
+    let status = fs::read_to_string(dir.join("status")).ok()?;
+    let uid = status_field(&status, "Uid:")?;
+    let name = match fs::read_to_string(dir.join("comm")) {
+        Ok(comm) => comm.trim_end().to_string(),
+        Err(_) => status_field(&status, "Name:")?,
+    };
+    // Kernel threads have an empty cmdline
+    let cmdline = fs::read(dir.join("cmdline"))
+        .map(|raw| {
+            raw.split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+    let exe = read_exe(&dir);
+
+    let mut tls_libraries = Vec::new();
+    let mut libssl = None;
+    if let Ok(maps) = fs::read_to_string(dir.join("maps")) {
+        for path in maps
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(5))
+        {
+            let Some(library) = tls_library(path) else {
+                continue;
+            };
+            if !tls_libraries.contains(&library) {
+                tls_libraries.push(library);
+            }
+            if matches!(library, OPENSSL | BORINGSSL) && libssl.is_none() {
+                libssl = Some(path.to_string());
+            }
+        }
+    }
+    if let Some(exe) = &exe {
+        // Read through /proc so that a replaced or deleted binary is still found
+        let is_go = *go_binaries
+            .entry(exe.clone())
+            .or_insert_with(|| is_go_binary(&dir.join("exe")));
+        if is_go {
+            tls_libraries.push(GOTLS);
+        }
+    }
+
+    Some(ProcessInfo {
+        pid,
+        uid: uid.parse().ok()?,
+        name,
+        cmdline,
+        exe: exe.map(|p| p.to_string_lossy().to_string()),
+        tls_libraries: tls_libraries.into_iter().map(String::from).collect(),
+        libssl,
+    })
+}
+
+/// Target of the `exe` link, without the suffix of a deleted binary.
+fn read_exe(dir: &Path) -> Option<PathBuf> {
+    let exe = fs::read_link(dir.join("exe")).ok()?;
+    Some(strip_deleted(exe))
+}
+
+fn strip_deleted(exe: PathBuf) -> PathBuf {
+    match exe.to_str().and_then(|e| e.strip_suffix(DELETED_SUFFIX)) {
+        Some(path) => PathBuf::from(path),
+        None => exe,
+    }
+}
+
+/// A process running one of our binaries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundProcess {
+    pub pid: u32,
+    /// Process group.
+    pub pgid: u32,
+    pub exe: PathBuf,
+}
+
+/// Finds the processes under `proc_root` whose executable `matches`. The
+/// executable is read from the `exe` link, or from the first argument of
+/// `cmdline` when the link cannot be read; processes whose arguments merely
+/// mention the binary do not match.
+pub fn find_by_exe(
+    proc_root: impl AsRef<Path>,
+    matches: impl Fn(&Path) -> bool,
+) -> Result<Vec<FoundProcess>> {
+    let proc_root = proc_root.as_ref();
+    let mut found: Vec<FoundProcess> = fs::read_dir(proc_root)?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let dir = proc_root.join(pid.to_string());
+            let exe = read_exe(&dir).or_else(|| {
+                let cmdline = fs::read(dir.join("cmdline")).ok()?;
+                let argv0 = cmdline.split(|b| *b == 0).next()?;
+                (!argv0.is_empty()).then(|| PathBuf::from(String::from_utf8_lossy(argv0).as_ref()))
+            })?;
+            if !matches(&exe) {
+                return None;
+            }
+            let stat = parse_stat(&fs::read_to_string(dir.join("stat")).ok()?)?;
+            Some(FoundProcess {
+                pid,
+                pgid: stat.pgid,
+                exe,
+            })
+        })
+        .collect();
+    found.sort_by_key(|p| p.pid);
+    Ok(found)
+}
+
+/// Shell script printing the `exe` link and `stat` line of every process
+/// whose executable is in `dir`, for [`parse_exe_listing`]. Used through
+/// `su` where `/proc` of other users is hidden from the app.
+pub fn exe_listing_script(dir: &Path) -> String {
+    let dir = dir.to_string_lossy().replace('\'', "'\\''");
+    format!(
+        "cd /proc && for p in [0-9]*; do \
+         e=$(readlink \"$p/exe\" 2>/dev/null) || continue; \
+         case \"$e\" in '{}'/*) \
+         read -r s < \"$p/stat\" && printf '%s\\n%s\\n' \"$e\" \"$s\";; \
+         esac; \
+         done",
+        dir
+    )
+}
+
+/// Parses the output of [`exe_listing_script`], keeping the processes whose
+/// executable `matches`.
+pub fn parse_exe_listing(output: &str, matches: impl Fn(&Path) -> bool) -> Vec<FoundProcess> {
+    let mut lines = output.lines();
+    let mut found = Vec::new();
+    while let (Some(exe), Some(stat)) = (lines.next(), lines.next()) {
+        let exe = strip_deleted(PathBuf::from(exe));
+        if let Some(stat) = parse_stat(stat)
+            && matches(&exe)
+        {
+            found.push(FoundProcess {
+                pid: stat.pid,
+                pgid: stat.pgid,
+                exe,
+            });
+        }
+    }
+    found.sort_by_key(|p| p.pid);
+    found
+}
+
+/// The fields of `/proc/<pid>/stat` we use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stat {
+    pub pid: u32,
+    /// `R`, `S`, ... or `Z` for an exited process not reaped yet.
+    pub state: char,
+    pub ppid: u32,
+    /// Process group.
+    pub pgid: u32,
+    /// Clock ticks after boot; tells a reused pid from the process that had
+    /// it before.
+    pub start_time: u64,
+}
+
+/// Parses a `/proc/<pid>/stat` line. The command name in parentheses may
+/// itself contain spaces and parentheses.
+pub fn parse_stat(stat: &str) -> Option<Stat> {
+    let (pid, rest) = stat.split_once(' ')?;
+    // pid (comm) state ppid pgrp session tty_nr tpgid flags minflt cminflt
+    // majflt cmajflt utime stime cutime cstime priority nice num_threads
+    // itrealvalue starttime ...
+    let fields: Vec<&str> = rest[rest.rfind(')')? + 1..].split_whitespace().collect();
+    Some(Stat {
+        pid: pid.parse().ok()?,
+        state: fields.first()?.chars().next()?,
+        ppid: fields.get(1)?.parse().ok()?,
+        pgid: fields.get(2)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+/// Reads the stat of a running process, `None` once it has exited, even if
+/// it has not been reaped yet.
+pub fn read_stat(proc_root: impl AsRef<Path>, pid: u32) -> Option<Stat> {
+    let stat = fs::read_to_string(proc_root.as_ref().join(pid.to_string()).join("stat")).ok()?;
+    parse_stat(&stat).filter(|stat| stat.state != 'Z')
+}
+
+/// What to signal to reach every found process: the whole group, as a
+/// negative pid, for groups led by one of them, and single pids otherwise,
+/// so that unrelated members of a foreign group are left alone.
+pub fn signal_targets(found: &[FoundProcess]) -> Vec<i32> {
+    let mut targets: Vec<i32> = Vec::new();
+    for process in found {
+        // Never a negative 1, which would signal every process
+        let leads_group = process.pgid > 1 && found.iter().any(|p| p.pid == process.pgid);
+        let target = if leads_group {
+            -(process.pgid as i32)
+        } else {
+            process.pid as i32
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// First value of a `Key:\tvalue...` line of `/proc/<pid>/status`; for `Uid:`
+/// this is the real uid.
+fn status_field(status: &str, key: &str) -> Option<String> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .and_then(|value| value.split_whitespace().next())
+        .map(str::to_string)
+}
+
+/// TLS implementation provided by a mapped file, by its name.
+fn tls_library(path: &str) -> Option<&'static str> {
+    let file = path.rsplit('/').next()?;
+    // NSS ships its own libssl3.so
+    if file.starts_with("libssl3.so") || file.starts_with("libnss3.so") {
+        Some(NSS)
+    } else if file.starts_with("libssl.so") {
+        if cfg!(target_os = "android") {
+            Some(BORINGSSL)
+        } else {
+            Some(OPENSSL)
+        }
+    } else if file.starts_with("libgnutls.so") {
+        Some(GNUTLS)
+    } else {
+        None
+    }
+}
+
+/// Section header tables larger than this are not read; real binaries have
+/// a few dozen 64-byte entries.
+const MAX_SECTION_HEADERS: u64 = 1 << 20;
+
+/// Whether `path` is a 64-bit little-endian ELF file with a `.go.buildinfo`
+/// section, which the Go linker adds to every binary.
+fn is_go_binary(path: &Path) -> bool {
+    read_section_names(path).is_ok_and(|names| names.iter().any(|n| n == ".go.buildinfo"))
+}
+
+fn read_section_names(path: &Path) -> Result<Vec<String>> {
+    let mut file = fs::File::open(path)?;
+    let mut header = [0u8; 64];
+    file.read_exact(&mut header)?;
+    // ELFCLASS64, ELFDATA2LSB
+    if &header[..4] != b"\x7fELF" || header[4] != 2 || header[5] != 1 {
+        return Err(anyhow!("not a 64-bit little-endian ELF file"));
+    }
+    let section_offset = read_le(&header, 0x28, 8);
+    let entry_size = read_le(&header, 0x3a, 2);
+    let count = read_le(&header, 0x3c, 2);
+    let names_index = read_le(&header, 0x3e, 2);
+    if entry_size < 64 || count == 0 || names_index >= count {
+        return Err(anyhow!("no section headers"));
+    }
+    // Read from the exe of any process, so sizes are checked against the
+    // file before anything is allocated for them
+    let file_len = file.metadata()?.len();
+    let sections_size = entry_size
+        .checked_mul(count)
+        .filter(|size| *size <= MAX_SECTION_HEADERS)
+        .filter(|size| {
+            section_offset
+                .checked_add(*size)
+                .is_some_and(|end| end <= file_len)
+        })
+        .ok_or_else(|| anyhow!("section headers out of bounds"))?;
+    let mut sections = vec![0u8; sections_size as usize];
+    file.seek(SeekFrom::Start(section_offset))?;
+    file.read_exact(&mut sections)?;
+
+    let names_header = &sections[(names_index * entry_size) as usize..];
+    let names_offset = read_le(names_header, 0x18, 8);
+    let names_size = read_le(names_header, 0x20, 8)
+        .min(1 << 20)
+        .min(file_len.saturating_sub(names_offset));
+    let mut names = vec![0u8; names_size as usize];
+    file.seek(SeekFrom::Start(names_offset))?;
+    file.read_exact(&mut names)?;
+
+    Ok(sections
+        .chunks_exact(entry_size as usize)
+        .filter_map(|section| {
+            let name = names.get(read_le(section, 0, 4) as usize..)?;
+            let end = name.iter().position(|b| *b == 0)?;
+            Some(String::from_utf8_lossy(&name[..end]).to_string())
+        })
+        .collect())
+}
+
+/// Little-endian unsigned integer of `len` bytes at `at`.
+fn read_le(bytes: &[u8], at: usize, len: usize) -> u64 {
+    bytes[at..at + len]
+        .iter()
+        .rev()
+        .fold(0, |n, b| (n << 8) | *b as u64)
+}
+
+/// ecapture options that capture the TLS traffic of `process`, picking the
+/// module for the TLS library it uses.
+pub fn capture_options(process: &ProcessInfo, scope: CaptureScope) -> Result<EcaptureOptions> {
+    let uses = |library: &str| process.tls_libraries.iter().any(|l| l == library);
+    let mut options = EcaptureOptions::default();
+    if uses(GOTLS) {
+        options.subcommand = Subcommand::Gotls;
+        options.elfpath = process.exe.clone();
+    } else if uses(OPENSSL) || uses(BORINGSSL) {
+        options.subcommand = Subcommand::Tls;
+        options.libssl = process.libssl.clone();
+    } else if uses(GNUTLS) {
+        options.subcommand = Subcommand::Gnutls;
+    } else if uses(NSS) {
+        options.subcommand = Subcommand::Nss;
+    } else {
+        return Err(anyhow!(
+            "{} ({}) does not use a TLS library eCapture can hook",
+            process.name,
+            process.pid
+        ));
+    }
+    match scope {
+        CaptureScope::Process => options.pid = Some(process.pid),
+        CaptureScope::User => options.uid = Some(process.uid),
+    }
+    options.validate()?;
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fake `/proc` tree, removed on drop.
+    struct FakeProc {
+        root: PathBuf,
+    }
+
+    impl FakeProc {
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let root = std::env::temp_dir().join(format!(
+                "ecaptureq-proc-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(&root).unwrap();
+            Self { root }
+        }
+
+        /// Adds a process; `exe` of `None` leaves the link out, as for a
+        /// process of another user.
+        fn add(&self, pid: u32, pgid: u32, comm: &str, exe: Option<&str>, argv: &[&str]) {
+            let dir = self.root.join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("status"),
+                format!("Name:\t{comm}\nUid:\t1000\t1000\t1000\t1000\n"),
+            )
+            .unwrap();
+            fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+            fs::write(dir.join("stat"), stat_line(pid, comm, 1, pgid)).unwrap();
+            let mut cmdline = argv.join("\0");
+            if !argv.is_empty() {
+                cmdline.push('\0');
+            }
+            fs::write(dir.join("cmdline"), cmdline).unwrap();
+            if let Some(exe) = exe {
+                symlink(exe, dir.join("exe")).unwrap();
+            }
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn stat_line(pid: u32, comm: &str, ppid: u32, pgid: u32) -> String {
+        format!(
+            "{pid} ({comm}) S {ppid} {pgid} {pgid} 0 -1 4194560 0 0 0 0 3 1 0 0 20 0 1 0 \
+             {} 2560000 345 18446744073709551615",
+            1000 + pid
+        )
+    }
+
+    const OURS: &str = "/data/app/files/linux_ecapture_amd64_abc";
+
+    fn is_ours(exe: &Path) -> bool {
+        exe == Path::new(OURS)
+    }
+
+    #[test]
+    fn finds_processes_by_exact_exe() {
+        let proc = FakeProc::new();
+        proc.add(100, 100, "ecapture", Some(OURS), &[OURS, "tls"]);
+        // Mentions the binary but runs something else
+        proc.add(
+            101,
+            101,
+            "tail",
+            Some("/usr/bin/tail"),
+            &["tail", "-f", OURS],
+        );
+        proc.add(102, 102, "bash", Some("/bin/bash"), &["bash"]);
+
+        let found = find_by_exe(&proc.root, is_ours).unwrap();
+        assert_eq!(
+            found,
+            vec![FoundProcess {
+                pid: 100,
+                pgid: 100,
+                exe: PathBuf::from(OURS),
+            }]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_argv0() {
+        let proc = FakeProc::new();
+        proc.add(200, 150, "ecapture", None, &[OURS, "tls"]);
+        proc.add(201, 201, "grep", None, &["grep", OURS]);
+        // Kernel thread: no exe, empty cmdline
+        proc.add(2, 0, "kthreadd", None, &[]);
+
+        let found = find_by_exe(&proc.root, is_ours).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].pid, found[0].pgid), (200, 150));
+    }
+
+    #[test]
+    fn matches_deleted_binary() {
+        let proc = FakeProc::new();
+        proc.add(
+            300,
+            300,
+            "ecapture",
+            Some(&format!("{OURS}{DELETED_SUFFIX}")),
+            &[OURS],
+        );
+
+        let found = find_by_exe(&proc.root, is_ours).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].exe, PathBuf::from(OURS));
+    }
+
+    #[test]
+    fn parses_stat_with_odd_comm() {
+        let expected = Stat {
+            pid: 42,
+            state: 'S',
+            ppid: 1,
+            pgid: 7,
+            start_time: 1042,
+        };
+        assert_eq!(parse_stat(&stat_line(42, "a) b (c", 1, 7)), Some(expected));
+        assert_eq!(parse_stat(&stat_line(42, "my proc", 1, 7)), Some(expected));
+        assert_eq!(parse_stat("42 (short) S 1 7 7 0"), None);
+        assert_eq!(parse_stat("42 (truncated"), None);
+        assert_eq!(parse_stat(""), None);
+    }
+
+    #[test]
+    fn signals_led_groups_and_single_pids() {
+        let found = [
+            // Leads its group, with a child in it
+            FoundProcess {
+                pid: 10,
+                pgid: 10,
+                exe: PathBuf::from(OURS),
+            },
+            FoundProcess {
+                pid: 11,
+                pgid: 10,
+                exe: PathBuf::from(OURS),
+            },
+            // In the group of a shell that is not ours
+            FoundProcess {
+                pid: 20,
+                pgid: 19,
+                exe: PathBuf::from(OURS),
+            },
+            // Never signal -1
+            FoundProcess {
+                pid: 1,
+                pgid: 1,
+                exe: PathBuf::from(OURS),
+            },
+        ];
+        assert_eq!(signal_targets(&found), vec![-10, 20, 1]);
+    }
+
+    #[test]
+    fn parses_exe_listing() {
+        let output = format!(
+            "{OURS}\n{}\n/system/bin/sh\n{}\n{OURS}{DELETED_SUFFIX}\n{}\n",
+            stat_line(500, "ecapture", 1, 500),
+            stat_line(501, "sh", 1, 501),
+            stat_line(502, "ecapture", 500, 500),
+        );
+        let found = parse_exe_listing(&output, is_ours);
+        let pids: Vec<_> = found.iter().map(|p| (p.pid, p.pgid)).collect();
+        assert_eq!(pids, vec![(500, 500), (502, 500)]);
+        assert_eq!(signal_targets(&found), vec![-500]);
+    }
+
+    /// An ELF header claiming `count` section headers of `entry_size` bytes
+    /// right after it.
+    fn elf_header(entry_size: u16, count: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 64];
+        header[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        header[0x28..0x30].copy_from_slice(&64u64.to_le_bytes());
+        header[0x3a..0x3c].copy_from_slice(&entry_size.to_le_bytes());
+        header[0x3c..0x3e].copy_from_slice(&count.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn section_headers_past_the_file_are_refused() {
+        let proc = FakeProc::new();
+        let path = proc.root.join("exe");
+        fs::write(&path, elf_header(u16::MAX, u16::MAX)).unwrap();
+        assert!(read_section_names(&path).is_err());
+        // A table claimed larger than the file is never read
+        let mut truncated = elf_header(64, 3);
+        truncated.extend([0u8; 64]);
+        fs::write(&path, truncated).unwrap();
+        assert!(read_section_names(&path).is_err());
+        assert!(!is_go_binary(&path));
+    }
+
+    #[test]
+    fn lists_processes() {
+        let proc = FakeProc::new();
+        proc.add(
+            7,
+            7,
+            "curl",
+            Some("/usr/bin/curl"),
+            &["curl", "https://example.com"],
+        );
+        fs::write(
+            proc.root.join("7/maps"),
+            "7f00-7f10 r-xp 00000000 08:01 1 /usr/lib/libssl.so.3\n",
+        )
+        .unwrap();
+        proc.add(3, 3, "init", None, &["/sbin/init"]);
+        fs::create_dir_all(proc.root.join("self")).unwrap();
+
+        let processes = list_processes(&proc.root).unwrap();
+        let pids: Vec<_> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![3, 7]);
+        let curl = &processes[1];
+        assert_eq!(curl.uid, 1000);
+        assert_eq!(curl.cmdline, "curl https://example.com");
+        assert_eq!(curl.exe.as_deref(), Some("/usr/bin/curl"));
+        assert_eq!(curl.libssl.as_deref(), Some("/usr/lib/libssl.so.3"));
+        assert!(find_process(&proc.root, 99).is_err());
+    }
+}
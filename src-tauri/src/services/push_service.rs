@@ -4,14 +4,16 @@ use log::{error, info};
 use std::{sync::Arc, time::Duration};
 use tauri::{AppHandle, Emitter};
 use tokio::sync::{Mutex, watch};
+use tokio::task::JoinHandle;
 
 pub struct PushService {
     df_actor_handle: DataFrameActorHandle,
     tauri_interface: String,
     done: watch::Receiver<()>,
     user_sql: Option<String>,
-    last_index: u64,
     app_handle: AppHandle,
+    // index of the last packet pushed; locked while pushing, so that
+    // clear_packets can rewind it between two pushes
     shared_last_index: Arc<Mutex<u64>>,
 }
 
@@ -20,11 +22,10 @@ impl PushService {
         handle: DataFrameActorHandle,
         interface: String,
         user_sql: Option<String>,
-        last_index: u64,
         shared_last_index: Arc<Mutex<u64>>,
         done: watch::Receiver<()>,
         app_handle: AppHandle,
-    ) -> Result<()> {
+    ) -> Result<JoinHandle<()>> {
         // 创建 Worker 实例
         let mut worker = Self {
             df_actor_handle: handle,
@@ -32,14 +33,12 @@ impl PushService {
             done,
             app_handle,
             user_sql,
-            last_index,
             shared_last_index,
         };
 
-        tokio::spawn(async move {
+        Ok(tokio::spawn(async move {
             worker.run().await;
-        });
-        Ok(())
+        }))
     }
 
     pub async fn run(&mut self) {
@@ -56,13 +55,14 @@ impl PushService {
                 }
 
                 _ = flush_timer.tick() => {
+                    let mut last_index = self.shared_last_index.lock().await;
 
                     let new_df_result = match &self.user_sql {
                         Some(sql_str) => {
-                            self.df_actor_handle.get_packets_customized_no_payload(&self.last_index, sql_str).await
+                            self.df_actor_handle.get_packets_customized_no_payload(&last_index, sql_str).await
                         }
                         None => {
-                            self.df_actor_handle.get_packets_since_index_no_payload(&self.last_index).await
+                            self.df_actor_handle.get_packets_since_index_no_payload(&last_index).await
                         }
                     };
 
@@ -72,9 +72,7 @@ impl PushService {
                                 if !vecs.is_empty() {
                                     // Update last_index to the highest index from the fetched data
                                     if let Some(last_packet) = vecs.last() {
-                                        self.last_index = last_packet.index;
-                                        let mut guard = self.shared_last_index.lock().await;
-                                        *guard = self.last_index;
+                                        *last_index = last_packet.index;
                                         info!("Fetched {} new packets. New last_index: {}", vecs.len(), last_packet.index);
                                     }
 
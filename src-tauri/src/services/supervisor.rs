@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+use crate::core::models::CaptureRestartEvent;
+use crate::services::capture::CaptureManager;
+use crate::services::events::EventSink;
+
+const RESTART_EVENT: &str = "ecapture-restart";
+
+/// When and how often a crashed ecapture is restarted.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Delay before the first restart; doubled for each further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Restarts allowed within `window` before giving up.
+    pub max_restarts: usize,
+    pub window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: 5,
+            window: Duration::from_secs(300),
+        }
+    }
+}
+
+struct RestartTracker {
+    policy: RestartPolicy,
+    /// When the restarts within the window happened.
+    restarts: VecDeque<Instant>,
+    backoff: Duration,
+}
+
+impl RestartTracker {
+    fn new(policy: RestartPolicy) -> Self {
+        let backoff = policy.initial_backoff;
+        Self {
+            policy,
+            restarts: VecDeque::new(),
+            backoff,
+        }
+    }
+
+    /// Delay before the next restart, or `None` to give up. A process that
+    /// stayed up for a whole window starts over from the initial backoff.
+    fn next_delay(&mut self, now: Instant, uptime: Duration) -> Option<Duration> {
+        if uptime >= self.policy.window {
+            self.backoff = self.policy.initial_backoff;
+        }
+        while self
+            .restarts
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > self.policy.window)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.policy.max_restarts {
+            return None;
+        }
+        self.restarts.push_back(now);
+        let delay = self.backoff;
+        self.backoff = (self.backoff * 2).min(self.policy.max_backoff);
+        Some(delay)
+    }
+}
+
+/// Runs ecapture until shutdown, restarting it with the same arguments when
+/// it exits unexpectedly. The captured data and push indexes are not touched,
+/// so the session goes on where it left off, with a gap for the downtime.
+///
+/// `ready` turns true once ecapture has accepted a connection. An exit
+/// before that is a failed launch, which is returned instead of restarted,
+/// as is the last error once the policy gives up.
+pub async fn supervise(
+    manager: &mut CaptureManager,
+    mut shutdown_rx: watch::Receiver<()>,
+    args: Vec<String>,
+    policy: RestartPolicy,
+    ready: &watch::Receiver<bool>,
+    events: &EventSink,
+) -> Result<()> {
+    let mut tracker = RestartTracker::new(policy);
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let Err(e) = manager.run(shutdown_rx.clone(), args.clone()).await else {
+            return Ok(());
+        };
+        let uptime = started.elapsed();
+        if attempt == 0 && !*ready.borrow() {
+            return Err(e);
+        }
+
+        attempt += 1;
+        let delay = tracker.next_delay(Instant::now(), uptime);
+        let event = CaptureRestartEvent {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64),
+            attempt,
+            reason: e.to_string(),
+            delay_ms: delay.map_or(0, |d| d.as_millis() as u64),
+            gave_up: delay.is_none(),
+        };
+        if let Err(emit_error) = events.emit(RESTART_EVENT, &event) {
+            error!("Failed to emit restart event: {}", emit_error);
+        }
+
+        let Some(delay) = delay else {
+            error!(
+                "eCapture crashed too often, giving up after {} restarts",
+                attempt - 1
+            );
+            return Err(e.context("eCapture crashed too often, restarts stopped"));
+        };
+        warn!(
+            "eCapture exited unexpectedly, restart #{} in {:?}: {}",
+            attempt, delay, e
+        );
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                info!("Shutdown requested while waiting to restart eCapture");
+                return Ok(());
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+    }
+}
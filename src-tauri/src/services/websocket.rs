@@ -1,31 +1,61 @@
 use anyhow::Result;
+#[cfg(all(not(decoupled), target_os = "linux"))]
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use futures_util::stream::StreamExt;
-use log::{error, info};
+use futures_util::stream::{BoxStream, StreamExt};
+use log::{error, info, warn};
+use tauri::{AppHandle, Emitter};
 use tokio::sync::{RwLock, watch};
 use tokio::time::Duration;
 use tokio_tungstenite::{
     connect_async_with_config,
-    tungstenite::{client::IntoClientRequest, http::HeaderValue, protocol::WebSocketConfig},
+    tungstenite::{
+        self, Message, client::IntoClientRequest, handshake::client::Request, http::HeaderValue,
+        protocol::WebSocketConfig,
+    },
 };
 
 use crate::core::{
     actor,
+    android_packages::PackageIndex,
     data_processing::parse_eq_message,
-    models::{HeartbeatMessage, PacketData, ParsedMessage, ProcessLogMessage},
+    ecapture_args::Subcommand,
+    models::{HeartbeatMessage, PacketData, ParsedMessage, ProcessLogMessage, ProtocolStatus},
+    protocol,
 };
+#[cfg(all(not(decoupled), target_os = "linux"))]
+use crate::services::helper;
 use crate::tauri_bridge::state::RunState;
 
+type MessageStream = BoxStream<'static, Result<Message, tungstenite::Error>>;
+
 pub struct WebsocketService {
     ws_url: String,
     df_handle: actor::DataFrameActorHandle,
     done: watch::Receiver<()>,
+    // set once ecapture has accepted a connection
+    ready: watch::Sender<bool>,
     run_status: Arc<RwLock<RunState>>,
+    protocol_status: Arc<RwLock<ProtocolStatus>>,
+    packages: Arc<RwLock<PackageIndex>>,
+    // uid ecapture was told to capture, if any
+    uid_filter: Option<u32>,
+    // capture session the events are recorded in
+    session_id: u64,
+    // ecapture module of that session, if known
+    module: Option<Subcommand>,
+    // socket of the privileged helper relaying ecapture's WebSocket
+    #[cfg(all(not(decoupled), target_os = "linux"))]
+    relay: Option<PathBuf>,
+    app_handle: AppHandle,
 }
 
 const BATCH_SIZE: usize = 20;
 const FLUSH_TIMEOUT: Duration = Duration::from_millis(300);
+// Time given to ecapture to identify itself after connecting
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const PROTOCOL_WARNING_EVENT: &str = "protocol-warning";
 
 impl WebsocketService {
     pub fn new(
@@ -33,15 +63,128 @@ impl WebsocketService {
         df_handle: actor::DataFrameActorHandle,
         done: watch::Receiver<()>,
         run_status: Arc<RwLock<RunState>>,
+        protocol_status: Arc<RwLock<ProtocolStatus>>,
+        packages: Arc<RwLock<PackageIndex>>,
+        app_handle: AppHandle,
     ) -> Result<Self> {
         Ok(Self {
             ws_url,
             df_handle,
             done,
+            ready: watch::channel(false).0,
             run_status,
+            protocol_status,
+            packages,
+            uid_filter: None,
+            session_id: 0,
+            module: None,
+            #[cfg(all(not(decoupled), target_os = "linux"))]
+            relay: None,
+            app_handle,
         })
     }
 
+    /// Sets the uid ecapture only captures, assumed for events that do not
+    /// report one.
+    pub fn set_uid_filter(&mut self, uid: Option<u32>) {
+        self.uid_filter = uid;
+    }
+
+    /// Sets the capture session received events are recorded in.
+    pub fn set_session_id(&mut self, session_id: u64) {
+        self.session_id = session_id;
+    }
+
+    /// Sets the ecapture module of the capture session.
+    pub fn set_module(&mut self, module: Option<Subcommand>) {
+        self.module = module;
+    }
+
+    /// Sets the flag raised once ecapture has accepted a connection.
+    pub fn set_ready(&mut self, ready: watch::Sender<bool>) {
+        self.ready = ready;
+    }
+
+    /// Connects through the privileged helper at `socket` instead of
+    /// directly, for an ecapture run by it.
+    #[cfg(all(not(decoupled), target_os = "linux"))]
+    pub fn set_relay(&mut self, socket: Option<PathBuf>) {
+        self.relay = socket;
+    }
+
+    /// Connects to ecapture, directly or through the helper's relay. Only
+    /// the receiving half is kept.
+    async fn connect(&self, request: Request, config: WebSocketConfig) -> Result<MessageStream> {
+        #[cfg(all(not(decoupled), target_os = "linux"))]
+        if let Some(socket) = &self.relay {
+            let stream = helper::connect_relay(socket).await?;
+            let (ws_stream, _) =
+                tokio_tungstenite::client_async_with_config(request, stream, Some(config)).await?;
+            return Ok(ws_stream.split().1.boxed());
+        }
+        let (ws_stream, _) = connect_async_with_config(request, Some(config), false).await?;
+        Ok(ws_stream.split().1.boxed())
+    }
+
+    /// Fills in the capture session and module, the uid if ecapture did not
+    /// report it and the Android package of the uid.
+    async fn annotate(&self, packet: &mut PacketData) {
+        packet.session_id = self.session_id;
+        packet.module = self.module;
+        if packet.uid.is_none() {
+            packet.uid = self.uid_filter;
+        }
+        if let Some(uid) = packet.uid
+            && let Some(package) = self.packages.read().await.name(uid)
+        {
+            packet.package = package.to_string();
+        }
+    }
+
+    /// Records the ecapture version and warns if the compatibility table
+    /// does not list it as supported.
+    async fn check_version(&self, ecapture_version: Option<String>, protocol_version: Option<u32>) {
+        let (compatibility, warning) =
+            protocol::check(ecapture_version.as_deref(), protocol_version);
+        let mut status = self.protocol_status.write().await;
+        info!(
+            "ecapture version: {}, protocol version: {}",
+            ecapture_version.as_deref().unwrap_or("unknown"),
+            protocol_version.unwrap_or(protocol::LEGACY_PROTOCOL_VERSION)
+        );
+        status.ecapture_version = ecapture_version;
+        status.protocol_version = protocol_version;
+        status.compatibility = compatibility.as_str().to_string();
+        if let Some(warning) = warning {
+            self.warn(&mut status, warning);
+        }
+    }
+
+    async fn record_unsupported(&self, log_type: i32) {
+        let mut status = self.protocol_status.write().await;
+        status.unsupported_messages += 1;
+        // Warn once per log type; later entries are only counted
+        if !status.unsupported_log_types.contains(&log_type) {
+            status.unsupported_log_types.push(log_type);
+            self.warn(
+                &mut status,
+                format!(
+                    "ecapture sent log entries of type {} that this version cannot decode; \
+                     they are skipped",
+                    log_type
+                ),
+            );
+        }
+    }
+
+    fn warn(&self, status: &mut ProtocolStatus, warning: String) {
+        warn!("{}", warning);
+        status.warnings.push(warning);
+        if let Err(e) = self.app_handle.emit(PROTOCOL_WARNING_EVENT, &*status) {
+            error!("Failed to emit protocol warning: {}", e);
+        }
+    }
+
     pub async fn receiver_task(&mut self) -> Result<()> {
         'retry_loop: loop {
             log::info!("Attempting WebSocket connection to: {}", self.ws_url);
@@ -54,11 +197,11 @@ impl WebsocketService {
 
             let config = WebSocketConfig::default();
 
-            let ws_stream = {
-                match connect_async_with_config(request, Some(config), false).await {
-                    Ok((ws_stream, _)) => ws_stream,
+            let mut read = {
+                match self.connect(request, config).await {
+                    Ok(read) => read,
                     Err(e) => {
-                        error!("{:?}", e);
+                        error!("{:#}", e);
                         tokio::select! {
                             _ = self.done.changed() => {
                                 break;
@@ -73,6 +216,14 @@ impl WebsocketService {
             };
 
             info!("WebSocket connected");
+            self.ready.send_replace(true);
+            *self.protocol_status.write().await = ProtocolStatus {
+                connected: true,
+                ..protocol::initial_status()
+            };
+            let handshake_timeout = tokio::time::sleep(HANDSHAKE_TIMEOUT);
+            tokio::pin!(handshake_timeout);
+            let mut version_checked = false;
 
             let mut buffer: Vec<PacketData> = Vec::with_capacity(BATCH_SIZE);
             let mut flush_timer = tokio::time::interval(FLUSH_TIMEOUT);
@@ -81,28 +232,46 @@ impl WebsocketService {
             let mut heartbeat_messages: Vec<HeartbeatMessage> = Vec::new();
             let mut log_messages: Vec<ProcessLogMessage> = Vec::new();
 
-            let (_, mut read) = ws_stream.split();
-
             loop {
                 tokio::select! {
                     biased;
 
                     _ = self.done.changed() => {
                         info!("Websocket service shutting down");
+                        if !buffer.is_empty() {
+                            self.df_handle.update_batch(std::mem::take(&mut buffer)).await;
+                        }
                         break 'retry_loop;
                     }
 
+                    _ = &mut handshake_timeout, if !version_checked => {
+                        version_checked = true;
+                        self.check_version(None, None).await;
+                    }
+
                     _ = flush_timer.tick() => {
                     if !buffer.is_empty() {
                         self.df_handle.update_batch(std::mem::take(&mut buffer)).await;
                     }
                 }
 
-                    Some(message) = read.next() => {
+                    message = read.next() => {
+                        // Packets buffered so far are kept whatever ends the
+                        // connection
+                        let Some(message) = message else {
+                            info!("WebSocket closed, reconnecting");
+                            if !buffer.is_empty() {
+                                self.df_handle.update_batch(std::mem::take(&mut buffer)).await;
+                            }
+                            break;
+                        };
                         let msg = match message {
                             Ok(msg) => msg,
                             Err(e) => {
                                 error!("WebSocket message error: {:?}", e);
+                                if !buffer.is_empty() {
+                                    self.df_handle.update_batch(std::mem::take(&mut buffer)).await;
+                                }
                                 match &*self.run_status.read().await {
                                     RunState::Capturing => {
                                         info!("into capturing branch");
@@ -115,11 +284,12 @@ impl WebsocketService {
                             }
                         };
 
-                        if let tokio_tungstenite::tungstenite::Message::Binary(bin) = msg {
+                        if let Message::Binary(bin) = msg {
                             match parse_eq_message(bin.as_ref()) {
                                 Ok(parsed_message) => {
                                     match parsed_message {
-                                        ParsedMessage::Event(packet_data) => {
+                                        ParsedMessage::Event(mut packet_data) => {
+                                            self.annotate(&mut packet_data).await;
                                             buffer.push(packet_data);
                                         }
                                         ParsedMessage::Heartbeat(heartbeat) => {
@@ -129,11 +299,31 @@ impl WebsocketService {
                                         ParsedMessage::ProcessLog(log) => {
                                             // println!("Log: level={:?}, message={:?}, time={:?}, info={}",
                                             //     log.level, log.message, log.time, log.log_info);
+                                            // Releases without a handshake log their version at startup
+                                            if !version_checked
+                                                && let Some(version) =
+                                                    protocol::version_from_log(&log.log_info)
+                                            {
+                                                version_checked = true;
+                                                self.check_version(Some(version), None).await;
+                                            }
                                             log_messages.push(log);
                                         }
+                                        ParsedMessage::Handshake(handshake) => {
+                                            version_checked = true;
+                                            self.check_version(
+                                                Some(handshake.ecapture_version),
+                                                Some(handshake.protocol_version),
+                                            )
+                                            .await;
+                                        }
+                                        ParsedMessage::Unsupported { log_type } => {
+                                            self.record_unsupported(log_type).await;
+                                        }
                                     }
                                 }
                                 Err(e) => {
+                                    self.protocol_status.write().await.decode_errors += 1;
                                     log::error!(
                                         "Parse message failed: {:?} ({} bytes)",
                                         e,
@@ -152,8 +342,10 @@ impl WebsocketService {
                     }
                 }
             }
+            self.protocol_status.write().await.connected = false;
             tokio::time::sleep(std::time::Duration::from_millis(300)).await;
         }
+        self.protocol_status.write().await.connected = false;
         Ok(())
     }
 }
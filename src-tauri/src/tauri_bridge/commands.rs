@@ -1,22 +1,157 @@
 use anyhow::{Result, anyhow};
 use log::{error, info};
-use polars::{prelude::IntoLazy, sql::SQLContext};
+use polars::prelude::{DataFrame, DataType, PolarsResult};
+use polars::sql::SQLContext;
+use std::collections::{HashMap, HashSet};
+#[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::Manager;
-use tokio::time::{Duration, sleep};
+use tauri::{Emitter, Manager};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
 use wg::AsyncWaitGroup;
 
-use crate::core::{actor::create_capture_df, queries};
+#[cfg(target_os = "android")]
+use crate::core::android_packages::PackageIndex;
+use crate::core::annotations::AnnotationEdit;
+use crate::core::decoders::{self, DecodeInput};
+#[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+use crate::core::ecapture_args::Subcommand;
+use crate::core::ecapture_args::{CaptureScope, EcaptureOptions};
+use crate::core::models::{
+    AndroidPackage, BrokerMessage, DbQuery, DecodedPayload, EcaptureInfo, EcaptureOutputLine,
+    ExportSummary, FlowRecord, MailMessage, PacketAnnotation, PacketDataFrontend, PacketExport,
+    ProcessInfo, ProtocolStatus, SessionInfo, WsMessage,
+};
+use crate::core::redaction::{RedactionMode, RedactionReport, Redactor};
+use crate::core::{actor::register_empty_tables, content_decoding, queries, sessions};
+#[cfg(target_os = "android")]
+use crate::services::android_packages;
+#[cfg(all(not(decoupled), target_os = "linux"))]
+use crate::services::{capture, helper, pidfile::PidFile};
+#[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+use crate::services::{
+    capture::{BinarySource, CaptureManager},
+    events::EventSink,
+    processes,
+    supervisor::{self, RestartPolicy},
+};
+use crate::services::{
+    flow_push_service::FlowPushService, push_service::PushService, websocket::WebsocketService,
+};
+use crate::tauri_bridge::state::{AppState, Configs, PendingTasks, RunState};
+
+/// Emitted with the id of a capture session whose rows were dropped.
+const SESSION_CLEARED_EVENT: &str = "session-cleared";
+/// Emitted with the packets left after `clear_packets`, replacing every
+/// packet shown.
+const PACKETS_RESET_EVENT: &str = "packets-reset";
+
+/// A session run by the privileged helper: its socket, the options and the
+/// WebSocket URL of ecapture.
 #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
-use crate::services::capture::CaptureManager;
-use crate::services::{push_service::PushService, websocket::WebsocketService};
-use crate::tauri_bridge::state::{AppState, Configs, RunState};
+type RemoteSession = (PathBuf, EcaptureOptions, String);
 
 #[tauri::command]
 pub async fn start_capture(
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    launch_session(&state, app_handle, None).await
+}
+
+/// Starts a capture session for the TLS traffic of one process, or of every
+/// process of its user, regardless of the configured eCapture arguments.
+#[tauri::command]
+pub async fn capture_process(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    pid: u32,
+    scope: CaptureScope,
+) -> Result<(), String> {
+    #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+    {
+        let process =
+            processes::find_process(processes::PROC_ROOT, pid).map_err(|e| e.to_string())?;
+        let options = processes::capture_options(&process, scope).map_err(|e| e.to_string())?;
+        info!(
+            "Capturing {} ({}) with the {} module",
+            process.name,
+            process.pid,
+            options.subcommand.as_str()
+        );
+        launch_session(&state, app_handle, Some(options)).await
+    }
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    {
+        let _ = (state, app_handle, pid, scope);
+        Err("This build connects to an external eCapture and cannot target a process".to_string())
+    }
+}
+
+/// Starts a capture session for the TLS traffic of an Android app, by the
+/// uid of its package.
+#[tauri::command]
+pub async fn capture_package(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    package: String,
+) -> Result<(), String> {
+    #[cfg(target_os = "android")]
+    {
+        let packages = refresh_packages(&state).await.map_err(|e| e.to_string())?;
+        // Sorted by uid, so the first one is the primary user's
+        let uid = packages
+            .iter()
+            .find(|p| p.name == package)
+            .map(|p| p.uid)
+            .ok_or_else(|| format!("Package {} is not installed", package))?;
+        // Apps use the system BoringSSL, hooked by the tls module
+        let options = EcaptureOptions {
+            uid: Some(uid),
+            ..Default::default()
+        };
+        info!("Capturing package {} (uid {})", package, uid);
+        launch_session(&state, app_handle, Some(options)).await
+    }
+    #[cfg(not(target_os = "android"))]
+    {
+        let _ = (state, app_handle, package);
+        Err("Capturing a package is only supported on Android".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn list_packages(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<AndroidPackage>, String> {
+    #[cfg(target_os = "android")]
+    {
+        refresh_packages(&state).await.map_err(|e| e.to_string())
+    }
+    #[cfg(not(target_os = "android"))]
+    {
+        let _ = state;
+        Err("Installed packages can only be listed on Android".to_string())
+    }
+}
+
+/// Lists the installed packages and updates the index events are annotated
+/// from.
+#[cfg(target_os = "android")]
+async fn refresh_packages(state: &AppState) -> Result<Vec<AndroidPackage>> {
+    let packages = android_packages::list_installed_packages().await?;
+    *state.packages.write().await = PackageIndex::new(&packages);
+    Ok(packages)
+}
+
+/// Starts a capture session; `target` replaces the configured eCapture
+/// arguments.
+async fn launch_session(
+    state: &AppState,
+    app_handle: tauri::AppHandle,
+    target: Option<EcaptureOptions>,
 ) -> Result<(), String> {
     if let RunState::Capturing = &*state.status.read().await {
         return Err("Capture session is already running.".into());
@@ -24,8 +159,16 @@ pub async fn start_capture(
 
     *state.status.write().await = RunState::Capturing;
     let error_inspector = Arc::new(AtomicBool::new(false));
+    // why the launch failed, with the last lines printed by ecapture
+    let launch_error: Arc<std::sync::Mutex<Option<String>>> = Arc::default();
     let (shutdown_tx, _) = tokio::sync::watch::channel(());
+    // true once ecapture has accepted the WebSocket connection
+    let (ready_tx, mut ready_rx) = tokio::sync::watch::channel(false);
+    // every task of the session, waited for when it stops
+    let session_wg = AsyncWaitGroup::new();
 
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    let _ = target;
     #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
     let data_dir = app_handle
         .path()
@@ -35,19 +178,76 @@ pub async fn start_capture(
         let guard = state.configs.lock().await;
         guard.clone()
     };
+    let timeouts = configs.as_ref().unwrap().timeouts();
+    let ws_url = configs.as_ref().unwrap().ws_url.clone().unwrap();
 
     let mut websocket_service = WebsocketService::new(
-        configs.as_ref().unwrap().ws_url.clone().unwrap(),
+        ws_url.clone(),
         state.df_actor_handle.clone(),
         shutdown_tx.subscribe(),
         state.status.clone(),
+        state.protocol_status.clone(),
+        state.packages.clone(),
+        app_handle.clone(),
     )
     .map_err(|e| e.to_string())?;
+    websocket_service.set_ready(ready_tx);
 
-    #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+    #[cfg(target_os = "android")]
+    if state.packages.read().await.is_empty()
+        && let Err(e) = refresh_packages(state).await
     {
+        error!("Failed to list installed packages: {}", e);
+    }
+
+    // Ends if the bundled ecapture fails to launch
+    #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+    let capture_wg = {
+        let configs = configs.as_ref().unwrap();
+        let options = target.as_ref().or(configs.ecapture_options.as_ref());
+        websocket_service.set_uid_filter(options.and_then(|o| o.uid));
+        let argv = match &target {
+            Some(options) => configs.ecapture_argv_for(options),
+            None => configs.ecapture_argv(),
+        };
+        let ecapture_argv = match argv {
+            Ok(argv) => argv,
+            Err(e) => {
+                *state.status.write().await = RunState::NotCapturing;
+                return Err(format!("Invalid eCapture arguments: {}", e));
+            }
+        };
+        let binary = match configs.ecapture_binary() {
+            Ok(binary) => binary,
+            Err(e) => {
+                *state.status.write().await = RunState::NotCapturing;
+                return Err(format!("Invalid eCapture binary: {}", e));
+            }
+        };
+        #[cfg(target_os = "linux")]
+        let remote = match remote_session(state, configs, options, &binary).await {
+            Ok(remote) => remote,
+            Err(e) => {
+                *state.status.write().await = RunState::NotCapturing;
+                return Err(e);
+            }
+        };
+        #[cfg(target_os = "linux")]
+        websocket_service.set_relay(remote.as_ref().map(|(socket, ..)| socket.clone()));
+        #[cfg(target_os = "android")]
+        let remote: Option<RemoteSession> = None;
         let capture_error_inspector = error_inspector.clone();
-        let mut capture_manager = CaptureManager::new(data_dir);
+        let capture_launch_error = launch_error.clone();
+        let capture_app_handle = app_handle.clone();
+        state.ecapture_output.clear();
+        let mut capture_manager = CaptureManager::new(
+            data_dir,
+            binary,
+            state.ecapture_output.clone(),
+            app_handle.clone(),
+        );
+        capture_manager.set_timeouts(timeouts);
+        let capture_ready_rx = ready_rx.clone();
 
         let rx = shutdown_tx.subscribe();
         info!("Spawning background services...");
@@ -56,33 +256,77 @@ pub async fn start_capture(
         let capture_wg = AsyncWaitGroup::new();
         capture_wg.add(1);
         let capture_wg_clone = capture_wg.clone();
+        session_wg.add(1);
+        let capture_session_wg = session_wg.clone();
+        state.pending_tasks.started("ecapture");
+        let capture_pending_tasks = state.pending_tasks.clone();
+
+        let session_id = state
+            .sessions
+            .write()
+            .await
+            .begin(ecapture_argv.clone(), ws_url.clone());
+        websocket_service.set_session_id(session_id);
+        websocket_service.set_module(Subcommand::from_argv(&ecapture_argv));
 
         tokio::spawn(async move {
-            let result = capture_manager
-                .run(
-                    rx,
-                    configs
-                        .as_ref()
-                        .unwrap()
-                        .ecapture_args
-                        .clone()
-                        .unwrap_or_default(),
-                )
-                .await;
+            let result = match remote {
+                #[cfg(target_os = "linux")]
+                Some((socket, options, ws_url)) => {
+                    helper::supervise_remote(
+                        &socket,
+                        options,
+                        ws_url,
+                        timeouts,
+                        rx,
+                        &capture_app_handle,
+                    )
+                    .await
+                }
+                _ => {
+                    supervisor::supervise(
+                        &mut capture_manager,
+                        rx,
+                        ecapture_argv,
+                        RestartPolicy::default(),
+                        &capture_ready_rx,
+                        &EventSink::App(capture_app_handle.clone()),
+                    )
+                    .await
+                }
+            };
 
             if let Err(e) = result {
-                error!("[CaptureManager] Task failed: {}", e);
+                error!("[CaptureManager] Task failed: {:#}", e);
+                *capture_launch_error.lock().unwrap() = Some(format!("{:#}", e));
                 capture_error_inspector.store(true, Ordering::Release);
+
+                // Once launched, the session ends here when restarts are
+                // given up; during launch start_capture stops it
+                let state = capture_app_handle.state::<AppState>();
+                if let Some(shutdown_tx) = state.shutdown_tx.lock().await.take() {
+                    let _ = shutdown_tx.send(());
+                    state.sessions.write().await.end_running(format!("{:#}", e));
+                    *state.status.write().await = RunState::NotCapturing;
+                }
             }
+            capture_pending_tasks.ended("ecapture");
             capture_wg_clone.done();
+            capture_session_wg.done();
         });
-        tokio::select! {
-        _ = sleep(Duration::from_millis(900)) => {
-            }
-
-        _ = capture_wg.wait() => {
-            }
-        }
+        Some(capture_wg)
+    };
+    // ecapture is run by the user and may come up at any time
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    let capture_wg: Option<AsyncWaitGroup> = None;
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    {
+        let session_id = state
+            .sessions
+            .write()
+            .await
+            .begin(Vec::new(), ws_url.clone());
+        websocket_service.set_session_id(session_id);
     }
 
     let ws_wg = AsyncWaitGroup::new();
@@ -91,69 +335,301 @@ pub async fn start_capture(
     let ws_error_inspector = error_inspector.clone();
 
     let ws_wg_clone = ws_wg.clone();
+    session_wg.add(1);
+    let ws_session_wg = session_wg.clone();
+    state.pending_tasks.started("websocket");
+    let ws_pending_tasks = state.pending_tasks.clone();
     tokio::spawn(async move {
         if let Err(e) = websocket_service.receiver_task().await {
             error!("[WebsocketService] Task failed: {}", e);
             ws_error_inspector.store(true, Ordering::Release);
         }
+        ws_pending_tasks.ended("websocket");
         ws_wg_clone.done();
+        ws_session_wg.done();
     });
+
+    let capture_ended = async {
+        match &capture_wg {
+            Some(capture_wg) => capture_wg.wait().await,
+            None => std::future::pending().await,
+        }
+    };
     tokio::select! {
-        _ = sleep(Duration::from_millis(100)) => {
-            }
+        Ok(_) = ready_rx.wait_for(|ready| *ready) => {
+            info!("eCapture accepted the WebSocket connection");
+        }
+
+        _ = capture_ended => {
+        }
 
         _ = ws_wg.wait() => {
         }
+
+        _ = sleep(timeouts.launch()) => {
+            if capture_wg.is_some() {
+                *launch_error.lock().unwrap() = Some(format!(
+                    "eCapture did not accept a WebSocket connection within {:?}",
+                    timeouts.launch()
+                ));
+                error_inspector.store(true, Ordering::Release);
+            } else {
+                info!("eCapture is not reachable yet, the session keeps waiting for it");
+            }
+        }
     }
 
     if error_inspector.load(Ordering::SeqCst) {
         error!("capture session launch error");
+        let _ = shutdown_tx.send(());
+        session_wg.wait().await;
+        let reason = match launch_error.lock().unwrap().take() {
+            Some(reason) => format!("capture session launch error: {}", reason),
+            None => "capture session launch error".into(),
+        };
+        state.sessions.write().await.end_running(reason.clone());
         *state.status.write().await = RunState::NotCapturing;
-        shutdown_tx
-            .send(())
-            .map_err(|_| "Failed to send shutdown signal.".to_string())?;
-        return Err("capture session launch error".into());
+        return Err(reason);
     }
 
-    let shared_last_index_val = *state.shared_last_index.lock().await;
     let shared_last_index = state.shared_last_index.clone();
 
     let user_sql = { state.user_sql.lock().await.clone() };
-    PushService::new(
+    let push_task = PushService::new(
         state.df_actor_handle.clone(),
         "packet-data".to_string(),
         user_sql,
-        shared_last_index_val,
         shared_last_index,
         shutdown_tx.subscribe(),
         app_handle.clone(),
     )
     .map_err(|e| e.to_string())?;
 
+    let flow_push_task = FlowPushService::new(
+        state.df_actor_handle.clone(),
+        "flow-data".to_string(),
+        shutdown_tx.subscribe(),
+        app_handle.clone(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    track_task(&session_wg, &state.pending_tasks, "packet push", push_task);
+    track_task(
+        &session_wg,
+        &state.pending_tasks,
+        "flow push",
+        flow_push_task,
+    );
+
     *state.shutdown_tx.lock().await = Some(shutdown_tx);
+    *state.session_wg.lock().await = Some(session_wg);
 
     info!("Capture session started successfully.");
     Ok(())
 }
 
+/// Counts `task` among the tasks of the session until it ends.
+fn track_task(
+    session_wg: &AsyncWaitGroup,
+    pending_tasks: &PendingTasks,
+    name: &'static str,
+    task: JoinHandle<()>,
+) {
+    session_wg.add(1);
+    pending_tasks.started(name);
+    let session_wg = session_wg.clone();
+    let pending_tasks = pending_tasks.clone();
+    tokio::spawn(async move {
+        let _ = task.await;
+        pending_tasks.ended(name);
+        session_wg.done();
+    });
+}
+
+/// Stops the capture session, returning once all of its services have shut
+/// down and ecapture has exited.
 #[tauri::command]
 pub async fn stop_capture(state: tauri::State<'_, AppState>) -> Result<(), String> {
     if let RunState::NotCapturing = &*state.status.read().await {
         return Err("Capture session is not running.".into());
     }
     if let Some(shutdown_tx) = state.shutdown_tx.lock().await.take() {
-        shutdown_tx
-            .send(())
-            .map_err(|_| "Failed to send shutdown signal.".to_string())?;
+        // Fails only if every service has already ended
+        let _ = shutdown_tx.send(());
 
         info!("Shutdown signal sent. Capture session stopping.");
+        if let Some(session_wg) = state.session_wg.lock().await.take() {
+            session_wg.wait().await;
+        }
+        state.sessions.write().await.end_running(sessions::STOPPED);
         *state.status.write().await = RunState::NotCapturing;
+        info!("Capture session stopped.");
         Ok(())
     } else {
         Err("Capture session is not running.".into())
     }
 }
 
+#[tauri::command]
+pub async fn get_protocol_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<ProtocolStatus, String> {
+    Ok(state.protocol_status.read().await.clone())
+}
+
+#[tauri::command]
+pub async fn get_ecapture_output(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<EcaptureOutputLine>, String> {
+    Ok(state.ecapture_output.snapshot())
+}
+
+#[tauri::command]
+pub async fn get_ecapture_info(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<EcaptureInfo, String> {
+    #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+    {
+        let data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?;
+        // A running helper keeps the ecapture it was started with
+        #[cfg(target_os = "linux")]
+        if let Some(socket) = &state.helper_socket
+            && helper::is_running(socket).await
+        {
+            return helper::remote_info(socket)
+                .await
+                .map_err(|e| format!("{:#}", e));
+        }
+        let binary = match state.configs.lock().await.as_ref() {
+            Some(configs) => configs.ecapture_binary().map_err(|e| e.to_string())?,
+            None => BinarySource::Bundled,
+        };
+        CaptureManager::new(data_dir, binary, state.ecapture_output.clone(), app_handle)
+            .info()
+            .await
+            .map_err(|e| e.to_string())
+    }
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    {
+        let _ = (state, app_handle);
+        Err("This build connects to an external eCapture and bundles none".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn list_processes() -> Result<Vec<ProcessInfo>, String> {
+    #[cfg(all(not(decoupled), any(target_os = "linux", target_os = "android")))]
+    {
+        tokio::task::spawn_blocking(|| processes::list_processes(processes::PROC_ROOT))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+    #[cfg(not(all(not(decoupled), any(target_os = "linux", target_os = "android"))))]
+    {
+        Err("This build connects to an external eCapture and cannot list its processes".to_string())
+    }
+}
+
+/// Prepares the privileged helper if the app is not root. It only takes
+/// typed options, so raw arguments are refused.
+#[cfg(all(not(decoupled), target_os = "linux"))]
+async fn remote_session(
+    state: &AppState,
+    configs: &Configs,
+    options: Option<&EcaptureOptions>,
+    binary: &BinarySource,
+) -> Result<Option<RemoteSession>, String> {
+    let Some(socket) = &state.helper_socket else {
+        return Ok(None);
+    };
+    let options = options.cloned().ok_or_else(|| {
+        "Without root, eCapture runs through the privileged helper, which only accepts \
+         the typed eCapture options, not raw arguments"
+            .to_string()
+    })?;
+    helper::ensure_running(socket, binary)
+        .await
+        .map_err(|e| format!("Privileged helper unavailable: {:#}", e))?;
+    let ws_url = configs.ws_url.clone().unwrap_or_default();
+    Ok(Some((socket.clone(), options, ws_url)))
+}
+
+/// eCapture processes left running by an earlier run of the app, e.g. after
+/// it crashed. Empty where there are none to track. Without root, only a
+/// helper that already runs is asked, so that no password prompt shows.
+#[tauri::command]
+pub async fn get_orphaned_processes(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ProcessInfo>, String> {
+    #[cfg(all(not(decoupled), target_os = "linux"))]
+    {
+        if let Some(socket) = &state.helper_socket {
+            if !helper::is_running(socket).await {
+                return Ok(Vec::new());
+            }
+            return helper::remote_orphans(socket)
+                .await
+                .map_err(|e| format!("{:#}", e));
+        }
+        let data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?;
+        Ok(PidFile::new(data_dir)
+            .orphans()
+            .iter()
+            .filter_map(|p| processes::find_process(processes::PROC_ROOT, p.pid).ok())
+            .collect())
+    }
+    #[cfg(not(all(not(decoupled), target_os = "linux")))]
+    {
+        let _ = (state, app_handle);
+        Ok(Vec::new())
+    }
+}
+
+#[tauri::command]
+pub async fn stop_orphaned_processes(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    #[cfg(all(not(decoupled), target_os = "linux"))]
+    {
+        let timeouts = match state.configs.lock().await.as_ref() {
+            Some(configs) => configs.timeouts(),
+            None => Default::default(),
+        };
+        if let Some(socket) = &state.helper_socket {
+            return helper::stop_remote_orphans(socket, timeouts)
+                .await
+                .map_err(|e| format!("{:#}", e));
+        }
+        let data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?;
+        let orphans = PidFile::new(data_dir).orphans();
+        info!(
+            "Stopping orphaned eCapture processes {:?}",
+            orphans.iter().map(|p| p.pid).collect::<Vec<_>>()
+        );
+        capture::stop_processes(&orphans, &timeouts)
+            .await
+            .map_err(|e| e.to_string())
+    }
+    #[cfg(not(all(not(decoupled), target_os = "linux")))]
+    {
+        let _ = (state, app_handle);
+        Err("No orphaned eCapture processes are tracked on this platform".to_string())
+    }
+}
+
 #[tauri::command]
 pub async fn get_configs(
     _app_handle: tauri::AppHandle,
@@ -182,6 +658,26 @@ pub async fn modify_configs(
         }
     }
 
+    // Normalize empty strings to None
+    if newConfigs
+        .ecapture_path
+        .as_deref()
+        .is_some_and(|p| p.trim().is_empty())
+    {
+        newConfigs.ecapture_path = None;
+        newConfigs.ecapture_sha256 = None;
+    }
+
+    newConfigs
+        .ecapture_argv()
+        .map_err(|e| format!("Invalid eCapture arguments: {}", e))?;
+    newConfigs
+        .ecapture_binary()
+        .map_err(|e| format!("Invalid eCapture binary: {}", e))?;
+    newConfigs
+        .redactor()
+        .map_err(|e| format!("Invalid redaction rules: {}", e))?;
+
     let data_dir = app_handle
         .path()
         .app_data_dir()
@@ -220,8 +716,7 @@ pub async fn verify_user_sql(user_sql: Option<String>) -> Result<(), String> {
         info!("Validating SQL: {}", sql_text);
 
         let mut ctx = SQLContext::new();
-        let df = create_capture_df();
-        ctx.register("packets", df.lazy());
+        register_empty_tables(&mut ctx);
 
         let zero_index: u64 = 0;
         let validation_sql = queries::new_packets_customized_no_payload(&zero_index, sql_text);
@@ -256,7 +751,11 @@ pub async fn get_packet_with_payload(
             let packets = crate::tauri_bridge::converters::df_to_packet_data_vec(&df)
                 .map_err(|e| e.to_string())?;
 
-            if let Some(packet) = packets.into_iter().next() {
+            if let Some(mut packet) = packets.into_iter().next() {
+                packet.decoded_body = content_decoding::decode_http_body(packet.payload_bytes());
+                if let Some(redactor) = display_redactor(&state).await {
+                    redactor.redact_packet(&mut packet, &mut RedactionReport::default());
+                }
                 Ok(packet)
             } else {
                 Err("Failed to convert packet data".to_string())
@@ -265,3 +764,435 @@ pub async fn get_packet_with_payload(
         Err(e) => Err(format!("Database query failed: {}", e)),
     }
 }
+
+#[tauri::command]
+pub async fn decode_payload(
+    state: tauri::State<'_, AppState>,
+    index: u64,
+    decoder: Option<String>,
+) -> Result<DecodedPayload, String> {
+    let df = state
+        .df_actor_handle
+        .get_packet_by_index(index)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    let packet = crate::tauri_bridge::converters::df_to_packet_data_vec(&df)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Packet not found".to_string())?;
+
+    let (content_type, body) = decoders::body_from_payload(packet.payload_bytes());
+    let input = DecodeInput {
+        content_type: content_type.as_deref(),
+        body: &body,
+        depth: 0,
+    };
+
+    let mut decoded = state
+        .decoders
+        .decode(&input, decoder.as_deref())
+        .map_err(|e| format!("Failed to decode payload: {}", e))?;
+    if let Some(redactor) = display_redactor(&state).await {
+        redactor.redact_decoded(&mut decoded, &mut RedactionReport::default());
+    }
+    Ok(decoded)
+}
+
+#[tauri::command]
+pub async fn get_ws_messages(
+    state: tauri::State<'_, AppState>,
+    uuid: String,
+) -> Result<Vec<WsMessage>, String> {
+    let df = state
+        .df_actor_handle
+        .get_ws_messages_by_uuid(&uuid)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    let mut rows =
+        crate::tauri_bridge::converters::df_to_ws_message_vec(&df).map_err(|e| e.to_string())?;
+    if let Some(redactor) = display_redactor(&state).await {
+        let mut report = RedactionReport::default();
+        for row in rows.iter_mut() {
+            redactor.redact_ws_message(row, &mut report);
+        }
+    }
+    Ok(rows)
+}
+
+#[tauri::command]
+pub async fn get_db_queries(
+    state: tauri::State<'_, AppState>,
+    uuid: String,
+) -> Result<Vec<DbQuery>, String> {
+    let df = state
+        .df_actor_handle
+        .get_db_queries_by_uuid(&uuid)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    let mut rows =
+        crate::tauri_bridge::converters::df_to_db_query_vec(&df).map_err(|e| e.to_string())?;
+    if let Some(redactor) = display_redactor(&state).await {
+        let mut report = RedactionReport::default();
+        for row in rows.iter_mut() {
+            redactor.redact_db_query(row, &mut report);
+        }
+    }
+    Ok(rows)
+}
+
+#[tauri::command]
+pub async fn get_mail_messages(
+    state: tauri::State<'_, AppState>,
+    uuid: String,
+) -> Result<Vec<MailMessage>, String> {
+    let df = state
+        .df_actor_handle
+        .get_mail_messages_by_uuid(&uuid)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    let mut rows =
+        crate::tauri_bridge::converters::df_to_mail_message_vec(&df).map_err(|e| e.to_string())?;
+    if let Some(redactor) = display_redactor(&state).await {
+        let mut report = RedactionReport::default();
+        for row in rows.iter_mut() {
+            redactor.redact_mail_message(row, &mut report);
+        }
+    }
+    Ok(rows)
+}
+
+#[tauri::command]
+pub async fn get_broker_messages(
+    state: tauri::State<'_, AppState>,
+    uuid: String,
+) -> Result<Vec<BrokerMessage>, String> {
+    let df = state
+        .df_actor_handle
+        .get_broker_messages_by_uuid(&uuid)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    let mut rows = crate::tauri_bridge::converters::df_to_broker_message_vec(&df)
+        .map_err(|e| e.to_string())?;
+    if let Some(redactor) = display_redactor(&state).await {
+        let mut report = RedactionReport::default();
+        for row in rows.iter_mut() {
+            redactor.redact_broker_message(row, &mut report);
+        }
+    }
+    Ok(rows)
+}
+
+/// Lists the capture sessions of this run, oldest first, with the number of
+/// rows each still has.
+#[tauri::command]
+pub async fn list_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<SessionInfo>, String> {
+    let df = state
+        .df_actor_handle
+        .get_session_row_counts()
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    let rows = session_rows(&df).map_err(|e| e.to_string())?;
+
+    Ok(state.sessions.read().await.list(&rows))
+}
+
+fn session_rows(df: &DataFrame) -> PolarsResult<HashMap<u64, u64>> {
+    let ids = df.column("session_id")?.u64()?;
+    let counts = df.column("row_count")?.cast(&DataType::UInt64)?;
+    let counts = counts.u64()?;
+    Ok(ids
+        .into_iter()
+        .zip(counts)
+        .filter_map(|(id, count)| Some((id?, count?)))
+        .collect())
+}
+
+/// Packets of a capture session, without payloads.
+#[tauri::command]
+pub async fn get_session_packets(
+    state: tauri::State<'_, AppState>,
+    session_id: u64,
+) -> Result<Vec<PacketDataFrontend>, String> {
+    let df = state
+        .df_actor_handle
+        .get_session_packets_no_payload(session_id)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    crate::tauri_bridge::converters::df_to_packet_data_frontend_vec(&df).map_err(|e| e.to_string())
+}
+
+/// Drops the rows of a stopped capture session. The frontend is told through
+/// `SESSION_CLEARED_EVENT` to drop them as well.
+#[tauri::command]
+pub async fn clear_session(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    session_id: u64,
+) -> Result<u64, String> {
+    {
+        let sessions = state.sessions.read().await;
+        if sessions.running() == Some(session_id) {
+            return Err("The running capture session cannot be cleared.".into());
+        }
+        if !sessions.contains(session_id) {
+            return Err(format!("Unknown capture session {}", session_id));
+        }
+    }
+    let removed = state
+        .df_actor_handle
+        .remove_session(session_id)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    state.sessions.write().await.remove(session_id);
+    info!("Cleared capture session {} ({} rows)", session_id, removed);
+
+    if let Err(e) = app_handle.emit(SESSION_CLEARED_EVENT, session_id) {
+        error!("Failed to emit {}: {}", SESSION_CLEARED_EVENT, e);
+    }
+    Ok(removed)
+}
+
+/// Drops the captured packets, except those matching the SQL condition
+/// `keep` and, with `keep_current_session`, those of the running session.
+/// The frontend gets the packets left through `PACKETS_RESET_EVENT`.
+#[tauri::command]
+pub async fn clear_packets(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    keep: Option<String>,
+    keep_current_session: bool,
+) -> Result<u64, String> {
+    let mut conditions: Vec<String> = keep
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .into_iter()
+        .collect();
+    if keep_current_session && let Some(id) = state.sessions.read().await.running() {
+        conditions.push(format!("session_id = {}", id));
+    }
+    let keep = (!conditions.is_empty()).then(|| {
+        conditions
+            .iter()
+            .map(|c| format!("({})", c))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    });
+
+    // Held until the reset is sent, so the push service neither pushes
+    // dropped rows after it nor pushes the rows left again
+    let mut last_index = state.shared_last_index.lock().await;
+    let dropped = state
+        .df_actor_handle
+        .clear(keep)
+        .await
+        .map_err(|e| format!("Failed to clear packets: {}", e))?;
+
+    let user_sql = state.user_sql.lock().await.clone();
+    let df = match &user_sql {
+        Some(sql) => {
+            state
+                .df_actor_handle
+                .get_packets_customized_no_payload(&0, sql)
+                .await
+        }
+        None => {
+            state
+                .df_actor_handle
+                .get_packets_since_index_no_payload(&0)
+                .await
+        }
+    }
+    .map_err(|e| format!("Database query failed: {}", e))?;
+    let kept = crate::tauri_bridge::converters::df_to_packet_data_frontend_vec(&df)
+        .map_err(|e| e.to_string())?;
+    *last_index = kept.last().map_or(0, |p| p.index);
+    info!("Cleared {} packets, {} left", dropped, kept.len());
+
+    if let Err(e) = app_handle.emit(PACKETS_RESET_EVENT, &kept) {
+        error!("Failed to emit {}: {}", PACKETS_RESET_EVENT, e);
+    }
+    Ok(dropped)
+}
+
+#[tauri::command]
+pub async fn tag_packet(
+    state: tauri::State<'_, AppState>,
+    index: u64,
+    tag: String,
+) -> Result<PacketAnnotation, String> {
+    annotate(&state, index, AnnotationEdit::Tag(tag)).await
+}
+
+#[tauri::command]
+pub async fn untag_packet(
+    state: tauri::State<'_, AppState>,
+    index: u64,
+    tag: String,
+) -> Result<PacketAnnotation, String> {
+    annotate(&state, index, AnnotationEdit::Untag(tag)).await
+}
+
+/// Sets the note of a packet; an empty note removes it.
+#[tauri::command]
+pub async fn set_packet_note(
+    state: tauri::State<'_, AppState>,
+    index: u64,
+    note: String,
+) -> Result<PacketAnnotation, String> {
+    annotate(&state, index, AnnotationEdit::Note(note)).await
+}
+
+async fn annotate(
+    state: &AppState,
+    index: u64,
+    edit: AnnotationEdit,
+) -> Result<PacketAnnotation, String> {
+    state
+        .df_actor_handle
+        .annotate(index, edit)
+        .await
+        .map_err(|e| format!("Failed to annotate packet: {}", e))
+}
+
+/// Every annotated packet's tags and note, by index.
+#[tauri::command]
+pub async fn list_annotations(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PacketAnnotation>, String> {
+    let df = state
+        .df_actor_handle
+        .get_annotations()
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    crate::tauri_bridge::converters::df_to_annotation_vec(&df).map_err(|e| e.to_string())
+}
+
+/// Every flow, for reloading them after packets were dropped.
+#[tauri::command]
+pub async fn get_flows(state: tauri::State<'_, AppState>) -> Result<Vec<FlowRecord>, String> {
+    let df = state
+        .df_actor_handle
+        .get_flows_updated_since(&0)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    crate::tauri_bridge::converters::df_to_flow_record_vec(&df).map_err(|e| e.to_string())
+}
+
+/// The redactor for what is shown, if redaction applies there.
+async fn display_redactor(state: &AppState) -> Option<Arc<Redactor>> {
+    state
+        .redactor
+        .read()
+        .await
+        .clone()
+        .filter(|r| r.redacts_display())
+}
+
+/// Writes the packets, or those matching `filter`, with their payloads and
+/// annotations to a JSON file in the download directory, redacted unless
+/// redaction is off.
+#[tauri::command]
+pub async fn export_packets(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    filter: Option<String>,
+) -> Result<ExportSummary, String> {
+    let filter = filter.filter(|f| !f.trim().is_empty());
+    let df = state
+        .df_actor_handle
+        .get_packets_with_payload(filter.as_deref())
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    let mut packets =
+        crate::tauri_bridge::converters::df_to_packet_data_vec(&df).map_err(|e| e.to_string())?;
+
+    let df = state
+        .df_actor_handle
+        .get_annotations()
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    let exported: HashSet<(u64, u64)> = packets.iter().map(|p| (p.session_id, p.index)).collect();
+    let annotations: Vec<PacketAnnotation> =
+        crate::tauri_bridge::converters::df_to_annotation_vec(&df)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|a| exported.contains(&(a.session_id, a.index)))
+            .collect();
+
+    let redactor = state.redactor.read().await.clone();
+    let redaction = redactor.map(|redactor| {
+        let mut report = RedactionReport::default();
+        for packet in packets.iter_mut() {
+            redactor.redact_export(packet, &mut report);
+        }
+        report
+    });
+
+    let dir = match app_handle.path().download_dir() {
+        Ok(dir) => dir,
+        Err(_) => app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|_| "failed to get download directory".to_string())?,
+    };
+    let exported_at = sessions::now_millis();
+    let path = dir.join(format!("ecaptureq-export-{}.json", exported_at));
+    let summary = ExportSummary {
+        path: path.display().to_string(),
+        packets: packets.len() as u64,
+        redaction: redaction.clone(),
+    };
+    let export = PacketExport {
+        exported_at,
+        packets,
+        annotations,
+        redaction,
+    };
+    let json = serde_json::to_vec(&export).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", summary.path, e))?;
+
+    info!("Exported {} packets to {}", summary.packets, summary.path);
+    Ok(summary)
+}
+
+/// What redaction does with the current rules: what was redacted before
+/// storing when redacting at ingest, else what would be redacted in the
+/// packets now stored. `None` if redaction is off.
+#[tauri::command]
+pub async fn get_redaction_report(
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<RedactionReport>, String> {
+    let Some(redactor) = state.redactor.read().await.clone() else {
+        return Ok(None);
+    };
+    if redactor.mode() == RedactionMode::Ingest {
+        return state
+            .df_actor_handle
+            .get_redaction_report()
+            .await
+            .map(Some)
+            .map_err(|e| format!("Failed to get redaction report: {}", e));
+    }
+
+    let df = state
+        .df_actor_handle
+        .get_packets_with_payload(None)
+        .await
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    let packets =
+        crate::tauri_bridge::converters::df_to_packet_data_vec(&df).map_err(|e| e.to_string())?;
+    let mut report = RedactionReport::default();
+    for mut packet in packets {
+        redactor.redact_packet(&mut packet, &mut report);
+    }
+    Ok(Some(report))
+}
@@ -2,7 +2,11 @@
 use polars::prelude::*;
 
 // Internal modules
-use crate::core::models::{PacketData, PacketDataFrontend};
+use crate::core::ecapture_args::Subcommand;
+use crate::core::models::{
+    BrokerMessage, DbQuery, FlowRecord, MailMessage, PacketAnnotation, PacketData,
+    PacketDataFrontend, WsMessage,
+};
 
 pub fn df_to_packet_data_vec(df: &DataFrame) -> PolarsResult<Vec<PacketData>> {
     if df.is_empty() {
@@ -11,6 +15,7 @@ pub fn df_to_packet_data_vec(df: &DataFrame) -> PolarsResult<Vec<PacketData>> {
 
     // Convert all columns to typed iterators for efficiency
     let index_iter = df.column("index")?.u64()?;
+    let session_id_iter = df.column("session_id")?.u64()?;
     let ts_iter = df.column("timestamp")?.i64()?;
     let uuid_iter = df.column("uuid")?.str()?;
     let src_ip_iter = df.column("src_ip")?.str()?;
@@ -19,9 +24,26 @@ pub fn df_to_packet_data_vec(df: &DataFrame) -> PolarsResult<Vec<PacketData>> {
     let dst_port_iter = df.column("dst_port")?.u32()?;
     let pid_iter = df.column("pid")?.i32()?;
     let pname_iter = df.column("pname")?.str()?;
+    let tid_iter = df.column("tid")?.i32()?;
+    let uid_iter = df.column("uid")?.u32()?;
+    let exe_path_iter = df.column("exe_path")?.str()?;
+    let cgroup_id_iter = df.column("cgroup_id")?.u64()?;
+    let container_id_iter = df.column("container_id")?.str()?;
+    let package_iter = df.column("package")?.str()?;
+    let fd_iter = df.column("fd")?.i32()?;
     let type_iter = df.column("type")?.u32()?;
+    let type_name_iter = df.column("type_name")?.str()?;
+    let direction_iter = df.column("direction")?.str()?;
+    let module_iter = df.column("module")?.str()?;
     let length_iter = df.column("length")?.u32()?;
     let is_binary_iter = df.column("is_binary")?.bool()?;
+    let content_kind_iter = df.column("content_kind")?.str()?;
+    let app_proto_iter = df.column("app_proto")?.str()?;
+    let tls_version_iter = df.column("tls_version")?.str()?;
+    let tls_cipher_iter = df.column("tls_cipher")?.str()?;
+    let tls_sni_iter = df.column("tls_sni")?.str()?;
+    let tls_alpn_iter = df.column("tls_alpn")?.str()?;
+    let tls_library_iter = df.column("tls_library")?.str()?;
     let payload_utf8_iter = df.column("payload_utf8")?.str()?;
     let payload_binary_iter = df.column("payload_binary")?.binary()?;
 
@@ -32,6 +54,7 @@ pub fn df_to_packet_data_vec(df: &DataFrame) -> PolarsResult<Vec<PacketData>> {
         // Use .get(i) to safely get elements - unwrap is safe here since iteration length is consistent
         result_vec.push(PacketData {
             index: index_iter.get(i).unwrap(),
+            session_id: session_id_iter.get(i).unwrap(),
             timestamp: ts_iter.get(i).unwrap(),
             uuid: uuid_iter.get(i).unwrap().to_string(),
             src_ip: src_ip_iter.get(i).unwrap().to_string(),
@@ -40,11 +63,30 @@ pub fn df_to_packet_data_vec(df: &DataFrame) -> PolarsResult<Vec<PacketData>> {
             dst_port: dst_port_iter.get(i).unwrap(),
             pid: pid_iter.get(i).unwrap(),
             pname: pname_iter.get(i).unwrap().to_string(),
+            tid: tid_iter.get(i),
+            uid: uid_iter.get(i),
+            exe_path: exe_path_iter.get(i).unwrap().to_string(),
+            cgroup_id: cgroup_id_iter.get(i),
+            container_id: container_id_iter.get(i).unwrap().to_string(),
+            package: package_iter.get(i).unwrap().to_string(),
+            fd: fd_iter.get(i),
             r#type: type_iter.get(i).unwrap(),
+            type_name: type_name_iter.get(i).unwrap().to_string(),
+            direction: direction_iter.get(i).unwrap().to_string(),
+            module: module_iter.get(i).and_then(Subcommand::from_name),
             length: length_iter.get(i).unwrap(),
             is_binary: is_binary_iter.get(i).unwrap(),
+            content_kind: content_kind_iter.get(i).unwrap().to_string(),
+            app_proto: app_proto_iter.get(i).unwrap().to_string(),
+            tls_version: tls_version_iter.get(i).unwrap().to_string(),
+            tls_cipher: tls_cipher_iter.get(i).unwrap().to_string(),
+            tls_sni: tls_sni_iter.get(i).unwrap().to_string(),
+            tls_alpn: tls_alpn_iter.get(i).unwrap().to_string(),
+            tls_library: tls_library_iter.get(i).unwrap().to_string(),
             payload_utf8: payload_utf8_iter.get(i).unwrap().to_string(),
             payload_binary: payload_binary_iter.get(i).unwrap_or(&[]).to_vec(),
+            // Rebuilt on demand, see get_packet_with_payload
+            decoded_body: None,
         });
     }
 
@@ -62,6 +104,7 @@ pub fn df_to_packet_data_frontend_vec(df: &DataFrame) -> PolarsResult<Vec<Packet
 
     // Convert all columns to typed iterators for efficiency
     let index_iter = df.column("index")?.u64()?;
+    let session_id_iter = df.column("session_id")?.u64()?;
     let ts_iter = df.column("timestamp")?.i64()?;
     let uuid_iter = df.column("uuid")?.str()?;
     let src_ip_iter = df.column("src_ip")?.str()?;
@@ -70,9 +113,26 @@ pub fn df_to_packet_data_frontend_vec(df: &DataFrame) -> PolarsResult<Vec<Packet
     let dst_port_iter = df.column("dst_port")?.u32()?;
     let pid_iter = df.column("pid")?.i32()?;
     let pname_iter = df.column("pname")?.str()?;
+    let tid_iter = df.column("tid")?.i32()?;
+    let uid_iter = df.column("uid")?.u32()?;
+    let exe_path_iter = df.column("exe_path")?.str()?;
+    let cgroup_id_iter = df.column("cgroup_id")?.u64()?;
+    let container_id_iter = df.column("container_id")?.str()?;
+    let package_iter = df.column("package")?.str()?;
+    let fd_iter = df.column("fd")?.i32()?;
     let type_iter = df.column("type")?.u32()?;
+    let type_name_iter = df.column("type_name")?.str()?;
+    let direction_iter = df.column("direction")?.str()?;
+    let module_iter = df.column("module")?.str()?;
     let length_iter = df.column("length")?.u32()?;
     let is_binary_iter = df.column("is_binary")?.bool()?;
+    let content_kind_iter = df.column("content_kind")?.str()?;
+    let app_proto_iter = df.column("app_proto")?.str()?;
+    let tls_version_iter = df.column("tls_version")?.str()?;
+    let tls_cipher_iter = df.column("tls_cipher")?.str()?;
+    let tls_sni_iter = df.column("tls_sni")?.str()?;
+    let tls_alpn_iter = df.column("tls_alpn")?.str()?;
+    let tls_library_iter = df.column("tls_library")?.str()?;
 
     let mut result_vec = Vec::with_capacity(df.height());
 
@@ -80,6 +140,7 @@ pub fn df_to_packet_data_frontend_vec(df: &DataFrame) -> PolarsResult<Vec<Packet
     for i in 0..df.height() {
         result_vec.push(PacketDataFrontend {
             index: index_iter.get(i).unwrap(),
+            session_id: session_id_iter.get(i).unwrap(),
             timestamp: ts_iter.get(i).unwrap(),
             uuid: uuid_iter.get(i).unwrap().to_string(),
             src_ip: src_ip_iter.get(i).unwrap().to_string(),
@@ -88,9 +149,283 @@ pub fn df_to_packet_data_frontend_vec(df: &DataFrame) -> PolarsResult<Vec<Packet
             dst_port: dst_port_iter.get(i).unwrap(),
             pid: pid_iter.get(i).unwrap(),
             pname: pname_iter.get(i).unwrap().to_string(),
+            tid: tid_iter.get(i),
+            uid: uid_iter.get(i),
+            exe_path: exe_path_iter.get(i).unwrap().to_string(),
+            cgroup_id: cgroup_id_iter.get(i),
+            container_id: container_id_iter.get(i).unwrap().to_string(),
+            package: package_iter.get(i).unwrap().to_string(),
+            fd: fd_iter.get(i),
             r#type: type_iter.get(i).unwrap(),
+            type_name: type_name_iter.get(i).unwrap().to_string(),
+            direction: direction_iter.get(i).unwrap().to_string(),
+            module: module_iter.get(i).and_then(Subcommand::from_name),
             length: length_iter.get(i).unwrap(),
             is_binary: is_binary_iter.get(i).unwrap(),
+            content_kind: content_kind_iter.get(i).unwrap().to_string(),
+            app_proto: app_proto_iter.get(i).unwrap().to_string(),
+            tls_version: tls_version_iter.get(i).unwrap().to_string(),
+            tls_cipher: tls_cipher_iter.get(i).unwrap().to_string(),
+            tls_sni: tls_sni_iter.get(i).unwrap().to_string(),
+            tls_alpn: tls_alpn_iter.get(i).unwrap().to_string(),
+            tls_library: tls_library_iter.get(i).unwrap().to_string(),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+/// Convert a `flows` table DataFrame to Vec<FlowRecord> for the frontend flows view
+pub fn df_to_flow_record_vec(df: &DataFrame) -> PolarsResult<Vec<FlowRecord>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let flow_id_iter = df.column("flow_id")?.u64()?;
+    let uuid_iter = df.column("uuid")?.str()?;
+    let src_ip_iter = df.column("src_ip")?.str()?;
+    let src_port_iter = df.column("src_port")?.u32()?;
+    let dst_ip_iter = df.column("dst_ip")?.str()?;
+    let dst_port_iter = df.column("dst_port")?.u32()?;
+    let pid_iter = df.column("pid")?.i32()?;
+    let pname_iter = df.column("pname")?.str()?;
+    let first_seen_iter = df.column("first_seen")?.i64()?;
+    let last_seen_iter = df.column("last_seen")?.i64()?;
+    let duration_iter = df.column("duration")?.i64()?;
+    let bytes_out_iter = df.column("bytes_out")?.u64()?;
+    let bytes_in_iter = df.column("bytes_in")?.u64()?;
+    let event_count_iter = df.column("event_count")?.u64()?;
+    let app_proto_iter = df.column("app_proto")?.str()?;
+    let update_seq_iter = df.column("update_seq")?.u64()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        result_vec.push(FlowRecord {
+            flow_id: flow_id_iter.get(i).unwrap(),
+            uuid: uuid_iter.get(i).unwrap().to_string(),
+            src_ip: src_ip_iter.get(i).unwrap().to_string(),
+            src_port: src_port_iter.get(i).unwrap(),
+            dst_ip: dst_ip_iter.get(i).unwrap().to_string(),
+            dst_port: dst_port_iter.get(i).unwrap(),
+            pid: pid_iter.get(i).unwrap(),
+            pname: pname_iter.get(i).unwrap().to_string(),
+            first_seen: first_seen_iter.get(i).unwrap(),
+            last_seen: last_seen_iter.get(i).unwrap(),
+            duration: duration_iter.get(i).unwrap(),
+            bytes_out: bytes_out_iter.get(i).unwrap(),
+            bytes_in: bytes_in_iter.get(i).unwrap(),
+            event_count: event_count_iter.get(i).unwrap(),
+            app_proto: app_proto_iter.get(i).unwrap().to_string(),
+            update_seq: update_seq_iter.get(i).unwrap(),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+pub fn df_to_ws_message_vec(df: &DataFrame) -> PolarsResult<Vec<WsMessage>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message_id_iter = df.column("message_id")?.u64()?;
+    let uuid_iter = df.column("uuid")?.str()?;
+    let packet_index_iter = df.column("packet_index")?.u64()?;
+    let ts_iter = df.column("timestamp")?.i64()?;
+    let direction_iter = df.column("direction")?.str()?;
+    let opcode_iter = df.column("opcode")?.u32()?;
+    let opcode_name_iter = df.column("opcode_name")?.str()?;
+    let compressed_iter = df.column("compressed")?.bool()?;
+    let length_iter = df.column("length")?.u64()?;
+    let is_binary_iter = df.column("is_binary")?.bool()?;
+    let payload_utf8_iter = df.column("payload_utf8")?.str()?;
+    let payload_binary_iter = df.column("payload_binary")?.binary()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        result_vec.push(WsMessage {
+            message_id: message_id_iter.get(i).unwrap(),
+            uuid: uuid_iter.get(i).unwrap().to_string(),
+            packet_index: packet_index_iter.get(i).unwrap(),
+            timestamp: ts_iter.get(i).unwrap(),
+            direction: direction_iter.get(i).unwrap().to_string(),
+            opcode: opcode_iter.get(i).unwrap(),
+            opcode_name: opcode_name_iter.get(i).unwrap().to_string(),
+            compressed: compressed_iter.get(i).unwrap(),
+            length: length_iter.get(i).unwrap(),
+            is_binary: is_binary_iter.get(i).unwrap(),
+            payload_utf8: payload_utf8_iter.get(i).unwrap().to_string(),
+            payload_binary: payload_binary_iter.get(i).unwrap_or(&[]).to_vec(),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+pub fn df_to_db_query_vec(df: &DataFrame) -> PolarsResult<Vec<DbQuery>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let query_id_iter = df.column("query_id")?.u64()?;
+    let uuid_iter = df.column("uuid")?.str()?;
+    let protocol_iter = df.column("protocol")?.str()?;
+    let kind_iter = df.column("kind")?.str()?;
+    let statement_iter = df.column("statement")?.str()?;
+    let packet_index_iter = df.column("packet_index")?.u64()?;
+    let ts_iter = df.column("timestamp")?.i64()?;
+    let response_index_iter = df.column("response_index")?.u64()?;
+    let latency_iter = df.column("latency")?.i64()?;
+    let rows_iter = df.column("rows")?.u64()?;
+    let error_iter = df.column("error")?.str()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        result_vec.push(DbQuery {
+            query_id: query_id_iter.get(i).unwrap(),
+            uuid: uuid_iter.get(i).unwrap().to_string(),
+            protocol: protocol_iter.get(i).unwrap().to_string(),
+            kind: kind_iter.get(i).unwrap().to_string(),
+            statement: statement_iter.get(i).unwrap().to_string(),
+            packet_index: packet_index_iter.get(i).unwrap(),
+            timestamp: ts_iter.get(i).unwrap(),
+            // Null until a response is seen
+            response_index: response_index_iter.get(i),
+            latency: latency_iter.get(i),
+            rows: rows_iter.get(i),
+            error: error_iter.get(i).map(str::to_string),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+pub fn df_to_mail_message_vec(df: &DataFrame) -> PolarsResult<Vec<MailMessage>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message_id_iter = df.column("message_id")?.u64()?;
+    let uuid_iter = df.column("uuid")?.str()?;
+    let protocol_iter = df.column("protocol")?.str()?;
+    let packet_index_iter = df.column("packet_index")?.u64()?;
+    let ts_iter = df.column("timestamp")?.i64()?;
+    let direction_iter = df.column("direction")?.str()?;
+    let tag_iter = df.column("tag")?.str()?;
+    let command_iter = df.column("command")?.str()?;
+    let arguments_iter = df.column("arguments")?.str()?;
+    let mail_from_iter = df.column("mail_from")?.str()?;
+    let rcpt_to_iter = df.column("rcpt_to")?.str()?;
+    let subject_iter = df.column("subject")?.str()?;
+    let content_iter = df.column("content")?.str()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        result_vec.push(MailMessage {
+            message_id: message_id_iter.get(i).unwrap(),
+            uuid: uuid_iter.get(i).unwrap().to_string(),
+            protocol: protocol_iter.get(i).unwrap().to_string(),
+            packet_index: packet_index_iter.get(i).unwrap(),
+            timestamp: ts_iter.get(i).unwrap(),
+            direction: direction_iter.get(i).unwrap().to_string(),
+            tag: tag_iter.get(i).unwrap().to_string(),
+            command: command_iter.get(i).unwrap().to_string(),
+            arguments: arguments_iter.get(i).unwrap().to_string(),
+            mail_from: mail_from_iter.get(i).unwrap().to_string(),
+            rcpt_to: rcpt_to_iter.get(i).unwrap().to_string(),
+            subject: subject_iter.get(i).unwrap().to_string(),
+            content: content_iter.get(i).unwrap().to_string(),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+pub fn df_to_broker_message_vec(df: &DataFrame) -> PolarsResult<Vec<BrokerMessage>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message_id_iter = df.column("message_id")?.u64()?;
+    let uuid_iter = df.column("uuid")?.str()?;
+    let protocol_iter = df.column("protocol")?.str()?;
+    let packet_index_iter = df.column("packet_index")?.u64()?;
+    let ts_iter = df.column("timestamp")?.i64()?;
+    let direction_iter = df.column("direction")?.str()?;
+    let method_iter = df.column("method")?.str()?;
+    let channel_iter = df.column("channel")?.u32()?;
+    let exchange_iter = df.column("exchange")?.str()?;
+    let topic_iter = df.column("topic")?.str()?;
+    let queue_iter = df.column("queue")?.str()?;
+    let packet_id_iter = df.column("packet_id")?.u32()?;
+    let qos_iter = df.column("qos")?.u32()?;
+    let detail_iter = df.column("detail")?.str()?;
+    let content_type_iter = df.column("content_type")?.str()?;
+    let length_iter = df.column("length")?.u64()?;
+    let is_binary_iter = df.column("is_binary")?.bool()?;
+    let payload_utf8_iter = df.column("payload_utf8")?.str()?;
+    let payload_binary_iter = df.column("payload_binary")?.binary()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        result_vec.push(BrokerMessage {
+            message_id: message_id_iter.get(i).unwrap(),
+            uuid: uuid_iter.get(i).unwrap().to_string(),
+            protocol: protocol_iter.get(i).unwrap().to_string(),
+            packet_index: packet_index_iter.get(i).unwrap(),
+            timestamp: ts_iter.get(i).unwrap(),
+            direction: direction_iter.get(i).unwrap().to_string(),
+            method: method_iter.get(i).unwrap().to_string(),
+            channel: channel_iter.get(i).unwrap(),
+            exchange: exchange_iter.get(i).unwrap().to_string(),
+            topic: topic_iter.get(i).unwrap().to_string(),
+            queue: queue_iter.get(i).unwrap().to_string(),
+            packet_id: packet_id_iter.get(i),
+            qos: qos_iter.get(i),
+            detail: detail_iter.get(i).unwrap().to_string(),
+            content_type: content_type_iter.get(i).unwrap().to_string(),
+            length: length_iter.get(i).unwrap(),
+            is_binary: is_binary_iter.get(i).unwrap(),
+            payload_utf8: payload_utf8_iter.get(i).unwrap().to_string(),
+            payload_binary: payload_binary_iter.get(i).unwrap_or(&[]).to_vec(),
+        });
+    }
+
+    Ok(result_vec)
+}
+
+/// Convert an `annotations` table DataFrame to Vec<PacketAnnotation>
+pub fn df_to_annotation_vec(df: &DataFrame) -> PolarsResult<Vec<PacketAnnotation>> {
+    if df.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let index_iter = df.column("index")?.u64()?;
+    let session_id_iter = df.column("session_id")?.u64()?;
+    let tags_iter = df.column("tags")?.list()?;
+    let note_iter = df.column("note")?.str()?;
+
+    let mut result_vec = Vec::with_capacity(df.height());
+
+    for i in 0..df.height() {
+        let tags = match tags_iter.get_as_series(i) {
+            Some(tags) => tags
+                .str()?
+                .into_no_null_iter()
+                .map(str::to_string)
+                .collect(),
+            None => Vec::new(),
+        };
+        result_vec.push(PacketAnnotation {
+            index: index_iter.get(i).unwrap(),
+            session_id: session_id_iter.get(i).unwrap(),
+            tags,
+            note: note_iter.get(i).unwrap_or_default().to_string(),
         });
     }
 
@@ -1,12 +1,24 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock, watch};
+use wg::AsyncWaitGroup;
 
 use crate::core::actor::DataFrameActorHandle;
+use crate::core::android_packages::PackageIndex;
+use crate::core::decoders::DecoderRegistry;
+use crate::core::ecapture_args::{self, EcaptureOptions};
+use crate::core::helper_protocol;
+use crate::core::models::ProtocolStatus;
+use crate::core::redaction::{RedactionConfig, Redactor};
+use crate::core::session_timeouts::SessionTimeouts;
+use crate::core::sessions::SessionRegistry;
+use crate::services::capture::BinarySource;
+use crate::services::ecapture_output::OutputRing;
 
-use anyhow::{Error, Result};
+use anyhow::{Error, Result, anyhow};
+use log::error;
 // use log::Level::Error;
 
 #[derive(Clone)]
@@ -18,12 +30,28 @@ pub enum RunState {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Configs {
     pub ws_url: Option<String>,
+    /// Raw arguments, used when `ecapture_options` is not set.
     pub ecapture_args: Option<String>,
     #[serde(default)]
+    pub ecapture_options: Option<EcaptureOptions>,
+    /// User-provided ecapture binary run instead of the bundled one.
+    #[serde(default)]
+    pub ecapture_path: Option<String>,
+    /// Expected SHA-256 of `ecapture_path`, hex encoded.
+    #[serde(default)]
+    pub ecapture_sha256: Option<String>,
+    #[serde(default)]
     pub user_sql: Option<String>,
+    /// Grace periods of capture sessions; defaults when not set.
+    #[serde(default)]
+    pub session_timeouts: Option<SessionTimeouts>,
+    /// Rules for redacting secrets; off when not set.
+    #[serde(default)]
+    pub redaction: Option<RedactionConfig>,
 }
 
 const CONFIG_FILE_NAME: &str = "config.json";
+const DEFAULT_WS_URL: &str = "ws://127.0.0.1:28257";
 
 impl Configs {
     pub fn apply_patch(&mut self, patch: &mut Configs) {
@@ -35,9 +63,97 @@ impl Configs {
             self.ecapture_args = Some(ecapture_args);
         }
 
+        if let Some(ecapture_options) = patch.ecapture_options.take() {
+            self.ecapture_options = Some(ecapture_options);
+        }
+
+        if patch.ecapture_path.is_some() {
+            self.ecapture_path = patch.ecapture_path.take();
+            self.ecapture_sha256 = patch.ecapture_sha256.take();
+        }
+
         if patch.user_sql.is_some() {
             self.user_sql = patch.user_sql.take();
         }
+
+        if let Some(session_timeouts) = patch.session_timeouts.take() {
+            self.session_timeouts = Some(session_timeouts);
+        }
+
+        if let Some(redaction) = patch.redaction.take() {
+            self.redaction = Some(redaction);
+        }
+    }
+
+    /// Arguments of the bundled ecapture, from the typed options if set and
+    /// from the raw string otherwise.
+    pub fn ecapture_argv(&self) -> Result<Vec<String>> {
+        match &self.ecapture_options {
+            Some(options) => self.ecapture_argv_for(options),
+            None => {
+                let argv =
+                    ecapture_args::split_args(self.ecapture_args.as_deref().unwrap_or_default())?;
+                if !argv.is_empty() {
+                    self.check_raw_argv(&argv)?;
+                }
+                Ok(argv)
+            }
+        }
+    }
+
+    /// Raw arguments are run as root too, so like typed options they must
+    /// stream to the configured WebSocket URL, on a loopback address.
+    fn check_raw_argv(&self, argv: &[String]) -> Result<()> {
+        let ws_url = self.ws_url.as_deref().unwrap_or(DEFAULT_WS_URL);
+        helper_protocol::loopback_address(ws_url)?;
+        match ecapture_args::ecaptureq_url(argv)? {
+            Some(url) if url == ws_url => Ok(()),
+            Some(url) => Err(anyhow!(
+                "--ecaptureq {} differs from the WebSocket URL {}",
+                url,
+                ws_url
+            )),
+            None => Err(anyhow!("raw arguments need --ecaptureq {}", ws_url)),
+        }
+    }
+
+    /// Arguments for `options` instead of the configured ones, streaming to
+    /// the configured server.
+    pub fn ecapture_argv_for(&self, options: &EcaptureOptions) -> Result<Vec<String>> {
+        options.to_argv(self.ws_url.as_deref().unwrap_or(DEFAULT_WS_URL))
+    }
+
+    pub fn timeouts(&self) -> SessionTimeouts {
+        self.session_timeouts.unwrap_or_default()
+    }
+
+    /// The redactor for the configured rules, `None` if redaction is off.
+    pub fn redactor(&self) -> Result<Option<Redactor>> {
+        match &self.redaction {
+            Some(config) => Redactor::new(config),
+            None => Ok(None),
+        }
+    }
+
+    /// The ecapture binary to run: the external one if a path is set, which
+    /// then needs a hash pinned, and the bundled one otherwise.
+    pub fn ecapture_binary(&self) -> Result<BinarySource> {
+        let Some(path) = &self.ecapture_path else {
+            return Ok(BinarySource::Bundled);
+        };
+        if !path.starts_with('/') {
+            return Err(anyhow!("eCapture path must be absolute: {}", path));
+        }
+        let sha256 = self.ecapture_sha256.as_deref().unwrap_or_default().trim();
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!(
+                "an external eCapture binary needs its SHA-256 pinned as 64 hex digits"
+            ));
+        }
+        Ok(BinarySource::External {
+            path: PathBuf::from(path),
+            sha256: sha256.to_ascii_lowercase(),
+        })
     }
 
     fn to_json(&self) -> serde_json::Result<String> {
@@ -62,9 +178,14 @@ impl Configs {
 
     pub fn init() -> Self {
         Configs {
-            ws_url: Some("ws://127.0.0.1:28257".to_string()),
-            ecapture_args: Some(" tls --ecaptureq ws://127.0.0.1:28257".to_string()),
+            ws_url: Some(DEFAULT_WS_URL.to_string()),
+            ecapture_args: Some(format!("tls --ecaptureq {}", DEFAULT_WS_URL)),
+            ecapture_options: Some(EcaptureOptions::default()),
+            ecapture_path: None,
+            ecapture_sha256: None,
             user_sql: None,
+            session_timeouts: None,
+            redaction: None,
         }
     }
 }
@@ -83,10 +204,38 @@ pub fn config_check(base_path: impl AsRef<Path>) -> Result<()> {
     }
 }
 
+/// Names of the tasks of the capture session that have not ended yet, to
+/// tell which ones hang when it does not stop.
+#[derive(Clone, Default)]
+pub struct PendingTasks(Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+impl PendingTasks {
+    pub fn started(&self, name: &'static str) {
+        self.0.lock().unwrap().push(name);
+    }
+
+    pub fn ended(&self, name: &'static str) {
+        let mut names = self.0.lock().unwrap();
+        if let Some(i) = names.iter().position(|n| *n == name) {
+            names.remove(i);
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
 pub struct AppState {
     pub df_actor_handle: DataFrameActorHandle,
     pub shutdown_tx: Mutex<Option<watch::Sender<()>>>,
 
+    // tasks of the running capture session, done once it has shut down
+    pub session_wg: Mutex<Option<AsyncWaitGroup>>,
+
+    // names of the tasks counted by session_wg that have not ended
+    pub pending_tasks: PendingTasks,
+
     // will trigger if the whole app shutdown
     pub done: Mutex<watch::Sender<()>>,
 
@@ -100,6 +249,28 @@ pub struct AppState {
     pub shared_last_index: Arc<Mutex<u64>>,
 
     pub status: Arc<RwLock<RunState>>,
+
+    // capture sessions of this run, the rows of each tagged with its id
+    pub sessions: Arc<RwLock<SessionRegistry>>,
+
+    // version and decoding state of the ecapture stream
+    pub protocol_status: Arc<RwLock<ProtocolStatus>>,
+
+    // Android packages by uid, for annotating events
+    pub packages: Arc<RwLock<PackageIndex>>,
+
+    // recent stdout and stderr lines of the bundled ecapture
+    pub ecapture_output: OutputRing,
+
+    // structured payload decoders used by decode_payload
+    pub decoders: DecoderRegistry,
+
+    // redaction rules in effect, `None` if redaction is off
+    pub redactor: RwLock<Option<Arc<Redactor>>>,
+
+    // socket of the privileged helper running ecapture, when the app is not
+    // root itself
+    pub helper_socket: Option<PathBuf>,
 }
 
 impl AppState {
@@ -108,7 +279,58 @@ impl AppState {
             let mut configs_lock = self.configs.lock().await;
             *configs_lock = Some(configs.clone());
         }
-        let mut user_sql_lock = self.user_sql.lock().await;
-        *user_sql_lock = configs.user_sql.clone();
+        {
+            let mut user_sql_lock = self.user_sql.lock().await;
+            *user_sql_lock = configs.user_sql.clone();
+        }
+
+        let redactor = match configs.redactor() {
+            Ok(redactor) => redactor.map(Arc::new),
+            Err(e) => {
+                error!("Invalid redaction rules, redaction is off: {}", e);
+                None
+            }
+        };
+        let ingest = redactor.clone().filter(|r| r.redacts_ingest());
+        self.df_actor_handle.set_redactor(ingest).await;
+        *self.redactor.write().await = redactor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(ws_url: &str, args: &str) -> Configs {
+        Configs {
+            ws_url: Some(ws_url.to_string()),
+            ecapture_args: Some(args.to_string()),
+            ecapture_options: None,
+            ..Configs::init()
+        }
+    }
+
+    #[test]
+    fn raw_args_stream_to_the_configured_loopback_url() {
+        let local = "ws://127.0.0.1:28257";
+        assert!(
+            raw(local, "tls --ecaptureq ws://127.0.0.1:28257")
+                .ecapture_argv()
+                .is_ok()
+        );
+        assert!(raw(local, "").ecapture_argv().unwrap().is_empty());
+
+        assert!(raw(local, "tls").ecapture_argv().is_err());
+        assert!(
+            raw(local, "tls --ecaptureq ws://127.0.0.1:9999")
+                .ecapture_argv()
+                .is_err()
+        );
+        let remote = "ws://10.0.0.1:28257";
+        assert!(
+            raw(remote, "tls --ecaptureq ws://10.0.0.1:28257")
+                .ecapture_argv()
+                .is_err()
+        );
     }
 }
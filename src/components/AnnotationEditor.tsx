@@ -0,0 +1,97 @@
+import { useEffect, useState } from "react";
+import { Tag, X } from "lucide-react";
+import type { PacketAnnotation } from "@/types";
+
+interface AnnotationEditorProps {
+  annotation?: PacketAnnotation;
+  onTag: (tag: string) => Promise<void>;
+  onUntag: (tag: string) => Promise<void>;
+  onNote: (note: string) => Promise<void>;
+}
+
+// 数据包的标签与备注，可在 SQL 中通过 tags / note 列筛选
+export function AnnotationEditor({
+  annotation,
+  onTag,
+  onUntag,
+  onNote,
+}: AnnotationEditorProps) {
+  const [newTag, setNewTag] = useState("");
+  const [note, setNote] = useState(annotation?.note ?? "");
+  const [error, setError] = useState<string | null>(null);
+
+  // 切换数据包或保存后同步备注
+  useEffect(() => {
+    setNote(annotation?.note ?? "");
+  }, [annotation?.index, annotation?.note]);
+
+  const run = async (action: () => Promise<void>) => {
+    setError(null);
+    try {
+      await action();
+    } catch (e) {
+      setError(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  const addTag = () => {
+    const tag = newTag.trim();
+    if (!tag) return;
+    run(async () => {
+      await onTag(tag);
+      setNewTag("");
+    });
+  };
+
+  return (
+    <div>
+      <h3 className="text-sm font-medium text-gray-900 dark:text-gray-100 mb-3 flex items-center">
+        <Tag className="w-4 h-4 mr-2" />
+        Tags & Note
+      </h3>
+      <div className="space-y-2 text-sm">
+        <div className="flex flex-wrap items-center gap-2">
+          {annotation?.tags.map((tag) => (
+            <span
+              key={tag}
+              className="flex items-center space-x-1 px-2 py-0.5 rounded bg-blue-100 text-blue-800 dark:bg-blue-900/40 dark:text-blue-300"
+            >
+              <span>{tag}</span>
+              <button
+                onClick={() => run(() => onUntag(tag))}
+                className="hover:text-blue-600 dark:hover:text-blue-100"
+                title="Remove tag"
+              >
+                <X className="w-3 h-3" />
+              </button>
+            </span>
+          ))}
+          <input
+            value={newTag}
+            onChange={(e) => setNewTag(e.target.value)}
+            onKeyDown={(e) => {
+              if (e.key === "Enter") addTag();
+            }}
+            placeholder="Add tag"
+            className="w-32 px-2 py-0.5 border border-gray-300 dark:border-gray-600 rounded bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
+          />
+        </div>
+        <textarea
+          value={note}
+          onChange={(e) => setNote(e.target.value)}
+          onBlur={() => {
+            if (note.trim() !== (annotation?.note ?? "")) {
+              run(() => onNote(note));
+            }
+          }}
+          rows={2}
+          placeholder="Note"
+          className="w-full px-2 py-1 border border-gray-300 dark:border-gray-600 rounded bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
+        />
+        {error && (
+          <div className="text-xs text-red-600 dark:text-red-400">{error}</div>
+        )}
+      </div>
+    </div>
+  );
+}
@@ -1,20 +1,34 @@
 import { X, Clock, Globe, Monitor, Database } from "lucide-react";
 import { useState, useEffect } from "react";
-import type { PacketData } from "@/types";
+import type { PacketAnnotation, PacketData } from "@/types";
 import { ApiService } from "@/services/apiService";
 import { useResponsive } from "@/hooks/useResponsive";
 import { formatTimestamp } from "@/utils/timeUtils";
+import { AnnotationEditor } from "./AnnotationEditor";
 
 interface DetailModalProps {
   packet: PacketData | null;
   onClose: () => void;
+  annotation?: PacketAnnotation;
+  onTag?: (tag: string) => Promise<void>;
+  onUntag?: (tag: string) => Promise<void>;
+  onNote?: (note: string) => Promise<void>;
 }
 
-export function DetailModal({ packet, onClose }: DetailModalProps) {
-  const [activeTab, setActiveTab] = useState<"overview" | "payload">(
-    "overview",
-  );
+export function DetailModal({
+  packet,
+  onClose,
+  annotation,
+  onTag,
+  onUntag,
+  onNote,
+}: DetailModalProps) {
+  const [activeTab, setActiveTab] = useState<
+    "overview" | "payload" | "structured"
+  >("overview");
   const [decodedPayload, setDecodedPayload] = useState<string>("Loading...");
+  const [structuredPayload, setStructuredPayload] =
+    useState<string>("Loading...");
   const { isMobile } = useResponsive();
 
   if (!packet) return null;
@@ -96,7 +110,16 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
             packet.index,
           );
 
-          if (fullPacket.is_binary) {
+          const decoded = fullPacket.decoded_body;
+          if (decoded && !decoded.is_binary) {
+            // Compressed HTTP body, already decoded by the backend
+            const notes = [`Content-Encoding: ${decoded.content_encoding} (decoded)`];
+            if (decoded.truncated) notes.push("body truncated");
+            if (decoded.error) notes.push(decoded.error);
+            setDecodedPayload(
+              `${decoded.head}\r\n\r\n${decoded.body_utf8}\n\n[${notes.join(", ")}]`,
+            );
+          } else if (fullPacket.is_binary) {
             // Display binary data as hex dump
             const hexDump = fullPacket.payload_binary
               .map((byte, i) => {
@@ -125,6 +148,23 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
       loadPayload();
     }, [packet.index]);
 
+    // Structured view (JSON, XML, protobuf, ...) decoded by the backend
+    useEffect(() => {
+      const loadStructured = async () => {
+        try {
+          setStructuredPayload("Loading...");
+          const decoded = await ApiService.decodePayload(packet.index);
+          setStructuredPayload(
+            `[${decoded.decoder}]\n${JSON.stringify(decoded.tree, null, 2)}`,
+          );
+        } catch (error) {
+          setStructuredPayload(`No structured view available: ${error}`);
+        }
+      };
+
+      loadStructured();
+    }, [packet.index]);
+
     // Protocol type mapping
     const getProtocolName = (type: number) => {
       try {
@@ -194,12 +234,31 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
             >
               Payload
             </button>
+            <button
+              onClick={() => setActiveTab("structured")}
+              className={`px-4 py-2 text-sm font-medium border-b-2 ${
+                activeTab === "structured"
+                  ? "border-blue-500 text-blue-600 dark:text-blue-400"
+                  : "border-transparent text-gray-500 hover:text-gray-700 dark:text-gray-400 dark:hover:text-gray-300"
+              }`}
+            >
+              Decoded
+            </button>
           </div>
 
           {/* Content */}
           <div className="flex-1 overflow-auto p-4 flex flex-col">
             {activeTab === "overview" ? (
               <div className="space-y-6">
+                {onTag && onUntag && onNote && (
+                  <AnnotationEditor
+                    annotation={annotation}
+                    onTag={onTag}
+                    onUntag={onUntag}
+                    onNote={onNote}
+                  />
+                )}
+
                 {/* Basic Information */}
                 <div>
                   <h3 className="text-sm font-medium text-gray-900 dark:text-gray-100 mb-3 flex items-center">
@@ -235,6 +294,26 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
                         {getProtocolName(packet.type)}
                       </div>
                     </div>
+                    <div>
+                      <span className="text-gray-500 dark:text-gray-400">
+                        Direction:
+                      </span>
+                      <div>{packet.direction || "unknown"}</div>
+                    </div>
+                    <div>
+                      <span className="text-gray-500 dark:text-gray-400">
+                        Capture Module:
+                      </span>
+                      <div>{packet.module ?? "unknown"}</div>
+                    </div>
+                    <div>
+                      <span className="text-gray-500 dark:text-gray-400">
+                        Application Protocol:
+                      </span>
+                      <div className="font-semibold">
+                        {packet.app_proto || "unknown"}
+                      </div>
+                    </div>
                     <div>
                       <span className="text-gray-500 dark:text-gray-400">
                         Data Size:
@@ -295,10 +374,18 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
                       </span>
                       <div>{packet.pid !== undefined ? packet.pid : "N/A"}</div>
                     </div>
+                    {packet.package && (
+                      <div>
+                        <span className="text-gray-500 dark:text-gray-400">
+                          Package:
+                        </span>
+                        <div className="font-semibold">{packet.package}</div>
+                      </div>
+                    )}
                   </div>
                 </div>
               </div>
-            ) : (
+            ) : activeTab === "payload" ? (
               <div className="flex flex-col h-full">
                 <h3 className="text-sm font-medium text-gray-900 dark:text-gray-100 flex items-center mb-4">
                   <Database className="w-4 h-4 mr-2" />
@@ -318,6 +405,22 @@ export function DetailModal({ packet, onClose }: DetailModalProps) {
                   </div>
                 </div>
               </div>
+            ) : (
+              <div className="flex flex-col h-full">
+                <h3 className="text-sm font-medium text-gray-900 dark:text-gray-100 flex items-center mb-4">
+                  <Database className="w-4 h-4 mr-2" />
+                  Decoded Payload
+                </h3>
+                <div
+                  className={`bg-gray-100 dark:bg-gray-900 rounded-md p-4 overflow-auto flex-1 ${
+                    isMobile ? "mb-4" : "mb-0"
+                  }`}
+                >
+                  <pre className="text-sm font-mono whitespace-pre-wrap break-all">
+                    {structuredPayload}
+                  </pre>
+                </div>
+              </div>
             )}
           </div>
         </div>
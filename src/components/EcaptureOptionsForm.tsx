@@ -0,0 +1,155 @@
+import type { EcaptureOptions, EcaptureSubcommand } from "@/types";
+
+interface EcaptureOptionsFormProps {
+  options: EcaptureOptions;
+  onChange: (options: EcaptureOptions) => void;
+}
+
+const SUBCOMMANDS: { value: EcaptureSubcommand; label: string }[] = [
+  { value: "tls", label: "tls (OpenSSL / BoringSSL)" },
+  { value: "gotls", label: "gotls (Go crypto/tls)" },
+  { value: "gnutls", label: "gnutls" },
+  { value: "nss", label: "nss" },
+  { value: "bash", label: "bash" },
+  { value: "mysqld", label: "mysqld" },
+  { value: "postgres", label: "postgres" },
+];
+
+const NETWORK_SUBCOMMANDS: EcaptureSubcommand[] = [
+  "tls",
+  "gotls",
+  "gnutls",
+  "nss",
+];
+
+const inputClassName =
+  "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 font-mono text-sm disabled:opacity-50";
+
+const labelClassName =
+  "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1";
+
+// 空输入表示不传该参数
+function toText(value: string): string | null {
+  return value.trim() === "" ? null : value;
+}
+
+function toNumber(value: string): number | null {
+  const trimmed = value.trim();
+  return trimmed === "" || isNaN(Number(trimmed)) ? null : Number(trimmed);
+}
+
+export function EcaptureOptionsForm({
+  options,
+  onChange,
+}: EcaptureOptionsFormProps) {
+  const update = (patch: Partial<EcaptureOptions>) =>
+    onChange({ ...options, ...patch });
+  const isNetwork = NETWORK_SUBCOMMANDS.includes(options.subcommand);
+
+  return (
+    <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+      <div className="md:col-span-2">
+        <label className={labelClassName}>Module</label>
+        <select
+          value={options.subcommand}
+          onChange={(e) =>
+            update({ subcommand: e.target.value as EcaptureSubcommand })
+          }
+          className={inputClassName}
+        >
+          {SUBCOMMANDS.map(({ value, label }) => (
+            <option key={value} value={value}>
+              {label}
+            </option>
+          ))}
+        </select>
+      </div>
+
+      <div>
+        <label className={labelClassName}>PID</label>
+        <input
+          type="number"
+          min={1}
+          value={options.pid ?? ""}
+          onChange={(e) => update({ pid: toNumber(e.target.value) })}
+          placeholder="All processes"
+          className={inputClassName}
+        />
+      </div>
+
+      <div>
+        <label className={labelClassName}>UID</label>
+        <input
+          type="number"
+          min={0}
+          value={options.uid ?? ""}
+          onChange={(e) => update({ uid: toNumber(e.target.value) })}
+          placeholder="All users"
+          className={inputClassName}
+        />
+      </div>
+
+      <div className="md:col-span-2">
+        <label className={labelClassName}>libssl Path</label>
+        <input
+          type="text"
+          value={options.libssl ?? ""}
+          onChange={(e) => update({ libssl: toText(e.target.value) })}
+          disabled={options.subcommand !== "tls"}
+          placeholder="/usr/lib/x86_64-linux-gnu/libssl.so.3"
+          className={inputClassName}
+        />
+      </div>
+
+      <div className="md:col-span-2">
+        <label className={labelClassName}>Go Binary Path</label>
+        <input
+          type="text"
+          value={options.elfpath ?? ""}
+          onChange={(e) => update({ elfpath: toText(e.target.value) })}
+          disabled={options.subcommand !== "gotls"}
+          placeholder="/usr/local/bin/myapp"
+          className={inputClassName}
+        />
+      </div>
+
+      <div>
+        <label className={labelClassName}>Interface</label>
+        <input
+          type="text"
+          value={options.interface ?? ""}
+          onChange={(e) => update({ interface: toText(e.target.value) })}
+          disabled={!isNetwork}
+          placeholder="eth0"
+          className={inputClassName}
+        />
+      </div>
+
+      <div>
+        <label className={labelClassName}>Port</label>
+        <input
+          type="number"
+          min={1}
+          max={65535}
+          value={options.port ?? ""}
+          onChange={(e) => update({ port: toNumber(e.target.value) })}
+          disabled={!isNetwork}
+          placeholder="443"
+          className={inputClassName}
+        />
+      </div>
+
+      <div className="md:col-span-2">
+        <label className={labelClassName}>pcap Filter</label>
+        <input
+          type="text"
+          value={options.pcap_filter ?? ""}
+          onChange={(e) => update({ pcap_filter: toText(e.target.value) })}
+          disabled={!isNetwork}
+          placeholder="tcp port 443"
+          className={inputClassName}
+        />
+      </div>
+    </div>
+  );
+}
@@ -140,7 +140,9 @@ export function NewPacketCard({
         {/* Process info */}
         <div className="flex items-center space-x-1 text-xs text-gray-600 dark:text-gray-400">
           <Monitor className="w-3 h-3 flex-shrink-0" />
-          <span className="truncate">{packet.pname || "N/A"}</span>
+          <span className="truncate" title={packet.pname}>
+            {packet.package || packet.pname || "N/A"}
+          </span>
           <span className="text-gray-400">
             ({packet.pid !== undefined ? packet.pid : "N/A"})
           </span>
@@ -0,0 +1,258 @@
+import { X, Loader2, RefreshCw } from "lucide-react";
+import { useCallback, useEffect, useMemo, useState } from "react";
+import type { AndroidPackage, CaptureScope, ProcessInfo } from "@/types";
+import { ApiService } from "@/services/apiService";
+import { useResponsive } from "@/hooks/useResponsive";
+
+interface ProcessPickerProps {
+  onPick: (pid: number, scope: CaptureScope) => void;
+  onPickPackage: (packageName: string) => void;
+  onClose: () => void;
+}
+
+export function ProcessPicker({
+  onPick,
+  onPickPackage,
+  onClose,
+}: ProcessPickerProps) {
+  const { isMobile } = useResponsive();
+  // 仅 Android 能列出已安装应用，其他平台为 null
+  const [packages, setPackages] = useState<AndroidPackage[] | null>(null);
+  const [activeTab, setActiveTab] = useState<"apps" | "processes">(
+    "processes",
+  );
+  const [processes, setProcesses] = useState<ProcessInfo[]>([]);
+  const [isLoading, setIsLoading] = useState(true);
+  const [error, setError] = useState<string | null>(null);
+  const [search, setSearch] = useState("");
+  // 默认只列出使用了 eCapture 能 hook 的 TLS 库的进程
+  const [tlsOnly, setTlsOnly] = useState(true);
+
+  const loadProcesses = useCallback(async () => {
+    setIsLoading(true);
+    try {
+      setProcesses(await ApiService.listProcesses());
+      setError(null);
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setIsLoading(false);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadProcesses();
+  }, [loadProcesses]);
+
+  useEffect(() => {
+    ApiService.listPackages()
+      .then((result) => {
+        setPackages(result);
+        setActiveTab("apps");
+      })
+      .catch(() => setPackages(null));
+  }, []);
+
+  const visiblePackages = useMemo(() => {
+    const keyword = search.trim().toLowerCase();
+    // 多用户时同一应用有多个 uid，只保留第一个（主用户）
+    const seen = new Set<string>();
+    return (packages ?? []).filter((p) => {
+      if (seen.has(p.name)) return false;
+      seen.add(p.name);
+      return (
+        keyword === "" ||
+        p.name.toLowerCase().includes(keyword) ||
+        String(p.uid) === keyword
+      );
+    });
+  }, [packages, search]);
+
+  const visible = useMemo(() => {
+    const keyword = search.trim().toLowerCase();
+    return processes.filter(
+      (p) =>
+        (!tlsOnly || p.tls_libraries.length > 0) &&
+        (keyword === "" ||
+          String(p.pid) === keyword ||
+          p.name.toLowerCase().includes(keyword) ||
+          p.cmdline.toLowerCase().includes(keyword)),
+    );
+  }, [processes, search, tlsOnly]);
+
+  return (
+    <div
+      className={`fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50 ${
+        isMobile ? "pt-[env(safe-area-inset-top)] p-0" : "p-4"
+      }`}
+    >
+      <div
+        className={`bg-white dark:bg-gray-800 rounded-lg shadow-xl flex flex-col ${
+          isMobile
+            ? "w-full h-full max-h-none rounded-none"
+            : "max-w-4xl w-full max-h-[90vh]"
+        }`}
+      >
+        {/* Header */}
+        <div className="flex items-center justify-between p-4 border-b border-gray-200 dark:border-gray-700">
+          <h2 className="text-lg font-semibold text-gray-900 dark:text-gray-100">
+            Capture an App or Process
+          </h2>
+          <div className="flex items-center space-x-1">
+            <button
+              onClick={loadProcesses}
+              disabled={isLoading}
+              className="p-1 hover:bg-gray-100 dark:hover:bg-gray-700 rounded-md"
+              title="Refresh"
+            >
+              <RefreshCw className="w-5 h-5" />
+            </button>
+            <button
+              onClick={onClose}
+              className="p-1 hover:bg-gray-100 dark:hover:bg-gray-700 rounded-md"
+            >
+              <X className="w-5 h-5" />
+            </button>
+          </div>
+        </div>
+
+        {/* Tabs */}
+        {packages && (
+          <div className="flex border-b border-gray-200 dark:border-gray-700">
+            {(["apps", "processes"] as const).map((tab) => (
+              <button
+                key={tab}
+                onClick={() => setActiveTab(tab)}
+                className={`px-4 py-2 text-sm font-medium border-b-2 ${
+                  activeTab === tab
+                    ? "border-blue-500 text-blue-600 dark:text-blue-400"
+                    : "border-transparent text-gray-500 hover:text-gray-700 dark:text-gray-400 dark:hover:text-gray-300"
+                }`}
+              >
+                {tab === "apps" ? "Apps" : "Processes"}
+              </button>
+            ))}
+          </div>
+        )}
+
+        {/* Filters */}
+        <div className="flex items-center space-x-4 p-4 border-b border-gray-200 dark:border-gray-700">
+          <input
+            type="text"
+            value={search}
+            onChange={(e) => setSearch(e.target.value)}
+            placeholder={
+              activeTab === "apps"
+                ? "Package name or UID"
+                : "Name, PID or command line"
+            }
+            className="flex-1 px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 text-sm"
+          />
+          {activeTab === "processes" && (
+            <label className="flex items-center space-x-2 text-sm text-gray-700 dark:text-gray-300 whitespace-nowrap">
+              <input
+                type="checkbox"
+                checked={tlsOnly}
+                onChange={(e) => setTlsOnly(e.target.checked)}
+                className="rounded border-gray-300 dark:border-gray-600"
+              />
+              <span>TLS only</span>
+            </label>
+          )}
+        </div>
+
+        {/* Process List */}
+        <div className="flex-1 overflow-auto">
+          {activeTab === "apps" ? (
+            visiblePackages.length === 0 ? (
+              <div className="p-4 text-sm text-gray-500 dark:text-gray-400">
+                No matching apps
+              </div>
+            ) : (
+              <ul className="divide-y divide-gray-200 dark:divide-gray-700">
+                {visiblePackages.map((p) => (
+                  <li
+                    key={p.name}
+                    className="flex items-center justify-between px-4 py-2 space-x-4"
+                  >
+                    <div className="min-w-0">
+                      <div className="text-sm font-medium text-gray-900 dark:text-gray-100 truncate">
+                        {p.name}
+                      </div>
+                      <div className="text-xs text-gray-500 dark:text-gray-400">
+                        uid {p.uid}
+                      </div>
+                    </div>
+                    <button
+                      onClick={() => onPickPackage(p.name)}
+                      className="px-3 py-1.5 text-sm bg-green-600 hover:bg-green-700 text-white rounded-md transition-colors flex-shrink-0"
+                      title="Capture this app"
+                    >
+                      Capture
+                    </button>
+                  </li>
+                ))}
+              </ul>
+            )
+          ) : isLoading ? (
+            <div className="flex items-center justify-center p-8 text-gray-500 dark:text-gray-400">
+              <Loader2 className="w-5 h-5 animate-spin" />
+            </div>
+          ) : error ? (
+            <div className="p-4 text-sm text-red-600 dark:text-red-400">
+              {error}
+            </div>
+          ) : visible.length === 0 ? (
+            <div className="p-4 text-sm text-gray-500 dark:text-gray-400">
+              No matching processes
+            </div>
+          ) : (
+            <ul className="divide-y divide-gray-200 dark:divide-gray-700">
+              {visible.map((p) => (
+                <li
+                  key={p.pid}
+                  className="flex items-center justify-between px-4 py-2 space-x-4"
+                >
+                  <div className="min-w-0">
+                    <div className="text-sm font-medium text-gray-900 dark:text-gray-100">
+                      {p.name}{" "}
+                      <span className="font-normal text-gray-500 dark:text-gray-400">
+                        pid {p.pid} · uid {p.uid}
+                        {p.tls_libraries.length > 0 &&
+                          ` · ${p.tls_libraries.join(", ")}`}
+                      </span>
+                    </div>
+                    <div
+                      className="font-mono text-xs text-gray-500 dark:text-gray-400 truncate"
+                      title={p.cmdline}
+                    >
+                      {p.cmdline || p.exe || "-"}
+                    </div>
+                  </div>
+                  <div className="flex items-center space-x-2 flex-shrink-0">
+                    <button
+                      onClick={() => onPick(p.pid, "process")}
+                      disabled={p.tls_libraries.length === 0}
+                      className="px-3 py-1.5 text-sm bg-green-600 hover:bg-green-700 disabled:opacity-50 text-white rounded-md transition-colors"
+                      title="Capture this process only"
+                    >
+                      Capture
+                    </button>
+                    <button
+                      onClick={() => onPick(p.pid, "user")}
+                      disabled={p.tls_libraries.length === 0}
+                      className="px-3 py-1.5 text-sm text-gray-600 dark:text-gray-400 border border-gray-300 dark:border-gray-600 hover:bg-gray-100 dark:hover:bg-gray-700 disabled:opacity-50 rounded-md transition-colors"
+                      title={`Capture all processes of uid ${p.uid}`}
+                    >
+                      User
+                    </button>
+                  </div>
+                </li>
+              ))}
+            </ul>
+          )}
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -1,7 +1,7 @@
 import { memo, useEffect, useRef, useState, useCallback } from "react";
 import { FixedSizeList as List } from "react-window";
 import { NewPacketCard } from "./NewPacketCard";
-import type { PacketData } from "@/types";
+import type { PacketAnnotation, PacketData } from "@/types";
 import { useResponsive } from "@/hooks/useResponsive";
 import { formatTimestamp as formatTimestampUtil } from "@/utils/timeUtils";
 
@@ -10,6 +10,7 @@ interface ResponsivePacketViewProps {
   onPacketClick: (packet: PacketData) => void;
   viewMode: "table" | "cards";
   autoScroll?: boolean;
+  annotations?: Record<number, PacketAnnotation>;
 }
 
 // Constants for card view
@@ -29,6 +30,8 @@ const TableRow = memo(
   }) => {
     const packet: PacketData = data.packets[index];
     const { onPacketClick } = data;
+    const annotation: PacketAnnotation | undefined =
+      data.annotations?.[packet.index];
 
     const formatTimestamp = (timestamp: number) => {
       return formatTimestampUtil(timestamp);
@@ -121,7 +124,10 @@ const TableRow = memo(
           {formatTimestamp(packet.timestamp)}
         </div>
         <div className="w-40 py-3 truncate pr-4 text-sm">
-          <span className="font-medium">{packet.pname || "N/A"}</span>
+          {/* Android 上优先显示包名 */}
+          <span className="font-medium" title={packet.pname}>
+            {packet.package || packet.pname || "N/A"}
+          </span>
           <span className="text-gray-500 dark:text-gray-400">
             {" "}
             ({packet.pid !== undefined ? packet.pid : "N/A"})
@@ -140,9 +146,16 @@ const TableRow = memo(
             {getProtocolInfo(packet.type).name}
           </span>
         </div>
-        <div className="w-24 py-3 truncate text-right text-sm text-gray-600 dark:text-gray-400">
+        <div className="w-24 py-3 truncate pr-4 text-right text-sm text-gray-600 dark:text-gray-400">
           {formatSize(packet.length)}
         </div>
+        <div
+          className="w-32 py-3 truncate text-xs text-blue-700 dark:text-blue-300"
+          title={annotation?.note || undefined}
+        >
+          {annotation?.tags.join(", ")}
+          {annotation?.note && !annotation.tags.length && "✎"}
+        </div>
       </div>
     );
   },
@@ -189,6 +202,7 @@ export function ResponsivePacketView({
   onPacketClick,
   viewMode,
   autoScroll = true,
+  annotations,
 }: ResponsivePacketViewProps) {
   const listRef = useRef<List>(null);
   const containerRef = useRef<HTMLDivElement>(null);
@@ -239,6 +253,7 @@ export function ResponsivePacketView({
   const itemData = {
     packets,
     onPacketClick,
+    annotations,
   };
 
   const itemHeight = viewMode === "table" ? TABLE_ROW_HEIGHT : CARD_HEIGHT;
@@ -254,7 +269,8 @@ export function ResponsivePacketView({
           <div className="w-48 pr-4">Source</div>
           <div className="w-48 pr-4">Destination</div>
           <div className="w-32 pr-4">Protocol</div>
-          <div className="w-24 text-right">Size</div>
+          <div className="w-24 pr-4 text-right">Size</div>
+          <div className="w-32">Tags</div>
         </div>
       )}
 
@@ -1,26 +1,159 @@
 import { useState, useCallback, useRef, useEffect } from "react";
 import { listen } from "@tauri-apps/api/event";
 import { ApiService } from "@/services/apiService";
-import type { PacketData } from "@/types";
+import type {
+  CaptureRestartEvent,
+  CaptureScope,
+  EcaptureOutputLine,
+  FlowData,
+  PacketAnnotation,
+  PacketData,
+  ProtocolStatus,
+  SessionInfo,
+} from "@/types";
+
+// 前端保留的 ecapture 输出行数，与后端一致
+const MAX_OUTPUT_LINES = 500;
 
 export interface AppState {
   isCapturing: boolean;
   isLoading: boolean;
   packets: PacketData[];
+  flows: FlowData[];
   selectedPacket: PacketData | null;
+  captureWarnings: string[];
+  ecaptureOutput: EcaptureOutputLine[];
+  sessions: SessionInfo[];
+  annotations: Record<number, PacketAnnotation>;
+}
+
+// 流记录按 flow_id 存放
+function flowsById(flows: FlowData[]): FlowData[] {
+  const byId: FlowData[] = [];
+  for (const flow of flows) {
+    byId[flow.flow_id] = flow;
+  }
+  return byId;
 }
 
 export function useAppState() {
   const [isCapturing, setIsCapturing] = useState(false);
   const [isLoading, setIsLoading] = useState(false);
   const [packets, setPackets] = useState<PacketData[]>([]);
+  const [flows, setFlows] = useState<FlowData[]>([]);
   const [selectedPacket, setSelectedPacket] = useState<PacketData | null>(null);
+  const [captureWarnings, setCaptureWarnings] = useState<string[]>([]);
+  const [ecaptureOutput, setEcaptureOutput] = useState<EcaptureOutputLine[]>(
+    [],
+  );
+  const [sessions, setSessions] = useState<SessionInfo[]>([]);
+  const [annotations, setAnnotations] = useState<
+    Record<number, PacketAnnotation>
+  >({});
 
   const unlistenRef = useRef<(() => void) | null>(null);
 
+  // ecapture 启动失败时也有输出，因此始终监听
+  useEffect(() => {
+    let unlistenOutput: (() => void) | null = null;
+    let cancelled = false;
+
+    ApiService.getEcaptureOutput()
+      .then((lines) => {
+        if (!cancelled) setEcaptureOutput(lines);
+      })
+      .catch(() => {});
+
+    listen<EcaptureOutputLine>("ecapture-output", (event) => {
+      setEcaptureOutput((prev) =>
+        [...prev, event.payload].slice(-MAX_OUTPUT_LINES),
+      );
+    }).then((unlisten) => {
+      if (cancelled) {
+        unlisten();
+      } else {
+        unlistenOutput = unlisten;
+      }
+    });
+
+    return () => {
+      cancelled = true;
+      if (unlistenOutput) {
+        unlistenOutput();
+      }
+    };
+  }, []);
+
+  // 删除数据后，被删除数据包的标注也随之删除
+  const refreshAnnotations = useCallback(async () => {
+    try {
+      const all = await ApiService.listAnnotations();
+      setAnnotations(Object.fromEntries(all.map((a) => [a.index, a])));
+    } catch {
+      // 保留旧标注
+    }
+  }, []);
+
+  const refreshSessions = useCallback(async () => {
+    try {
+      setSessions(await ApiService.listSessions());
+    } catch {
+      // 保留旧列表
+    }
+  }, []);
+
+  // 删除数据后，移除对应数据包并重新加载流记录（流会按剩余数据重建）
+  useEffect(() => {
+    const unlisteners: (() => void)[] = [];
+    let cancelled = false;
+
+    refreshSessions();
+    refreshAnnotations();
+
+    const reloadFlows = () => {
+      ApiService.getFlows()
+        .then((all) => setFlows(flowsById(all)))
+        .catch(() => {});
+      refreshSessions();
+      refreshAnnotations();
+    };
+
+    const keep = (unlisten: () => void) => {
+      if (cancelled) {
+        unlisten();
+      } else {
+        unlisteners.push(unlisten);
+      }
+    };
+
+    listen<number>("session-cleared", (event) => {
+      const sessionId = event.payload;
+      setPackets((prev) => prev.filter((p) => p.session_id !== sessionId));
+      setSelectedPacket((prev) =>
+        prev && prev.session_id === sessionId ? null : prev,
+      );
+      reloadFlows();
+    }).then(keep);
+
+    // 清空后保留下来的数据包，替换当前列表
+    listen<PacketData[]>("packets-reset", (event) => {
+      setPackets(event.payload);
+      setSelectedPacket(null);
+      reloadFlows();
+    }).then(keep);
+
+    return () => {
+      cancelled = true;
+      unlisteners.forEach((unlisten) => unlisten());
+    };
+  }, [refreshSessions, refreshAnnotations]);
+
   // 设置事件监听
   useEffect(() => {
     let unlisten: (() => void) | null = null;
+    let unlistenFlows: (() => void) | null = null;
+    let unlistenProtocol: (() => void) | null = null;
+    let unlistenRestart: (() => void) | null = null;
 
     const setupEventListener = async () => {
       try {
@@ -31,6 +164,48 @@ export function useAppState() {
           }
         });
         unlistenRef.current = unlisten;
+
+        // 流记录会原地更新，按 flow_id 合并
+        unlistenFlows = await listen<FlowData[]>("flow-data", (event) => {
+          const updatedFlows = event.payload;
+          if (updatedFlows.length > 0) {
+            setFlows((prev) => {
+              const merged = [...prev];
+              for (const flow of updatedFlows) {
+                merged[flow.flow_id] = flow;
+              }
+              return merged;
+            });
+          }
+        });
+
+        // 版本不匹配或无法解码的消息
+        unlistenProtocol = await listen<ProtocolStatus>(
+          "protocol-warning",
+          (event) => {
+            // 每次事件只新增一条警告
+            const { warnings } = event.payload;
+            const latest = warnings[warnings.length - 1];
+            if (latest) {
+              setCaptureWarnings((prev) => [...prev, latest]);
+            }
+          },
+        );
+
+        // ecapture 崩溃后自动重启，提示用户数据可能有缺口
+        unlistenRestart = await listen<CaptureRestartEvent>(
+          "ecapture-restart",
+          (event) => {
+            const { attempt, delay_ms, gave_up } = event.payload;
+            const notice = gave_up
+              ? `eCapture crashed ${attempt} times and will not be restarted; the capture session was stopped.`
+              : `eCapture exited unexpectedly and restarts in ${delay_ms / 1000}s (restart #${attempt}); packets captured meanwhile are missing.`;
+            setCaptureWarnings((prev) => [...prev, notice]);
+            if (gave_up) {
+              setIsCapturing(false);
+            }
+          },
+        );
       } catch (error) {
         console.error("Failed to setup event listener:", error);
       }
@@ -45,32 +220,67 @@ export function useAppState() {
       if (unlisten) {
         unlisten();
       }
+      if (unlistenFlows) {
+        unlistenFlows();
+      }
+      if (unlistenProtocol) {
+        unlistenProtocol();
+      }
+      if (unlistenRestart) {
+        unlistenRestart();
+      }
     };
   }, [isCapturing]);
 
   /**
    * 启动捕获会话
-   * 1. 调用 start_capture API
+   * 1. 调用 start_capture 或 capture_process API
    * 2. 设置事件监听状态（通过 isCapturing 状态变化触发 useEffect）
    */
-  const startCapture = useCallback(async () => {
-    if (isCapturing) return;
+  const launchCapture = useCallback(
+    async (start: () => Promise<void>) => {
+      if (isCapturing) return;
 
-    setIsLoading(true);
-    try {
-      // 启动后端捕获服务
-      await ApiService.startCapture();
+      setIsLoading(true);
+      setCaptureWarnings([]);
+      setEcaptureOutput([]);
+      try {
+        // 启动后端捕获服务
+        await start();
 
-      // 设置捕获状态为 true，这会触发 useEffect 设置事件监听
-      setIsCapturing(true);
-    } catch (error) {
-      console.error("启动流程出错:", error);
-      setIsCapturing(false);
-      throw error;
-    } finally {
-      setIsLoading(false);
-    }
-  }, [isCapturing]);
+        // 设置捕获状态为 true，这会触发 useEffect 设置事件监听
+        setIsCapturing(true);
+      } catch (error) {
+        console.error("启动流程出错:", error);
+        setIsCapturing(false);
+        throw error;
+      } finally {
+        setIsLoading(false);
+        // 启动失败的会话也会记录，附带原因
+        refreshSessions();
+      }
+    },
+    [isCapturing, refreshSessions],
+  );
+
+  const startCapture = useCallback(
+    () => launchCapture(() => ApiService.startCapture()),
+    [launchCapture],
+  );
+
+  // 只抓取指定进程（或其所属用户）的流量
+  const captureProcess = useCallback(
+    (pid: number, scope: CaptureScope) =>
+      launchCapture(() => ApiService.captureProcess(pid, scope)),
+    [launchCapture],
+  );
+
+  // 只抓取指定 Android 应用（按其 uid）的流量
+  const capturePackage = useCallback(
+    (packageName: string) =>
+      launchCapture(() => ApiService.capturePackage(packageName)),
+    [launchCapture],
+  );
 
   /**
    * 停止捕获会话
@@ -98,14 +308,82 @@ export function useAppState() {
       throw error;
     } finally {
       setIsLoading(false);
+      refreshSessions();
     }
-  }, [isCapturing]);
+  }, [isCapturing, refreshSessions]);
+
+  /**
+   * 只显示某个会话的数据包（未抓包时）
+   */
+  const showSession = useCallback(
+    async (sessionId: number) => {
+      if (isCapturing) return;
+      setPackets(await ApiService.getSessionPackets(sessionId));
+      setSelectedPacket(null);
+    },
+    [isCapturing],
+  );
+
+  /**
+   * 删除某个已结束会话的数据，界面由 session-cleared 事件更新
+   */
+  const clearSession = useCallback(async (sessionId: number) => {
+    await ApiService.clearSession(sessionId);
+  }, []);
+
+  /**
+   * 删除已捕获的数据包，可保留匹配 SQL 条件或当前会话的数据包；
+   * 界面由 packets-reset 事件更新
+   */
+  const clearPackets = useCallback(
+    async (keep: string | null = null, keepCurrentSession = false) => {
+      await ApiService.clearPackets(keep, keepCurrentSession);
+    },
+    [],
+  );
+
+  /**
+   * 给数据包加标签、去标签或写备注
+   */
+  const annotatePacket = useCallback(
+    async (update: Promise<PacketAnnotation>) => {
+      const annotation = await update;
+      setAnnotations((prev) => {
+        const next = { ...prev };
+        if (annotation.tags.length === 0 && annotation.note === "") {
+          delete next[annotation.index];
+        } else {
+          next[annotation.index] = annotation;
+        }
+        return next;
+      });
+    },
+    [],
+  );
+
+  const tagPacket = useCallback(
+    (index: number, tag: string) =>
+      annotatePacket(ApiService.tagPacket(index, tag)),
+    [annotatePacket],
+  );
+
+  const untagPacket = useCallback(
+    (index: number, tag: string) =>
+      annotatePacket(ApiService.untagPacket(index, tag)),
+    [annotatePacket],
+  );
+
+  const setPacketNote = useCallback(
+    (index: number, note: string) =>
+      annotatePacket(ApiService.setPacketNote(index, note)),
+    [annotatePacket],
+  );
 
   /**
-   * 清空数据包列表
+   * 关闭协议警告与重启提示
    */
-  const clearPackets = useCallback(() => {
-    setPackets([]);
+  const dismissCaptureWarnings = useCallback(() => {
+    setCaptureWarnings([]);
   }, []);
 
   /**
@@ -120,12 +398,26 @@ export function useAppState() {
     isCapturing,
     isLoading,
     packets,
+    flows,
     selectedPacket,
+    captureWarnings,
+    ecaptureOutput,
+    sessions,
+    annotations,
 
     // 操作方法
     startCapture,
+    captureProcess,
+    capturePackage,
     stopCapture,
     clearPackets,
     selectPacket,
+    dismissCaptureWarnings,
+    refreshSessions,
+    showSession,
+    clearSession,
+    tagPacket,
+    untagPacket,
+    setPacketNote,
   };
 }
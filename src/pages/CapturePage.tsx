@@ -1,10 +1,31 @@
-import { useCallback } from "react";
+import { useCallback, useEffect, useState } from "react";
 import { useAppState } from "@/hooks/useAppState";
 import { useResponsive } from "@/hooks/useResponsive";
 import { ResponsivePacketView } from "@/components/ResponsivePacketView";
 import { DetailModal } from "@/components/DetailModal";
-import { Play, Square, Loader2, Trash2 } from "lucide-react";
-import type { PacketData } from "@/types";
+import { ProcessPicker } from "@/components/ProcessPicker";
+import { ApiService } from "@/services/apiService";
+import {
+  Play,
+  Square,
+  Loader2,
+  Trash2,
+  AlertTriangle,
+  X,
+  Terminal,
+  Crosshair,
+  History,
+  Download,
+} from "lucide-react";
+import type {
+  CaptureScope,
+  EcaptureOutputLine,
+  ExportSummary,
+  PacketAnnotation,
+  PacketData,
+  ProcessInfo,
+  SessionInfo,
+} from "@/types";
 
 interface CapturePageProps {
   appState?: {
@@ -12,13 +33,33 @@ interface CapturePageProps {
     isLoading: boolean;
     packets: PacketData[];
     selectedPacket: PacketData | null;
+    captureWarnings: string[];
+    ecaptureOutput: EcaptureOutputLine[];
+    sessions: SessionInfo[];
+    annotations: Record<number, PacketAnnotation>;
     startCapture: () => Promise<void>;
+    captureProcess: (pid: number, scope: CaptureScope) => Promise<void>;
+    capturePackage: (packageName: string) => Promise<void>;
     stopCapture: () => Promise<void>;
-    clearPackets: () => void;
+    clearPackets: (
+      keep?: string | null,
+      keepCurrentSession?: boolean,
+    ) => Promise<void>;
     selectPacket: (packet: PacketData | null) => void;
+    dismissCaptureWarnings: () => void;
+    refreshSessions: () => Promise<void>;
+    showSession: (sessionId: number) => Promise<void>;
+    clearSession: (sessionId: number) => Promise<void>;
+    tagPacket: (index: number, tag: string) => Promise<void>;
+    untagPacket: (index: number, tag: string) => Promise<void>;
+    setPacketNote: (index: number, note: string) => Promise<void>;
   };
 }
 
+function formatTime(millis: number): string {
+  return new Date(millis).toLocaleTimeString();
+}
+
 export function CapturePage({ appState: providedAppState }: CapturePageProps) {
   const localAppState = useAppState();
   const appState = providedAppState || localAppState;
@@ -29,20 +70,92 @@ export function CapturePage({ appState: providedAppState }: CapturePageProps) {
     isLoading,
     packets,
     selectedPacket,
+    captureWarnings,
+    ecaptureOutput,
+    sessions,
+    annotations,
     startCapture,
+    captureProcess,
+    capturePackage,
     stopCapture,
     clearPackets,
     selectPacket,
+    dismissCaptureWarnings,
+    refreshSessions,
+    showSession,
+    clearSession,
+    tagPacket,
+    untagPacket,
+    setPacketNote,
   } = appState;
 
+  const [startError, setStartError] = useState<string | null>(null);
+  const [showOutput, setShowOutput] = useState(false);
+  const [showSessions, setShowSessions] = useState(false);
+  const [exportSummary, setExportSummary] = useState<ExportSummary | null>(
+    null,
+  );
+  const [showProcessPicker, setShowProcessPicker] = useState(false);
+  // 上次运行崩溃后残留的 eCapture 进程
+  const [orphans, setOrphans] = useState<ProcessInfo[]>([]);
+  const [isStoppingOrphans, setIsStoppingOrphans] = useState(false);
+
+  useEffect(() => {
+    ApiService.getOrphanedProcesses()
+      .then(setOrphans)
+      .catch(() => setOrphans([]));
+  }, []);
+
+  const handleStopOrphans = useCallback(async () => {
+    setIsStoppingOrphans(true);
+    try {
+      await ApiService.stopOrphanedProcesses();
+    } catch (error) {
+      setStartError(error instanceof Error ? error.message : String(error));
+    } finally {
+      setOrphans(await ApiService.getOrphanedProcesses().catch(() => []));
+      setIsStoppingOrphans(false);
+    }
+  }, []);
+
   const handleStart = useCallback(async () => {
+    setStartError(null);
     try {
       await startCapture();
     } catch (error) {
       console.error("Failed to start capture:", error);
+      setStartError(error instanceof Error ? error.message : String(error));
     }
   }, [startCapture]);
 
+  const handleCaptureProcess = useCallback(
+    async (pid: number, scope: CaptureScope) => {
+      setShowProcessPicker(false);
+      setStartError(null);
+      try {
+        await captureProcess(pid, scope);
+      } catch (error) {
+        console.error("Failed to capture process:", error);
+        setStartError(error instanceof Error ? error.message : String(error));
+      }
+    },
+    [captureProcess],
+  );
+
+  const handleCapturePackage = useCallback(
+    async (packageName: string) => {
+      setShowProcessPicker(false);
+      setStartError(null);
+      try {
+        await capturePackage(packageName);
+      } catch (error) {
+        console.error("Failed to capture package:", error);
+        setStartError(error instanceof Error ? error.message : String(error));
+      }
+    },
+    [capturePackage],
+  );
+
   const handleStop = useCallback(async () => {
     try {
       await stopCapture();
@@ -71,11 +184,38 @@ export function CapturePage({ appState: providedAppState }: CapturePageProps) {
     selectPacket(null);
   }, [selectPacket]);
 
-  const handleClear = useCallback(() => {
-    if (packets.length > 0) {
-      clearPackets();
+  const toggleSessions = useCallback(() => {
+    // 打开时刷新行数
+    if (!showSessions) refreshSessions();
+    setShowSessions(!showSessions);
+  }, [refreshSessions, showSessions]);
+
+  const handleSessionAction = useCallback(
+    async (action: () => Promise<void>) => {
+      try {
+        await action();
+      } catch (error) {
+        setStartError(error instanceof Error ? error.message : String(error));
+      }
+    },
+    [],
+  );
+
+  const handleClear = useCallback(async () => {
+    try {
+      await clearPackets();
+    } catch (error) {
+      setStartError(error instanceof Error ? error.message : String(error));
     }
-  }, [clearPackets, packets.length]);
+  }, [clearPackets]);
+
+  const handleExport = useCallback(async () => {
+    try {
+      setExportSummary(await ApiService.exportPackets());
+    } catch (error) {
+      setStartError(error instanceof Error ? error.message : String(error));
+    }
+  }, []);
 
   return (
     <div className="h-full flex flex-col overflow-hidden">
@@ -104,6 +244,55 @@ export function CapturePage({ appState: providedAppState }: CapturePageProps) {
 
             {/* Control Buttons */}
             <div className="flex items-center space-x-2 flex-shrink-0">
+              {/* eCapture Output Toggle */}
+              <button
+                onClick={() => setShowOutput((show) => !show)}
+                className={`flex items-center space-x-2 px-3 py-2 rounded-md text-sm font-medium transition-colors ${
+                  showOutput
+                    ? "text-blue-700 dark:text-blue-300 bg-blue-100 dark:bg-blue-900/40"
+                    : "text-gray-600 dark:text-gray-400 bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600"
+                }`}
+                title="eCapture output"
+              >
+                <Terminal size={16} />
+                {!isMobile && <span>Output</span>}
+              </button>
+
+              {/* Sessions Toggle */}
+              <button
+                onClick={toggleSessions}
+                className={`flex items-center space-x-2 px-3 py-2 rounded-md text-sm font-medium transition-colors ${
+                  showSessions
+                    ? "text-blue-700 dark:text-blue-300 bg-blue-100 dark:bg-blue-900/40"
+                    : "text-gray-600 dark:text-gray-400 bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600"
+                }`}
+                title="Capture sessions"
+              >
+                <History size={16} />
+                {!isMobile && <span>Sessions</span>}
+              </button>
+
+              {/* Capture a Process */}
+              <button
+                onClick={() => setShowProcessPicker(true)}
+                disabled={isCapturing || isLoading}
+                className="flex items-center space-x-2 px-3 py-2 rounded-md text-sm font-medium text-gray-600 dark:text-gray-400 bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600 disabled:opacity-50 transition-colors"
+                title="Capture a single app or process"
+              >
+                <Crosshair size={16} />
+                {!isMobile && <span>Process</span>}
+              </button>
+
+              {/* Export, redacted unless redaction is off */}
+              <button
+                onClick={handleExport}
+                className="flex items-center space-x-2 px-3 py-2 rounded-md text-sm font-medium text-gray-600 dark:text-gray-400 bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600 transition-colors"
+                title="Export packets with payloads, tags and notes"
+              >
+                <Download size={16} />
+                {!isMobile && <span>Export</span>}
+              </button>
+
               {/* Clear Button - always visible */}
               <button
                 onClick={handleClear}
@@ -141,17 +330,223 @@ export function CapturePage({ appState: providedAppState }: CapturePageProps) {
         </div>
       </div>
 
+      {/* Launch Error */}
+      {exportSummary && (
+        <div className="mx-6 mt-4 flex items-start space-x-3 rounded-lg border border-green-200 dark:border-green-800 bg-green-50 dark:bg-green-900/20 p-4 text-sm text-green-700 dark:text-green-300">
+          <div className="flex-1 min-w-0 space-y-1">
+            <div className="break-all">
+              Exported {exportSummary.packets.toLocaleString()} packets to{" "}
+              <span className="font-mono">{exportSummary.path}</span>
+            </div>
+            {exportSummary.redaction ? (
+              <div className="font-mono text-xs">
+                {exportSummary.redaction.records} redacted
+                {Object.entries(exportSummary.redaction.matches).map(
+                  ([rule, count]) => ` · ${rule}: ${count}`,
+                )}
+                {exportSummary.redaction.binary_payloads_removed > 0 &&
+                  ` · binary payloads removed: ${exportSummary.redaction.binary_payloads_removed}`}
+              </div>
+            ) : (
+              <div className="text-xs">Redaction is off</div>
+            )}
+          </div>
+          <button
+            onClick={() => setExportSummary(null)}
+            className="flex-shrink-0 hover:text-green-500 dark:hover:text-green-100"
+            title="Dismiss"
+          >
+            <X size={16} />
+          </button>
+        </div>
+      )}
+
+      {startError && (
+        <div className="mx-6 mt-4 flex items-start space-x-3 rounded-lg border border-red-200 dark:border-red-800 bg-red-50 dark:bg-red-900/20 p-4 text-sm text-red-700 dark:text-red-300">
+          <pre className="flex-1 whitespace-pre-wrap break-words font-mono text-xs">
+            {startError}
+          </pre>
+          <button
+            onClick={() => setStartError(null)}
+            className="flex-shrink-0 hover:text-red-500 dark:hover:text-red-100"
+            title="Dismiss"
+          >
+            <X size={16} />
+          </button>
+        </div>
+      )}
+
+      {/* Orphaned eCapture Processes */}
+      {orphans.length > 0 && (
+        <div className="mx-6 mt-4 flex items-start space-x-3 rounded-lg border border-yellow-200 dark:border-yellow-800 bg-yellow-50 dark:bg-yellow-900/20 p-4 text-sm text-yellow-800 dark:text-yellow-300">
+          <AlertTriangle size={16} className="mt-0.5 flex-shrink-0" />
+          <div className="flex-1 space-y-1">
+            <div>
+              eCapture processes from an earlier session are still running and
+              may keep their probes attached:
+            </div>
+            <ul className="font-mono text-xs">
+              {orphans.map((p) => (
+                <li key={p.pid}>
+                  pid {p.pid} · {p.cmdline || p.exe || p.name}
+                </li>
+              ))}
+            </ul>
+          </div>
+          <button
+            onClick={handleStopOrphans}
+            disabled={isStoppingOrphans}
+            className="flex-shrink-0 px-3 py-1.5 text-sm bg-yellow-600 hover:bg-yellow-700 disabled:opacity-50 text-white rounded-md transition-colors"
+          >
+            {isStoppingOrphans ? (
+              <Loader2 size={16} className="animate-spin" />
+            ) : (
+              "Stop"
+            )}
+          </button>
+          <button
+            onClick={() => setOrphans([])}
+            className="flex-shrink-0 hover:text-yellow-600 dark:hover:text-yellow-100"
+            title="Dismiss"
+          >
+            <X size={16} />
+          </button>
+        </div>
+      )}
+
+      {/* eCapture Output */}
+      {showOutput && (
+        <div className="mx-6 mt-4 max-h-48 overflow-auto rounded-lg border border-gray-200 dark:border-gray-700 bg-gray-900 p-3 font-mono text-xs">
+          {ecaptureOutput.length === 0 ? (
+            <div className="text-gray-400">No output from eCapture</div>
+          ) : (
+            ecaptureOutput.map((line, i) => (
+              <div
+                key={i}
+                className={`whitespace-pre-wrap break-all ${
+                  line.stream === "stderr" ? "text-red-400" : "text-gray-200"
+                }`}
+              >
+                {line.line}
+              </div>
+            ))
+          )}
+        </div>
+      )}
+
+      {/* Capture Sessions */}
+      {showSessions && (
+        <div className="mx-6 mt-4 max-h-48 overflow-auto rounded-lg border border-gray-200 dark:border-gray-700 bg-white dark:bg-gray-800 text-sm">
+          {sessions.length === 0 ? (
+            <div className="p-3 text-gray-500 dark:text-gray-400">
+              No capture sessions yet
+            </div>
+          ) : (
+            <ul className="divide-y divide-gray-200 dark:divide-gray-700">
+              {sessions.map((session) => {
+                const running = session.stopped_at === null;
+                return (
+                  <li
+                    key={session.session_id}
+                    className="flex items-center space-x-3 px-3 py-2"
+                  >
+                    <div className="flex-1 min-w-0">
+                      <div className="text-gray-700 dark:text-gray-200">
+                        #{session.session_id} · {formatTime(session.started_at)}
+                        {" – "}
+                        {running ? "running" : formatTime(session.stopped_at!)}
+                        {" · "}
+                        {session.rows.toLocaleString()} rows
+                      </div>
+                      <div
+                        className="truncate font-mono text-xs text-gray-500 dark:text-gray-400"
+                        title={session.ecapture_args.join(" ")}
+                      >
+                        {session.ecapture_args.join(" ") || session.source_url}
+                        {session.exit_reason &&
+                          session.exit_reason !== "stopped" &&
+                          ` · ${session.exit_reason}`}
+                      </div>
+                    </div>
+                    <button
+                      onClick={() =>
+                        handleSessionAction(() =>
+                          showSession(session.session_id),
+                        )
+                      }
+                      disabled={isCapturing}
+                      className="flex-shrink-0 px-3 py-1.5 text-sm text-gray-600 dark:text-gray-300 bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600 disabled:opacity-50 rounded-md transition-colors"
+                      title="Show only the packets of this session"
+                    >
+                      Show
+                    </button>
+                    <button
+                      onClick={() =>
+                        handleSessionAction(() =>
+                          clearSession(session.session_id),
+                        )
+                      }
+                      disabled={running}
+                      className="flex-shrink-0 p-1.5 text-gray-500 hover:text-red-600 dark:text-gray-400 dark:hover:text-red-400 disabled:opacity-50 transition-colors"
+                      title="Drop the rows of this session"
+                    >
+                      <Trash2 size={16} />
+                    </button>
+                  </li>
+                );
+              })}
+            </ul>
+          )}
+        </div>
+      )}
+
+      {/* Protocol Warnings and Restart Notices */}
+      {captureWarnings.length > 0 && (
+        <div className="mx-6 mt-4 flex items-start space-x-3 rounded-lg border border-yellow-200 dark:border-yellow-800 bg-yellow-50 dark:bg-yellow-900/20 p-4 text-sm text-yellow-800 dark:text-yellow-300">
+          <AlertTriangle size={16} className="mt-0.5 flex-shrink-0" />
+          <ul className="flex-1 space-y-1">
+            {captureWarnings.map((warning) => (
+              <li key={warning}>{warning}</li>
+            ))}
+          </ul>
+          <button
+            onClick={dismissCaptureWarnings}
+            className="flex-shrink-0 hover:text-yellow-600 dark:hover:text-yellow-100"
+            title="Dismiss"
+          >
+            <X size={16} />
+          </button>
+        </div>
+      )}
+
       {/* Packet View */}
       <ResponsivePacketView
         packets={packets}
         onPacketClick={handlePacketClick}
         viewMode={isMobile ? "cards" : "table"}
         autoScroll={true}
+        annotations={annotations}
       />
 
+      {/* Process Picker */}
+      {showProcessPicker && (
+        <ProcessPicker
+          onPick={handleCaptureProcess}
+          onPickPackage={handleCapturePackage}
+          onClose={() => setShowProcessPicker(false)}
+        />
+      )}
+
       {/* Detail Modal */}
       {selectedPacket && (
-        <DetailModal packet={selectedPacket} onClose={handleModalClose} />
+        <DetailModal
+          packet={selectedPacket}
+          onClose={handleModalClose}
+          annotation={annotations[selectedPacket.index]}
+          onTag={(tag) => tagPacket(selectedPacket.index, tag)}
+          onUntag={(tag) => untagPacket(selectedPacket.index, tag)}
+          onNote={(note) => setPacketNote(selectedPacket.index, note)}
+        />
       )}
     </div>
   );
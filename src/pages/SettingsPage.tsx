@@ -8,12 +8,73 @@ import {
   Github,
   Filter,
   FileText,
+  HardDrive,
+  Timer,
+  EyeOff,
 } from "lucide-react";
 import { useNavigate } from "react-router-dom";
 import { useResponsive } from "@/hooks/useResponsive";
 import { useConfigs } from "@/hooks/useConfigs";
-import { useState } from "react";
+import { useCallback, useEffect, useRef, useState } from "react";
 import { open } from "@tauri-apps/plugin-shell";
+import { EcaptureOptionsForm } from "@/components/EcaptureOptionsForm";
+import { ApiService } from "@/services/apiService";
+import type {
+  EcaptureInfo,
+  EcaptureOptions,
+  RedactionConfig,
+  RedactionMode,
+  RedactionReport,
+  SessionTimeouts,
+} from "@/types";
+
+// 与后端 SessionTimeouts 的默认值一致
+const DEFAULT_SESSION_TIMEOUTS: SessionTimeouts = {
+  launch_ms: 10000,
+  interrupt_grace_ms: 2000,
+  terminate_grace_ms: 2000,
+  exit_ms: 8000,
+};
+
+const TIMEOUT_FIELDS: { key: keyof SessionTimeouts; label: string }[] = [
+  { key: "launch_ms", label: "Launch Timeout (ms)" },
+  { key: "interrupt_grace_ms", label: "SIGINT Grace (ms)" },
+  { key: "terminate_grace_ms", label: "SIGTERM Grace (ms)" },
+  { key: "exit_ms", label: "Exit Timeout (ms)" },
+];
+
+// 与后端 RedactionConfig 的默认值一致
+const DEFAULT_REDACTION: RedactionConfig = {
+  mode: "off",
+  headers: ["Authorization", "Proxy-Authorization", "Cookie", "Set-Cookie"],
+  json_keys: [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "access_token",
+    "refresh_token",
+    "api_key",
+  ],
+  patterns: ["eyJ[\\w-]+\\.[\\w-]+\\.[\\w-]+"],
+};
+
+const REDACTION_MODES: { value: RedactionMode; label: string }[] = [
+  { value: "off", label: "Off" },
+  { value: "ingest", label: "Before storing (hidden from SQL too)" },
+  { value: "display", label: "When shown and exported" },
+  { value: "export", label: "Only when exported" },
+];
+
+// 每行一项，保留空行以便编辑，后端会忽略空项
+const REDACTION_LISTS: {
+  key: "headers" | "json_keys" | "patterns";
+  label: string;
+}[] = [
+  { key: "headers", label: "HTTP Headers" },
+  { key: "json_keys", label: "JSON Keys" },
+  { key: "patterns", label: "Regex Patterns" },
+];
 
 export function SettingsPage() {
   const navigate = useNavigate();
@@ -28,6 +89,45 @@ export function SettingsPage() {
   } = useConfigs();
   const [saveLoading, setSaveLoading] = useState(false);
   const [errorMessage, setErrorMessage] = useState<string | null>(null);
+  // 切换到原始参数模式时保留结构化参数，切回时恢复
+  const lastOptionsRef = useRef<EcaptureOptions>({ subcommand: "tls" });
+  const [ecaptureInfo, setEcaptureInfo] = useState<EcaptureInfo | null>(null);
+  const [ecaptureInfoError, setEcaptureInfoError] = useState<string | null>(
+    null,
+  );
+  const [redactionReport, setRedactionReport] =
+    useState<RedactionReport | null>(null);
+  const [redactionReportError, setRedactionReportError] = useState<
+    string | null
+  >(null);
+  const redaction = configs.redaction ?? DEFAULT_REDACTION;
+
+  // 按已保存的规则统计脱敏情况
+  const loadRedactionReport = async () => {
+    try {
+      const report = await ApiService.getRedactionReport();
+      setRedactionReport(report);
+      setRedactionReportError(report ? null : "Redaction is off");
+    } catch (error) {
+      setRedactionReport(null);
+      setRedactionReportError(String(error));
+    }
+  };
+
+  // 读取已保存配置对应的 ecapture，保存后重新读取
+  const loadEcaptureInfo = useCallback(async () => {
+    try {
+      setEcaptureInfo(await ApiService.getEcaptureInfo());
+      setEcaptureInfoError(null);
+    } catch (error) {
+      setEcaptureInfo(null);
+      setEcaptureInfoError(String(error));
+    }
+  }, []);
+
+  useEffect(() => {
+    loadEcaptureInfo();
+  }, [loadEcaptureInfo]);
 
   const handleSave = async () => {
     if (!hasChanges) return;
@@ -36,6 +136,7 @@ export function SettingsPage() {
     try {
       await saveConfigs();
       setErrorMessage(null);
+      loadEcaptureInfo();
     } catch (error) {
       setErrorMessage(error instanceof Error ? error.message : String(error));
     } finally {
@@ -47,6 +148,17 @@ export function SettingsPage() {
     resetConfigs();
   };
 
+  const handleRawModeChange = (raw: boolean) => {
+    if (raw) {
+      if (configs.ecapture_options) {
+        lastOptionsRef.current = configs.ecapture_options;
+      }
+      updateConfigs({ ecapture_options: null });
+    } else {
+      updateConfigs({ ecapture_options: lastOptionsRef.current });
+    }
+  };
+
   // Handle GitHub link click - open in system default browser
   const handleGitHubClick = async () => {
     await open("https://github.com/gojue/ecaptureQ");
@@ -263,24 +375,313 @@ export function SettingsPage() {
             </div>
 
             <div className="space-y-3">
+              <label className="flex items-center space-x-2 text-sm text-gray-700 dark:text-gray-300">
+                <input
+                  type="checkbox"
+                  checked={!configs.ecapture_options}
+                  onChange={(e) => handleRawModeChange(e.target.checked)}
+                  className="rounded border-gray-300 dark:border-gray-600"
+                />
+                <span>Use raw command arguments</span>
+              </label>
+
+              {configs.ecapture_options ? (
+                <EcaptureOptionsForm
+                  options={configs.ecapture_options}
+                  onChange={(options) =>
+                    updateConfigs({ ecapture_options: options })
+                  }
+                />
+              ) : (
+                <>
+                  <label className="block text-sm font-medium text-gray-700 dark:text-gray-300">
+                    Command Arguments
+                  </label>
+                  <textarea
+                    value={configs.ecapture_args || ""}
+                    onChange={(e) =>
+                      updateConfigs({ ecapture_args: e.target.value })
+                    }
+                    placeholder={`tls --ecaptureq ws://127.0.0.1:28257 "tcp port 443"`}
+                    rows={3}
+                    className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 font-mono text-sm"
+                  />
+                </>
+              )}
+              <p className="text-xs text-gray-500 dark:text-gray-400 space-y-1">
+                <span className="block">
+                  • Only needed when using integrated eCapture mode
+                </span>
+                <span className="block">
+                  • Leave empty if connecting to external eCapture instance
+                </span>
+                <span className="block">
+                  • Structured arguments stream events to the WebSocket Server
+                  URL above
+                </span>
+                <span className="block">
+                  • Raw arguments are split like a shell command; quote
+                  arguments that contain spaces
+                </span>
+                <span className="block">
+                  • Raw arguments must pass --ecaptureq with the WebSocket
+                  Server URL above, on a loopback address
+                </span>
+              </p>
+            </div>
+          </div>
+          {/* eCapture Binary */}
+          <div className="bg-white dark:bg-gray-800 rounded-lg border border-gray-200 dark:border-gray-700 p-6">
+            <div className="flex items-center space-x-3 mb-4">
+              <div className="p-2 bg-orange-100 dark:bg-orange-900/40 rounded-lg">
+                <HardDrive className="w-5 h-5 text-orange-600 dark:text-orange-300" />
+              </div>
+              <div>
+                <h3 className="text-lg font-semibold text-gray-900 dark:text-gray-100">
+                  eCapture Binary
+                </h3>
+                <p className="text-sm text-gray-500 dark:text-gray-400">
+                  The bundled eCapture, or your own build pinned by its
+                  SHA-256 (integrated mode only)
+                </p>
+              </div>
+            </div>
+
+            <div className="space-y-3">
+              {ecaptureInfo && (
+                <dl className="grid grid-cols-[auto_1fr] gap-x-4 gap-y-1 text-sm">
+                  <dt className="text-gray-500 dark:text-gray-400">Source</dt>
+                  <dd className="text-gray-900 dark:text-gray-100">
+                    {ecaptureInfo.source}
+                  </dd>
+                  <dt className="text-gray-500 dark:text-gray-400">Version</dt>
+                  <dd className="text-gray-900 dark:text-gray-100">
+                    {ecaptureInfo.version ?? "unknown"} (
+                    {ecaptureInfo.compatibility})
+                  </dd>
+                  <dt className="text-gray-500 dark:text-gray-400">Path</dt>
+                  <dd className="font-mono text-xs break-all text-gray-900 dark:text-gray-100">
+                    {ecaptureInfo.path}
+                  </dd>
+                  <dt className="text-gray-500 dark:text-gray-400">SHA-256</dt>
+                  <dd className="font-mono text-xs break-all text-gray-900 dark:text-gray-100">
+                    {ecaptureInfo.sha256}
+                  </dd>
+                </dl>
+              )}
+              {ecaptureInfo?.warning && (
+                <p className="text-xs text-yellow-700 dark:text-yellow-300">
+                  {ecaptureInfo.warning}
+                </p>
+              )}
+              {ecaptureInfoError && (
+                <p className="text-xs text-red-600 dark:text-red-400">
+                  {ecaptureInfoError}
+                </p>
+              )}
+
               <label className="block text-sm font-medium text-gray-700 dark:text-gray-300">
-                Command Arguments
+                External Binary Path
               </label>
-              <textarea
-                value={configs.ecapture_args || ""}
+              <input
+                type="text"
+                value={configs.ecapture_path || ""}
                 onChange={(e) =>
-                  updateConfigs({ ecapture_args: e.target.value })
+                  updateConfigs({ ecapture_path: e.target.value || null })
                 }
-                placeholder="--interface=eth0 --port=8080"
-                rows={3}
+                placeholder="/usr/local/bin/ecapture"
                 className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 font-mono text-sm"
               />
+              <label className="block text-sm font-medium text-gray-700 dark:text-gray-300">
+                Pinned SHA-256
+              </label>
+              <input
+                type="text"
+                value={configs.ecapture_sha256 || ""}
+                onChange={(e) =>
+                  updateConfigs({ ecapture_sha256: e.target.value || null })
+                }
+                disabled={!configs.ecapture_path}
+                placeholder="sha256sum of the binary"
+                className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 font-mono text-sm disabled:opacity-50"
+              />
               <p className="text-xs text-gray-500 dark:text-gray-400 space-y-1">
                 <span className="block">
-                  • Only needed when using integrated eCapture mode
+                  • Leave the path empty to use the bundled eCapture
                 </span>
                 <span className="block">
-                  • Leave empty if connecting to external eCapture instance
+                  • The binary is hashed before every launch and refused if it
+                  does not match
+                </span>
+                <span className="block">
+                  • With the privileged helper the binary must be owned and
+                  only writable by root
+                </span>
+              </p>
+            </div>
+          </div>
+          {/* Session Timeouts */}
+          <div className="bg-white dark:bg-gray-800 rounded-lg border border-gray-200 dark:border-gray-700 p-6">
+            <div className="flex items-center space-x-3 mb-4">
+              <div className="p-2 bg-teal-100 dark:bg-teal-900/40 rounded-lg">
+                <Timer className="w-5 h-5 text-teal-600 dark:text-teal-300" />
+              </div>
+              <div>
+                <h3 className="text-lg font-semibold text-gray-900 dark:text-gray-100">
+                  Session Timeouts
+                </h3>
+                <p className="text-sm text-gray-500 dark:text-gray-400">
+                  How long a capture session waits for eCapture to start and
+                  to stop
+                </p>
+              </div>
+            </div>
+
+            <div className="space-y-3">
+              <div className="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                {TIMEOUT_FIELDS.map(({ key, label }) => (
+                  <div key={key}>
+                    <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                      {label}
+                    </label>
+                    <input
+                      type="number"
+                      min={0}
+                      step={100}
+                      value={
+                        (configs.session_timeouts ?? DEFAULT_SESSION_TIMEOUTS)[
+                          key
+                        ]
+                      }
+                      onChange={(e) =>
+                        updateConfigs({
+                          session_timeouts: {
+                            ...(configs.session_timeouts ??
+                              DEFAULT_SESSION_TIMEOUTS),
+                            [key]: Math.max(0, Number(e.target.value) || 0),
+                          },
+                        })
+                      }
+                      className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 font-mono text-sm"
+                    />
+                  </div>
+                ))}
+              </div>
+              <p className="text-xs text-gray-500 dark:text-gray-400 space-y-1">
+                <span className="block">
+                  • A launch fails if eCapture does not accept the WebSocket
+                  connection in time; an external eCapture is waited for
+                  instead
+                </span>
+                <span className="block">
+                  • Stopping sends SIGINT, then SIGTERM, then SIGKILL, each
+                  after its grace period
+                </span>
+              </p>
+            </div>
+          </div>
+          {/* Redaction */}
+          <div className="bg-white dark:bg-gray-800 rounded-lg border border-gray-200 dark:border-gray-700 p-6">
+            <div className="flex items-center space-x-3 mb-4">
+              <div className="p-2 bg-rose-100 dark:bg-rose-900/40 rounded-lg">
+                <EyeOff className="w-5 h-5 text-rose-600 dark:text-rose-300" />
+              </div>
+              <div>
+                <h3 className="text-lg font-semibold text-gray-900 dark:text-gray-100">
+                  Redaction
+                </h3>
+                <p className="text-sm text-gray-500 dark:text-gray-400">
+                  Replace tokens, cookies and passwords with [REDACTED]
+                </p>
+              </div>
+            </div>
+
+            <div className="space-y-3">
+              <div>
+                <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                  Redact
+                </label>
+                <select
+                  value={redaction.mode}
+                  onChange={(e) =>
+                    updateConfigs({
+                      redaction: {
+                        ...redaction,
+                        mode: e.target.value as RedactionMode,
+                      },
+                    })
+                  }
+                  className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 text-sm"
+                >
+                  {REDACTION_MODES.map(({ value, label }) => (
+                    <option key={value} value={value}>
+                      {label}
+                    </option>
+                  ))}
+                </select>
+              </div>
+              <div className="grid grid-cols-1 sm:grid-cols-3 gap-3">
+                {REDACTION_LISTS.map(({ key, label }) => (
+                  <div key={key}>
+                    <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                      {label}
+                    </label>
+                    <textarea
+                      rows={5}
+                      value={redaction[key].join("\n")}
+                      onChange={(e) =>
+                        updateConfigs({
+                          redaction: {
+                            ...redaction,
+                            [key]: e.target.value.split("\n"),
+                          },
+                        })
+                      }
+                      placeholder="One per line"
+                      className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-md focus:ring-2 focus:ring-blue-500 focus:border-blue-500 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 placeholder-gray-400 dark:placeholder-gray-500 font-mono text-sm"
+                    />
+                  </div>
+                ))}
+              </div>
+              <div className="flex items-center space-x-3">
+                <button
+                  onClick={loadRedactionReport}
+                  className="px-3 py-1.5 text-sm border border-gray-300 dark:border-gray-600 rounded-md text-gray-700 dark:text-gray-300 hover:bg-gray-50 dark:hover:bg-gray-700"
+                >
+                  Show Report
+                </button>
+                {redactionReportError && (
+                  <span className="text-sm text-gray-500 dark:text-gray-400">
+                    {redactionReportError}
+                  </span>
+                )}
+              </div>
+              {redactionReport && (
+                <div className="text-sm text-gray-700 dark:text-gray-300 font-mono space-y-1">
+                  <div>
+                    {redactionReport.records} packets or messages redacted
+                  </div>
+                  {Object.entries(redactionReport.matches).map(
+                    ([rule, count]) => (
+                      <div key={rule}>
+                        {rule}: {count}
+                      </div>
+                    ),
+                  )}
+                </div>
+              )}
+              <p className="text-xs text-gray-500 dark:text-gray-400 space-y-1">
+                <span className="block">
+                  • Before storing, custom SQL no longer matches the redacted
+                  values; otherwise it still does
+                </span>
+                <span className="block">
+                  • Rules rewrite text only; binary payloads a rule matches in
+                  are left out, and exports leave out all raw bytes
+                </span>
+                <span className="block">
+                  • The report covers captured packets with the saved rules;
+                  before storing, what was redacted since they were saved
                 </span>
               </p>
             </div>
@@ -1,5 +1,25 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Configs, PacketDataWithPayload } from "@/types";
+import type {
+  AndroidPackage,
+  CaptureScope,
+  Configs,
+  BrokerMessage,
+  DbQuery,
+  DecodedPayload,
+  EcaptureInfo,
+  EcaptureOutputLine,
+  ExportSummary,
+  FlowData,
+  MailMessage,
+  PacketAnnotation,
+  PacketData,
+  PacketDataWithPayload,
+  ProcessInfo,
+  ProtocolStatus,
+  RedactionReport,
+  SessionInfo,
+  WsMessage,
+} from "@/types";
 
 export class ApiService {
   static async startCapture(): Promise<void> {
@@ -12,6 +32,65 @@ export class ApiService {
     }
   }
 
+  static async captureProcess(pid: number, scope: CaptureScope): Promise<void> {
+    try {
+      await invoke("capture_process", { pid, scope });
+      console.log(`Capture session started for process ${pid}`);
+    } catch (error) {
+      console.error("Failed to capture process:", error);
+      throw error;
+    }
+  }
+
+  static async capturePackage(packageName: string): Promise<void> {
+    try {
+      await invoke("capture_package", { package: packageName });
+      console.log(`Capture session started for package ${packageName}`);
+    } catch (error) {
+      console.error("Failed to capture package:", error);
+      throw error;
+    }
+  }
+
+  static async listPackages(): Promise<AndroidPackage[]> {
+    try {
+      const result = await invoke("list_packages");
+      return result as AndroidPackage[];
+    } catch (error) {
+      console.error("Failed to list packages:", error);
+      throw error;
+    }
+  }
+
+  static async listProcesses(): Promise<ProcessInfo[]> {
+    try {
+      const result = await invoke("list_processes");
+      return result as ProcessInfo[];
+    } catch (error) {
+      console.error("Failed to list processes:", error);
+      throw error;
+    }
+  }
+
+  static async getOrphanedProcesses(): Promise<ProcessInfo[]> {
+    try {
+      const result = await invoke("get_orphaned_processes");
+      return result as ProcessInfo[];
+    } catch (error) {
+      console.error("Failed to get orphaned processes:", error);
+      throw error;
+    }
+  }
+
+  static async stopOrphanedProcesses(): Promise<void> {
+    try {
+      await invoke("stop_orphaned_processes");
+    } catch (error) {
+      console.error("Failed to stop orphaned processes:", error);
+      throw error;
+    }
+  }
+
   static async stopCapture(): Promise<void> {
     try {
       await invoke("stop_capture");
@@ -63,4 +142,211 @@ export class ApiService {
       throw error;
     }
   }
+
+  static async decodePayload(
+    index: number,
+    decoder?: string,
+  ): Promise<DecodedPayload> {
+    try {
+      const result = await invoke("decode_payload", { index, decoder });
+      return result as DecodedPayload;
+    } catch (error) {
+      console.error("Failed to decode payload:", error);
+      throw error;
+    }
+  }
+
+  static async getWsMessages(uuid: string): Promise<WsMessage[]> {
+    try {
+      const result = await invoke("get_ws_messages", { uuid });
+      return result as WsMessage[];
+    } catch (error) {
+      console.error("Failed to get WebSocket messages:", error);
+      throw error;
+    }
+  }
+
+  static async getDbQueries(uuid: string): Promise<DbQuery[]> {
+    try {
+      const result = await invoke("get_db_queries", { uuid });
+      return result as DbQuery[];
+    } catch (error) {
+      console.error("Failed to get database queries:", error);
+      throw error;
+    }
+  }
+
+  static async getMailMessages(uuid: string): Promise<MailMessage[]> {
+    try {
+      const result = await invoke("get_mail_messages", { uuid });
+      return result as MailMessage[];
+    } catch (error) {
+      console.error("Failed to get mail messages:", error);
+      throw error;
+    }
+  }
+
+  static async getBrokerMessages(uuid: string): Promise<BrokerMessage[]> {
+    try {
+      const result = await invoke("get_broker_messages", { uuid });
+      return result as BrokerMessage[];
+    } catch (error) {
+      console.error("Failed to get broker messages:", error);
+      throw error;
+    }
+  }
+
+  static async listSessions(): Promise<SessionInfo[]> {
+    try {
+      const result = await invoke("list_sessions");
+      return result as SessionInfo[];
+    } catch (error) {
+      console.error("Failed to list sessions:", error);
+      throw error;
+    }
+  }
+
+  static async getSessionPackets(sessionId: number): Promise<PacketData[]> {
+    try {
+      const result = await invoke("get_session_packets", { sessionId });
+      return result as PacketData[];
+    } catch (error) {
+      console.error("Failed to get session packets:", error);
+      throw error;
+    }
+  }
+
+  static async clearSession(sessionId: number): Promise<number> {
+    try {
+      const result = await invoke("clear_session", { sessionId });
+      return result as number;
+    } catch (error) {
+      console.error("Failed to clear session:", error);
+      throw error;
+    }
+  }
+
+  // keep 为保留数据包的 SQL 条件
+  static async clearPackets(
+    keep: string | null,
+    keepCurrentSession: boolean,
+  ): Promise<number> {
+    try {
+      const result = await invoke("clear_packets", {
+        keep,
+        keepCurrentSession,
+      });
+      return result as number;
+    } catch (error) {
+      console.error("Failed to clear packets:", error);
+      throw error;
+    }
+  }
+
+  static async tagPacket(index: number, tag: string): Promise<PacketAnnotation> {
+    try {
+      const result = await invoke("tag_packet", { index, tag });
+      return result as PacketAnnotation;
+    } catch (error) {
+      console.error("Failed to tag packet:", error);
+      throw error;
+    }
+  }
+
+  static async untagPacket(
+    index: number,
+    tag: string,
+  ): Promise<PacketAnnotation> {
+    try {
+      const result = await invoke("untag_packet", { index, tag });
+      return result as PacketAnnotation;
+    } catch (error) {
+      console.error("Failed to untag packet:", error);
+      throw error;
+    }
+  }
+
+  static async setPacketNote(
+    index: number,
+    note: string,
+  ): Promise<PacketAnnotation> {
+    try {
+      const result = await invoke("set_packet_note", { index, note });
+      return result as PacketAnnotation;
+    } catch (error) {
+      console.error("Failed to set packet note:", error);
+      throw error;
+    }
+  }
+
+  static async listAnnotations(): Promise<PacketAnnotation[]> {
+    try {
+      const result = await invoke("list_annotations");
+      return result as PacketAnnotation[];
+    } catch (error) {
+      console.error("Failed to list annotations:", error);
+      throw error;
+    }
+  }
+
+  // 导出数据包（含负载和标签备注）到下载目录，filter 为 packets 上的 SQL 条件
+  static async exportPackets(filter: string | null = null): Promise<ExportSummary> {
+    try {
+      const result = await invoke("export_packets", { filter });
+      return result as ExportSummary;
+    } catch (error) {
+      console.error("Failed to export packets:", error);
+      throw error;
+    }
+  }
+
+  static async getRedactionReport(): Promise<RedactionReport | null> {
+    try {
+      const result = await invoke("get_redaction_report");
+      return result as RedactionReport | null;
+    } catch (error) {
+      console.error("Failed to get redaction report:", error);
+      throw error;
+    }
+  }
+
+  static async getFlows(): Promise<FlowData[]> {
+    try {
+      const result = await invoke("get_flows");
+      return result as FlowData[];
+    } catch (error) {
+      console.error("Failed to get flows:", error);
+      throw error;
+    }
+  }
+
+  static async getEcaptureOutput(): Promise<EcaptureOutputLine[]> {
+    try {
+      const result = await invoke("get_ecapture_output");
+      return result as EcaptureOutputLine[];
+    } catch (error) {
+      console.error("Failed to get eCapture output:", error);
+      throw error;
+    }
+  }
+
+  static async getEcaptureInfo(): Promise<EcaptureInfo> {
+    try {
+      const result = await invoke("get_ecapture_info");
+      return result as EcaptureInfo;
+    } catch (error) {
+      console.error("Failed to get eCapture info:", error);
+      throw error;
+    }
+  }
+
+  static async getProtocolStatus(): Promise<ProtocolStatus> {
+    try {
+      const result = await invoke("get_protocol_status");
+      return result as ProtocolStatus;
+    } catch (error) {
+      console.error("Failed to get protocol status:", error);
+      throw error;
+    }
+  }
 }
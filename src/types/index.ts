@@ -1,5 +1,6 @@
 export interface PacketData {
   index: number;
+  session_id: number; // 所属抓包会话，见 SessionInfo
   timestamp: number;
   uuid: string;
   src_ip: string;
@@ -8,18 +9,293 @@ export interface PacketData {
   dst_port: number;
   pid: number;
   pname: string;
-  type: number;
+  // Process and TLS metadata: null or "" when ecapture did not report it
+  tid: number | null;
+  uid: number | null;
+  exe_path: string;
+  cgroup_id: number | null;
+  container_id: string;
+  package: string; // Android 包名，由 uid 得出；未知时为空
+  fd: number | null;
+  type_name:
+    | "raw"
+    | "http_request"
+    | "http2_request"
+    | "http_response"
+    | "http2_response"
+    | "websocket"
+    | "unknown";
+  direction: "read" | "write" | "unknown";
+  module: EcaptureSubcommand | null; // 抓包会话的 ecapture 模块；未知时为 null
   length: number;
   is_binary: boolean;
+  content_kind: "http" | "tls-record" | "protobuf" | "text" | "binary";
+  app_proto: AppProto;
+  tls_version: string;
+  tls_cipher: string;
+  tls_sni: string;
+  tls_alpn: string;
+  tls_library: string;
+}
+
+export type AppProto =
+  | "http1"
+  | "http2"
+  | "grpc"
+  | "websocket"
+  | "mysql"
+  | "postgres"
+  | "redis"
+  | "smtp"
+  | "unknown";
+
+export interface DecodedBody {
+  content_encoding: string;
+  head: string;
+  is_binary: boolean;
+  body_utf8: string;
+  body_binary: number[];
+  truncated: boolean;
+  error: string | null;
+}
+
+export interface MailMessage {
+  message_id: number;
+  uuid: string;
+  protocol: "smtp" | "imap";
+  packet_index: number;
+  timestamp: number;
+  direction: "client_to_server" | "server_to_client";
+  tag: string;
+  command: string;
+  arguments: string;
+  mail_from: string;
+  rcpt_to: string;
+  subject: string;
+  content: string;
+}
+
+export interface BrokerMessage {
+  message_id: number;
+  uuid: string;
+  protocol: "amqp" | "mqtt";
+  packet_index: number;
+  timestamp: number;
+  direction: "client_to_server" | "server_to_client";
+  method: string;
+  channel: number;
+  exchange: string;
+  topic: string;
+  queue: string;
+  packet_id: number | null;
+  qos: number | null;
+  detail: string;
+  content_type: string;
+  length: number;
+  is_binary: boolean;
+  payload_utf8: string;
+  payload_binary: number[];
 }
 
 export interface PacketDataWithPayload extends PacketData {
   payload_utf8: string;
   payload_binary: number[];
+  decoded_body: DecodedBody | null;
+}
+
+export interface DecodedPayload {
+  decoder: string;
+  content_type: string | null;
+  tree: unknown;
+}
+
+export interface FlowData {
+  flow_id: number;
+  uuid: string;
+  src_ip: string;
+  src_port: number;
+  dst_ip: string;
+  dst_port: number;
+  pid: number;
+  pname: string;
+  first_seen: number;
+  last_seen: number;
+  duration: number;
+  bytes_out: number;
+  bytes_in: number;
+  event_count: number;
+  app_proto: AppProto;
+  update_seq: number;
+}
+
+export interface WsMessage {
+  message_id: number;
+  uuid: string;
+  packet_index: number;
+  timestamp: number;
+  direction: "client_to_server" | "server_to_client";
+  opcode: number;
+  opcode_name: string;
+  compressed: boolean;
+  length: number;
+  is_binary: boolean;
+  payload_utf8: string;
+  payload_binary: number[];
+}
+
+export interface DbQuery {
+  query_id: number;
+  uuid: string;
+  protocol: "mysql" | "postgresql" | "redis";
+  kind: string;
+  statement: string;
+  packet_index: number;
+  timestamp: number;
+  response_index: number | null;
+  latency: number | null;
+  rows: number | null;
+  error: string | null;
+}
+
+// 内置 ecapture 进程输出的一行
+export interface EcaptureOutputLine {
+  timestamp: number; // 毫秒
+  stream: "stdout" | "stderr";
+  line: string;
+}
+
+// 内置 ecapture 意外退出时发送，重启前后的数据可能缺失
+export interface CaptureRestartEvent {
+  timestamp: number; // 毫秒
+  attempt: number;
+  reason: string;
+  delay_ms: number;
+  gave_up: boolean; // 不再重启，抓包会话已停止
+}
+
+// ecapture 数据流的版本与解码状态
+export interface ProtocolStatus {
+  connected: boolean;
+  ecapture_version: string | null;
+  protocol_version: number | null; // 旧版本 ecapture 不发送握手，为 null
+  supported_protocol_version: number;
+  compatibility: "supported" | "untested" | "unsupported" | "unknown";
+  warnings: string[];
+  unsupported_messages: number;
+  unsupported_log_types: number[];
+  decode_errors: number;
+}
+
+// 已安装的 Android 应用
+export interface AndroidPackage {
+  name: string;
+  uid: number;
+}
+
+// 正在运行的进程
+export interface ProcessInfo {
+  pid: number;
+  uid: number;
+  name: string;
+  cmdline: string; // 内核线程为空
+  exe: string | null;
+  tls_libraries: ("openssl" | "boringssl" | "gnutls" | "nss" | "gotls")[];
+  libssl: string | null;
+}
+
+// 按进程抓包时的过滤范围：仅该进程，或该进程所属用户的全部进程
+export type CaptureScope = "process" | "user";
+
+// 抓包会话将运行的 ecapture 可执行文件
+export interface EcaptureInfo {
+  source: "bundled" | "external";
+  path: string;
+  sha256: string; // 已校验的 SHA-256
+  version: string | null; // 无法读取版本时为 null
+  compatibility: "supported" | "untested" | "unsupported" | "unknown";
+  warning: string | null;
+}
+
+// 用户给数据包加的标签和备注，按 index 对应
+export interface PacketAnnotation {
+  index: number;
+  session_id: number;
+  tags: string[];
+  note: string; // 无备注时为空
+}
+
+// 本次运行中的一次抓包会话
+export interface SessionInfo {
+  session_id: number;
+  started_at: number; // 毫秒时间戳
+  stopped_at: number | null; // 仍在运行时为 null
+  ecapture_args: string[]; // 外部 ecapture 时为空
+  module: EcaptureSubcommand | null; // ecapture_args 运行的模块
+  source_url: string;
+  rows: number;
+  exit_reason: string | null;
+}
+
+export type EcaptureSubcommand =
+  | "tls"
+  | "gotls"
+  | "gnutls"
+  | "nss"
+  | "bash"
+  | "mysqld"
+  | "postgres";
+
+// ecapture 的结构化参数，未设置的字段使用 ecapture 的默认值
+export interface EcaptureOptions {
+  subcommand: EcaptureSubcommand;
+  pid?: number | null;
+  uid?: number | null;
+  libssl?: string | null; // 仅 tls
+  elfpath?: string | null; // 仅 gotls，要抓取的 Go 程序
+  interface?: string | null; // 以下三项仅 TLS 类模块
+  port?: number | null;
+  pcap_filter?: string | null;
+}
+
+// 抓包会话的等待时间（毫秒），未设置时使用默认值
+export interface SessionTimeouts {
+  launch_ms: number; // 等待 ecapture 接受 WebSocket 连接
+  interrupt_grace_ms: number; // SIGINT 后等待退出，超时发送 SIGTERM
+  terminate_grace_ms: number; // SIGTERM 后等待退出，超时发送 SIGKILL
+  exit_ms: number; // 退出应用时等待会话关闭
 }
 
 export interface Configs {
   ws_url?: string;
-  ecapture_args?: string;
+  ecapture_args?: string; // 原始参数，ecapture_options 为空时使用
+  ecapture_options?: EcaptureOptions | null;
+  ecapture_path?: string | null; // 外部 ecapture，为空时使用内置版本
+  ecapture_sha256?: string | null; // 外部 ecapture 的 SHA-256，必填
   user_sql?: string | null;
+  session_timeouts?: SessionTimeouts | null;
+  redaction?: RedactionConfig | null;
+}
+
+// 敏感数据脱敏：ingest 入库前脱敏（SQL 也查不到原文），
+// display 展示和导出时脱敏，export 仅导出时脱敏
+export type RedactionMode = "off" | "ingest" | "display" | "export";
+
+export interface RedactionConfig {
+  mode: RedactionMode;
+  headers: string[]; // HTTP 头名，不区分大小写
+  json_keys: string[]; // JSON 键名，不区分大小写
+  patterns: string[]; // 正则表达式，匹配内容整体替换
+}
+
+// 脱敏报告，matches 按规则统计替换次数，如 header:Authorization
+export interface RedactionReport {
+  records: number;
+  matches: Record<string, number>;
+  binary_payloads_removed: number; // 导出时移除的二进制负载
+}
+
+export interface ExportSummary {
+  path: string;
+  packets: number;
+  redaction?: RedactionReport | null;
 }